@@ -1,55 +1,55 @@
 //! benches
 #![allow(missing_docs, reason = "inner doc in external macro lib")]
 
-use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
-use regex::Regex;
-use regextrie::RegexTrie;
-use std::error::Error;
-use std::hint::black_box;
+use criterion::{
+    BenchmarkGroup, BenchmarkId, Criterion, criterion_group, criterion_main,
+    measurement::WallTime,
+};
+use regextrie::{PatternMatcher, RegexTrie, reference::NaiveMatcher as Naive};
+use std::{fmt::Display, hint::black_box};
 
 /// Small set
 const SMALL_SET: &str = include_str!("../assets/small_set.txt");
 /// Big set
 const BIG_SET: &str = include_str!("../assets/big_set.txt");
 
-/// Naive implementation
-struct Naive {
-    /// Stores the original pattern string and its compiled DFA, with an
-    /// optional score
-    compiled_patterns: Vec<(String, Regex, usize)>,
+/// Split an asset file into a list of patterns
+fn load_corpus(set: &str) -> Vec<String> {
+    set.split('\n')
+        .filter(|item| !item.is_empty())
+        .map(ToString::to_string)
+        .collect()
 }
 
-impl Naive {
-    /// From a collection
-    pub fn from(patterns: &[String]) -> Result<Self, Box<dyn Error>> {
-        let mut compiled_patterns = Vec::with_capacity(patterns.len());
-
-        for pattern in patterns {
-            let dfa = Regex::new(pattern)?;
-            compiled_patterns.push((pattern.clone(), dfa, pattern.len()));
-        }
-
-        Ok(Self { compiled_patterns })
-    }
-
-    /// Find best match naively
-    pub fn find_best_match(&self, query: &str) -> Option<String> {
-        let mut best_match = None;
-        for (pattern, regex, score) in &self.compiled_patterns {
-            if regex.is_match(query) {
-                match best_match {
-                    Some((_, best_score)) => {
-                        if score < best_score {
-                            best_match = Some((pattern, score));
-                        }
-                    }
-                    None => best_match = Some((pattern, score)),
-                }
-            }
-        }
+/// Bench finding the best match of a query, with a single implementation
+fn bench_best_match<M, P>(
+    group: &mut BenchmarkGroup<'_, WallTime>,
+    name: &str,
+    parameter: P,
+    corpus: &[String],
+    query: &str,
+) where
+    M: PatternMatcher + Default,
+    P: Display,
+{
+    // Build the matcher *once* so setup cost isn't timed.
+    let matcher = M::from_patterns(corpus).expect("can't init matcher");
+    group.bench_with_input(
+        BenchmarkId::new(name, parameter),
+        &matcher,
+        |bencher, matcher| bencher.iter(|| black_box(matcher.find_best_match(query))),
+    );
+}
 
-        best_match.map(|(pattern, _)| pattern.clone())
-    }
+/// Bench all implementation finding the best match of a query
+fn bench_all_best_match<P: Display + Copy>(
+    group: &mut BenchmarkGroup<'_, WallTime>,
+    parameter: P,
+    corpus: &[String],
+    query: &str,
+) {
+    bench_best_match::<Naive, _>(group, "Naive", parameter, corpus, query);
+    bench_best_match::<RegexTrie, _>(group, "RegexTrie", parameter, corpus, query);
 }
 
 /// Bench all implementation
@@ -60,24 +60,7 @@ fn random_bench_best_match(c: &mut Criterion) {
     for &size in &[10_usize, 1_000, 10_000] {
         // Build a deterministic corpus *once* per size so setup cost isn't timed.
         let corpus: Vec<String> = (0..size).map(|i| format!("test{i}")).collect();
-        let query = "test42";
-
-        // Own a private copy for each algorithm so they can mutate freely.
-        let naive = Naive::from(&corpus).expect("can't init Naive");
-        // let bin = BinarySearchIndex::from(corpus.clone());
-        let trie = RegexTrie::from(&corpus).expect("can't init RegexTrie");
-
-        group.bench_with_input(
-            BenchmarkId::new("Naive", size),
-            &naive,
-            |bencher, matcher| bencher.iter(|| black_box(matcher.find_best_match(query))),
-        );
-
-        group.bench_with_input(
-            BenchmarkId::new("RegexTrie", size),
-            &trie,
-            |bencher, matcher| bencher.iter(|| black_box(matcher.find_best_match(query))),
-        );
+        bench_all_best_match(&mut group, size, &corpus, "test42");
     }
 
     group.finish();
@@ -86,101 +69,38 @@ fn random_bench_best_match(c: &mut Criterion) {
 /// Bench all implementation with the given asset files
 fn assets_small_bench_best_match(c: &mut Criterion) {
     let mut group = c.benchmark_group("small_assets_best_match");
-
-    // Measure behaviour at different scales.
-    let corpus = SMALL_SET
-        .split('\n')
-        .filter_map(|item| {
-            if item.is_empty() {
-                None
-            } else {
-                Some(item.to_string())
-            }
-        })
-        .collect::<Vec<String>>();
-
-    // Build a deterministic corpus *once* per size so setup cost isn't timed.
+    let corpus = load_corpus(SMALL_SET);
     let query = "https://www.google.com/b4a/test/mqgzumi/another/yh936/again/kk839gym/abc123";
-
-    // Own a private copy for each algorithm so they can mutate freely.
-    let naive = Naive::from(&corpus).expect("can't init Naive");
-    // let bin = BinarySearchIndex::from(corpus.clone());
-    let trie = RegexTrie::from(&corpus).expect("can't init RegexTrie");
-
-    group.bench_with_input(BenchmarkId::new("Naive", ""), &naive, |bencher, matcher| {
-        bencher.iter(|| black_box(matcher.find_best_match(query)));
-    });
-
-    group.bench_with_input(
-        BenchmarkId::new("RegexTrie", ""),
-        &trie,
-        |bencher, matcher| bencher.iter(|| black_box(matcher.find_best_match(query))),
-    );
-
+    bench_all_best_match(&mut group, "", &corpus, query);
     group.finish();
 }
 
 /// Bench all implementation with the given asset files
 fn assets_big_bench_best_match(c: &mut Criterion) {
     let mut group = c.benchmark_group("big_assets_best_match");
-
-    // Measure behaviour at different scales.
-    let corpus = BIG_SET
-        .split('\n')
-        .filter_map(|item| {
-            if item.is_empty() {
-                None
-            } else {
-                Some(item.to_string())
-            }
-        })
-        .collect::<Vec<String>>();
-
-    // Build a deterministic corpus *once* per size so setup cost isn't timed.
+    let corpus = load_corpus(BIG_SET);
     let query = "https://www.yahoo.com/hz2s/test/nf4266/another/test";
+    bench_all_best_match(&mut group, "", &corpus, query);
+    group.finish();
+}
 
-    // Own a private copy for each algorithm so they can mutate freely.
-    let naive = Naive::from(&corpus).expect("can't init Naive");
-    // let bin = BinarySearchIndex::from(corpus.clone());
-    let trie = RegexTrie::from(&corpus).expect("can't init RegexTrie");
-
-    group.bench_with_input(BenchmarkId::new("Naive", ""), &naive, |bencher, matcher| {
-        bencher.iter(|| black_box(matcher.find_best_match(query)));
+/// Bench loading a corpus, with a single implementation
+fn bench_load<M: PatternMatcher + Default>(
+    group: &mut BenchmarkGroup<'_, WallTime>,
+    name: &str,
+    corpus: &[String],
+) {
+    group.bench_function(BenchmarkId::new(name, ""), |bencher| {
+        bencher.iter(|| black_box(M::from_patterns(corpus).expect("can't init matcher")));
     });
-
-    group.bench_with_input(
-        BenchmarkId::new("RegexTrie", ""),
-        &trie,
-        |bencher, matcher| bencher.iter(|| black_box(matcher.find_best_match(query))),
-    );
-
-    group.finish();
 }
 
 /// Bench all implementation with the given asset files, on the loading part
 fn bench_loading(c: &mut Criterion) {
     let mut group = c.benchmark_group("loading_time");
-
-    // Measure behaviour at different scales.
-    let corpus = BIG_SET
-        .split('\n')
-        .filter_map(|item| {
-            if item.is_empty() {
-                None
-            } else {
-                Some(item.to_string())
-            }
-        })
-        .collect::<Vec<String>>();
-
-    group.bench_function(BenchmarkId::new("Naive", ""), |bencher| {
-        bencher.iter(|| black_box(Naive::from(&corpus).expect("can't init Naive")));
-    });
-
-    group.bench_function(BenchmarkId::new("RegexTrie", ""), |bencher| {
-        bencher.iter(|| black_box(RegexTrie::from(&corpus).expect("can't init RegexTrie")));
-    });
-
+    let corpus = load_corpus(BIG_SET);
+    bench_load::<Naive>(&mut group, "Naive", &corpus);
+    bench_load::<RegexTrie>(&mut group, "RegexTrie", &corpus);
     group.finish();
 }
 
@@ -0,0 +1,29 @@
+use std::{error::Error, fs, path::PathBuf};
+
+use clap::Args;
+
+use crate::PatternSource;
+
+/// Arguments of the compile command
+#[derive(Debug, Args)]
+pub struct CompileArgs {
+    /// Where to load the patterns from
+    #[command(flatten)]
+    source: PatternSource,
+    /// Where to write the compiled snapshot
+    #[arg(short, long)]
+    output: PathBuf,
+}
+
+/// Build the trie and write its serialized form. Always returns `true`, as
+/// there is nothing to match.
+///
+/// ## Errors
+///
+/// If the patterns can't be loaded, or the snapshot can't be written
+pub fn run(args: &CompileArgs) -> Result<bool, Box<dyn Error>> {
+    let trie = args.source.load()?;
+    fs::write(&args.output, trie.to_bytes())
+        .map_err(|err| format!("can't write {}: {err}", args.output.display()))?;
+    Ok(true)
+}
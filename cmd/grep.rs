@@ -0,0 +1,118 @@
+use std::{
+    error::Error,
+    fs::File,
+    io::{self, BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+};
+
+use clap::Args;
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use regextrie::{RegexTrie, Utf8Handling};
+
+use crate::PatternSource;
+
+/// Arguments of the grep command
+#[derive(Debug, Args)]
+pub struct GrepArgs {
+    /// Where to load the patterns from
+    #[command(flatten)]
+    source: PatternSource,
+    /// Scan the files in parallel. Output is still in the files order
+    #[arg(short = 'j', long)]
+    parallel: bool,
+    /// Files to scan, line by line
+    #[arg(required = true)]
+    paths: Vec<PathBuf>,
+}
+
+/// Pattern reported for a line: the best match if the line is valid UTF-8,
+/// or the first one matching its raw bytes otherwise, since lines aren't
+/// required to be valid UTF-8 in log files.
+fn match_line(trie: &RegexTrie, line: &[u8]) -> Option<String> {
+    if let Ok(line) = std::str::from_utf8(line) {
+        return trie.find_best_match(line);
+    }
+    // Can't fail, as the trie matches the raw bytes of invalid inputs.
+    let matches = trie.find_matches_bytes(line).unwrap_or_default();
+    matches.first().map(ToString::to_string)
+}
+
+/// Scan a single file, writing an output line for every matching line as
+/// they're found, and returning whether any line matched.
+///
+/// ## Errors
+///
+/// The outer error if the output can't be written, the inner one if the file
+/// can't be read
+fn scan_file(trie: &RegexTrie, path: &Path, out: &mut impl Write) -> io::Result<io::Result<bool>> {
+    let read_error = |err: io::Error| {
+        io::Error::new(err.kind(), format!("can't read {}: {err}", path.display()))
+    };
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(err) => return Ok(Err(read_error(err))),
+    };
+
+    let mut any_match = false;
+    for (index, line) in BufReader::new(file).split(b'\n').enumerate() {
+        let line = match line {
+            Ok(line) => line,
+            Err(err) => return Ok(Err(read_error(err))),
+        };
+        if let Some(pattern) = match_line(trie, line.strip_suffix(b"\r").unwrap_or(&line)) {
+            any_match = true;
+            writeln!(out, "{}:{}: {pattern}", path.display(), index + 1)?;
+        }
+    }
+
+    Ok(Ok(any_match))
+}
+
+/// Run the grep command, returning whether any line matched. A file which
+/// can't be read is reported on stderr, and the other files are still
+/// scanned.
+///
+/// ## Errors
+///
+/// If the patterns or a file can't be loaded, or the output can't be written
+pub fn run(args: &GrepArgs) -> Result<bool, Box<dyn Error>> {
+    let trie = args.source.load()?.with_utf8_handling(Utf8Handling::Bytes);
+
+    let mut out = io::stdout().lock();
+    let mut any_match = false;
+    let mut failures = 0;
+    let mut report = |scanned: io::Result<bool>| match scanned {
+        Ok(matched) => any_match |= matched,
+        Err(err) => {
+            failures += 1;
+            let _ = writeln!(io::stderr(), "regextrie: {err}");
+        }
+    };
+
+    if args.parallel {
+        // Files are scanned a batch at a time, so that only the output of a
+        // batch is buffered, and written in the files order.
+        for paths in args.paths.chunks(rayon::current_num_threads()) {
+            let outputs = paths
+                .par_iter()
+                .map(|path| {
+                    let mut output = Vec::new();
+                    scan_file(&trie, path, &mut output).map(|scanned| (output, scanned))
+                })
+                .collect::<io::Result<Vec<_>>>()?;
+            for (output, scanned) in outputs {
+                out.write_all(&output)?;
+                report(scanned);
+            }
+        }
+    } else {
+        for path in &args.paths {
+            report(scan_file(&trie, path, &mut out)?);
+        }
+    }
+
+    if failures > 0 {
+        return Err(format!("{failures} file(s) couldn't be read").into());
+    }
+    Ok(any_match)
+}
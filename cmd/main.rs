@@ -1,166 +1,140 @@
-//! regextrie main func
+//! regextrie command line: match inputs against a file of regex patterns.
+//!
+//! Exit status is 0 if at least one input matched, 1 if none did, and 2 on
+//! error, so it can be used in scripts like `grep`. Without any input on the
+//! command line, inputs are read from stdin, one per line.
+use std::{
+    error::Error,
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use clap::{Args, Parser, Subcommand};
 use regextrie::RegexTrie;
 
-#[expect(
-    clippy::too_many_lines,
-    clippy::print_stdout,
-    clippy::print_stderr,
-    reason = "..."
+/// Compile patterns into a snapshot
+mod compile;
+/// Multi-pattern grep over files
+mod grep;
+/// Default command, matching inputs
+mod matching;
+/// Interactive prompt
+mod repl;
+/// Pattern files checks
+mod validate;
+
+/// Match inputs against a set of regex patterns
+#[derive(Debug, Parser)]
+#[command(
+    version,
+    about,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
 )]
-fn main() {
-    // Create a new RegexTrie.
-    let mut trie = RegexTrie::new();
-
-    // Insert the regex patterns. Note that insert can now fail if the regex is invalid.
-    let patterns = vec![
-        "something[0-9]+",
-        "hello.*",
-        "hello.*test",
-        "hello[a-z]+test",
-        "anotherpattern",
-        ".*test", // A pattern with no literal prefix
-    ];
-
-    println!("Inserting patterns...");
-    for pattern in patterns {
-        if let Err(e) = trie.insert(pattern) {
-            eprintln!("Failed to insert pattern '{pattern}': {e}");
-        }
-    }
-
-    // Define an input string to test.
-    let input = "helloabctest";
-    println!("\n---------------------------------");
-    println!("Input string: \"{input}\"");
-
-    // Find and print the matches.
-    let matches = trie.find_matches(input);
-
-    println!("\nFound matching regex patterns:");
-    if matches.is_empty() {
-        println!("  No matches found.");
-    } else {
-        // Sort for consistent output order
-        let mut sorted_matches = matches;
-        sorted_matches.sort();
-        for m in sorted_matches {
-            println!("  - {m}");
-        }
-    }
-
-    println!("\n---------------------------------");
-
-    // Another example
-    let input2 = "something12345";
-    println!("\nInput string: \"{input2}\"");
-    let matches2 = trie.find_matches(input2);
-    println!("\nFound matching regex patterns:");
-    if matches2.is_empty() {
-        println!("  No matches found.");
-    } else {
-        for m in matches2 {
-            println!("  - {m}");
-        }
-    }
-
-    println!("\n---------------------------------");
+struct Cli {
+    /// Sub command to run, matching the given inputs if none
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// Arguments of the default command
+    #[command(flatten)]
+    matching: matching::MatchArgs,
+}
 
-    {
-        // Create a new RegexTrie.
-        let mut trie = RegexTrie::new();
+/// All the sub commands
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Scan files line by line, printing the best pattern of matching lines
+    Grep(grep::GrepArgs),
+    /// Interactive prompt showing the matches of every typed input
+    Repl(repl::ReplArgs),
+    /// Build the trie and write its compiled form, to be loaded quickly with
+    /// `--from-snapshot`
+    Compile(compile::CompileArgs),
+    /// Check pattern files, reporting invalid patterns and the ones hurting
+    /// performance
+    Validate(validate::ValidateArgs),
+}
 
-        // Insert the regex patterns. Note that insert can now fail if the regex is invalid.
-        let patterns = vec![
-            ".*",
-            ".*test",
-            "test",
-            "test.*",
-            ".*test.*",
-            ".*(test).*",
-            ".*(test|toto).*",
-        ];
+/// Where the patterns are loaded from, shared by all commands
+#[derive(Debug, Args)]
+struct PatternSource {
+    /// File containing one pattern per line
+    // Optional for clap, so it's not enforced on the default command when a
+    // sub command is used instead.
+    #[arg(
+        short,
+        long,
+        required_unless_present = "from_snapshot",
+        conflicts_with = "from_snapshot"
+    )]
+    patterns: Option<PathBuf>,
+    /// Compiled snapshot produced by the `compile` command, loaded instead
+    /// of compiling a pattern file
+    #[arg(long)]
+    from_snapshot: Option<PathBuf>,
+}
 
-        println!("Inserting patterns...");
-        for pattern in patterns {
-            if let Err(e) = trie.insert(pattern) {
-                eprintln!("Failed to insert pattern '{pattern}': {e}");
-            }
-        }
+/// Read a whole file, mentioning its path on error.
+///
+/// ## Errors
+///
+/// If the file can't be read
+fn read_file(path: &Path) -> io::Result<Vec<u8>> {
+    fs::read(path)
+        .map_err(|err| io::Error::new(err.kind(), format!("can't read {}: {err}", path.display())))
+}
 
-        // Another example
-        let input2 = "test";
-        println!("\nInput string: \"{input2}\"");
-        let matches2 = trie.find_matches(input2);
-        println!("\nFound matching regex patterns:");
-        if matches2.is_empty() {
-            println!("  No matches found.");
-        } else {
-            for m in matches2 {
-                println!("  - {m}");
-            }
-        }
+impl PatternSource {
+    /// Load the pattern file, one pattern per line, skipping empty lines.
+    ///
+    /// ## Errors
+    ///
+    /// If the file can't be read
+    fn load_patterns(&self) -> io::Result<Vec<String>> {
+        let path = self
+            .patterns
+            .as_deref()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "missing pattern file"))?;
+        let content = String::from_utf8(read_file(path)?)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        Ok(content
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(ToString::to_string)
+            .collect())
     }
 
-    {
-        // Create a new RegexTrie.
-        let mut trie = RegexTrie::new();
-
-        // Insert the regex patterns. Note that insert can now fail if the regex is invalid.
-        let patterns = vec![
-            "https://google.com/.*",
-            "https://google.com/user/.*",
-            "https://google.com/user/.*/photos/*",
-            "https://facebook.com",
-        ];
-
-        println!("Inserting patterns...");
-        for pattern in patterns {
-            if let Err(e) = trie.insert(pattern) {
-                eprintln!("Failed to insert pattern '{pattern}': {e}");
-            }
-        }
-
-        // Another example
-        let input2 = "https://google.com/user/1234";
-        println!("\nInput string: \"{input2}\"");
-        let matches2 = trie.find_matches(input2);
-        println!("\nFound matching regex patterns:");
-        if matches2.is_empty() {
-            println!("  No matches found.");
-        } else {
-            for m in matches2 {
-                println!("  - {m}");
-            }
+    /// Build the trie from the pattern file, or load it from the snapshot.
+    ///
+    /// ## Errors
+    ///
+    /// If the file can't be read, or a pattern can't be compiled
+    fn load(&self) -> Result<RegexTrie, Box<dyn Error>> {
+        if let Some(snapshot) = &self.from_snapshot {
+            return Ok(RegexTrie::from_bytes(&read_file(snapshot)?)?);
         }
+        Ok(RegexTrie::from(&self.load_patterns()?)?)
     }
+}
 
-    {
-        // Create a new RegexTrie.
-        let mut trie = RegexTrie::new();
-
-        // Insert the regex patterns. Note that insert can now fail if the regex is invalid.
-        let patterns = vec![".*", ".*ac", ".*bc", ".*abcd"];
-
-        println!("Inserting patterns...");
-        for pattern in patterns {
-            if let Err(e) = trie.insert(pattern) {
-                eprintln!("Failed to insert pattern '{pattern}': {e}");
-            }
-        }
-
-        // dbg!(&trie);
-
-        // Another example
-        let input2 = "https://google.com/user/1234";
-        println!("\nInput string: \"{input2}\"");
-        let matches2 = trie.find_matches(input2);
-        println!("\nFound matching regex patterns:");
-        if matches2.is_empty() {
-            println!("  No matches found.");
-        } else {
-            for m in matches2 {
-                println!("  - {m}");
-            }
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let result = match &cli.command {
+        None => matching::run(&cli.matching),
+        Some(Command::Grep(args)) => grep::run(args),
+        Some(Command::Repl(args)) => repl::run(args),
+        Some(Command::Compile(args)) => compile::run(args),
+        Some(Command::Validate(args)) => validate::run(args),
+    };
+
+    match result {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::from(1),
+        Err(err) => {
+            let _ = writeln!(io::stderr(), "regextrie: {err}");
+            ExitCode::from(2)
         }
     }
 }
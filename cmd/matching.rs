@@ -0,0 +1,154 @@
+use std::{
+    error::Error,
+    io::{self, BufRead, Write},
+};
+
+use clap::{Args, ValueEnum};
+use regextrie::RegexTrie;
+use serde::Serialize;
+
+use crate::PatternSource;
+
+/// Output format of the results
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    /// One matching pattern per line
+    #[default]
+    Plain,
+    /// A single JSON array with one object per input
+    Json,
+    /// One JSON object per input and per line
+    Jsonl,
+}
+
+/// Structured result for a single input
+#[derive(Debug, Serialize)]
+struct QueryResult {
+    /// Matched input
+    input: String,
+    /// All the matching patterns
+    matches: Vec<String>,
+    /// The best matching pattern
+    best: Option<String>,
+}
+
+/// Arguments of the default command, matching inputs against the patterns
+#[derive(Debug, Args)]
+#[expect(clippy::struct_excessive_bools, reason = "independent cli flags")]
+pub struct MatchArgs {
+    /// Where to load the patterns from
+    #[command(flatten)]
+    source: PatternSource,
+    /// Print only the best matching pattern (default)
+    #[arg(long, conflicts_with = "all")]
+    best: bool,
+    /// Print all the matching patterns
+    #[arg(long)]
+    all: bool,
+    /// Output format. Structured formats always report all the matches
+    /// along with the best one
+    #[arg(short, long, value_enum, default_value_t)]
+    output: OutputFormat,
+    /// Only print the number of matching patterns of each input
+    #[arg(short, long, conflicts_with_all = ["best", "output"])]
+    count: bool,
+    /// Don't print anything, only report through the exit status whether any
+    /// input matched
+    #[arg(short, long, conflicts_with_all = ["best", "all", "output", "count"])]
+    quiet: bool,
+    /// Inputs to match against the patterns. Without any (or with `-`),
+    /// inputs are read from stdin, one per line
+    queries: Vec<String>,
+}
+
+/// Match every query against the trie and print the results, returning
+/// whether any query matched.
+///
+/// ## Errors
+///
+/// If a query can't be read, or the output can't be written
+fn match_queries(
+    args: &MatchArgs,
+    trie: &RegexTrie,
+    queries: impl Iterator<Item = io::Result<String>>,
+    show_input: bool,
+) -> Result<bool, Box<dyn Error>> {
+    let mut out = io::stdout().lock();
+    let mut any_match = false;
+    let mut json_results = Vec::new();
+
+    for query in queries {
+        let query = query?;
+        let best = trie.find_best_match(&query);
+        any_match |= best.is_some();
+        if args.quiet {
+            if any_match {
+                // Like `grep -q`, no need to go further
+                return Ok(true);
+            }
+            continue;
+        }
+
+        let matches = if args.all || args.count || args.output != OutputFormat::Plain {
+            trie.find_matches(&query)
+        } else {
+            best.iter().cloned().collect()
+        };
+
+        match args.output {
+            OutputFormat::Plain if args.count => {
+                if show_input {
+                    write!(out, "{query}\t")?;
+                }
+                writeln!(out, "{}", matches.len())?;
+            }
+            OutputFormat::Plain => {
+                for pattern in matches {
+                    // Like grep with many files, only prefix by the input when
+                    // ambiguous.
+                    if show_input {
+                        write!(out, "{query}\t")?;
+                    }
+                    writeln!(out, "{pattern}")?;
+                }
+            }
+            OutputFormat::Json => json_results.push(QueryResult {
+                input: query,
+                matches,
+                best,
+            }),
+            OutputFormat::Jsonl => {
+                let result = QueryResult {
+                    input: query,
+                    matches,
+                    best,
+                };
+                serde_json::to_writer(&mut out, &result)?;
+                writeln!(out)?;
+            }
+        }
+    }
+
+    if args.output == OutputFormat::Json {
+        serde_json::to_writer(&mut out, &json_results)?;
+        writeln!(out)?;
+    }
+
+    Ok(any_match)
+}
+
+/// Run the default command, returning whether any query matched.
+///
+/// ## Errors
+///
+/// If the patterns can't be loaded, or the output can't be written
+pub fn run(args: &MatchArgs) -> Result<bool, Box<dyn Error>> {
+    let trie = args.source.load()?;
+
+    if args.queries.is_empty() || args.queries == ["-"] {
+        match_queries(args, &trie, io::stdin().lock().lines(), true)
+    } else {
+        let queries = args.queries.iter().cloned().map(Ok);
+        match_queries(args, &trie, queries, args.queries.len() > 1)
+    }
+}
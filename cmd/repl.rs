@@ -0,0 +1,63 @@
+use std::{
+    error::Error,
+    io::{self, BufRead, Write},
+};
+
+use clap::Args;
+
+use crate::PatternSource;
+
+/// Arguments of the repl command
+#[derive(Debug, Args)]
+pub struct ReplArgs {
+    /// Where to load the patterns from
+    #[command(flatten)]
+    source: PatternSource,
+    /// Also print how each input walks the trie and which candidates are
+    /// evaluated
+    #[arg(short, long)]
+    explain: bool,
+}
+
+/// Run an interactive prompt, printing all the matches and the best one of
+/// every typed input, until EOF or `:quit`. Returns whether any input
+/// matched.
+///
+/// ## Errors
+///
+/// If the patterns can't be loaded, or the prompt can't be read or written
+pub fn run(args: &ReplArgs) -> Result<bool, Box<dyn Error>> {
+    let trie = args.source.load()?;
+    let mut out = io::stdout().lock();
+    let mut lines = io::stdin().lock().lines();
+    let mut any_match = false;
+
+    loop {
+        write!(out, "> ")?;
+        out.flush()?;
+        let Some(input) = lines.next().transpose()? else {
+            writeln!(out)?;
+            break;
+        };
+        if input == ":quit" || input == ":q" {
+            break;
+        }
+
+        for pattern in &trie.find_matches(&input) {
+            writeln!(out, "  - {pattern}")?;
+        }
+        match trie.find_best_match(&input) {
+            Some(best) => {
+                any_match = true;
+                writeln!(out, "best: {best}")?;
+            }
+            None => writeln!(out, "no match")?,
+        }
+
+        if args.explain {
+            writeln!(out, "{}", trie.explain(&input))?;
+        }
+    }
+
+    Ok(any_match)
+}
@@ -0,0 +1,76 @@
+use std::{
+    error::Error,
+    fs,
+    io::{self, Write},
+    path::PathBuf,
+};
+
+use clap::Args;
+use regextrie::RegexTrie;
+
+/// Arguments of the validate command
+#[derive(Debug, Args)]
+pub struct ValidateArgs {
+    /// Also fail on warnings
+    #[arg(short, long)]
+    strict: bool,
+    /// Pattern files to check, one pattern per line
+    #[arg(required = true)]
+    files: Vec<PathBuf>,
+}
+
+/// Innermost reason of an error, as the compilation ones are wrapped a few
+/// levels deep (DFA build, NFA build) before the actual syntax error.
+fn reason(err: &dyn Error) -> String {
+    let mut err = err;
+    while let Some(source) = err.source() {
+        err = source;
+    }
+    err.to_string()
+}
+
+/// Compile every pattern of the files, reporting each failure with its line
+/// number and reason, and warning about patterns without any literal prefix.
+/// Returns whether the files are valid.
+///
+/// ## Errors
+///
+/// If a file can't be read
+pub fn run(args: &ValidateArgs) -> Result<bool, Box<dyn Error>> {
+    // Reports go to stderr, like compiler diagnostics.
+    let mut out = io::stderr().lock();
+    let mut errors = 0_usize;
+    let mut warnings = 0_usize;
+
+    for path in &args.files {
+        let content = fs::read_to_string(path)
+            .map_err(|err| format!("can't read {}: {err}", path.display()))?;
+        // A failed insertion leaves the trie untouched, so a single one is
+        // enough for the whole file.
+        let mut trie = RegexTrie::new();
+        for (index, pattern) in content.lines().enumerate() {
+            if pattern.is_empty() {
+                continue;
+            }
+
+            let location = format!("{}:{}", path.display(), index + 1);
+            if let Err(err) = trie.insert(pattern) {
+                errors += 1;
+                writeln!(out, "{location}: error: {pattern:?}: {}", reason(&err))?;
+            } else if RegexTrie::literal_prefix(pattern).is_empty() {
+                warnings += 1;
+                writeln!(
+                    out,
+                    "{location}: warning: {pattern:?} has no literal prefix, it will be \
+                     evaluated against every input"
+                )?;
+            }
+        }
+    }
+
+    if errors > 0 || warnings > 0 {
+        writeln!(out, "{errors} error(s), {warnings} warning(s)")?;
+    }
+
+    Ok(errors == 0 && (!args.strict || warnings == 0))
+}
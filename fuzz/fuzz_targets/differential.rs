@@ -0,0 +1,104 @@
+//! Differential fuzzing between the trie and the naive reference matcher.
+//!
+//! Run with `cargo +nightly fuzz run differential`.
+#![no_main]
+
+use arbitrary::{Arbitrary, Result, Unstructured};
+use libfuzzer_sys::fuzz_target;
+use regextrie::{RegexTrie, reference::NaiveMatcher};
+
+/// Building blocks for patterns. They are always valid regex on their own,
+/// so any pattern made of them should compile, and they focus on what the
+/// trie prefix extraction has to deal with: plain literals, escaped
+/// meta-characters, class escapes read differently by plain patterns, and
+/// regex constructs stopping the literal prefix.
+const ATOMS: &[&str] = &[
+    "a", "b", "c", "d", "1", "/", "-", ":", "\\.", "\\*", "\\+", "\\?", "\\(", "\\)", "\\[", "\\]",
+    "\\{", "\\}", "\\d", "\\w", "\\\\", ".", "[a-c]", "[^a]", "(ab|c)", "(a)",
+];
+
+/// Building blocks which can't be quantified
+const BARE_ATOMS: &[&str] = &["|", "^", "$"];
+
+/// Quantifiers which can follow an atom
+const QUANTIFIERS: &[&str] = &["", "", "", "*", "+", "?", "{1,2}"];
+
+/// Characters used to build inputs, covering all the literal atoms
+const INPUT_CHARS: &[char] = &[
+    'a', 'b', 'c', 'd', '1', '/', '-', ':', '.', '*', '+', '?', '(', ')', '[', ']', '{', '}', '\\',
+    '|', '^', '$',
+];
+
+/// Build an arbitrary pattern from the atoms.
+fn arbitrary_pattern(u: &mut Unstructured<'_>) -> Result<String> {
+    let mut pattern = String::new();
+    for _ in 0..u.int_in_range(0..=6)? {
+        if u.ratio(1, 8)? {
+            pattern.push_str(u.choose(BARE_ATOMS)?);
+        } else {
+            pattern.push_str(u.choose(ATOMS)?);
+            pattern.push_str(u.choose(QUANTIFIERS)?);
+        }
+    }
+    Ok(pattern)
+}
+
+/// A set of distinct patterns, and an input to match against them
+#[derive(Debug)]
+struct Case {
+    /// Patterns to insert
+    patterns: Vec<String>,
+    /// Input to match
+    input: String,
+}
+
+impl<'a> Arbitrary<'a> for Case {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let mut patterns = Vec::new();
+        for _ in 0..u.int_in_range(1..=8)? {
+            let pattern = arbitrary_pattern(u)?;
+            if !patterns.contains(&pattern) {
+                patterns.push(pattern);
+            }
+        }
+
+        // Inputs are either built from scratch, or a pattern, as is or
+        // unescaped, so literal paths in the trie are exercised.
+        let input = if u.arbitrary()? {
+            let base = u.choose(&patterns)?;
+            if u.arbitrary()? {
+                base.clone()
+            } else {
+                base.replace('\\', "")
+            }
+        } else {
+            let mut input = String::new();
+            for _ in 0..u.int_in_range(0..=12)? {
+                input.push(*u.choose(INPUT_CHARS)?);
+            }
+            input
+        };
+
+        Ok(Self { patterns, input })
+    }
+}
+
+fuzz_target!(|case: Case| {
+    let trie = RegexTrie::from(&case.patterns);
+    let naive = NaiveMatcher::from(&case.patterns);
+    let (Ok(trie), Ok(naive)) = (trie, naive) else {
+        panic!("both should compile: {case:?}");
+    };
+
+    let mut expected = naive.find_matches(&case.input);
+    let mut result = trie.find_matches(&case.input);
+    expected.sort();
+    result.sort();
+    assert_eq!(expected, result, "{case:?}");
+
+    // Ties may be broken differently, but the best match must be one of the
+    // matches.
+    let best = trie.find_best_match(&case.input);
+    assert_eq!(naive.find_best_match(&case.input).is_some(), best.is_some());
+    assert!(best.is_none_or(|best| expected.contains(&best)), "{case:?}");
+});
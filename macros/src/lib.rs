@@ -0,0 +1,51 @@
+//! `regextrie!` macro, building a `RegexTrie` from a static set of patterns
+//! checked at compile time.
+
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{LitStr, Token, parse::Parser, punctuated::Punctuated};
+
+/// Special characters making a pattern a regex, as in the trie.
+const SPECIALS: &str = ".?*+()[]{}";
+
+/// Builds a `regextrie::RegexTrie` from a list of string literals, e.g.
+/// `regextrie!{ "https://google.com/.*", "https://yahoo.com" }`.
+///
+/// Regex patterns are parsed at compile time, so an invalid static rule set
+/// fails the build, pointing at the faulty pattern, instead of failing at
+/// startup. The DFAs are still built at runtime.
+#[proc_macro]
+pub fn regextrie(input: TokenStream) -> TokenStream {
+    let parser = Punctuated::<LitStr, Token![,]>::parse_terminated;
+    let patterns = match parser.parse(input) {
+        Ok(patterns) => patterns,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    for pattern in &patterns {
+        if let Err(err) = check(&pattern.value()) {
+            return syn::Error::new(pattern.span(), err)
+                .to_compile_error()
+                .into();
+        }
+    }
+
+    let patterns = patterns.iter();
+    quote! {
+        ::regextrie::RegexTrie::from(&[#(::std::string::String::from(#patterns)),*])
+            .expect("patterns are checked at compile time")
+    }
+    .into()
+}
+
+/// Checks a pattern can be compiled, returning the error to report if not.
+/// Plain patterns (without special characters) are always valid.
+fn check(pattern: &str) -> Result<(), String> {
+    if !pattern.contains(|ch| SPECIALS.contains(ch)) {
+        return Ok(());
+    }
+    regex_syntax::Parser::new()
+        .parse(pattern)
+        .map(|_| ())
+        .map_err(|err| format!("invalid regextrie pattern: {err}"))
+}
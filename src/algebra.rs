@@ -0,0 +1,99 @@
+use std::{borrow::Cow, sync::Arc};
+
+use crate::{
+    PatternId, RegexTrie, alternation::NodeAlternations, memo::CandidateMemo,
+    options::stored_prefix,
+};
+
+impl RegexTrie {
+    /// New trie holding the patterns of `self` which are also in `other`,
+    /// reusing their compiled DFAs. Scores and settings are the ones of
+    /// `self`.
+    #[must_use]
+    pub fn intersection(&self, other: &Self) -> Self {
+        self.filtered(|pattern| other.id_of(pattern).is_some(), false)
+    }
+
+    /// New trie holding the patterns of `self` which aren't in `other`,
+    /// reusing their compiled DFAs. Scores and settings are the ones of
+    /// `self`.
+    #[must_use]
+    pub fn difference(&self, other: &Self) -> Self {
+        self.filtered(|pattern| other.id_of(pattern).is_none(), false)
+    }
+
+    /// New trie holding the patterns of `self` accepted by `keep`, in the same
+    /// order. Their DFAs are copied instead of being compiled again, and their
+    /// scores, options, tags, weights, expirations, verbatim strings, the scorer
+    /// and the settings (match mode, selection policy, tie break, exact literal
+    /// preference, empty patterns and inputs, literal detection, UTF-8 handling,
+    /// regex deduplication, DFA memory budget and size warning, pattern capacity,
+    /// fallback engine, DFA settings, extra meta-characters, candidate memoization,
+    /// node alternation, trigram and suffix indices) are the ones of `self`. With
+    /// `keep_ids`, the ids of the patterns left out (or already removed) stay
+    /// reserved, so the others keep theirs.
+    pub(crate) fn filtered(&self, keep: impl Fn(&str) -> bool, keep_ids: bool) -> Self {
+        let mut trie = Self::new_with_shared_scorer(Arc::clone(&self.scorer))
+            .with_match_mode(self.match_mode)
+            .with_selection_policy(self.selection_policy)
+            .with_tie_break(self.tie_break())
+            .with_prefer_exact_literal(self.prefer_exact_literal)
+            .with_empty_patterns(self.empty_patterns)
+            .with_empty_inputs(self.empty_inputs)
+            .with_literal_detection(self.literal_detection)
+            .with_utf8_handling(self.utf8_handling)
+            .with_regex_dedup(self.canonical_regexes.is_some())
+            .with_candidate_memoization(
+                self.candidate_memo
+                    .as_ref()
+                    .map_or(0, CandidateMemo::capacity),
+            )
+            .with_node_alternation(
+                self.node_alternations
+                    .as_ref()
+                    .map_or(0, NodeAlternations::threshold),
+            )
+            .with_trigram_index(self.trigram_index.is_some())
+            .with_suffix_index(self.suffix_index.is_some());
+        trie.max_total_dfa_bytes = self.max_total_dfa_bytes;
+        trie.max_patterns = self.max_patterns;
+        trie.fallback_engine = self.fallback_engine;
+        trie.dfa_config = self.dfa_config;
+        trie.extra_specials.clone_from(&self.extra_specials);
+        for (index, slot) in self.patterns.iter().enumerate() {
+            let Some((pattern, compiled_index)) =
+                slot.as_ref().filter(|(pattern, _)| keep(pattern))
+            else {
+                if keep_ids {
+                    trie.add_removed_pattern();
+                }
+                continue;
+            };
+            let options = self.options(PatternId::new(index));
+            let prefix = stored_prefix(pattern, options.case_insensitive, &self.extra_specials);
+            let compiled = compiled_index.map(|compiled_index| {
+                let (_, dfa, score) = &self.compiled_patterns[compiled_index];
+                (dfa.clone(), *score)
+            });
+            trie.add_pattern(Cow::Borrowed(pattern), &prefix, compiled, options);
+            if let Some(string) = self.verbatim_literals.string(pattern) {
+                trie.record_verbatim(pattern, string);
+            }
+            if let Some(id) = trie.id_of(pattern) {
+                for tag in self.tags(PatternId::new(index)) {
+                    trie.add_tag(id, tag);
+                }
+                if let Some(&weight) = self.tie_breaker.weights.get(&PatternId::new(index)) {
+                    trie.set_weight(id, weight);
+                }
+                if let Some(&expiration) = self.expirations.get(&PatternId::new(index)) {
+                    trie.expirations.insert(id, expiration);
+                }
+            }
+        }
+        trie.refresh_alternations();
+        // Set last, not to warn again about the patterns of `self`.
+        trie.dfa_size_warning.clone_from(&self.dfa_size_warning);
+        trie
+    }
+}
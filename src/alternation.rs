@@ -0,0 +1,200 @@
+use std::collections::{HashMap, HashSet};
+
+use regex_automata::{Anchored, Input, MatchKind, PatternID, PatternSet, meta};
+
+use crate::{
+    MatchMode, RegexTrie,
+    options::regex_source,
+    regex_trie::{Scratch, TrieNode},
+};
+
+/// Regexes of the nodes holding many of them, combined into a single one per
+/// node, reporting which of them match: a query runs it once instead of
+/// every regex of the node.
+#[derive(Debug)]
+pub(crate) struct NodeAlternations {
+    /// Minimum number of regexes a node must hold for them to be combined
+    threshold: usize,
+    /// Combined regex of every node holding enough regexes, by id
+    regexes: HashMap<usize, meta::Regex>,
+    /// Id of the combined regex of every compiled pattern, and its position
+    /// in it, indexed like `compiled_patterns`
+    slots: Vec<Option<(usize, PatternID)>>,
+    /// Id of the next combined regex
+    next_id: usize,
+    /// Literal prefix of the nodes whose regexes changed since they were
+    /// last combined
+    stale: HashSet<Vec<(char, bool)>>,
+}
+
+impl NodeAlternations {
+    /// No combined regex yet, for the nodes of at least `threshold` regexes.
+    fn new(threshold: usize) -> Self {
+        Self {
+            threshold,
+            regexes: HashMap::default(),
+            slots: Vec::default(),
+            next_id: 0,
+            stale: HashSet::default(),
+        }
+    }
+
+    /// Minimum number of regexes of a combined node.
+    pub(crate) const fn threshold(&self) -> usize {
+        self.threshold
+    }
+}
+
+/// Compiled indices of all the nodes of the subtree holding at least
+/// `threshold` regexes.
+fn collect_nodes(node: &TrieNode, threshold: usize, nodes: &mut Vec<Vec<usize>>) {
+    if node.pattern_indices.len() >= threshold {
+        nodes.push(node.pattern_indices.clone());
+    }
+    for child in node.children.values() {
+        collect_nodes(child, threshold, nodes);
+    }
+}
+
+impl RegexTrie {
+    /// Combines the regexes of every node holding at least `threshold` of
+    /// them into a single regex reporting which ones match (or stops with
+    /// 0): a query reaching such a node runs it once, instead of running
+    /// every regex, which is only run again to check that its match spans
+    /// the whole input in `MatchMode::Full`. Inserting more regexes in a
+    /// node combines them again, trading insertion time for fewer regex runs
+    /// by query. Combined regexes use the fallback engine, and don't count in
+    /// the DFA memory budget.
+    #[must_use]
+    pub fn with_node_alternation(mut self, threshold: usize) -> Self {
+        self.node_alternations = (threshold > 0).then(|| NodeAlternations::new(threshold));
+        self.rebuild_alternations();
+        self
+    }
+
+    /// Number of nodes whose regexes are combined.
+    #[must_use]
+    pub fn combined_nodes(&self) -> usize {
+        self.node_alternations
+            .as_ref()
+            .map_or(0, |alternations| alternations.regexes.len())
+    }
+
+    /// Remembers that the regexes of the node of the given literal prefix
+    /// have to be combined again, by `refresh_alternations`.
+    pub(crate) fn mark_alternation_stale(&mut self, prefix: &[(char, bool)]) {
+        if let Some(alternations) = &mut self.node_alternations {
+            alternations.stale.insert(prefix.to_vec());
+        }
+    }
+
+    /// Combines again the regexes of the nodes modified by the last
+    /// insertions.
+    pub(crate) fn refresh_alternations(&mut self) {
+        let Some(alternations) = &mut self.node_alternations else {
+            return;
+        };
+        let threshold = alternations.threshold;
+        let stale = std::mem::take(&mut alternations.stale);
+        for prefix in stale {
+            let node = prefix
+                .iter()
+                .try_fold(&self.root, |node, (ch, _)| node.children.get(ch));
+            if let Some(node) = node.filter(|node| node.pattern_indices.len() >= threshold) {
+                let indices = node.pattern_indices.clone();
+                self.combine(&indices);
+            }
+        }
+    }
+
+    /// Combines again the regexes of every node, once the compiled patterns
+    /// have been moved.
+    pub(crate) fn rebuild_alternations(&mut self) {
+        let Some(alternations) = &mut self.node_alternations else {
+            return;
+        };
+        alternations.regexes.clear();
+        alternations.slots.clear();
+        alternations.stale.clear();
+        let mut nodes = Vec::new();
+        collect_nodes(&self.root, alternations.threshold, &mut nodes);
+        for indices in nodes {
+            self.combine(&indices);
+        }
+    }
+
+    /// Combines the regexes of the given compiled indices, those of a node,
+    /// replacing their previous combined regex. They're left uncombined if
+    /// it can't be built.
+    fn combine(&mut self, indices: &[usize]) {
+        let sources: Vec<String> = indices
+            .iter()
+            .map(|&index| {
+                let pattern = &self.compiled_patterns[index].0;
+                let case_insensitive = self
+                    .ids
+                    .get(pattern)
+                    .is_some_and(|&id| self.is_case_insensitive(id));
+                let flags = if case_insensitive { "?i" } else { "?" };
+                format!("({flags}:{})", regex_source(pattern, &self.extra_specials))
+            })
+            .collect();
+        let regex = meta::Regex::builder()
+            .configure(meta::Config::new().match_kind(MatchKind::All))
+            .build_many(&sources);
+
+        let Some(alternations) = &mut self.node_alternations else {
+            return;
+        };
+        alternations
+            .slots
+            .resize(self.compiled_patterns.len(), None);
+        for &index in indices {
+            if let Some((id, _)) = alternations.slots[index].take() {
+                alternations.regexes.remove(&id);
+            }
+        }
+        let Ok(regex) = regex else {
+            return;
+        };
+        let id = alternations.next_id;
+        alternations.next_id += 1;
+        alternations.regexes.insert(id, regex);
+        for (position, &index) in indices.iter().enumerate() {
+            alternations.slots[index] =
+                PatternID::new(position).ok().map(|position| (id, position));
+        }
+    }
+
+    /// Whether the regex of the given compiled index matches a prefix of the
+    /// input, according to the combined regex of its node, run once per
+    /// query. `None` if its node isn't combined.
+    fn alternation_match(&self, index: usize, input: &[u8], scratch: &Scratch) -> Option<bool> {
+        let alternations = self.node_alternations.as_ref()?;
+        let (id, position) = (*alternations.slots.get(index)?)?;
+        let mut sets = scratch.alternation_sets.borrow_mut();
+        if let Some((_, set)) = sets.iter().find(|(set_id, _)| *set_id == id) {
+            return Some(set.contains(position));
+        }
+        let regex = alternations.regexes.get(&id)?;
+        let mut set = PatternSet::new(regex.pattern_len());
+        regex.which_overlapping_matches(&Input::new(input).anchored(Anchored::Yes), &mut set);
+        let matched = set.contains(position);
+        sets.push((id, set));
+        Some(matched)
+    }
+
+    /// Same as `is_match_cached`, for the candidate of the given compiled
+    /// index, ruled out by the combined regex of its node first, if any. In
+    /// prefix mode, its verdict is final.
+    pub(crate) fn candidate_matches(&self, index: usize, input: &[u8], scratch: &Scratch) -> bool {
+        match self.alternation_match(index, input, scratch) {
+            Some(false) => false,
+            Some(true) if self.match_mode == MatchMode::Prefix => true,
+            _ => {
+                let (pattern, dfa, _) = &self.compiled_patterns[index];
+                self.is_match_cached(pattern, dfa, input, &scratch.meta_caches)
+            }
+        }
+    }
+}
@@ -0,0 +1,212 @@
+use std::{
+    borrow::Cow,
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        Arc, Condvar, Mutex, PoisonError, RwLock, RwLockReadGuard,
+        mpsc::{Receiver, Sender, channel},
+    },
+    thread::JoinHandle,
+};
+
+use crate::{RegexTrie, RegexTrieError};
+
+/// State shared with the worker thread.
+#[derive(Debug, Default)]
+struct Shared {
+    /// Trie holding the patterns compiled so far
+    trie: RwLock<RegexTrie>,
+    /// Number of patterns enqueued and not compiled yet
+    pending: Mutex<usize>,
+    /// Notified when there's no pending pattern left
+    idle: Condvar,
+    /// Patterns which couldn't be inserted, with their error
+    errors: Mutex<Vec<(String, RegexTrieError)>>,
+}
+
+impl Shared {
+    /// Compiles and inserts the enqueued patterns, until the queue is closed.
+    /// Patterns are compiled under a read lock, so queries aren't blocked,
+    /// and only added under the write lock. A pattern whose insert panics,
+    /// e.g. in a custom scorer, is reported as an error, and the next ones
+    /// are still inserted.
+    fn run(&self, receiver: &Receiver<String>) {
+        while let Ok(pattern) = receiver.recv() {
+            let inserted = panic::catch_unwind(AssertUnwindSafe(|| self.insert(&pattern)))
+                .unwrap_or_else(|_| {
+                    Err(RegexTrieError::Inconsistent(
+                        "panicked while inserting".to_string(),
+                    ))
+                });
+            if let Err(err) = inserted {
+                self.errors
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .push((pattern, err));
+            }
+
+            let mut pending = self.pending.lock().unwrap_or_else(PoisonError::into_inner);
+            *pending -= 1;
+            if *pending == 0 {
+                self.idle.notify_all();
+            }
+        }
+    }
+
+    /// Compiles a pattern under the read lock, and adds it under the write
+    /// lock.
+    ///
+    /// ## Errors
+    ///
+    /// If the pattern can't be compiled
+    fn insert(&self, pattern: &str) -> Result<(), RegexTrieError> {
+        let trie = self.trie.read().unwrap_or_else(PoisonError::into_inner);
+        let prepared = trie.prepare_many(vec![Cow::Borrowed(pattern)]);
+        drop(trie);
+        self.trie
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .commit_many(prepared?);
+        Ok(())
+    }
+}
+
+/// A trie whose inserts are compiled by a background thread, so live rule
+/// pushes don't block the caller for the DFA compilation. Queries use the
+/// patterns compiled so far, and `flush` waits for the others.
+///
+/// Patterns are compiled one by one, in insertion order, and the ones which
+/// can't be inserted are reported by `take_errors`. Dropping the trie waits
+/// for the pending patterns to be compiled.
+#[derive(Debug)]
+pub struct BackgroundRegexTrie {
+    /// State shared with the worker
+    shared: Arc<Shared>,
+    /// Queue of the patterns to compile, `None` once closed
+    sender: Option<Sender<String>>,
+    /// Worker thread, `None` once joined
+    worker: Option<JoinHandle<()>>,
+}
+
+impl Default for BackgroundRegexTrie {
+    fn default() -> Self {
+        Self::new(RegexTrie::new())
+    }
+}
+
+impl BackgroundRegexTrie {
+    /// Starts a worker thread compiling the patterns inserted into the given
+    /// trie, with its settings.
+    #[must_use]
+    pub fn new(trie: RegexTrie) -> Self {
+        let shared = Arc::new(Shared {
+            trie: RwLock::new(trie),
+            ..Shared::default()
+        });
+        let (sender, receiver) = channel();
+        let worker_shared = Arc::clone(&shared);
+        let worker = std::thread::spawn(move || worker_shared.run(&receiver));
+        Self {
+            shared,
+            sender: Some(sender),
+            worker: Some(worker),
+        }
+    }
+
+    /// Enqueues a pattern, to be compiled and inserted by the worker thread.
+    /// Returns immediately.
+    pub fn insert(&self, pattern: impl Into<String>) {
+        let Some(sender) = &self.sender else {
+            return;
+        };
+        *self
+            .shared
+            .pending
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) += 1;
+        if let Err(err) = sender.send(pattern.into()) {
+            // The worker is gone, which only happens if it panicked.
+            *self
+                .shared
+                .pending
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner) -= 1;
+            self.shared
+                .errors
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push((
+                    err.0,
+                    RegexTrieError::Inconsistent("worker stopped".to_string()),
+                ));
+        }
+    }
+
+    /// Number of enqueued patterns not compiled yet.
+    #[must_use]
+    pub fn pending(&self) -> usize {
+        *self
+            .shared
+            .pending
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Blocks until all the enqueued patterns are compiled.
+    pub fn flush(&self) {
+        let pending = self
+            .shared
+            .pending
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        drop(
+            self.shared
+                .idle
+                .wait_while(pending, |pending| *pending > 0)
+                .unwrap_or_else(PoisonError::into_inner),
+        );
+    }
+
+    /// Takes the patterns which couldn't be inserted since the last call,
+    /// with their error.
+    pub fn take_errors(&self) -> Vec<(String, RegexTrieError)> {
+        std::mem::take(
+            &mut *self
+                .shared
+                .errors
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner),
+        )
+    }
+
+    /// Read access to the trie, with the patterns compiled so far, for the
+    /// whole query API. The worker can't insert while it's held.
+    pub fn read(&self) -> RwLockReadGuard<'_, RegexTrie> {
+        self.shared
+            .trie
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Same as `RegexTrie::find_matches`, with the patterns compiled so far.
+    #[must_use]
+    pub fn find_matches(&self, input: &str) -> Vec<String> {
+        self.read().find_matches(input)
+    }
+
+    /// Same as `RegexTrie::find_best_match`, with the patterns compiled so
+    /// far.
+    #[must_use]
+    pub fn find_best_match(&self, input: &str) -> Option<String> {
+        self.read().find_best_match(input)
+    }
+}
+
+impl Drop for BackgroundRegexTrie {
+    fn drop(&mut self) {
+        // Closing the queue stops the worker once it's drained.
+        self.sender = None;
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
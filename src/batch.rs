@@ -0,0 +1,76 @@
+use std::{
+    future::Future,
+    panic,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll, ready},
+};
+
+use futures_core::Stream;
+use tokio::task::JoinHandle;
+
+use crate::RegexTrie;
+
+/// Stream of `RegexTrie::find_best_matches_stream`, yielding the best match
+/// of every input of the underlying stream, in order.
+#[derive(Debug)]
+#[must_use = "streams do nothing unless polled"]
+pub struct BestMatchStream<S> {
+    /// Trie the inputs are matched against
+    trie: Arc<RegexTrie>,
+    /// Inputs to match
+    inputs: S,
+    /// Matching of the current input, on the blocking pool
+    pending: Option<JoinHandle<Option<String>>>,
+}
+
+impl<S: Stream<Item = String> + Unpin> Stream for BestMatchStream<S> {
+    type Item = Option<String>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        if this.pending.is_none() {
+            let Some(input) = ready!(Pin::new(&mut this.inputs).poll_next(cx)) else {
+                return Poll::Ready(None);
+            };
+            let trie = Arc::clone(&this.trie);
+            this.pending = Some(tokio::task::spawn_blocking(move || {
+                trie.find_best_match(&input)
+            }));
+        }
+        let Some(pending) = this.pending.as_mut() else {
+            return Poll::Ready(None);
+        };
+        let result = ready!(Pin::new(pending).poll(cx));
+        this.pending = None;
+        match result {
+            Ok(best) => Poll::Ready(Some(best)),
+            // A panic while matching is raised by the stream.
+            Err(err) if err.is_panic() => panic::resume_unwind(err.into_panic()),
+            // The runtime is shutting down.
+            Err(_) => Poll::Ready(None),
+        }
+    }
+}
+
+impl RegexTrie {
+    /// Finds the best match of every input of a stream on the tokio blocking
+    /// pool (`spawn_blocking`), off the async executor threads, and returns a
+    /// stream of them, in order. It must be polled from a tokio runtime.
+    ///
+    /// Meant for consumers of an async stream of inputs (e.g. a Kafka topic):
+    /// the next input is only pulled once the best match of the previous one
+    /// has been yielded, so a slow matching pushes back on the stream instead
+    /// of piling up inputs. A panic while matching an input is raised when
+    /// polling the stream.
+    pub fn find_best_matches_stream<S>(self: &Arc<Self>, inputs: S) -> BestMatchStream<S>
+    where
+        S: Stream<Item = String> + Unpin,
+    {
+        BestMatchStream {
+            trie: Arc::clone(self),
+            inputs,
+            pending: None,
+        }
+    }
+}
@@ -0,0 +1,55 @@
+use std::{
+    future::poll_fn,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use futures_core::Stream;
+use pretty_assertions::assert_eq;
+
+use crate::RegexTrie;
+
+/// Stream of inputs, all already available
+struct ReadyInputs(std::vec::IntoIter<String>);
+
+impl Stream for ReadyInputs {
+    type Item = String;
+
+    fn poll_next(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Poll::Ready(self.0.next())
+    }
+}
+
+/// Test the best matches of a stream are yielded in order
+#[test]
+fn test_find_best_matches_stream() {
+    let tree = Arc::new(
+        RegexTrie::from(&["/api/.*".to_string(), "/api/users".to_string()]).expect("can't init"),
+    );
+    let inputs = vec![
+        "/api/users".to_string(),
+        "/api/orders".to_string(),
+        "/nope".to_string(),
+    ];
+    let mut stream = tree.find_best_matches_stream(ReadyInputs(inputs.into_iter()));
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .expect("can't build runtime");
+
+    let results = runtime.block_on(async {
+        let mut results = Vec::new();
+        while let Some(best) = poll_fn(|cx| Pin::new(&mut stream).poll_next(cx)).await {
+            results.push(best);
+        }
+        results
+    });
+    assert_eq!(
+        results,
+        vec![
+            Some("/api/users".to_string()),
+            Some("/api/.*".to_string()),
+            None
+        ]
+    );
+}
@@ -0,0 +1,136 @@
+use std::{collections::HashSet, sync::Arc};
+
+use crate::{PatternId, RegexTrie, RegexTrieError, engine::CompiledRegex};
+
+/// Type for the callback warning about a large DFA
+/// 1st arg is the pattern
+/// 2nd arg is the memory taken by its compiled regex, in bytes
+pub type DfaSizeWarningFuncType = Box<dyn Fn(&str, usize) + Send + Sync>;
+/// Callback warning about a large DFA, shared by the tries derived from one
+/// another
+pub(crate) type SharedDfaSizeWarning = Arc<dyn Fn(&str, usize) + Send + Sync>;
+
+impl RegexTrie {
+    /// Caps the memory all the compiled DFAs may take, in bytes: inserts
+    /// which would exceed it fail with `RegexTrieError::BudgetExceeded`,
+    /// leaving the trie untouched. The DFAs of removed patterns count until
+    /// `compact` frees them. Like the match mode, it isn't serialized.
+    #[must_use]
+    pub const fn with_max_total_dfa_bytes(mut self, budget: usize) -> Self {
+        self.max_total_dfa_bytes = Some(budget);
+        self
+    }
+
+    /// Caps the number of patterns the trie may hold: inserts which would
+    /// exceed it fail with `RegexTrieError::CapacityExceeded`, leaving the
+    /// trie untouched. Removed patterns don't count. Like the match mode, it
+    /// isn't serialized.
+    #[must_use]
+    pub const fn with_max_patterns(mut self, capacity: usize) -> Self {
+        self.max_patterns = Some(capacity);
+        self
+    }
+
+    /// Calls `warning` with the pattern and the memory taken by its compiled
+    /// regex, whenever a regex taking more than `threshold` bytes is
+    /// inserted, e.g. to log the pathological rules before they blow the
+    /// memory up. It's only called for the patterns inserted afterwards, and
+    /// isn't serialized.
+    #[must_use]
+    pub fn with_dfa_size_warning(
+        mut self,
+        threshold: usize,
+        warning: DfaSizeWarningFuncType,
+    ) -> Self {
+        self.dfa_size_warning = Some((threshold, Arc::from(warning)));
+        self
+    }
+
+    /// Calls the DFA size warning, if any, for a regex being inserted.
+    pub(crate) fn warn_dfa_size(&self, pattern: &str, dfa: &CompiledRegex) {
+        if let Some((threshold, warning)) = &self.dfa_size_warning {
+            let bytes = dfa.memory_usage();
+            if bytes > *threshold {
+                warning(pattern, bytes);
+            }
+        }
+    }
+
+    /// Memory taken by the compiled regex of every regex pattern, in bytes,
+    /// in insertion order, removed patterns excluded. The DFAs of removed
+    /// patterns still count in `total_dfa_bytes` until `compact` frees them.
+    #[must_use]
+    pub fn dfa_bytes(&self) -> Vec<(PatternId, usize)> {
+        self.patterns
+            .iter()
+            .enumerate()
+            .filter_map(|(index, slot)| {
+                let (_, compiled_index) = slot.as_ref()?;
+                let (_, dfa, _) = &self.compiled_patterns[(*compiled_index)?];
+                Some((PatternId::new(index), dfa.memory_usage()))
+            })
+            .collect()
+    }
+
+    /// Memory taken by all the compiled DFAs, in bytes.
+    #[must_use]
+    pub fn total_dfa_bytes(&self) -> usize {
+        self.compiled_patterns
+            .iter()
+            .map(|(_, dfa, _)| dfa.memory_usage())
+            .sum()
+    }
+
+    /// Verifies that the DFAs of a batch of compiled patterns fit in the
+    /// budget, if any. Known patterns and duplicates of the batch aren't
+    /// counted, since they won't be inserted.
+    ///
+    /// ## Errors
+    ///
+    /// If the budget would be exceeded
+    pub(crate) fn check_dfa_budget<'batch>(
+        &self,
+        batch: impl IntoIterator<Item = (&'batch str, &'batch CompiledRegex)>,
+    ) -> Result<(), RegexTrieError> {
+        let Some(budget) = self.max_total_dfa_bytes else {
+            return Ok(());
+        };
+        let mut seen = HashSet::new();
+        let added: usize = batch
+            .into_iter()
+            .filter(|(pattern, _)| !self.ids.contains_key(*pattern) && seen.insert(*pattern))
+            .map(|(_, dfa)| dfa.memory_usage())
+            .sum();
+        let required = self.total_dfa_bytes() + added;
+        if required > budget {
+            return Err(RegexTrieError::BudgetExceeded { required, budget });
+        }
+        Ok(())
+    }
+
+    /// Verifies that a batch of patterns fits in the maximum pattern count,
+    /// if any. Known patterns and duplicates of the batch aren't counted,
+    /// since they won't be inserted.
+    ///
+    /// ## Errors
+    ///
+    /// If the capacity would be exceeded
+    pub(crate) fn check_capacity<'batch>(
+        &self,
+        batch: impl IntoIterator<Item = &'batch str>,
+    ) -> Result<(), RegexTrieError> {
+        let Some(capacity) = self.max_patterns else {
+            return Ok(());
+        };
+        let mut seen = HashSet::new();
+        let added = batch
+            .into_iter()
+            .filter(|pattern| !self.ids.contains_key(*pattern) && seen.insert(*pattern))
+            .count();
+        let required = self.ids.len() + added;
+        if required > capacity {
+            return Err(RegexTrieError::CapacityExceeded { required, capacity });
+        }
+        Ok(())
+    }
+}
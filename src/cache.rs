@@ -0,0 +1,122 @@
+use std::sync::Arc;
+
+use crate::{
+    PatternId, RegexTrie,
+    engine::MetaCaches,
+    regex_trie::{Matched, Scratch},
+};
+
+/// Scratch memory of the queries, to keep one per thread and pass to the
+/// `*_with_cache` matching calls: the buffers collecting the candidates, and
+/// the search caches of the patterns running on the fallback engine (see
+/// `with_fallback_engine`), are then reused from one query to the other
+/// instead of being allocated for every input. Dense DFAs need none.
+///
+/// A cache can be used with several tries. It keeps the search cache of
+/// every fallback pattern it has run, until `clear` is called.
+///
+/// Once warmed up, the `*_id_with_cache` calls perform no heap allocation:
+/// they return ids instead of pattern strings, in a buffer of the cache.
+/// Only a miss of the candidate memo (see `with_candidate_memoization`), the
+/// first query running a fallback pattern, and breaking a tie with
+/// `TieBreak::Random` or `TieBreak::Weighted` still allocate.
+#[derive(Debug)]
+pub struct QueryCache {
+    /// Buffers of the queries, with enabled search caches
+    scratch: Scratch,
+    /// Ids of the patterns matching the last query
+    ids: Vec<PatternId>,
+}
+
+impl Default for QueryCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl QueryCache {
+    /// Creates a new, empty cache.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            scratch: Scratch {
+                meta_caches: MetaCaches::enabled(),
+                ..Scratch::default()
+            },
+            ids: Vec::new(),
+        }
+    }
+
+    /// Number of fallback patterns whose search cache is kept.
+    #[must_use]
+    pub fn cached_regexes(&self) -> usize {
+        self.scratch.meta_caches.len()
+    }
+
+    /// Drops the search caches, e.g. once the patterns they were created for
+    /// have been removed.
+    pub fn clear(&mut self) {
+        self.scratch.meta_caches.clear();
+    }
+}
+
+impl RegexTrie {
+    /// Same as `find_matches_shared`, reusing the scratch memory of the
+    /// cache.
+    #[must_use]
+    pub fn find_matches_with_cache(&self, input: &str, cache: &mut QueryCache) -> Vec<Arc<str>> {
+        let mut matching_patterns = Vec::new();
+        self.find_matches_into(input, &mut cache.scratch, &mut matching_patterns);
+        matching_patterns
+    }
+
+    /// Ids of all the patterns matching the input, in the `find_matches`
+    /// order, reusing the memory of the cache: no allocation once warmed
+    /// up.
+    pub fn find_match_ids_with_cache<'cache>(
+        &self,
+        input: &str,
+        cache: &'cache mut QueryCache,
+    ) -> &'cache [PatternId] {
+        let ids = &mut cache.ids;
+        ids.clear();
+        self.for_each_match(input, &mut cache.scratch, |matched| {
+            ids.extend(self.matched_id(&matched));
+        });
+        ids
+    }
+
+    /// Id of the best pattern matching the input, see `find_best_match`,
+    /// reusing the memory of the cache: no allocation once warmed up.
+    #[must_use]
+    pub fn find_best_match_id_with_cache(
+        &self,
+        input: &str,
+        cache: &mut QueryCache,
+    ) -> Option<PatternId> {
+        self.find_best_match_with_cache(input, cache)
+            .and_then(|pattern| self.id_of(&pattern))
+    }
+
+    /// Id of a matching pattern.
+    fn matched_id(&self, matched: &Matched<'_>) -> Option<PatternId> {
+        let pattern: &str = match matched {
+            Matched::Literal(pattern) => pattern,
+            Matched::Regex(pattern, _, _) => pattern,
+        };
+        self.id_of(pattern)
+    }
+
+    /// Same as `find_best_match_shared`, reusing the scratch memory of the
+    /// cache.
+    #[must_use]
+    pub fn find_best_match_with_cache(
+        &self,
+        input: &str,
+        cache: &mut QueryCache,
+    ) -> Option<Arc<str>> {
+        self.best_match_among(input, |on_match| {
+            self.try_for_each_match(input, &mut cache.scratch, on_match);
+        })
+    }
+}
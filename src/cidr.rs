@@ -0,0 +1,61 @@
+use std::{net::Ipv4Addr, str::FromStr};
+
+use crate::{RegexTrie, RegexTrieError, range::range_regex};
+
+/// Error of a CIDR block which can't be translated.
+fn invalid(cidr: &str, reason: &str) -> RegexTrieError {
+    RegexTrieError::InvalidPattern(format!("{cidr:?} isn't a valid IPv4 CIDR block: {reason}"))
+}
+
+impl RegexTrie {
+    /// Translates an IPv4 CIDR block (e.g. `10.0.0.0/8`, or a single address)
+    /// to a regex matching the dotted-decimal addresses it contains, without
+    /// leading zeros. The fixed octets are kept literal, so the trie indexes
+    /// them: `10.0.0.0/8` becomes `10\.` followed by three octets in 0-255,
+    /// and a `/32` block a plain pattern.
+    ///
+    /// ## Errors
+    ///
+    /// If it's not an IPv4 address with an optional prefix length up to 32,
+    /// or if the address has bits set after the prefix
+    pub fn translate_cidr(cidr: &str) -> Result<String, RegexTrieError> {
+        let (address, prefix_len) = cidr.split_once('/').unwrap_or((cidr, "32"));
+        let address = Ipv4Addr::from_str(address).map_err(|err| invalid(cidr, &err.to_string()))?;
+        let prefix_len: u32 = prefix_len
+            .parse()
+            .ok()
+            .filter(|&prefix_len| prefix_len <= 32)
+            .ok_or_else(|| invalid(cidr, "invalid prefix length"))?;
+        let mask = u32::MAX.checked_shl(32 - prefix_len).unwrap_or(0);
+        if address.to_bits() & !mask != 0 {
+            return Err(invalid(cidr, "bits set after the prefix"));
+        }
+
+        let mut octets = Vec::with_capacity(4);
+        for (index, octet) in (0..4).zip(address.octets()) {
+            // Bits of the octet fixed by the prefix
+            let fixed_bits = prefix_len.saturating_sub(8 * index).min(8);
+            let first = u64::from(octet);
+            let last = first + (1 << (8 - fixed_bits)) - 1;
+            octets.push(if first == last {
+                first.to_string()
+            } else {
+                format!("(?:{})", range_regex(first, last))
+            });
+        }
+        Ok(octets.join(r"\."))
+    }
+
+    /// Translates an IPv4 CIDR block with `translate_cidr`, and inserts it.
+    /// The trie only knows the translated pattern, which is returned, e.g. to
+    /// map the matches back to the original rules.
+    ///
+    /// ## Errors
+    ///
+    /// If it's not a valid IPv4 CIDR block
+    pub fn insert_cidr(&mut self, cidr: &str) -> Result<String, RegexTrieError> {
+        let translated = Self::translate_cidr(cidr)?;
+        self.insert(&translated)?;
+        Ok(translated)
+    }
+}
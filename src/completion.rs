@@ -0,0 +1,108 @@
+use std::collections::{HashSet, VecDeque};
+
+use regex_automata::{
+    Anchored,
+    dfa::{Automaton, dense::DFA},
+    util::{primitives::StateID, start},
+};
+
+use crate::{
+    MatchMode, RegexTrie,
+    regex_trie::{Scratch, TrieNode},
+};
+
+/// Collects the regex indices of all the nodes below `node` (excluded), and
+/// returns whether any of them holds a plain pattern.
+fn collect_descendants(node: &TrieNode, indices: &mut Vec<usize>) -> bool {
+    let mut has_literal = false;
+    for child in node.children.values() {
+        indices.extend_from_slice(&child.pattern_indices);
+        has_literal |= child.contains_non_regex_prefix;
+        has_literal |= collect_descendants(child, indices);
+    }
+    has_literal
+}
+
+impl RegexTrie {
+    /// Whether some continuation of the input (including the empty one)
+    /// could still match at least one pattern, e.g. to validate an input
+    /// while it's being typed.
+    ///
+    /// The trie must not be dead-ended by the input, and at least one
+    /// candidate DFA must still be able to reach a match after reading it.
+    #[must_use]
+    pub fn input_could_extend_to_match(&self, input: &str) -> bool {
+        let mut scratch = Scratch::default();
+        let (node, input_match_entirely) = self.candidates_into(input, &mut scratch);
+
+        let mut indices = scratch.candidate_indices;
+        if input_match_entirely {
+            // Every plain pattern below (or at) the node extends the input.
+            if node.contains_non_regex_prefix || collect_descendants(node, &mut indices) {
+                return true;
+            }
+        } else if self.match_mode == MatchMode::Prefix && !scratch.literal_ends.is_empty() {
+            // A plain pattern already matches a prefix of the input.
+            return true;
+        }
+
+        let input_bytes = input.as_bytes();
+        indices.iter().any(|&index| {
+            // The fallback engine can't tell, so assume it could.
+            self.compiled_patterns[index]
+                .1
+                .dfa()
+                .is_none_or(|dfa| self.could_extend(dfa.forward(), input_bytes))
+        })
+    }
+
+    /// Whether the DFA could still match some continuation of the input.
+    fn could_extend(&self, dfa: &DFA<Vec<u32>>, input: &[u8]) -> bool {
+        let config = start::Config::new().anchored(Anchored::Yes);
+        let Ok(mut state) = dfa.start_state(&config) else {
+            return false;
+        };
+
+        for &byte in input {
+            state = dfa.next_state(state, byte);
+            if dfa.is_dead_state(state) || dfa.is_quit_state(state) {
+                return false;
+            }
+            // Match states are delayed by one byte: a prefix of the input
+            // already matches.
+            if self.match_mode == MatchMode::Prefix && dfa.is_match_state(state) {
+                return true;
+            }
+        }
+
+        self.can_reach_match(dfa, state)
+    }
+
+    /// Explores the DFA from the given state, looking for a state matching at
+    /// the end of the input. A state which isn't dead may still never reach
+    /// a match, as the DFAs aren't minimized.
+    fn can_reach_match(&self, dfa: &DFA<Vec<u32>>, start: StateID) -> bool {
+        let mut visited = HashSet::from([start]);
+        let mut queue = VecDeque::from([start]);
+        while let Some(state) = queue.pop_front() {
+            if dfa.is_match_state(dfa.next_eoi_state(state))
+                || (self.match_mode == MatchMode::Prefix && dfa.is_match_state(state))
+            {
+                return true;
+            }
+
+            // A single byte per equivalence class is enough.
+            for unit in dfa.byte_classes().representatives(..) {
+                let Some(byte) = unit.as_u8() else {
+                    continue;
+                };
+                let next = dfa.next_state(state, byte);
+                if !dfa.is_dead_state(next) && !dfa.is_quit_state(next) && visited.insert(next) {
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        false
+    }
+}
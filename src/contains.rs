@@ -0,0 +1,229 @@
+use std::{collections::HashMap, sync::Arc};
+
+use crate::{
+    PatternMatcher, RegexTrieError,
+    engine::{CompiledRegex, DfaConfig, compile},
+    prefilter::longest_required_literal,
+    regex_trie::{ScorerFuncType, default_scorer, parse_literal_prefix},
+};
+
+/// A node of a `ContainsTrie`, reached by the characters of a literal.
+#[derive(Debug, Default)]
+struct ContainsNode {
+    /// Children, by character
+    children: HashMap<char, ContainsNode>,
+    /// Patterns whose literal leads here
+    patterns: Vec<usize>,
+}
+
+impl ContainsNode {
+    /// Number of nodes of the subtree.
+    fn count(&self) -> usize {
+        1 + self.children.values().map(Self::count).sum::<usize>()
+    }
+}
+
+/// Alternative to `RegexTrie` for the "contains" mode: a pattern matches if
+/// it matches somewhere in the input, as if it started and ended with `.*`,
+/// e.g. `ERROR.*timeout` (or `.*ERROR.*timeout.*`) for log lines.
+///
+/// Patterns are indexed by a literal every one of their matches contains
+/// instead of their literal prefix: the whole pattern if it's plain, the
+/// longest literal it requires if it's a regex (`timeout` for
+/// `.*ERROR.*timeout.*`). The trie is walked from every character of the
+/// input, i.e. along all its suffixes, so only the patterns whose literal
+/// occurs somewhere are candidates. A plain pattern found this way matches,
+/// a regex is then run against the input. Regexes without any required
+/// literal are run against every input.
+pub struct ContainsTrie {
+    /// Root node, holding the patterns without a literal
+    root: ContainsNode,
+    /// Every pattern, with its compiled regex (`None` if it's plain) and its
+    /// score, in insertion order
+    patterns: Vec<(Arc<str>, Option<CompiledRegex>, usize)>,
+    /// Reverse lookup of `patterns`
+    ids: HashMap<Arc<str>, usize>,
+    /// Scorer function
+    scorer: ScorerFuncType,
+}
+
+impl std::fmt::Debug for ContainsTrie {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ContainsTrie")
+            .field("root", &self.root)
+            .field("patterns", &self.patterns)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for ContainsTrie {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ContainsTrie {
+    /// Creates a new, empty trie with the default scorer.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::new_with_custom_scorer(Box::new(default_scorer))
+    }
+
+    /// Creates a new, empty trie with a custom scorer.
+    #[must_use]
+    pub fn new_with_custom_scorer(scorer: ScorerFuncType) -> Self {
+        Self {
+            root: ContainsNode::default(),
+            patterns: Vec::default(),
+            ids: HashMap::default(),
+            scorer,
+        }
+    }
+
+    /// Creates a new trie from a set of patterns.
+    ///
+    /// ## Errors
+    ///
+    /// If any of the regex pattern can't be compiled
+    pub fn from(patterns: &[String]) -> Result<Self, RegexTrieError> {
+        let mut trie = Self::new();
+        trie.insert_many(patterns)?;
+        Ok(trie)
+    }
+
+    /// Compiles a pattern and inserts it into the trie. Inserting an already
+    /// known pattern does nothing.
+    ///
+    /// ## Errors
+    ///
+    /// If the regex pattern can't be compiled
+    pub fn insert(&mut self, pattern: &str) -> Result<(), RegexTrieError> {
+        self.insert_many(&[pattern.to_string()])
+    }
+
+    /// Insert many patterns at once. Nothing is inserted if any pattern
+    /// can't be compiled.
+    ///
+    /// ## Errors
+    ///
+    /// If any regex pattern can't be compiled
+    pub fn insert_many(&mut self, patterns: &[String]) -> Result<(), RegexTrieError> {
+        let compiled = patterns
+            .iter()
+            .map(|pattern| {
+                let (prefix, is_regex) = parse_literal_prefix(pattern);
+                if !is_regex {
+                    let literal: String = prefix.into_iter().map(|(ch, _)| ch).collect();
+                    return Ok((None, literal));
+                }
+                let regex = compile(pattern, false, false, &DfaConfig::default())?;
+                Ok((
+                    Some(regex),
+                    longest_required_literal(pattern).unwrap_or_default(),
+                ))
+            })
+            .collect::<Result<Vec<_>, RegexTrieError>>()?;
+
+        for (pattern, (regex, literal)) in patterns.iter().zip(compiled) {
+            if self.ids.contains_key(pattern.as_str()) {
+                continue;
+            }
+            let index = self.patterns.len();
+            let mut node = &mut self.root;
+            for ch in literal.chars() {
+                node = node.children.entry(ch).or_default();
+            }
+            node.patterns.push(index);
+
+            let score = (self.scorer)(pattern, regex.is_some());
+            let pattern: Arc<str> = Arc::from(pattern.as_str());
+            self.ids.insert(Arc::clone(&pattern), index);
+            self.patterns.push((pattern, regex, score));
+        }
+
+        Ok(())
+    }
+
+    /// Number of nodes of the trie, including the root.
+    #[must_use]
+    pub fn node_count(&self) -> usize {
+        self.root.count()
+    }
+
+    /// Finds all patterns matching somewhere in the input, in insertion
+    /// order.
+    #[must_use]
+    pub fn find_matches(&self, input: &str) -> Vec<String> {
+        self.matching(input)
+            .map(|(pattern, _)| pattern.to_string())
+            .collect()
+    }
+
+    /// Finds the pattern with the lowest score matching somewhere in the
+    /// input. On equal scores, the first inserted one wins.
+    #[must_use]
+    pub fn find_best_match(&self, input: &str) -> Option<String> {
+        let mut best_match: Option<(&str, usize)> = None;
+        for (pattern, score) in self.matching(input) {
+            if best_match.is_none_or(|(_, best_score)| score < best_score) {
+                best_match = Some((pattern, score));
+            }
+        }
+
+        best_match.map(|(pattern, _)| pattern.to_string())
+    }
+
+    /// Iterates over all the patterns matching somewhere in the input, with
+    /// their score, in insertion order.
+    fn matching<'trie>(
+        &'trie self,
+        input: &'trie str,
+    ) -> impl Iterator<Item = (&'trie str, usize)> {
+        // Whether the literal of every pattern occurs in the input.
+        let mut found = vec![false; self.patterns.len()];
+        for &index in &self.root.patterns {
+            found[index] = true;
+        }
+        for (start, _) in input.char_indices() {
+            let mut node = &self.root;
+            for ch in input[start..].chars() {
+                let Some(child) = node.children.get(&ch) else {
+                    break;
+                };
+                node = child;
+                for &index in &node.patterns {
+                    found[index] = true;
+                }
+            }
+        }
+
+        self.patterns
+            .iter()
+            .zip(found)
+            .filter_map(move |((pattern, regex, score), found)| {
+                let is_match = found
+                    && regex
+                        .as_ref()
+                        .is_none_or(|regex| regex.find(input.as_bytes()).is_some());
+                is_match.then_some((&**pattern, *score))
+            })
+    }
+}
+
+impl PatternMatcher for ContainsTrie {
+    fn insert(&mut self, pattern: &str) -> Result<(), RegexTrieError> {
+        Self::insert(self, pattern)
+    }
+
+    fn insert_many(&mut self, patterns: &[String]) -> Result<(), RegexTrieError> {
+        Self::insert_many(self, patterns)
+    }
+
+    fn find_matches(&self, input: &str) -> Vec<String> {
+        Self::find_matches(self, input)
+    }
+
+    fn find_best_match(&self, input: &str) -> Option<String> {
+        Self::find_best_match(self, input)
+    }
+}
@@ -0,0 +1,59 @@
+use regex_automata::nfa::thompson::NFA;
+use regex_syntax::Parser;
+
+use crate::{
+    Engine, RegexTrie, RegexTrieError, engine::compile, options::regex_source,
+    regex_trie::parse_literal_prefix_with,
+};
+
+/// How expensive a pattern would be for the trie, see
+/// `RegexTrie::estimate_cost`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CostEstimate {
+    /// Engine running the regex, `None` for a plain pattern, which costs
+    /// nothing but its trie nodes
+    pub engine: Option<Engine>,
+    /// Number of states of the regex as a Thompson NFA, which the DFAs are
+    /// built from: a rough measure of its complexity
+    pub nfa_states: usize,
+    /// Heap memory the compiled regex would use, in bytes, counted in the
+    /// DFA memory budget for the dense DFAs
+    pub memory: usize,
+    /// Whether the regex has an unbounded repetition (`*`, `+`, `{n,}`), so
+    /// its matches have no maximum length
+    pub unbounded_repetition: bool,
+}
+
+impl RegexTrie {
+    /// Estimates how expensive the pattern would be once inserted with the
+    /// default options, with the settings of this trie (DFA settings,
+    /// fallback engine, extra meta-characters), without inserting it: the
+    /// regex is compiled, then dropped. Meant to warn authors about
+    /// expensive rules before they are submitted.
+    ///
+    /// ## Errors
+    ///
+    /// If the regex pattern can't be compiled
+    pub fn estimate_cost(&self, pattern: &str) -> Result<CostEstimate, RegexTrieError> {
+        let (_, is_regex) = parse_literal_prefix_with(pattern, &self.extra_specials);
+        if !is_regex {
+            return Ok(CostEstimate {
+                engine: None,
+                nfa_states: 0,
+                memory: 0,
+                unbounded_repetition: false,
+            });
+        }
+
+        let source = regex_source(pattern, &self.extra_specials);
+        let compiled = compile(&source, false, self.fallback_engine, &self.dfa_config)?;
+        Ok(CostEstimate {
+            engine: Some(compiled.engine()),
+            nfa_states: NFA::new(&source).map_or(0, |nfa| nfa.states().len()),
+            memory: compiled.memory_usage(),
+            unbounded_repetition: Parser::new()
+                .parse(&source)
+                .is_ok_and(|hir| hir.properties().maximum_len().is_none()),
+        })
+    }
+}
@@ -0,0 +1,65 @@
+use std::{borrow::Cow, time::Instant};
+
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+
+use crate::{
+    InsertOptions, RegexTrie, RegexTrieError, engine::compile, regex_trie::parse_literal_prefix,
+};
+
+impl RegexTrie {
+    /// Creates a new `RegexTrie` from a set of patterns, compiling as many
+    /// regexes as possible before the deadline, in parallel. Returns the trie
+    /// and the regexes which weren't compiled in time, in their original
+    /// order, to be inserted later (e.g. with `insert_many` from a background
+    /// task). Plain patterns are always inserted, as they need no
+    /// compilation.
+    ///
+    /// The deadline is checked before compiling each regex, so the build can
+    /// overrun it by the compilation time of the slowest regex in flight.
+    ///
+    /// ## Errors
+    ///
+    /// If any of the regex pattern compiled before the deadline is invalid
+    pub fn from_with_deadline(
+        patterns: &[String],
+        deadline: Instant,
+    ) -> Result<(Self, Vec<String>), RegexTrieError> {
+        let mut trie = Self::new();
+        let parsed: Vec<_> = patterns
+            .iter()
+            .map(|pattern| (pattern, parse_literal_prefix(pattern)))
+            .collect();
+
+        // `None` when the deadline has been reached before compiling it
+        let compiled = parsed
+            .par_iter()
+            .map(|(pattern, (_, is_regex))| {
+                if !is_regex {
+                    return Ok(Some(None));
+                }
+                if Instant::now() >= deadline {
+                    return Ok(None);
+                }
+                compile(pattern, false, trie.fallback_engine, &trie.dfa_config)
+                    .map(|dfa| Some(Some(dfa)))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut pending = Vec::new();
+        for ((pattern, (prefix, _)), dfa) in parsed.into_iter().zip(compiled) {
+            let Some(dfa) = dfa else {
+                pending.push(pattern.clone());
+                continue;
+            };
+            let compiled = dfa.map(|dfa| (dfa, (trie.scorer)(pattern, true)));
+            trie.add_pattern(
+                Cow::Borrowed(pattern),
+                &prefix,
+                compiled,
+                InsertOptions::default(),
+            );
+        }
+
+        Ok((trie, pending))
+    }
+}
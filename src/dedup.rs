@@ -0,0 +1,93 @@
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+};
+
+use crate::{PatternId, PatternKind, RegexTrie, options::regex_source};
+
+/// Canonical form of a regex, from its HIR, so equivalent spellings (e.g.
+/// `(?:ab)c` and `abc`, or `a{1}` and `a`) get the same one. `None` if the
+/// regex doesn't parse.
+pub(crate) fn canonical_form(pattern: &str, case_insensitive: bool) -> Option<String> {
+    regex_syntax::ParserBuilder::new()
+        .case_insensitive(case_insensitive)
+        .build()
+        .parse(pattern)
+        .ok()
+        .map(|hir| hir.to_string())
+}
+
+impl RegexTrie {
+    /// Enables (or disables) detecting equivalent regexes on insert: a regex
+    /// spelled differently from a known one, but with the same canonical form
+    /// (e.g. redundant groups, `a{1}` for `a`), is handled as a duplicate,
+    /// i.e. it's neither compiled nor inserted. Plain patterns are always
+    /// deduplicated, as is.
+    #[must_use]
+    pub fn with_regex_dedup(mut self, enabled: bool) -> Self {
+        self.canonical_regexes = enabled.then(HashMap::new);
+        let known: Vec<_> = self
+            .iter()
+            .filter(|entry| entry.kind == PatternKind::Regex)
+            .map(|entry| entry.id)
+            .collect();
+        for id in known {
+            self.register_canonical(id);
+        }
+        self
+    }
+
+    /// Drops the regexes equivalent to a known one, or to a previous one of
+    /// the batch, if deduplication is enabled. `is_regex` tells whether a
+    /// pattern of the batch is a regex.
+    pub(crate) fn drop_equivalent<T>(
+        &self,
+        batch: &mut Vec<(Cow<'_, str>, T)>,
+        is_regex: impl Fn(&T) -> bool,
+    ) {
+        let Some(canonical_regexes) = &self.canonical_regexes else {
+            return;
+        };
+        let mut seen = HashSet::new();
+        batch.retain(|(pattern, parsed)| {
+            if !is_regex(parsed) || self.ids.contains_key(pattern.as_ref()) {
+                return true;
+            }
+            // Invalid regexes are kept, to report their error.
+            canonical_form(pattern, false).is_none_or(|canonical| {
+                !canonical_regexes.contains_key(&canonical) && seen.insert(canonical)
+            })
+        });
+    }
+
+    /// Records the canonical form of a regex, if deduplication is enabled.
+    pub(crate) fn register_canonical(&mut self, id: PatternId) {
+        let Some(canonical) = self.canonical_regexes.as_ref().and_then(|_| {
+            canonical_form(
+                &regex_source(self.pattern(id)?, &self.extra_specials),
+                self.is_case_insensitive(id),
+            )
+        }) else {
+            return;
+        };
+        if let Some(canonical_regexes) = &mut self.canonical_regexes {
+            canonical_regexes.entry(canonical).or_insert(id);
+        }
+    }
+
+    /// Forgets the canonical form of a removed regex.
+    pub(crate) fn unregister_canonical(&mut self, id: PatternId, pattern: &str) {
+        let case_insensitive = self.is_case_insensitive(id);
+        let Some(canonical_regexes) = &mut self.canonical_regexes else {
+            return;
+        };
+        if let Some(canonical) = canonical_form(
+            &regex_source(pattern, &self.extra_specials),
+            case_insensitive,
+        ) {
+            if canonical_regexes.get(&canonical) == Some(&id) {
+                canonical_regexes.remove(&canonical);
+            }
+        }
+    }
+}
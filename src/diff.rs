@@ -0,0 +1,82 @@
+use std::fmt;
+
+use crate::RegexTrie;
+
+/// A pattern present in both tries, whose score changed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScoreChange {
+    /// Changed pattern
+    pub pattern: String,
+    /// Score in the first trie
+    pub old_score: usize,
+    /// Score in the second trie
+    pub new_score: usize,
+}
+
+/// Differences between two tries, as computed by `RegexTrie::diff`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TrieDiff {
+    /// Patterns only in the first trie (i.e. removed), in its insertion order
+    pub only_in_self: Vec<String>,
+    /// Patterns only in the second trie (i.e. added), in its insertion order
+    pub only_in_other: Vec<String>,
+    /// Patterns in both tries with a different score, in the insertion order
+    /// of the first trie
+    pub changed: Vec<ScoreChange>,
+}
+
+impl TrieDiff {
+    /// Whether both tries are equal.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.only_in_self.is_empty() && self.only_in_other.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// One line per difference, prefixed like a unified diff (`-` removed, `+`
+/// added, `~` changed).
+impl fmt::Display for TrieDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for pattern in &self.only_in_self {
+            writeln!(f, "- {pattern:?}")?;
+        }
+        for pattern in &self.only_in_other {
+            writeln!(f, "+ {pattern:?}")?;
+        }
+        for change in &self.changed {
+            writeln!(
+                f,
+                "~ {:?}: score {} -> {}",
+                change.pattern, change.old_score, change.new_score
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl RegexTrie {
+    /// Lists the patterns only in `self`, only in `other`, and the ones in
+    /// both with a different score, e.g. to log what changed when reloading
+    /// a configuration. Empty if the tries are equal.
+    #[must_use]
+    pub fn diff(&self, other: &Self) -> TrieDiff {
+        let mut diff = TrieDiff::default();
+        for entry in self.iter() {
+            match other.id_of(entry.pattern).and_then(|id| other.entry(id)) {
+                None => diff.only_in_self.push(entry.pattern.to_string()),
+                Some(other) if other.score != entry.score => diff.changed.push(ScoreChange {
+                    pattern: entry.pattern.to_string(),
+                    old_score: entry.score,
+                    new_score: other.score,
+                }),
+                Some(_) => {}
+            }
+        }
+        diff.only_in_other = other
+            .iter()
+            .filter(|entry| self.id_of(entry.pattern).is_none())
+            .map(|entry| entry.pattern.to_string())
+            .collect();
+        diff
+    }
+}
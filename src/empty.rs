@@ -0,0 +1,81 @@
+use crate::{RegexTrie, RegexTrieError, options::regex_source, prefilter::match_length_range};
+
+/// Whether the empty patterns, or inputs, are accepted, see
+/// `RegexTrie::with_empty_patterns` and `RegexTrie::with_empty_inputs`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EmptyPolicy {
+    /// Handled like any other pattern or input
+    #[default]
+    Allow,
+    /// Rejected
+    Deny,
+}
+
+impl RegexTrie {
+    /// Allows (the default), or denies, the patterns matching the empty
+    /// string: the plain pattern `""`, and the regexes like `a*` or `(x|)`.
+    /// By default, `""` matches the empty input only (and every input in
+    /// `MatchMode::Prefix`, as a prefix of it), like any plain pattern, and
+    /// such regexes match the empty input too. Once denied, inserting them
+    /// fails with `RegexTrieError::EmptyPattern`, and nothing of the batch is
+    /// inserted. The patterns already inserted are kept. Like the match mode,
+    /// it isn't serialized.
+    #[must_use]
+    pub const fn with_empty_patterns(mut self, policy: EmptyPolicy) -> Self {
+        self.empty_patterns = policy;
+        self
+    }
+
+    /// Whether the patterns matching the empty string can be inserted.
+    #[must_use]
+    pub const fn empty_patterns(&self) -> EmptyPolicy {
+        self.empty_patterns
+    }
+
+    /// Lets the empty input match the patterns matching the empty string
+    /// (the default), or denies it, so it matches no pattern at all, e.g. for
+    /// an empty field which should never fire a rule. Like the match mode, it
+    /// isn't serialized.
+    #[must_use]
+    pub const fn with_empty_inputs(mut self, policy: EmptyPolicy) -> Self {
+        self.empty_inputs = policy;
+        self
+    }
+
+    /// Whether the empty input can match patterns.
+    #[must_use]
+    pub const fn empty_inputs(&self) -> EmptyPolicy {
+        self.empty_inputs
+    }
+
+    /// Verifies that a batch of patterns can be inserted, when the patterns
+    /// matching the empty string are denied. Known patterns aren't checked,
+    /// since they won't be inserted.
+    ///
+    /// ## Errors
+    ///
+    /// With the first pattern matching the empty string
+    pub(crate) fn check_empty_patterns<'batch>(
+        &self,
+        batch: impl IntoIterator<Item = (&'batch str, bool)>,
+    ) -> Result<(), RegexTrieError> {
+        if self.empty_patterns == EmptyPolicy::Allow {
+            return Ok(());
+        }
+        for (pattern, case_insensitive) in batch {
+            if self.ids.contains_key(pattern) {
+                continue;
+            }
+            let source = regex_source(pattern, &self.extra_specials);
+            if match_length_range(&source, case_insensitive).0 == 0 {
+                return Err(RegexTrieError::EmptyPattern(pattern.to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the input can match patterns, see `with_empty_inputs`.
+    pub(crate) fn input_allowed(&self, input: &[u8]) -> bool {
+        self.empty_inputs == EmptyPolicy::Allow || !input.is_empty()
+    }
+}
@@ -0,0 +1,264 @@
+use std::{cell::RefCell, collections::HashMap, sync::Arc};
+
+use regex_automata::{
+    Input, Match,
+    dfa::{dense, regex::Regex},
+    meta,
+    util::syntax,
+};
+use regex_syntax::ParserBuilder;
+
+#[cfg(unix)]
+use crate::shared::SharedRegex;
+use crate::{PatternId, RegexTrie, RegexTrieError};
+
+/// Regex engine running a compiled pattern.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum Engine {
+    /// Dense DFA, the fastest one, used whenever it can be built
+    Dfa,
+    /// Meta regex engine (lazy DFA, backtracker, ...), slower, for the
+    /// patterns with Unicode word boundaries, or no dense DFA can be built
+    /// for when the fallback is enabled
+    Meta,
+}
+
+/// Settings of the dense DFAs the regexes are compiled into, forwarded to
+/// `regex_automata`, for the patterns inserted once set with
+/// `RegexTrie::with_dfa_config`. The defaults are the ones of
+/// `regex_automata`. The DFAs always support both anchored and unanchored
+/// searches, the queries needing both whatever the match mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DfaConfig {
+    /// Whether the DFAs are minimized: smaller and faster to run, but much
+    /// slower to build
+    pub minimize: bool,
+    /// Whether bytes no regex distinguishes share their transitions, making
+    /// the DFAs much smaller
+    pub byte_classes: bool,
+    /// Maximum memory used while building a DFA, in bytes, if limited: the
+    /// regexes exceeding it are rejected, or fall back to the slower engine
+    /// if enabled
+    pub determinize_size_limit: Option<usize>,
+}
+
+impl Default for DfaConfig {
+    fn default() -> Self {
+        Self {
+            minimize: false,
+            byte_classes: true,
+            determinize_size_limit: None,
+        }
+    }
+}
+
+impl DfaConfig {
+    /// Same settings, for `regex_automata`.
+    fn dense(&self) -> dense::Config {
+        dense::Config::new()
+            .minimize(self.minimize)
+            .byte_classes(self.byte_classes)
+            .determinize_size_limit(self.determinize_size_limit)
+    }
+}
+
+/// A compiled regex pattern.
+#[derive(Debug, Clone)]
+pub(crate) enum CompiledRegex {
+    /// Dense DFAs, forward and reverse
+    Dfa(Box<Regex>),
+    /// Dense DFAs, forward and reverse, used in place from a shared memory
+    /// region
+    #[cfg(unix)]
+    Shared(Box<SharedRegex>),
+    /// Fallback engine
+    Meta(meta::Regex),
+}
+
+impl CompiledRegex {
+    /// Leftmost-first match of the regex in the input.
+    pub(crate) fn find<'input>(&self, input: impl Into<Input<'input>>) -> Option<Match> {
+        match self {
+            Self::Dfa(dfa) => dfa.find(input),
+            #[cfg(unix)]
+            Self::Shared(shared) => shared.regex().find(input),
+            Self::Meta(regex) => regex.find(input),
+        }
+    }
+
+    /// Dense DFAs of the regex, `None` for the fallback engine, and the
+    /// shared ones (only used by frozen tries).
+    pub(crate) const fn dfa(&self) -> Option<&Regex> {
+        match self {
+            Self::Dfa(dfa) => Some(dfa),
+            #[cfg(unix)]
+            Self::Shared(_) => None,
+            Self::Meta(_) => None,
+        }
+    }
+
+    /// Engine running the regex.
+    pub(crate) const fn engine(&self) -> Engine {
+        match self {
+            Self::Dfa(_) => Engine::Dfa,
+            #[cfg(unix)]
+            Self::Shared(_) => Engine::Dfa,
+            Self::Meta(_) => Engine::Meta,
+        }
+    }
+
+    /// Memory taken by the regex, in bytes.
+    pub(crate) fn memory_usage(&self) -> usize {
+        match self {
+            Self::Dfa(dfa) => dfa.forward().memory_usage() + dfa.reverse().memory_usage(),
+            #[cfg(unix)]
+            Self::Shared(shared) => {
+                shared.regex().forward().memory_usage() + shared.regex().reverse().memory_usage()
+            }
+            Self::Meta(regex) => regex.memory_usage(),
+        }
+    }
+}
+
+/// Search caches of the fallback engine, reused from one query to the other
+/// instead of taken from the regex pool, `None` when disabled. Keyed by the
+/// address of the pattern, which is kept alive so it can't be reused by
+/// another one.
+#[derive(Debug, Default)]
+pub(crate) struct MetaCaches(RefCell<Option<HashMap<usize, PatternCache>>>);
+
+/// Search cache of a pattern, with the pattern keeping its address alive.
+type PatternCache = (Arc<str>, meta::Cache);
+
+impl MetaCaches {
+    /// Enabled caches, empty.
+    pub(crate) fn enabled() -> Self {
+        Self(RefCell::new(Some(HashMap::new())))
+    }
+
+    /// Leftmost-first match of the regex of the given pattern in the input,
+    /// with the cache of its previous searches if enabled.
+    pub(crate) fn find(
+        &self,
+        pattern: &Arc<str>,
+        regex: &CompiledRegex,
+        input: Input<'_>,
+    ) -> Option<Match> {
+        let CompiledRegex::Meta(meta) = regex else {
+            return regex.find(input);
+        };
+        let mut caches = self.0.borrow_mut();
+        let Some(caches) = caches.as_mut() else {
+            return meta.find(input);
+        };
+        let (_, cache) = caches
+            .entry(Arc::as_ptr(pattern).cast::<u8>().addr())
+            .or_insert_with(|| (Arc::clone(pattern), meta.create_cache()));
+        meta.search_with(cache, &input)
+    }
+
+    /// Number of cached regexes.
+    pub(crate) fn len(&self) -> usize {
+        self.0.borrow().as_ref().map_or(0, HashMap::len)
+    }
+
+    /// Drops the cached regexes, keeping the caches enabled if they were.
+    pub(crate) fn clear(&self) {
+        if let Some(caches) = self.0.borrow_mut().as_mut() {
+            caches.clear();
+        }
+    }
+}
+
+/// Compiles a regex into dense DFAs with the given settings, or with the
+/// fallback engine if they can't be built and either `fallback` is set or
+/// the regex has Unicode word boundaries, which no dense DFA supports.
+///
+/// ## Errors
+///
+/// If the DFAs can't be built, and the fallback engine is disabled or fails
+/// as well
+pub(crate) fn compile(
+    pattern: &str,
+    case_insensitive: bool,
+    fallback: bool,
+    config: &DfaConfig,
+) -> Result<CompiledRegex, RegexTrieError> {
+    let syntax = syntax::Config::new().case_insensitive(case_insensitive);
+    let err = match Regex::builder()
+        .syntax(syntax)
+        .dense(config.dense())
+        .build(pattern)
+    {
+        Ok(dfa) => return Ok(CompiledRegex::Dfa(Box::new(dfa))),
+        Err(err) => err,
+    };
+    if fallback || has_unicode_word_boundary(pattern, case_insensitive) {
+        if let Ok(regex) = compile_fallback(pattern, case_insensitive) {
+            return Ok(regex);
+        }
+    }
+    Err(RegexTrieError::RegexCompilationFailed(Box::new(err)))
+}
+
+/// Whether the regex has a Unicode word boundary (`\b`, `\B`, `\<`, ...).
+fn has_unicode_word_boundary(pattern: &str, case_insensitive: bool) -> bool {
+    ParserBuilder::new()
+        .case_insensitive(case_insensitive)
+        .build()
+        .parse(pattern)
+        .is_ok_and(|hir| hir.properties().look_set().contains_word_unicode())
+}
+
+/// Compiles a regex with the fallback engine.
+///
+/// ## Errors
+///
+/// If the regex is invalid
+pub(crate) fn compile_fallback(
+    pattern: &str,
+    case_insensitive: bool,
+) -> Result<CompiledRegex, Box<meta::BuildError>> {
+    meta::Regex::builder()
+        .syntax(syntax::Config::new().case_insensitive(case_insensitive))
+        .build(pattern)
+        .map(CompiledRegex::Meta)
+        .map_err(Box::new)
+}
+
+impl RegexTrie {
+    /// Enables (or disables) falling back to a slower regex engine for all
+    /// the patterns no dense DFA can be built for, instead of rejecting them.
+    /// Patterns with Unicode word boundaries always fall back. Streams buffer
+    /// their input as soon as such a pattern is a candidate, and
+    /// `input_could_extend_to_match` assumes they could always match. See
+    /// `engine` and `MatchStats`.
+    #[must_use]
+    pub const fn with_fallback_engine(mut self, enabled: bool) -> Self {
+        self.fallback_engine = enabled;
+        self
+    }
+
+    /// Sets the settings of the dense DFAs of the patterns inserted from now
+    /// on, see `DfaConfig`. Like the fallback engine, it isn't serialized.
+    #[must_use]
+    pub const fn with_dfa_config(mut self, config: DfaConfig) -> Self {
+        self.dfa_config = config;
+        self
+    }
+
+    /// Settings of the dense DFAs of the inserted patterns.
+    #[must_use]
+    pub const fn dfa_config(&self) -> DfaConfig {
+        self.dfa_config
+    }
+
+    /// Engine running the pattern of the given id, `None` if it's a plain
+    /// pattern, or if the id is unknown.
+    #[must_use]
+    pub fn engine(&self, id: PatternId) -> Option<Engine> {
+        let (_, compiled_index) = self.patterns.get(id.index())?.as_ref()?;
+        Some(self.compiled_patterns[(*compiled_index)?].1.engine())
+    }
+}
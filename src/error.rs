@@ -4,4 +4,42 @@ pub enum RegexTrieError {
     /// When no columns are found in the specs
     #[error(transparent)]
     RegexCompilationFailed(Box<regex_automata::dfa::dense::BuildError>),
+    /// When a pattern written in another dialect can't be translated
+    #[error("invalid pattern: {0}")]
+    InvalidPattern(String),
+    /// When inserting patterns would exceed the DFA memory budget
+    #[error("DFA memory budget exceeded: {required} bytes required, {budget} allowed")]
+    BudgetExceeded {
+        /// Memory all the DFAs would take, in bytes
+        required: usize,
+        /// Configured budget, in bytes
+        budget: usize,
+    },
+    /// When inserting a pattern matching the empty string, and they're
+    /// rejected
+    #[error("pattern matching the empty string: {0:?}")]
+    EmptyPattern(String),
+    /// When inserting patterns would exceed the maximum pattern count
+    #[error("pattern capacity exceeded: {required} patterns required, {capacity} allowed")]
+    CapacityExceeded {
+        /// Number of patterns the trie would hold
+        required: usize,
+        /// Configured maximum pattern count
+        capacity: usize,
+    },
+    /// When an input isn't valid UTF-8, and `Utf8Handling::Strict` is set
+    #[error("invalid UTF-8 input: {0}")]
+    InvalidUtf8(std::str::Utf8Error),
+    /// When loading a serialized trie from invalid data
+    #[error("invalid serialized trie: {0}")]
+    InvalidSerializedData(String),
+    /// When the write-ahead log can't be read, written, or is corrupted
+    #[error("write-ahead log failed: {0}")]
+    LogFailed(String),
+    /// When a trie can't be written to, or mapped from, a shared file
+    #[error("shared memory failed: {0}")]
+    SharedMemoryFailed(String),
+    /// When `self_check` finds a broken invariant in the trie
+    #[error("inconsistent trie: {0}")]
+    Inconsistent(String),
 }
@@ -0,0 +1,82 @@
+use regex_syntax::{
+    Parser,
+    hir::{Class, Hir, HirKind},
+};
+
+/// Maximum size of a generated example, to bound nested repetitions.
+const MAX_EXAMPLE_LEN: usize = 4096;
+/// Character ranges favored in examples, by order of preference
+const READABLE: [(char, char); 3] = [('a', 'z'), ('0', '9'), ('A', 'Z')];
+
+/// Synthesizes a string matching the given regex pattern, or `None` if the
+/// pattern can't be parsed or the example would be too long.
+///
+/// The expansion is bounded: alternations take their first branch, classes
+/// their most readable member, and repetitions are expanded at least once
+/// (an empty example isn't very telling), but never more than their minimum
+/// when it is greater.
+pub(crate) fn generate_example(pattern: &str) -> Option<String> {
+    let hir = Parser::new().parse(pattern).ok()?;
+    let mut example = Vec::new();
+    expand(&hir, &mut example)?;
+    String::from_utf8(example).ok()
+}
+
+/// Appends a sample of `hir` to `example`. Returns `None` when growing past
+/// `MAX_EXAMPLE_LEN`.
+fn expand(hir: &Hir, example: &mut Vec<u8>) -> Option<()> {
+    match hir.kind() {
+        HirKind::Empty | HirKind::Look(_) => {}
+        HirKind::Literal(literal) => example.extend_from_slice(&literal.0),
+        HirKind::Class(Class::Unicode(class)) => {
+            let ranges = class
+                .ranges()
+                .iter()
+                .map(|range| (u32::from(range.start()), u32::from(range.end())))
+                .collect::<Vec<_>>();
+            let ch = char::from_u32(pick(&ranges)?)?;
+            example.extend_from_slice(ch.encode_utf8(&mut [0; 4]).as_bytes());
+        }
+        HirKind::Class(Class::Bytes(class)) => {
+            let ranges = class
+                .ranges()
+                .iter()
+                .map(|range| (u32::from(range.start()), u32::from(range.end())))
+                .collect::<Vec<_>>();
+            example.push(u8::try_from(pick(&ranges)?).ok()?);
+        }
+        HirKind::Repetition(repetition) => {
+            let count = repetition
+                .min
+                .max(1)
+                .min(repetition.max.unwrap_or(u32::MAX));
+            for _ in 0..count {
+                expand(&repetition.sub, example)?;
+            }
+        }
+        HirKind::Capture(capture) => expand(&capture.sub, example)?,
+        HirKind::Concat(hirs) => {
+            for hir in hirs {
+                expand(hir, example)?;
+            }
+        }
+        HirKind::Alternation(hirs) => expand(hirs.first()?, example)?,
+    }
+
+    (example.len() <= MAX_EXAMPLE_LEN).then_some(())
+}
+
+/// Pick the most readable code point among the given ranges: a lowercase
+/// letter, then a digit, then an uppercase letter, and the very first one
+/// otherwise.
+fn pick(ranges: &[(u32, u32)]) -> Option<u32> {
+    READABLE
+        .iter()
+        .find_map(|&(readable_start, readable_end)| {
+            ranges.iter().find_map(|&(start, end)| {
+                let start = start.max(u32::from(readable_start));
+                (start <= end.min(u32::from(readable_end))).then_some(start)
+            })
+        })
+        .or_else(|| ranges.first().map(|&(start, _)| start))
+}
@@ -0,0 +1,116 @@
+use std::fmt;
+
+use crate::{
+    RegexTrie,
+    regex_trie::{MatchMode, Walk},
+};
+
+/// How a single candidate pattern has been evaluated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CandidateTrace {
+    /// Candidate pattern
+    pub pattern: String,
+    /// Depth of the trie node holding the pattern, i.e. the length of its
+    /// literal prefix in characters
+    pub depth: usize,
+    /// Score of the pattern
+    pub score: usize,
+    /// Whether its DFA fully matched the input
+    pub matched: bool,
+}
+
+/// Step by step trace of how an input has been matched against the trie.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MatchTrace {
+    /// Matched input
+    pub input: String,
+    /// Longest path of the trie matching the start of the input, escaped as
+    /// it was inserted
+    pub walked_prefix: String,
+    /// Plain (non regex) pattern equal to the input (or the best one
+    /// matching a prefix of it, in prefix mode), with its score
+    pub literal_match: Option<(String, usize)>,
+    /// All the regex candidates found along the path, from the root to the
+    /// deepest node
+    pub candidates: Vec<CandidateTrace>,
+    /// Best match, as returned by `find_best_match`
+    pub best: Option<String>,
+}
+
+impl fmt::Display for MatchTrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "input:   {:?}", self.input)?;
+        writeln!(f, "walked:  {:?}", self.walked_prefix)?;
+        match &self.literal_match {
+            Some((pattern, score)) => writeln!(f, "literal: {pattern:?} (score {score})")?,
+            None => writeln!(f, "literal: none")?,
+        }
+        writeln!(f, "candidates: {}", self.candidates.len())?;
+        for candidate in &self.candidates {
+            writeln!(
+                f,
+                "  [{}] {:?} at depth {} (score {})",
+                if candidate.matched { "x" } else { " " },
+                candidate.pattern,
+                candidate.depth,
+                candidate.score,
+            )?;
+        }
+        match &self.best {
+            Some(best) => write!(f, "best: {best:?}"),
+            None => write!(f, "best: none"),
+        }
+    }
+}
+
+impl RegexTrie {
+    /// Explains how the input is matched: how far it walks into the trie,
+    /// which candidates are collected along the way, and which of them match.
+    /// Slower than `find_matches`, meant for debugging rule sets.
+    #[must_use]
+    pub fn explain(&self, input: &str) -> MatchTrace {
+        let mut candidates = Vec::new();
+        let mut literals = Vec::new();
+        let Walk {
+            node,
+            input_match_entirely,
+            escaped_pattern,
+        } = self.walk_input(input, |depth, node, path| {
+            if node.contains_non_regex_prefix {
+                literals.push(path.to_string());
+            }
+            for &index in &node.pattern_indices {
+                let (pattern, dfa, score) = &self.compiled_patterns[index];
+                let trace = CandidateTrace {
+                    pattern: pattern.to_string(),
+                    depth,
+                    score: *score,
+                    matched: self.dfa_matches(dfa, input.as_bytes()),
+                };
+                candidates.push(trace);
+            }
+        });
+
+        let literal_match = match self.match_mode {
+            MatchMode::Full => (input_match_entirely && node.contains_non_regex_prefix)
+                .then(|| escaped_pattern.clone()),
+            // The first of the best plain patterns along the way
+            MatchMode::Prefix => literals
+                .into_iter()
+                .min_by_key(|literal| (self.scorer)(literal, false)),
+        }
+        .map(|literal| {
+            let score = (self.scorer)(&literal, false);
+            (literal, score)
+        });
+
+        MatchTrace {
+            input: input.to_string(),
+            walked_prefix: escaped_pattern,
+            literal_match,
+            candidates,
+            // Same selection as the real query, whatever the policy
+            best: self.find_best_match(input),
+        }
+    }
+}
@@ -0,0 +1,37 @@
+use crate::regex_trie::TrieNode;
+
+/// Set of the first bytes (in UTF-8) of the children of the root, i.e. of
+/// the non-empty literal prefixes: an input starting with any other byte
+/// can't go past the root, so its walk stops at once, without hashing its
+/// first character.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct FirstBytes([u64; 4]);
+
+impl FirstBytes {
+    /// Set of the first bytes of the children of the given root.
+    pub(crate) fn of_root(root: &TrieNode) -> Self {
+        let mut first_bytes = Self::default();
+        for &ch in root.children.keys() {
+            first_bytes.insert(ch);
+        }
+        first_bytes
+    }
+
+    /// Adds the first byte of a character.
+    pub(crate) fn insert(&mut self, ch: char) {
+        let byte = first_byte(ch);
+        self.0[usize::from(byte / 64)] |= 1 << (byte % 64);
+    }
+
+    /// Whether the byte starts a character of the set.
+    pub(crate) const fn contains(&self, byte: u8) -> bool {
+        self.0[(byte / 64) as usize] & (1 << (byte % 64)) != 0
+    }
+}
+
+/// First byte of the UTF-8 encoding of a character.
+const fn first_byte(ch: char) -> u8 {
+    let mut buffer = [0; 4];
+    ch.encode_utf8(&mut buffer);
+    buffer[0]
+}
@@ -0,0 +1,185 @@
+use std::{collections::HashMap, sync::Arc};
+
+use crate::{
+    PatternId, RegexTrie,
+    regex_trie::{Matched, Scratch, TrieNode},
+};
+
+/// A node of a frozen trie. Identical subtrees are stored once, so nodes are
+/// compared structurally: children are sorted, and point to already shared
+/// nodes.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct FrozenNode {
+    /// Children, sorted by character, with their index in the arena
+    children: Box<[(char, u32)]>,
+    /// Compiled patterns whose literal prefix ends here
+    pattern_indices: Box<[usize]>,
+    /// Whether a plain pattern ends here
+    contains_non_regex_prefix: bool,
+    /// Whether the character leading here is escaped
+    is_escaped: bool,
+}
+
+/// Read-only form of a `RegexTrie`, where structurally identical subtrees
+/// (same children, same patterns) are merged into shared nodes. Only
+/// subtrees holding plain patterns can be identical (a regex is stored in a
+/// single node), which is what URL corpora repeat the most, e.g. the same
+/// paths under many domains.
+///
+/// Matching gives the same results as the trie it was frozen from.
+#[derive(Debug)]
+pub struct FrozenRegexTrie {
+    /// Patterns, DFAs and settings, without the nodes
+    pub(crate) trie: RegexTrie,
+    /// All the distinct nodes, the root being the last one
+    nodes: Vec<FrozenNode>,
+}
+
+/// Stores the subtree of `node` in the arena, sharing the nodes already
+/// known, and returns the index of its root.
+fn freeze_node(
+    node: &TrieNode,
+    nodes: &mut Vec<FrozenNode>,
+    known: &mut HashMap<FrozenNode, u32>,
+) -> u32 {
+    let mut children: Vec<_> = node
+        .children
+        .iter()
+        .map(|(&ch, child)| (ch, freeze_node(child, nodes, known)))
+        .collect();
+    children.sort_unstable_by_key(|&(ch, _)| ch);
+    let frozen = FrozenNode {
+        children: children.into_boxed_slice(),
+        pattern_indices: node.pattern_indices.clone().into_boxed_slice(),
+        contains_non_regex_prefix: node.contains_non_regex_prefix,
+        is_escaped: node.is_escaped,
+    };
+
+    *known.entry(frozen).or_insert_with_key(|frozen| {
+        nodes.push(frozen.clone());
+        u32::try_from(nodes.len() - 1).unwrap_or(u32::MAX)
+    })
+}
+
+impl RegexTrie {
+    /// Freezes the trie into its read-only form, merging the identical
+    /// subtrees. See `FrozenRegexTrie`.
+    #[must_use]
+    pub fn freeze(mut self) -> FrozenRegexTrie {
+        let root = std::mem::take(&mut self.root);
+        let mut nodes = Vec::new();
+        freeze_node(&root, &mut nodes, &mut HashMap::new());
+        nodes.shrink_to_fit();
+        FrozenRegexTrie { trie: self, nodes }
+    }
+}
+
+impl FrozenRegexTrie {
+    /// Number of distinct nodes, once the identical subtrees are merged.
+    #[must_use]
+    pub const fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Pattern of the given id, as it was inserted.
+    #[must_use]
+    pub fn pattern(&self, id: PatternId) -> Option<&str> {
+        self.trie.pattern(id)
+    }
+
+    /// Id of the given pattern, if it has been inserted.
+    #[must_use]
+    pub fn id_of(&self, pattern: &str) -> Option<PatternId> {
+        self.trie.id_of(pattern)
+    }
+
+    /// Finds all the patterns matching the input, see
+    /// `RegexTrie::find_matches`.
+    #[must_use]
+    pub fn find_matches(&self, input: &str) -> Vec<String> {
+        self.find_matches_shared(input)
+            .iter()
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    /// Same as `find_matches`, sharing the patterns instead of copying them.
+    #[must_use]
+    pub fn find_matches_shared(&self, input: &str) -> Vec<Arc<str>> {
+        let mut matches = Vec::new();
+        let mut scratch = Scratch::default();
+        let literal_match = self.candidates_into(input, &mut scratch);
+        self.trie
+            .match_candidates(
+                input.as_bytes(),
+                &scratch,
+                literal_match,
+                |matched| match matched {
+                    Matched::Literal(pattern) => matches.push(self.trie.shared_literal(pattern)),
+                    Matched::Regex(pattern, _, _) => matches.push(Arc::clone(pattern)),
+                },
+            );
+        matches
+    }
+
+    /// Finds the best pattern matching the input, see
+    /// `RegexTrie::find_best_match`.
+    #[must_use]
+    pub fn find_best_match(&self, input: &str) -> Option<String> {
+        self.find_best_match_shared(input)
+            .map(|pattern| pattern.to_string())
+    }
+
+    /// Same as `find_best_match`, sharing the pattern instead of copying it.
+    #[must_use]
+    pub fn find_best_match_shared(&self, input: &str) -> Option<Arc<str>> {
+        let mut scratch = Scratch::default();
+        let literal_match = self.candidates_into(input, &mut scratch);
+        self.trie.best_match_among(input, |on_match| {
+            self.trie
+                .try_match_candidates(input.as_bytes(), &scratch, literal_match, on_match);
+        })
+    }
+
+    /// Walks the nodes along the input, collecting the candidates in the
+    /// scratch buffers like `RegexTrie::candidates_into`. Returns whether a
+    /// plain pattern is equal to the input.
+    fn candidates_into(&self, input: &str, scratch: &mut Scratch) -> bool {
+        scratch.candidate_indices.clear();
+        scratch.literal_ends.clear();
+        scratch.escaped_pattern.clear();
+
+        let Some(mut node) = self.nodes.last() else {
+            return false;
+        };
+        let visit = |node: &FrozenNode, scratch: &mut Scratch| {
+            scratch
+                .candidate_indices
+                .extend_from_slice(&node.pattern_indices);
+            if node.contains_non_regex_prefix {
+                scratch.literal_ends.push(scratch.escaped_pattern.len());
+            }
+        };
+        visit(node, scratch);
+
+        let mut input_match_entirely = true;
+        for ch in input.chars() {
+            let Ok(position) = node
+                .children
+                .binary_search_by_key(&ch, |&(child_ch, _)| child_ch)
+            else {
+                input_match_entirely = false;
+                break;
+            };
+            node = &self.nodes[node.children[position].1 as usize];
+            if node.is_escaped {
+                scratch.escaped_pattern.push('\\');
+            }
+            scratch.escaped_pattern.push(ch);
+            visit(node, scratch);
+        }
+
+        scratch.candidate_indices.sort_unstable();
+        input_match_entirely && node.contains_non_regex_prefix
+    }
+}
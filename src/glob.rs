@@ -0,0 +1,100 @@
+use std::{iter::Peekable, str::Chars};
+
+use crate::{RegexTrie, RegexTrieError};
+
+/// What a glob star stands for, see `RegexTrie::translate_glob_stars`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GlobStar {
+    /// A single path segment, i.e. `[^/]*`
+    #[default]
+    Segment,
+    /// Anything, including other segments, i.e. `.*`
+    Any,
+}
+
+impl GlobStar {
+    /// Regex a glob star is translated to.
+    const fn regex(self) -> &'static str {
+        match self {
+            Self::Segment => "[^/]*",
+            Self::Any => ".*",
+        }
+    }
+}
+
+/// Copies a character class, whose opening `[` has been consumed, up to its
+/// closing `]`, so the stars it contains are left as is.
+fn copy_class(chars: &mut Peekable<Chars<'_>>, out: &mut String) {
+    out.push('[');
+    if let Some(ch) = chars.next_if_eq(&'^') {
+        out.push(ch);
+    }
+    // A leading `]` is a literal one.
+    if let Some(ch) = chars.next_if_eq(&']') {
+        out.push(ch);
+    }
+    while let Some(ch) = chars.next() {
+        match ch {
+            ']' => {
+                out.push(ch);
+                return;
+            }
+            '\\' => {
+                out.push(ch);
+                out.extend(chars.next());
+            }
+            // Nested class, like `[a[^b]]` or `[[:alpha:]]`
+            '[' => copy_class(chars, out),
+            _ => out.push(ch),
+        }
+    }
+}
+
+impl RegexTrie {
+    /// Translates the glob stars of a pattern, i.e. the bare `*` making up a
+    /// whole path segment (right after a `/` or at the start, and followed by
+    /// a `/` or at the end), to the regex of the given convention, e.g.
+    /// `/user/.*/photos/*` to `/user/.*/photos/[^/]*`. As a regex, such a `*`
+    /// would repeat the `/` before it (zero or more slashes), which is
+    /// hardly ever meant. Other stars, escaped ones and the ones in character
+    /// classes are left as is.
+    #[must_use]
+    pub fn translate_glob_stars(pattern: &str, glob_star: GlobStar) -> String {
+        let mut out = String::with_capacity(pattern.len());
+        // Whether the next character starts a path segment
+        let mut segment_start = true;
+        let mut chars = pattern.chars().peekable();
+        while let Some(ch) = chars.next() {
+            match ch {
+                '*' if segment_start && matches!(chars.peek(), None | Some('/')) => {
+                    out.push_str(glob_star.regex());
+                }
+                '\\' => {
+                    out.push(ch);
+                    out.extend(chars.next());
+                }
+                '[' => copy_class(&mut chars, &mut out),
+                _ => out.push(ch),
+            }
+            segment_start = out.ends_with('/');
+        }
+        out
+    }
+
+    /// Translates the glob stars of a pattern with `translate_glob_stars`,
+    /// and inserts it. The trie only knows the translated pattern, which is
+    /// returned, e.g. to map the matches back to the original rules.
+    ///
+    /// ## Errors
+    ///
+    /// If the translated pattern can't be compiled
+    pub fn insert_with_glob_stars(
+        &mut self,
+        pattern: &str,
+        glob_star: GlobStar,
+    ) -> Result<String, RegexTrieError> {
+        let translated = Self::translate_glob_stars(pattern, glob_star);
+        self.insert(&translated)?;
+        Ok(translated)
+    }
+}
@@ -0,0 +1,159 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::{
+    Engine, PatternId, RegexTrie,
+    regex_trie::{Matched, Scratch},
+};
+
+/// Runtime counters of a trie, updated by every query.
+#[derive(Debug, Default)]
+pub(crate) struct HitCounters {
+    /// Number of matches of every pattern, indexed by its `PatternId`
+    pub(crate) patterns: Vec<AtomicU64>,
+    /// Number of queries
+    queries: AtomicU64,
+    /// Number of queries which matched no pattern
+    queries_without_match: AtomicU64,
+    /// Number of regex candidates evaluated, over all the queries
+    candidates: AtomicU64,
+}
+
+/// Aggregated match statistics, as returned by `RegexTrie::stats`, to be
+/// exported to a monitoring system.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct MatchStats {
+    /// Number of queries
+    pub queries: u64,
+    /// Number of queries which matched no pattern
+    pub queries_without_match: u64,
+    /// Average number of regex candidates evaluated by a query
+    pub average_candidates: f64,
+    /// Number of matches of every pattern, in insertion order
+    pub matches: Vec<(String, u64)>,
+    /// Patterns run by the fallback engine (see `Engine`), in insertion
+    /// order, as they're slower to match
+    pub fallback_patterns: Vec<String>,
+    /// Memory taken by the compiled regex of every regex pattern, in bytes,
+    /// in insertion order, to find the ones blowing the memory up
+    pub dfa_bytes: Vec<(String, usize)>,
+}
+
+impl RegexTrie {
+    /// Enables (or disables) counting how many times every pattern matches,
+    /// e.g. to find the rules which never fire in production, along with the
+    /// statistics of `stats`. Counters are atomic, so they're updated by the
+    /// queries taking `&self` (`find_matches`, `find_best_match`,
+    /// `scan_text`, ...), and aren't serialized.
+    #[must_use]
+    pub fn with_hit_counting(mut self, enabled: bool) -> Self {
+        self.hit_counters = enabled.then(|| HitCounters {
+            patterns: self.patterns.iter().map(|_| AtomicU64::new(0)).collect(),
+            ..HitCounters::default()
+        });
+        self
+    }
+
+    /// Number of matches of every pattern since counting was enabled, in
+    /// insertion order, removed patterns excluded. Empty if counting isn't
+    /// enabled.
+    #[must_use]
+    pub fn hit_counts(&self) -> Vec<(PatternId, u64)> {
+        self.hit_counters
+            .as_ref()
+            .map_or_else(Vec::new, |hit_counters| {
+                hit_counters
+                    .patterns
+                    .iter()
+                    .enumerate()
+                    .map(|(index, count)| (PatternId::new(index), count.load(Ordering::Relaxed)))
+                    .filter(|&(id, _)| self.pattern(id).is_some())
+                    .collect()
+            })
+    }
+
+    /// Statistics of the queries since counting was enabled (see
+    /// `with_hit_counting`), or `None` if it isn't.
+    #[must_use]
+    #[expect(
+        clippy::cast_precision_loss,
+        reason = "an approximate average is enough"
+    )]
+    pub fn stats(&self) -> Option<MatchStats> {
+        let hit_counters = self.hit_counters.as_ref()?;
+        let queries = hit_counters.queries.load(Ordering::Relaxed);
+        let candidates = hit_counters.candidates.load(Ordering::Relaxed);
+        Some(MatchStats {
+            queries,
+            queries_without_match: hit_counters.queries_without_match.load(Ordering::Relaxed),
+            average_candidates: if queries == 0 {
+                0.0
+            } else {
+                candidates as f64 / queries as f64
+            },
+            matches: self
+                .hit_counts()
+                .into_iter()
+                .filter_map(|(id, count)| Some((self.pattern(id)?.to_string(), count)))
+                .collect(),
+            fallback_patterns: self
+                .iter()
+                .filter(|entry| self.engine(entry.id) == Some(Engine::Meta))
+                .map(|entry| entry.pattern.to_string())
+                .collect(),
+            dfa_bytes: self
+                .dfa_bytes()
+                .into_iter()
+                .filter_map(|(id, bytes)| Some((self.pattern(id)?.to_string(), bytes)))
+                .collect(),
+        })
+    }
+
+    /// Resets all the counters to 0, e.g. after exporting them.
+    pub fn reset_hit_counts(&self) {
+        let Some(hit_counters) = &self.hit_counters else {
+            return;
+        };
+        for count in hit_counters.patterns.iter().chain([
+            &hit_counters.queries,
+            &hit_counters.queries_without_match,
+            &hit_counters.candidates,
+        ]) {
+            count.store(0, Ordering::Relaxed);
+        }
+    }
+
+    /// Counts a match, if counting is enabled.
+    pub(crate) fn count_hit(&self, matched: &Matched<'_>) {
+        let Some(hit_counters) = &self.hit_counters else {
+            return;
+        };
+        let pattern = match matched {
+            Matched::Literal(pattern) => pattern,
+            Matched::Regex(pattern, _, _) => &***pattern,
+        };
+        if let Some(count) = self
+            .id_of(pattern)
+            .and_then(|id| hit_counters.patterns.get(id.index()))
+        {
+            count.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Counts a query, once its matches have been counted, if counting is
+    /// enabled.
+    pub(crate) fn count_query(&self, scratch: &Scratch, matched: bool) {
+        let Some(hit_counters) = &self.hit_counters else {
+            return;
+        };
+        hit_counters.queries.fetch_add(1, Ordering::Relaxed);
+        if !matched {
+            hit_counters
+                .queries_without_match
+                .fetch_add(1, Ordering::Relaxed);
+        }
+        hit_counters
+            .candidates
+            .fetch_add(scratch.candidate_indices.len() as u64, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,202 @@
+use std::{collections::HashMap, sync::Arc};
+
+use crate::RegexTrieError;
+
+/// A node of a `HostnameTrie`, reached by a sequence of labels, from the TLD
+/// inward.
+#[derive(Debug, Default)]
+struct LabelNode {
+    /// Children reached by a literal label
+    children: HashMap<Box<str>, LabelNode>,
+    /// Child reached by any single label (a `*` which isn't the leftmost
+    /// label)
+    any_label: Option<Box<LabelNode>>,
+    /// Pattern ending here
+    exact: Option<usize>,
+    /// Pattern ending here with a leftmost `*`, matching any subdomain
+    subdomains: Option<usize>,
+}
+
+impl LabelNode {
+    /// Number of nodes of the subtree.
+    fn count(&self) -> usize {
+        1 + self.children.values().map(Self::count).sum::<usize>()
+            + self.any_label.as_deref().map_or(0, Self::count)
+    }
+
+    /// Collects the patterns matching the remaining labels, given from the
+    /// TLD inward.
+    fn matches(&self, labels: &[&str], out: &mut Vec<usize>) {
+        let Some((label, rest)) = labels.split_first() else {
+            out.extend(self.exact);
+            return;
+        };
+        out.extend(self.subdomains);
+        if let Some(child) = self.children.get(*label) {
+            child.matches(rest, out);
+        }
+        if let Some(child) = &self.any_label {
+            child.matches(rest, out);
+        }
+    }
+}
+
+/// Normalizes a hostname: DNS names are case-insensitive, and the trailing
+/// dot of a fully qualified name is optional.
+fn normalize(hostname: &str) -> String {
+    hostname
+        .strip_suffix('.')
+        .unwrap_or(hostname)
+        .to_ascii_lowercase()
+}
+
+/// Trie of hostname patterns, indexed label by label from the TLD inward,
+/// e.g. `com`, then `example`, then `cdn` for `*.cdn.example.com`, where a
+/// character trie would have nothing to index before the `*`.
+///
+/// Patterns are hostnames, whose labels are either literal or `*`. The
+/// leftmost `*` matches any subdomain (one or more labels, but not the
+/// domain itself), and any other `*` matches a single label. Matching is
+/// case-insensitive, and ignores the trailing dot of fully qualified names.
+#[derive(Debug, Default)]
+pub struct HostnameTrie {
+    /// Root node, reached by no label
+    root: LabelNode,
+    /// Every pattern, as inserted, in insertion order
+    patterns: Vec<Arc<str>>,
+    /// Number of literal labels of every pattern, its specificity
+    literal_labels: Vec<usize>,
+    /// Reverse lookup of `patterns`, by normalized pattern
+    ids: HashMap<String, usize>,
+}
+
+impl HostnameTrie {
+    /// Creates a new, empty trie.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new trie from a set of patterns.
+    ///
+    /// ## Errors
+    ///
+    /// If any pattern isn't a valid hostname pattern
+    pub fn from(patterns: &[String]) -> Result<Self, RegexTrieError> {
+        let mut trie = Self::new();
+        trie.insert_many(patterns)?;
+        Ok(trie)
+    }
+
+    /// Inserts a hostname pattern. Inserting an already known pattern (up to
+    /// case and trailing dot) does nothing.
+    ///
+    /// ## Errors
+    ///
+    /// If the pattern has an empty label, or a `*` within a label
+    pub fn insert(&mut self, pattern: &str) -> Result<(), RegexTrieError> {
+        self.insert_many(&[pattern.to_string()])
+    }
+
+    /// Insert many patterns at once. Nothing is inserted if any pattern is
+    /// invalid.
+    ///
+    /// ## Errors
+    ///
+    /// If any pattern has an empty label, or a `*` within a label
+    pub fn insert_many(&mut self, patterns: &[String]) -> Result<(), RegexTrieError> {
+        let normalized = patterns
+            .iter()
+            .map(|pattern| {
+                let normalized = normalize(pattern);
+                let is_valid = normalized
+                    .split('.')
+                    .all(|label| !label.is_empty() && (label == "*" || !label.contains('*')));
+                if is_valid {
+                    Ok(normalized)
+                } else {
+                    Err(RegexTrieError::InvalidPattern(format!(
+                        "{pattern:?} isn't a valid hostname pattern"
+                    )))
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        for (pattern, normalized) in patterns.iter().zip(normalized) {
+            if self.ids.contains_key(&normalized) {
+                continue;
+            }
+            let index = self.patterns.len();
+            let any_subdomain = normalized.starts_with('*');
+            let labels = normalized.trim_start_matches('*').trim_start_matches('.');
+
+            let mut node = &mut self.root;
+            for label in labels.rsplit('.').filter(|label| !label.is_empty()) {
+                node = if label == "*" {
+                    node.any_label.get_or_insert_with(Box::default)
+                } else {
+                    node.children.entry(Box::from(label)).or_default()
+                };
+            }
+            let slot = if any_subdomain {
+                &mut node.subdomains
+            } else {
+                &mut node.exact
+            };
+            *slot = Some(index);
+
+            self.literal_labels.push(
+                labels
+                    .split('.')
+                    .filter(|&label| !label.is_empty() && label != "*")
+                    .count(),
+            );
+            self.patterns.push(Arc::from(pattern.as_str()));
+            self.ids.insert(normalized, index);
+        }
+
+        Ok(())
+    }
+
+    /// Number of nodes of the trie, including the root.
+    #[must_use]
+    pub fn node_count(&self) -> usize {
+        self.root.count()
+    }
+
+    /// Finds all the patterns matching the hostname, in insertion order.
+    #[must_use]
+    pub fn find_matches(&self, hostname: &str) -> Vec<String> {
+        self.matching(hostname)
+            .into_iter()
+            .map(|index| self.patterns[index].to_string())
+            .collect()
+    }
+
+    /// Finds the most specific pattern matching the hostname, i.e. the one
+    /// with the most literal labels, a pattern without a leftmost `*` winning
+    /// over one with. On ties, the first inserted pattern wins.
+    #[must_use]
+    pub fn find_best_match(&self, hostname: &str) -> Option<String> {
+        self.matching(hostname)
+            .into_iter()
+            .min_by_key(|&index| {
+                let is_subdomain = self.patterns[index].starts_with('*');
+                (std::cmp::Reverse(self.literal_labels[index]), is_subdomain)
+            })
+            .map(|index| self.patterns[index].to_string())
+    }
+
+    /// Indices of the patterns matching the hostname, in insertion order.
+    fn matching(&self, hostname: &str) -> Vec<usize> {
+        let hostname = normalize(hostname);
+        if hostname.is_empty() {
+            return Vec::new();
+        }
+        let labels: Vec<&str> = hostname.rsplit('.').collect();
+        let mut matches = Vec::new();
+        self.root.matches(&labels, &mut matches);
+        matches.sort_unstable();
+        matches
+    }
+}
@@ -0,0 +1,162 @@
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use http::Request;
+use regex_automata::{meta::Regex as CaptureRegex, util::syntax};
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::RegexTrie;
+
+/// Request extension inserted when a request URI matches one of the patterns
+/// of the trie.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MatchedPattern {
+    /// The best matching pattern, as inserted in the trie
+    pub pattern: String,
+    /// Named capture groups of the pattern (e.g. `(?P<id>[0-9]+)`), with
+    /// the part of the URI they captured
+    pub params: HashMap<String, String>,
+}
+
+/// Which part of the request URI is matched against the trie.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UriTarget {
+    /// Only the path, e.g. `/user/1234`
+    #[default]
+    Path,
+    /// The whole URI as received, e.g. `https://google.com/user/1234?a=b`
+    Full,
+}
+
+/// Shared state between the layer and all the services it creates.
+#[derive(Debug)]
+struct Router {
+    /// Trie used to find the best match
+    trie: RegexTrie,
+    /// Capture-able regex for patterns having named groups, as the DFA used
+    /// by the trie can't report captures
+    captures: HashMap<String, CaptureRegex>,
+    /// Which part of the URI to match
+    target: UriTarget,
+}
+
+impl Router {
+    /// Find the best pattern for the given request, extracting its params.
+    fn match_request<B>(&self, request: &Request<B>) -> Option<MatchedPattern> {
+        let input = match self.target {
+            UriTarget::Path => request.uri().path().to_string(),
+            UriTarget::Full => request.uri().to_string(),
+        };
+        let pattern = self.trie.find_best_match(&input)?;
+
+        let mut params = HashMap::new();
+        if let Some(regex) = self.captures.get(&pattern) {
+            let mut caps = regex.create_captures();
+            regex.captures(input.as_str(), &mut caps);
+            for (group, name) in regex.group_info().pattern_names(0.into()).enumerate() {
+                let (Some(name), Some(span)) = (name, caps.get_group(group)) else {
+                    continue;
+                };
+                params.insert(name.to_string(), input[span.range()].to_string());
+            }
+        }
+
+        Some(MatchedPattern { pattern, params })
+    }
+}
+
+/// Tower layer matching every request URI against a `RegexTrie`, and
+/// exposing the result as a `MatchedPattern` request extension.
+#[derive(Debug, Clone)]
+pub struct RegexTrieLayer {
+    /// Shared matching state
+    router: Arc<Router>,
+}
+
+impl RegexTrieLayer {
+    /// Creates a new layer matching the URI path against the given trie.
+    #[must_use]
+    pub fn new(trie: RegexTrie) -> Self {
+        Self::with_target(trie, UriTarget::default())
+    }
+
+    /// Creates a new layer matching the chosen part of the URI against the
+    /// given trie.
+    #[must_use]
+    pub fn with_target(trie: RegexTrie, target: UriTarget) -> Self {
+        // Only patterns with named groups need a second, capture-able regex,
+        // compiled like the trie does.
+        let captures = trie
+            .regex_patterns()
+            .filter_map(|(pattern, source, case_insensitive)| {
+                let regex = CaptureRegex::builder()
+                    .syntax(syntax::Config::new().case_insensitive(case_insensitive))
+                    .build(&source)
+                    .ok()?;
+                let has_names = regex
+                    .group_info()
+                    .pattern_names(0.into())
+                    .any(|name| name.is_some());
+                has_names.then(|| (pattern.to_string(), regex))
+            })
+            .collect();
+
+        Self {
+            router: Arc::new(Router {
+                trie,
+                captures,
+                target,
+            }),
+        }
+    }
+
+    /// Match a single request without going through the service stack.
+    #[must_use]
+    pub fn match_request<B>(&self, request: &Request<B>) -> Option<MatchedPattern> {
+        self.router.match_request(request)
+    }
+}
+
+impl<S> Layer<S> for RegexTrieLayer {
+    type Service = RegexTrieService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RegexTrieService {
+            inner,
+            router: Arc::clone(&self.router),
+        }
+    }
+}
+
+/// Service created by `RegexTrieLayer`.
+#[derive(Debug, Clone)]
+pub struct RegexTrieService<S> {
+    /// Wrapped service
+    inner: S,
+    /// Shared matching state
+    router: Arc<Router>,
+}
+
+impl<S, B> Service<Request<B>> for RegexTrieService<S>
+where
+    S: Service<Request<B>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: Request<B>) -> Self::Future {
+        if let Some(matched) = self.router.match_request(&request) {
+            request.extensions_mut().insert(matched);
+        }
+        self.inner.call(request)
+    }
+}
@@ -0,0 +1,119 @@
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    future::{Ready, ready},
+    task::{Context, Poll},
+};
+
+use http::Request;
+use pretty_assertions::assert_eq;
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::{InsertOptions, MatchedPattern, RegexTrie, RegexTrieLayer, UriTarget};
+
+/// Inner service which just hands back the matched extension
+struct Echo;
+
+impl Service<Request<()>> for Echo {
+    type Response = Option<MatchedPattern>;
+    type Error = Infallible;
+    type Future = Ready<Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: Request<()>) -> Self::Future {
+        ready(Ok(request.extensions().get::<MatchedPattern>().cloned()))
+    }
+}
+
+/// Build a test trie
+fn routes() -> RegexTrie {
+    RegexTrie::from(&[
+        "/user/(?P<id>[0-9]+)".to_string(),
+        "/user/.*".to_string(),
+        "/health".to_string(),
+    ])
+    .expect("can't init regex trie")
+}
+
+/// Test the best pattern and its params are exposed as an extension
+#[test]
+fn test_layer_inserts_extension() {
+    let mut service = RegexTrieLayer::new(routes()).layer(Echo);
+    let request = Request::get("/user/1234?verbose=1")
+        .body(())
+        .expect("valid request");
+
+    let matched = service.call(request).into_inner().expect("infallible");
+    assert_eq!(
+        Some(MatchedPattern {
+            pattern: "/user/.*".to_string(),
+            params: HashMap::new(),
+        }),
+        matched,
+        "shortest pattern should win"
+    );
+}
+
+/// Test named groups are extracted as params
+#[test]
+fn test_route_params() {
+    let trie = RegexTrie::from_with_scorer(
+        &["/user/(?P<id>[0-9]+)".to_string(), "/user/.*".to_string()],
+        Box::new(|pattern: &str, _| usize::from(pattern.ends_with(".*"))),
+    )
+    .expect("can't init regex trie");
+    let layer = RegexTrieLayer::new(trie);
+    let request = Request::get("/user/1234").body(()).expect("valid request");
+
+    let matched = layer.match_request(&request).expect("should match");
+    assert_eq!("/user/(?P<id>[0-9]+)", matched.pattern, "wrong pattern");
+    assert_eq!(
+        Some(&"1234".to_string()),
+        matched.params.get("id"),
+        "wrong param"
+    );
+}
+
+/// Test the params of a case-insensitive pattern
+#[test]
+fn test_case_insensitive_route_params() {
+    let mut trie = RegexTrie::new();
+    trie.insert_with_options(
+        "/Item/(?P<id>[0-9]+)",
+        InsertOptions {
+            case_insensitive: true,
+        },
+    )
+    .expect("can't insert");
+    let layer = RegexTrieLayer::new(trie);
+    let request = Request::get("/item/7").body(()).expect("valid request");
+
+    let matched = layer.match_request(&request).expect("should match");
+    assert_eq!(
+        Some(&"7".to_string()),
+        matched.params.get("id"),
+        "wrong param"
+    );
+}
+
+/// Test that no extension is added when nothing matches, and that the full
+/// URI can be targeted
+#[test]
+fn test_no_match_and_full_uri() {
+    let layer = RegexTrieLayer::new(routes());
+    let request = Request::get("/nothing").body(()).expect("valid request");
+    assert_eq!(None, layer.match_request(&request), "should not match");
+
+    let layer = RegexTrieLayer::with_target(
+        RegexTrie::from(&["https://google\\.com/.*".to_string()]).expect("can't init regex trie"),
+        UriTarget::Full,
+    );
+    let request = Request::get("https://google.com/a")
+        .body(())
+        .expect("valid request");
+    assert!(layer.match_request(&request).is_some(), "should match");
+}
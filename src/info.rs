@@ -0,0 +1,121 @@
+use regex_syntax::ast::{self, Ast, Flag, FlagsItemKind, GroupKind};
+
+use crate::{Engine, PatternId, PatternKind, RegexTrie, options::stored_prefix};
+
+/// What the trie knows about a pattern, as computed when it was inserted,
+/// see `RegexTrie::pattern_info`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PatternInfo {
+    /// Literal prefix under which the pattern is stored in the trie,
+    /// unescaped, i.e. the whole pattern if it's plain
+    pub literal_prefix: String,
+    /// Whether the pattern is plain or a compiled regex
+    pub kind: PatternKind,
+    /// Engine running the regex, `None` for a plain pattern
+    pub engine: Option<Engine>,
+    /// Heap memory used by the compiled regex, in bytes, 0 for a plain
+    /// pattern
+    pub dfa_bytes: usize,
+    /// Minimum length of its matches, in bytes
+    pub min_len: usize,
+    /// Maximum length of its matches, in bytes, `None` if unbounded
+    pub max_len: Option<usize>,
+    /// Whether it has been inserted as case-insensitive, see
+    /// `InsertOptions`
+    pub case_insensitive: bool,
+    /// Inline flags enabled somewhere in the regex (`i`, `m`, `s`, `U`, `u`,
+    /// `R` or `x`), sorted, e.g. `is` for `(?i)id-(?s:.+)`
+    pub inline_flags: String,
+}
+
+/// Collects the inline flags enabled in a regex.
+struct InlineFlags(Vec<char>);
+
+impl ast::Visitor for InlineFlags {
+    type Output = Vec<char>;
+    type Err = ();
+
+    fn finish(self) -> Result<Self::Output, Self::Err> {
+        Ok(self.0)
+    }
+
+    fn visit_pre(&mut self, ast: &Ast) -> Result<(), Self::Err> {
+        let flags = match ast {
+            Ast::Flags(set_flags) => &set_flags.flags,
+            Ast::Group(group) => match &group.kind {
+                GroupKind::NonCapturing(flags) => flags,
+                _ => return Ok(()),
+            },
+            _ => return Ok(()),
+        };
+        // Flags after a `-` are disabled.
+        for item in &flags.items {
+            match item.kind {
+                FlagsItemKind::Negation => break,
+                FlagsItemKind::Flag(flag) => self.0.push(match flag {
+                    Flag::CaseInsensitive => 'i',
+                    Flag::MultiLine => 'm',
+                    Flag::DotMatchesNewLine => 's',
+                    Flag::SwapGreed => 'U',
+                    Flag::Unicode => 'u',
+                    Flag::CRLF => 'R',
+                    Flag::IgnoreWhitespace => 'x',
+                }),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Inline flags enabled somewhere in the regex, sorted. Empty if it can't be
+/// parsed.
+fn inline_flags(pattern: &str) -> String {
+    let Ok(ast) = ast::parse::Parser::new().parse(pattern) else {
+        return String::new();
+    };
+    let mut flags = ast::visit(&ast, InlineFlags(Vec::new())).unwrap_or_default();
+    flags.sort_unstable();
+    flags.dedup();
+    flags.into_iter().collect()
+}
+
+impl RegexTrie {
+    /// What the trie extracted from the pattern of the given id when it was
+    /// inserted (literal prefix, kind, engine and size of the compiled regex,
+    /// match lengths, flags), for tools reasoning about the rules without
+    /// parsing them again. `None` if the id is unknown or removed.
+    #[must_use]
+    pub fn pattern_info(&self, id: PatternId) -> Option<PatternInfo> {
+        let (pattern, compiled_index) = self.patterns.get(id.index())?.as_ref()?;
+        let case_insensitive = self.is_case_insensitive(id);
+        let literal_prefix: String = stored_prefix(pattern, case_insensitive, &self.extra_specials)
+            .into_iter()
+            .map(|(ch, _)| ch)
+            .collect();
+
+        let Some(compiled_index) = *compiled_index else {
+            return Some(PatternInfo {
+                kind: PatternKind::Literal,
+                engine: None,
+                dfa_bytes: 0,
+                min_len: literal_prefix.len(),
+                max_len: Some(literal_prefix.len()),
+                case_insensitive,
+                inline_flags: String::new(),
+                literal_prefix,
+            });
+        };
+        let (_, dfa, _) = &self.compiled_patterns[compiled_index];
+        let (min_len, max_len) = self.match_lengths[compiled_index];
+        Some(PatternInfo {
+            literal_prefix,
+            kind: PatternKind::Regex,
+            engine: Some(dfa.engine()),
+            dfa_bytes: dfa.memory_usage(),
+            min_len,
+            max_len,
+            case_insensitive,
+            inline_flags: inline_flags(pattern),
+        })
+    }
+}
@@ -0,0 +1,68 @@
+use std::{borrow::Cow, sync::OnceLock};
+
+use crate::{RegexTrie, RegexTrieError};
+
+/// A trie built from static patterns on first use, meant to be stored in a
+/// `static`:
+///
+/// ```
+/// use regextrie::LazyRegexTrie;
+///
+/// static RULES: LazyRegexTrie = LazyRegexTrie::new(&["/api/.*", "/health"]);
+///
+/// let rules = RULES.get().expect("invalid rules");
+/// assert_eq!(rules.find_matches("/health"), vec!["/health"]);
+/// ```
+#[derive(Debug)]
+pub struct LazyRegexTrie {
+    /// Patterns to build the trie from
+    patterns: &'static [&'static str],
+    /// Custom scorer, if any
+    scorer: Option<fn(&str, bool) -> usize>,
+    /// Outcome of the build, once done
+    trie: OnceLock<Result<RegexTrie, RegexTrieError>>,
+}
+
+impl LazyRegexTrie {
+    /// Trie of the given patterns, with the default scorer, not built yet.
+    #[must_use]
+    pub const fn new(patterns: &'static [&'static str]) -> Self {
+        Self {
+            patterns,
+            scorer: None,
+            trie: OnceLock::new(),
+        }
+    }
+
+    /// Trie of the given patterns, with a custom scorer, not built yet.
+    #[must_use]
+    pub const fn with_scorer(
+        patterns: &'static [&'static str],
+        scorer: fn(&str, bool) -> usize,
+    ) -> Self {
+        Self {
+            patterns,
+            scorer: Some(scorer),
+            trie: OnceLock::new(),
+        }
+    }
+
+    /// The trie, built by the first call (concurrent calls wait for it).
+    ///
+    /// ## Errors
+    ///
+    /// If any pattern can't be compiled. The build is only attempted once,
+    /// so every call returns the same error.
+    pub fn get(&self) -> Result<&RegexTrie, RegexTrieError> {
+        self.trie
+            .get_or_init(|| {
+                let mut trie = self.scorer.map_or_else(RegexTrie::new, |scorer| {
+                    RegexTrie::new_with_custom_scorer(Box::new(scorer))
+                });
+                trie.insert_many_lazy(self.patterns.iter().copied().map(Cow::Borrowed).collect())?;
+                Ok(trie)
+            })
+            .as_ref()
+            .map_err(Clone::clone)
+    }
+}
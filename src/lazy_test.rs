@@ -0,0 +1,57 @@
+use pretty_assertions::assert_eq;
+
+use crate::{LazyRegexTrie, RegexTrieError};
+
+/// Valid static rules
+static RULES: LazyRegexTrie = LazyRegexTrie::new(&["/api/.*", "/api/users", "/health"]);
+
+/// Static rules with an invalid pattern
+static BROKEN: LazyRegexTrie = LazyRegexTrie::new(&["/api/.*", "/api/[", "/health"]);
+
+/// Static rules with a custom scorer
+static SCORED: LazyRegexTrie =
+    LazyRegexTrie::with_scorer(&["/api/.*", "/api/[a-z]+"], |pattern, _| {
+        100 - pattern.len()
+    });
+
+#[test]
+fn test_lazy_trie() {
+    let rules = RULES.get().expect("invalid rules");
+    assert_eq!(
+        rules.find_matches("/api/users"),
+        vec!["/api/users", "/api/.*"]
+    );
+    // Built once
+    assert!(std::ptr::eq(rules, RULES.get().expect("invalid rules")));
+
+    let threads: Vec<_> = (0..4)
+        .map(|_| std::thread::spawn(|| RULES.get().map(|trie| trie.find_best_match("/health"))))
+        .collect();
+    for thread in threads {
+        assert_eq!(
+            thread
+                .join()
+                .expect("thread panicked")
+                .expect("invalid rules"),
+            Some("/health".to_string())
+        );
+    }
+
+    assert_eq!(
+        SCORED
+            .get()
+            .expect("invalid rules")
+            .find_best_match("/api/users"),
+        Some("/api/[a-z]+".to_string())
+    );
+}
+
+#[test]
+fn test_lazy_trie_error() {
+    for _ in 0..2 {
+        assert!(matches!(
+            BROKEN.get(),
+            Err(RegexTrieError::RegexCompilationFailed(_))
+        ));
+    }
+}
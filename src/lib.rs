@@ -2,12 +2,287 @@
 
 /// Regex trie
 mod regex_trie;
-pub use regex_trie::RegexTrie;
+pub use regex_trie::{
+    MatchMode, PatternEntry, PatternId, PatternKind, RegexTrie, ScorerFuncType, SelectionPolicy,
+};
+
+/// Step by step explanation of a match
+mod explain;
+pub use explain::{CandidateTrace, MatchTrace};
+
+/// Introspection of the inserted patterns
+mod info;
+pub use info::PatternInfo;
+
+/// Cost estimation of the patterns before insertion
+mod cost;
+pub use cost::CostEstimate;
+
+/// Handling of the empty patterns and inputs
+mod empty;
+pub use empty::EmptyPolicy;
+
+/// Substring matching, with match offsets
+mod unanchored;
+pub use unanchored::UnanchoredMatch;
+
+/// Matching of whole documents, line by line
+mod scan;
+pub use scan::LineMatches;
+
+/// Matching of chunked streams
+mod stream;
+pub use stream::StreamMatcher;
+
+/// Tie breaking between best matches
+mod tie_break;
+pub use tie_break::TieBreak;
+
+/// Per pattern hit counters
+mod hits;
+pub use hits::MatchStats;
+
+/// Pattern tags, to group matches
+mod tags;
+
+/// Trie associating a value to every pattern
+mod map;
+pub use map::{Entry, OccupiedEntry, RegexTrieMap, VacantEntry};
+
+/// Static tries built on first use
+mod lazy;
+pub use lazy::LazyRegexTrie;
+
+/// Completion queries, for interactive input validation
+mod completion;
+
+/// Traversal of the trie structure
+mod visit;
+
+/// Differences between two tries
+mod diff;
+pub use diff::{ScoreChange, TrieDiff};
+
+/// Deduplication of equivalent regexes
+mod dedup;
+
+/// Per-pattern insertion settings
+mod options;
+pub use options::InsertOptions;
+
+/// Explicit insertion of plain patterns and regexes
+mod literal;
+pub use literal::LiteralDetection;
+
+/// Translation of POSIX extended regexes
+mod posix;
+
+/// Translation of PCRE patterns
+mod pcre;
+
+/// Numeric range tokens in patterns
+mod range;
+
+/// Translation of IPv4 CIDR blocks
+mod cidr;
+
+/// Translation of the glob stars of path patterns
+mod glob;
+pub use glob::GlobStar;
+
+/// Bulk removal of patterns
+mod remove;
+pub use remove::CompactReport;
+
+/// Builds bounded in time
+mod deadline;
+
+/// Compilation of the inserted patterns in a background thread
+mod background;
+pub use background::BackgroundRegexTrie;
+
+/// Stream matching off the async executor, on the tokio blocking pool
+#[cfg(feature = "tokio")]
+mod batch;
+#[cfg(feature = "tokio")]
+pub use batch::BestMatchStream;
+
+/// Required literals of the regexes, for the substring mode
+mod prefilter;
+
+/// Filter of the first bytes of the literal prefixes
+mod first_byte;
+
+/// Candidates ordered by their number of matches
+mod profile;
+
+/// Statistics-driven optimization of the query path
+mod optimize;
+pub use optimize::OptimizeReport;
+
+/// Memoization of the candidates by trie node
+mod memo;
+
+/// Combined regexes of the nodes holding many of them
+mod alternation;
+
+/// Trigram index of the literals required by the regexes
+mod trigram;
+
+/// Trie of the reversed literal suffixes of the regexes
+mod suffix;
+
+/// Scratch memory reused across queries
+mod cache;
+pub use cache::QueryCache;
+
+/// Tries partitioned in shards, for concurrent inserts
+mod sharded;
+pub use sharded::ShardedRegexTrie;
+
+/// Memory budget of the compiled DFAs
+mod budget;
+pub use budget::DfaSizeWarningFuncType;
+
+/// Temporary patterns, expiring after a TTL
+mod ttl;
+
+/// Write-ahead log of the inserts and removals
+mod wal;
+pub use wal::LoggedRegexTrie;
+
+/// In-memory snapshots, to roll back the changes of a trie
+mod snapshot;
+pub use snapshot::TrieSnapshot;
+
+/// Frozen tries shared between processes through memory mapped files
+#[cfg(unix)]
+mod shared;
+
+/// Minimum score of every subtree, bounding the best match walk
+mod score_bound;
+
+/// Regex engines of the compiled patterns
+mod engine;
+pub use engine::{DfaConfig, Engine};
+
+/// Matching of bytes and paths, which may not be valid UTF-8
+mod utf8;
+pub use utf8::Utf8Handling;
+
+/// Set algebra on tries
+mod algebra;
+
+/// Read-only tries sharing identical subtrees
+mod frozen;
+pub use frozen::FrozenRegexTrie;
+
+/// Trie keyed on path segments
+mod segment;
+pub use segment::PathSegmentTrie;
+
+/// Trie keyed on hostname labels
+mod hostname;
+pub use hostname::HostnameTrie;
+
+/// Trie keyed on the tokens of a custom tokenizer
+mod token;
+pub use token::{TokenTrie, TokenizerFuncType};
+
+/// Trie of the literals the patterns contain, for the "contains" mode
+mod contains;
+pub use contains::ContainsTrie;
+
+/// Example generation from patterns
+mod example;
+
+/// Binary serialization of a compiled trie
+mod serialize;
+
+/// Common trait for all matching backends
+mod matcher;
+pub use matcher::PatternMatcher;
+
+/// Naive reference implementation, for differential testing
+pub mod reference;
 
 /// Error for regex trie
 mod error;
 pub use error::RegexTrieError;
 
+/// Tower/http integration
+#[cfg(feature = "http")]
+mod http;
+#[cfg(feature = "http")]
+pub use http::{MatchedPattern, RegexTrieLayer, RegexTrieService, UriTarget};
+/// Compile-time checked pattern sets
+#[cfg(feature = "macros")]
+pub use regextrie_macros::regextrie;
+// The generated code refers to `::regextrie`, also within this crate.
+#[cfg(feature = "macros")]
+extern crate self as regextrie;
+
+/// Javascript bindings
+#[cfg(feature = "wasm")]
+mod wasm;
+#[cfg(feature = "wasm")]
+pub use wasm::WasmRegexTrie;
+
+/// Python bindings
+#[cfg(feature = "python")]
+mod python;
+#[cfg(feature = "python")]
+pub use python::PyRegexTrie;
+
 /// Test for pattern parser
 #[cfg(test)]
 mod regex_trie_test;
+
+/// Test for the substring matching
+#[cfg(test)]
+mod unanchored_test;
+
+/// Test for the stream matching
+#[cfg(test)]
+mod stream_test;
+
+/// Test for the map variant
+#[cfg(test)]
+mod map_test;
+
+/// Test for the static tries
+#[cfg(test)]
+mod lazy_test;
+
+/// Test for the matcher trait
+#[cfg(test)]
+mod matcher_test;
+
+/// Test for the naive reference implementation
+#[cfg(test)]
+mod reference_test;
+
+/// Test for the tower/http integration
+#[cfg(test)]
+#[cfg(feature = "http")]
+mod http_test;
+
+/// Test for the stream matching
+#[cfg(test)]
+#[cfg(feature = "tokio")]
+mod batch_test;
+
+/// Test for the compile-time checked pattern sets
+#[cfg(test)]
+#[cfg(feature = "macros")]
+mod macros_test;
+
+/// Test for the python bindings
+#[cfg(test)]
+#[cfg(feature = "python")]
+mod python_test;
+
+/// Test for the javascript bindings
+#[cfg(test)]
+#[cfg(feature = "wasm")]
+mod wasm_test;
@@ -0,0 +1,242 @@
+use std::{borrow::Cow, collections::HashMap, sync::Arc};
+
+use crate::{PatternId, PatternKind, RegexTrie, RegexTrieError, regex_trie::PreparedBatch};
+
+/// How `RegexTrie::insert` tells the plain patterns from the regexes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LiteralDetection {
+    /// A pattern without any meta-character is plain
+    #[default]
+    Auto,
+    /// Same, but the plain patterns which would read differently as a regex
+    /// (with a `\d`-like escape, `|`, `^` or `$`) are rejected, to be
+    /// inserted with `RegexTrie::insert_literal` or `RegexTrie::insert_regex`
+    Strict,
+}
+
+/// Strings the plain patterns inserted with `RegexTrie::insert_literal` have
+/// been escaped from, when they differ from their escaped pattern.
+#[derive(Debug, Default)]
+pub(crate) struct VerbatimLiterals {
+    /// String, by escaped pattern
+    strings: HashMap<Arc<str>, Arc<str>>,
+    /// Escaped pattern, by string
+    patterns: HashMap<Arc<str>, Arc<str>>,
+}
+
+impl VerbatimLiterals {
+    /// String the escaped pattern comes from, if it's been escaped.
+    pub(crate) fn string(&self, pattern: &str) -> Option<&Arc<str>> {
+        self.strings.get(pattern)
+    }
+
+    /// Records the string an escaped pattern comes from.
+    pub(crate) fn insert(&mut self, pattern: Arc<str>, string: Arc<str>) {
+        self.patterns
+            .insert(Arc::clone(&string), Arc::clone(&pattern));
+        self.strings.insert(pattern, string);
+    }
+
+    /// Forgets the string of a removed pattern.
+    pub(crate) fn remove(&mut self, pattern: &str) {
+        if let Some(string) = self.strings.remove(pattern) {
+            self.patterns.remove(&string);
+        }
+    }
+}
+
+impl RegexTrie {
+    /// Sets how `insert` (and the other insertions of patterns as is) tells
+    /// the plain patterns from the regexes. With `LiteralDetection::Strict`,
+    /// a pattern taken for a plain one while it would read differently as a
+    /// regex, like `/id/\d` (a literal backslash for the trie) or `^/home$`,
+    /// fails to insert with `RegexTrieError::InvalidPattern`, instead of
+    /// being silently matched verbatim. Like the match mode, it isn't
+    /// serialized.
+    #[must_use]
+    pub const fn with_literal_detection(mut self, literal_detection: LiteralDetection) -> Self {
+        self.literal_detection = literal_detection;
+        self
+    }
+
+    /// How `insert` tells the plain patterns from the regexes.
+    #[must_use]
+    pub const fn literal_detection(&self) -> LiteralDetection {
+        self.literal_detection
+    }
+
+    /// How `insert` would take the pattern, with the extra meta-characters
+    /// of the trie: as a regex if it has an unescaped meta-character or an
+    /// assertion (e.g. `\b`) after its literal prefix, as a plain pattern
+    /// otherwise. It's scored the same way, the scorer being told whether
+    /// it's a regex. A case-insensitive plain pattern is compiled anyway (see
+    /// `insert_with_options`), but keeps its plain score.
+    #[must_use]
+    pub fn classify(&self, pattern: &str) -> PatternKind {
+        let (_, is_regex) = self.parse_prefix(pattern);
+        if is_regex {
+            PatternKind::Regex
+        } else {
+            PatternKind::Literal
+        }
+    }
+
+    /// Byte offset of the first character of a plain pattern reading
+    /// differently as a regex: a `\` which doesn't escape a meta-character,
+    /// `|`, `^` or `$`. `None` for a regex, or a plain pattern reading the
+    /// same.
+    fn ambiguous_offset(&self, pattern: &str) -> Option<usize> {
+        let (_, is_regex) = self.parse_prefix(pattern);
+        if is_regex {
+            return None;
+        }
+        let mut chars = pattern.char_indices().peekable();
+        while let Some((offset, ch)) = chars.next() {
+            match ch {
+                '\\' if chars.next_if(|&(_, next)| self.is_special(next)).is_none() => {
+                    return Some(offset);
+                }
+                '|' | '^' | '$' => return Some(offset),
+                _ => {}
+            }
+        }
+        None
+    }
+
+    /// Verifies that a batch of patterns can be inserted, according to the
+    /// literal detection.
+    ///
+    /// ## Errors
+    ///
+    /// With the first plain pattern reading differently as a regex, in
+    /// `LiteralDetection::Strict`
+    pub(crate) fn check_literal_detection<'batch>(
+        &self,
+        batch: impl IntoIterator<Item = &'batch str>,
+    ) -> Result<(), RegexTrieError> {
+        if self.literal_detection == LiteralDetection::Auto {
+            return Ok(());
+        }
+        let ambiguous = batch
+            .into_iter()
+            .find(|pattern| self.ambiguous_offset(pattern).is_some());
+        if let Some(pattern) = ambiguous {
+            return Err(RegexTrieError::InvalidPattern(format!(
+                "{pattern:?} would be plain, insert it with insert_literal or insert_regex"
+            )));
+        }
+        Ok(())
+    }
+
+    /// Rewrites a regex the trie would take for a plain pattern (see
+    /// `LiteralDetection::Strict`), so it's read as a regex: its part after
+    /// the literal prefix is put in a group, e.g. `/id/(?:\d)` for `/id/\d`,
+    /// or the whole regex if it has an alternation. Other patterns are left
+    /// as is, including the ones without any regex syntax, which match the
+    /// same either way and stay plain.
+    #[must_use]
+    pub fn force_regex(&self, pattern: &str) -> String {
+        match self.ambiguous_offset(pattern) {
+            None => pattern.to_string(),
+            Some(_) if pattern.contains('|') => format!("(?:{pattern})"),
+            Some(offset) => format!("{}(?:{})", &pattern[..offset], &pattern[offset..]),
+        }
+    }
+
+    /// Inserts a pattern as a regex, whatever the literal detection: it's
+    /// rewritten with `force_regex` if the trie would take it for a plain
+    /// pattern. The trie only knows the rewritten pattern, which is returned,
+    /// e.g. to map the matches back to the original rules.
+    ///
+    /// ## Errors
+    ///
+    /// If the regex pattern can't be compiled, or if the pattern capacity or
+    /// the DFA memory budget would be exceeded
+    pub fn insert_regex(&mut self, pattern: &str) -> Result<String, RegexTrieError> {
+        let regex = self.force_regex(pattern);
+        self.insert(&regex)?;
+        Ok(regex)
+    }
+
+    /// Escapes the meta-characters of a string (including the extra ones of
+    /// the trie), so it's stored as a plain pattern matching the string
+    /// verbatim, e.g. `/search\?q=a\+b` for `/search?q=a+b`.
+    ///
+    /// ## Errors
+    ///
+    /// If the string can't be a plain pattern: a `\` followed by a letter of
+    /// an assertion (e.g. `\b`) is always read as one
+    pub fn escape_literal(&self, literal: &str) -> Result<String, RegexTrieError> {
+        let mut escaped = String::with_capacity(literal.len());
+        for ch in literal.chars() {
+            if self.is_special(ch) {
+                escaped.push('\\');
+            }
+            escaped.push(ch);
+        }
+
+        let (prefix, is_regex) = self.parse_prefix(&escaped);
+        if is_regex || !prefix.iter().map(|&(ch, _)| ch).eq(literal.chars()) {
+            return Err(RegexTrieError::InvalidPattern(format!(
+                "{literal:?} can't be stored as a plain pattern"
+            )));
+        }
+        Ok(escaped)
+    }
+
+    /// Inserts a string as a plain pattern matching it verbatim, without any
+    /// regex interpretation, whatever the literal detection: its
+    /// meta-characters are escaped with `escape_literal`, so it takes the
+    /// fast literal path even with `?` or `+` in it, like most URLs. The
+    /// matches return the string as is, and `id_of` finds it, but the trie
+    /// stores (and `iter` lists) the escaped pattern, which is returned.
+    /// Like the match mode, the strings aren't serialized, so a loaded trie
+    /// returns the escaped patterns.
+    ///
+    /// ## Errors
+    ///
+    /// If the string can't be a plain pattern, or if the pattern capacity
+    /// would be exceeded
+    pub fn insert_literal(&mut self, literal: &str) -> Result<String, RegexTrieError> {
+        let escaped = self.prepare_literal(literal)?;
+        let parsed = self.parse_prefix(&escaped);
+        let is_new = !self.ids.contains_key(escaped.as_str());
+        self.commit_many(PreparedBatch {
+            parsed: vec![(Cow::Borrowed(escaped.as_str()), parsed)],
+            compiled: vec![None],
+        });
+        if is_new && escaped != literal {
+            self.record_verbatim(&escaped, literal);
+        }
+        Ok(escaped)
+    }
+
+    /// First stage of `insert_literal`, which doesn't modify the trie:
+    /// escapes the string, and checks it can be inserted.
+    ///
+    /// ## Errors
+    ///
+    /// If the string can't be a plain pattern, or if the pattern capacity
+    /// would be exceeded
+    pub(crate) fn prepare_literal(&self, literal: &str) -> Result<String, RegexTrieError> {
+        let escaped = self.escape_literal(literal)?;
+        // Not checked against the literal detection, being plain anyway
+        self.check_capacity([escaped.as_str()])?;
+        self.check_empty_patterns([(escaped.as_str(), false)])?;
+        Ok(escaped)
+    }
+
+    /// Records the string a known plain pattern has been escaped from.
+    pub(crate) fn record_verbatim(&mut self, pattern: &str, string: &str) {
+        if let Some((pattern, _)) = self.ids.get_key_value(pattern) {
+            self.verbatim_literals
+                .insert(Arc::clone(pattern), Arc::from(string));
+        }
+    }
+
+    /// Id of a pattern escaped by `insert_literal`, from its string.
+    pub(crate) fn verbatim_id(&self, string: &str) -> Option<PatternId> {
+        let pattern = self.verbatim_literals.patterns.get(string)?;
+        self.ids.get(pattern).copied()
+    }
+}
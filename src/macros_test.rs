@@ -0,0 +1,25 @@
+use pretty_assertions::assert_eq;
+
+use crate::regextrie;
+
+#[test]
+fn test_regextrie_macro() {
+    let tree = regextrie! {
+        "https://google.com/.*",
+        r"https://yahoo\.com/[0-9]+",
+        "plain",
+    };
+    assert_eq!(tree.iter().count(), 3);
+    assert_eq!(
+        tree.find_matches("https://google.com/search"),
+        vec!["https://google.com/.*"]
+    );
+    assert_eq!(
+        tree.find_best_match("https://yahoo.com/42"),
+        Some(r"https://yahoo\.com/[0-9]+".to_string())
+    );
+    assert_eq!(tree.find_matches("plain"), vec!["plain"]);
+
+    let empty = regextrie! {};
+    assert!(empty.iter().next().is_none());
+}
@@ -0,0 +1,304 @@
+use crate::{
+    MatchMode, PatternEntry, PatternId, RegexTrie, RegexTrieError, ScorerFuncType, SelectionPolicy,
+    TieBreak,
+};
+
+/// A `RegexTrie` associating a value to each pattern (e.g. owner, team or
+/// expiry of a rule, or a request handler), returned along with the matches.
+#[derive(Debug)]
+pub struct RegexTrieMap<V> {
+    /// Patterns
+    trie: RegexTrie,
+    /// Value of every pattern, indexed by its `PatternId`
+    values: Vec<V>,
+}
+
+impl<V> Default for RegexTrieMap<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<V> RegexTrieMap<V> {
+    /// Creates a new, empty map with default scorer.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            trie: RegexTrie::new(),
+            values: Vec::new(),
+        }
+    }
+
+    /// Creates a new, empty map with a custom scorer.
+    #[must_use]
+    pub fn new_with_custom_scorer(scorer: ScorerFuncType) -> Self {
+        Self {
+            trie: RegexTrie::new_with_custom_scorer(scorer),
+            values: Vec::new(),
+        }
+    }
+
+    /// Sets how patterns are matched, see `RegexTrie::with_match_mode`.
+    #[must_use]
+    pub fn with_match_mode(mut self, match_mode: MatchMode) -> Self {
+        self.trie = self.trie.with_match_mode(match_mode);
+        self
+    }
+
+    /// Sets how the best match is selected, see
+    /// `RegexTrie::with_selection_policy`.
+    #[must_use]
+    pub fn with_selection_policy(mut self, selection_policy: SelectionPolicy) -> Self {
+        self.trie = self.trie.with_selection_policy(selection_policy);
+        self
+    }
+
+    /// Sets how ties between best matches are broken, see
+    /// `RegexTrie::with_tie_break`.
+    #[must_use]
+    pub fn with_tie_break(mut self, tie_break: TieBreak) -> Self {
+        self.trie = self.trie.with_tie_break(tie_break);
+        self
+    }
+
+    /// Sets the weight of a pattern when breaking ties, see
+    /// `RegexTrie::set_weight`. Returns false if the pattern is unknown.
+    pub fn set_weight(&mut self, pattern: &str, weight: u32) -> bool {
+        self.trie
+            .id_of(pattern)
+            .is_some_and(|id| self.trie.set_weight(id, weight))
+    }
+
+    /// The underlying trie, for all the queries not involving the values.
+    #[must_use]
+    pub const fn trie(&self) -> &RegexTrie {
+        &self.trie
+    }
+
+    /// Compiles and inserts a pattern with its value. If the pattern is
+    /// already known, its value is replaced (without compiling it again) and
+    /// the previous one is returned.
+    ///
+    /// ## Errors
+    ///
+    /// If the regex pattern can't be compiled
+    pub fn insert(&mut self, pattern: &str, value: V) -> Result<Option<V>, RegexTrieError> {
+        if let Some(id) = self.trie.id_of(pattern) {
+            return Ok(Some(std::mem::replace(&mut self.values[id.index()], value)));
+        }
+        self.trie.insert(pattern)?;
+        self.values.push(value);
+        Ok(None)
+    }
+
+    /// Value of the given pattern.
+    #[must_use]
+    pub fn get(&self, pattern: &str) -> Option<&V> {
+        self.trie.id_of(pattern).and_then(|id| self.value(id))
+    }
+
+    /// Mutable value of the given pattern.
+    #[must_use]
+    pub fn get_mut(&mut self, pattern: &str) -> Option<&mut V> {
+        let id = self.trie.id_of(pattern)?;
+        self.values.get_mut(id.index())
+    }
+
+    /// Value of the pattern of the given id.
+    #[must_use]
+    pub fn value(&self, id: PatternId) -> Option<&V> {
+        self.values.get(id.index())
+    }
+
+    /// Iterates over every pattern in insertion order, with its value.
+    pub fn iter(&self) -> impl Iterator<Item = (PatternEntry<'_>, &V)> {
+        self.trie.iter().zip(&self.values)
+    }
+
+    /// Finds all the patterns matching the input, with their values, in the
+    /// order of `RegexTrie::find_matches`.
+    #[must_use]
+    pub fn find_matches(&self, input: &str) -> Vec<(&str, &V)> {
+        self.trie
+            .find_matches_shared(input)
+            .iter()
+            .filter_map(|pattern| self.with_value(pattern))
+            .collect()
+    }
+
+    /// Finds the best pattern matching the input, with its value.
+    #[must_use]
+    pub fn find_best_match(&self, input: &str) -> Option<(&str, &V)> {
+        self.trie
+            .find_best_match_shared(input)
+            .and_then(|pattern| self.with_value(&pattern))
+    }
+
+    /// Finds the values of all the patterns matching the input, in the order
+    /// of `RegexTrie::find_matches`.
+    #[must_use]
+    pub fn find_values(&self, input: &str) -> Vec<&V> {
+        self.trie
+            .find_matches_shared(input)
+            .iter()
+            .filter_map(|pattern| self.get(pattern))
+            .collect()
+    }
+
+    /// Finds the value of the best pattern matching the input, e.g. to go
+    /// straight from a request to its handler.
+    #[must_use]
+    pub fn find_best_value(&self, input: &str) -> Option<&V> {
+        self.trie
+            .find_best_match_shared(input)
+            .and_then(|pattern| self.get(&pattern))
+    }
+
+    /// Stored pattern and value of a matched pattern.
+    fn with_value(&self, pattern: &str) -> Option<(&str, &V)> {
+        let id = self.trie.id_of(pattern)?;
+        Some((self.trie.pattern(id)?, self.value(id)?))
+    }
+}
+
+/// A pattern of a `RegexTrieMap`, known or not, as returned by
+/// `RegexTrieMap::entry`.
+#[derive(Debug)]
+pub enum Entry<'map, 'pattern, V> {
+    /// Already inserted pattern
+    Occupied(OccupiedEntry<'map, V>),
+    /// Unknown pattern
+    Vacant(VacantEntry<'map, 'pattern, V>),
+}
+
+/// A pattern of the map, with its value.
+#[derive(Debug)]
+pub struct OccupiedEntry<'map, V> {
+    /// Map holding the pattern
+    map: &'map mut RegexTrieMap<V>,
+    /// Id of the pattern
+    id: PatternId,
+}
+
+/// A pattern which isn't in the map yet.
+#[derive(Debug)]
+pub struct VacantEntry<'map, 'pattern, V> {
+    /// Map to insert the pattern into
+    map: &'map mut RegexTrieMap<V>,
+    /// Pattern, not compiled yet
+    pattern: &'pattern str,
+}
+
+impl<V> RegexTrieMap<V> {
+    /// Entry of the given pattern, to insert or update its value with a
+    /// single lookup. The pattern is only compiled when inserting a vacant
+    /// entry.
+    pub fn entry<'pattern>(&mut self, pattern: &'pattern str) -> Entry<'_, 'pattern, V> {
+        match self.trie.id_of(pattern) {
+            Some(id) => Entry::Occupied(OccupiedEntry { map: self, id }),
+            None => Entry::Vacant(VacantEntry { map: self, pattern }),
+        }
+    }
+}
+
+impl<'map, V> Entry<'map, '_, V> {
+    /// The pattern of the entry.
+    #[must_use]
+    pub fn pattern(&self) -> &str {
+        match self {
+            Self::Occupied(entry) => entry.pattern(),
+            Self::Vacant(entry) => entry.pattern,
+        }
+    }
+
+    /// Updates the value if the pattern is known.
+    #[must_use]
+    pub fn and_modify(mut self, update: impl FnOnce(&mut V)) -> Self {
+        if let Self::Occupied(entry) = &mut self {
+            update(entry.get_mut());
+        }
+        self
+    }
+
+    /// Value of the pattern, inserting it with the given value if unknown.
+    ///
+    /// ## Errors
+    ///
+    /// If the pattern is unknown and can't be compiled
+    pub fn or_insert(self, value: V) -> Result<&'map mut V, RegexTrieError> {
+        self.or_insert_with(|| value)
+    }
+
+    /// Value of the pattern, inserting it with the value computed by
+    /// `default` if unknown.
+    ///
+    /// ## Errors
+    ///
+    /// If the pattern is unknown and can't be compiled
+    pub fn or_insert_with(
+        self,
+        default: impl FnOnce() -> V,
+    ) -> Result<&'map mut V, RegexTrieError> {
+        match self {
+            Self::Occupied(entry) => Ok(entry.into_mut()),
+            Self::Vacant(entry) => entry.insert(default()),
+        }
+    }
+}
+
+impl<'map, V> OccupiedEntry<'map, V> {
+    /// Id of the pattern.
+    #[must_use]
+    pub const fn id(&self) -> PatternId {
+        self.id
+    }
+
+    /// The pattern, as it was inserted.
+    #[must_use]
+    pub fn pattern(&self) -> &str {
+        self.map.trie.pattern(self.id).unwrap_or_default()
+    }
+
+    /// Value of the pattern.
+    #[must_use]
+    pub fn get(&self) -> &V {
+        &self.map.values[self.id.index()]
+    }
+
+    /// Mutable value of the pattern.
+    #[must_use]
+    pub fn get_mut(&mut self) -> &mut V {
+        &mut self.map.values[self.id.index()]
+    }
+
+    /// Mutable value of the pattern, bound to the map lifetime.
+    #[must_use]
+    pub fn into_mut(self) -> &'map mut V {
+        &mut self.map.values[self.id.index()]
+    }
+
+    /// Replaces the value of the pattern, returning the previous one.
+    pub fn insert(&mut self, value: V) -> V {
+        std::mem::replace(self.get_mut(), value)
+    }
+}
+
+impl<'map, V> VacantEntry<'map, '_, V> {
+    /// The pattern, not inserted yet.
+    #[must_use]
+    pub const fn pattern(&self) -> &str {
+        self.pattern
+    }
+
+    /// Compiles and inserts the pattern with its value.
+    ///
+    /// ## Errors
+    ///
+    /// If the regex pattern can't be compiled
+    pub fn insert(self, value: V) -> Result<&'map mut V, RegexTrieError> {
+        self.map.trie.insert(self.pattern)?;
+        let index = self.map.values.len();
+        self.map.values.push(value);
+        Ok(&mut self.map.values[index])
+    }
+}
@@ -0,0 +1,141 @@
+use pretty_assertions::assert_eq;
+
+use crate::{Entry, MatchMode, RegexTrieMap, TieBreak};
+
+#[test]
+fn test_map_values() {
+    let mut map = RegexTrieMap::new();
+    assert_eq!(
+        map.insert("/api/users/[0-9]+", "users")
+            .expect("can't insert"),
+        None
+    );
+    assert_eq!(map.insert("/api/.*", "api").expect("can't insert"), None);
+    assert_eq!(map.insert("/health", "health").expect("can't insert"), None);
+    map.insert("/api/[", "broken")
+        .expect_err("invalid pattern inserted");
+
+    assert_eq!(
+        map.find_matches("/api/users/42"),
+        vec![("/api/users/[0-9]+", &"users"), ("/api/.*", &"api")]
+    );
+    assert_eq!(
+        map.find_best_match("/api/users/42"),
+        Some(("/api/.*", &"api"))
+    );
+    assert_eq!(map.find_best_match("/health"), Some(("/health", &"health")));
+    assert_eq!(map.find_best_match("/metrics"), None);
+
+    // Replacing a value keeps the pattern and its id
+    let id = map.trie().id_of("/api/.*");
+    assert_eq!(
+        map.insert("/api/.*", "other").expect("can't insert"),
+        Some("api")
+    );
+    assert_eq!(map.trie().id_of("/api/.*"), id);
+    assert_eq!(map.get("/api/.*"), Some(&"other"));
+    *map.get_mut("/health").expect("missing pattern") = "up";
+    assert_eq!(map.value(id.expect("missing pattern")), Some(&"other"));
+    assert_eq!(map.get("/unknown"), None);
+
+    let entries: Vec<_> = map
+        .iter()
+        .map(|(entry, value)| (entry.pattern, *value))
+        .collect();
+    assert_eq!(
+        entries,
+        vec![
+            ("/api/users/[0-9]+", "users"),
+            ("/api/.*", "other"),
+            ("/health", "up")
+        ]
+    );
+}
+
+#[test]
+fn test_map_settings() {
+    let mut map = RegexTrieMap::new_with_custom_scorer(Box::new(|pattern, _| pattern.len()))
+        .with_match_mode(MatchMode::Prefix);
+    map.insert("GET /", 1).expect("can't insert");
+    map.insert("GET /api/.*", 2).expect("can't insert");
+    assert_eq!(map.trie().match_mode(), MatchMode::Prefix);
+    assert_eq!(map.find_best_match("GET /api/x"), Some(("GET /", &1)));
+    assert!(RegexTrieMap::<()>::default().find_matches("").is_empty());
+}
+
+#[test]
+fn test_map_entry() {
+    let mut map = RegexTrieMap::new();
+    for pattern in ["/api/.*", "/health", "/api/.*"] {
+        *map.entry(pattern)
+            .and_modify(|count| *count += 10)
+            .or_insert(1)
+            .expect("can't insert") += 1;
+    }
+    assert_eq!(map.get("/api/.*"), Some(&13));
+    assert_eq!(map.get("/health"), Some(&2));
+
+    let id = map.trie().id_of("/health");
+    assert!(matches!(map.entry("/health"), Entry::Occupied(_)));
+    if let Entry::Occupied(mut entry) = map.entry("/health") {
+        assert_eq!(entry.pattern(), "/health");
+        assert_eq!(Some(entry.id()), id);
+        assert_eq!(entry.insert(5), 2);
+        assert_eq!(entry.get(), &5);
+    }
+    assert!(matches!(map.entry("/a["), Entry::Vacant(_)));
+    if let Entry::Vacant(entry) = map.entry("/a[") {
+        assert_eq!(entry.pattern(), "/a[");
+        entry.insert(0).expect_err("invalid pattern inserted");
+    }
+    assert_eq!(map.entry("/a[").pattern(), "/a[");
+    assert_eq!(
+        map.entry("/b")
+            .or_insert_with(|| 7)
+            .copied()
+            .expect("can't insert"),
+        7
+    );
+    assert_eq!(map.find_best_match("/b"), Some(("/b", &7)));
+    assert_eq!(map.trie().iter().count(), 3);
+}
+
+#[test]
+fn test_map_find_values() {
+    let mut map: RegexTrieMap<fn(u32) -> u32> = RegexTrieMap::new();
+    map.insert("/double/[0-9]+", |value| value * 2)
+        .expect("can't insert");
+    map.insert("/.*", |value| value).expect("can't insert");
+
+    // The shortest regex wins
+    let handler = map.find_best_value("/double/21").expect("no handler");
+    assert_eq!(handler(21), 21);
+    let results: Vec<_> = map
+        .find_values("/double/21")
+        .iter()
+        .map(|handler| handler(3))
+        .collect();
+    assert_eq!(results, vec![6, 3]);
+    assert_eq!(
+        map.find_best_value("/other").map(|handler| handler(1)),
+        Some(1)
+    );
+    assert!(map.find_best_value("other").is_none());
+    assert!(map.find_values("other").is_empty());
+}
+
+#[test]
+fn test_map_tie_break() {
+    let mut map = RegexTrieMap::new_with_custom_scorer(Box::new(|_, _| 0))
+        .with_tie_break(TieBreak::RoundRobin);
+    map.insert("/api/.*", "pool-a").expect("can't insert");
+    map.insert("/api/v[0-9]/.*", "pool-b")
+        .expect("can't insert");
+    assert!(map.set_weight("/api/v[0-9]/.*", 2));
+    assert!(!map.set_weight("/unknown", 2));
+    let picks: Vec<_> = (0..3)
+        .filter_map(|_| map.find_best_value("/api/v1/users"))
+        .copied()
+        .collect();
+    assert_eq!(picks, vec!["pool-a", "pool-b", "pool-b"]);
+}
@@ -0,0 +1,79 @@
+use crate::{RegexTrie, RegexTrieError, reference::NaiveMatcher};
+
+/// Common interface of every pattern matching backend, so applications can
+/// swap implementations behind a generic.
+pub trait PatternMatcher {
+    /// Compiles and inserts a pattern.
+    ///
+    /// ## Errors
+    ///
+    /// If the regex pattern can't be compiled
+    fn insert(&mut self, pattern: &str) -> Result<(), RegexTrieError>;
+
+    /// Insert many patterns at once.
+    ///
+    /// ## Errors
+    ///
+    /// If any regex pattern can't be compiled
+    fn insert_many(&mut self, patterns: &[String]) -> Result<(), RegexTrieError> {
+        patterns.iter().try_for_each(|pattern| self.insert(pattern))
+    }
+
+    /// Finds all patterns fully matching the given input.
+    fn find_matches(&self, input: &str) -> Vec<String>;
+
+    /// Finds the pattern with the best (lowest) score fully matching the
+    /// given input.
+    fn find_best_match(&self, input: &str) -> Option<String>;
+
+    /// Creates a new matcher, with its default scorer, from a set of
+    /// patterns.
+    ///
+    /// ## Errors
+    ///
+    /// If any of the regex pattern can't be compiled
+    fn from_patterns(patterns: &[String]) -> Result<Self, RegexTrieError>
+    where
+        Self: Default + Sized,
+    {
+        let mut matcher = Self::default();
+        matcher.insert_many(patterns)?;
+        Ok(matcher)
+    }
+}
+
+impl PatternMatcher for RegexTrie {
+    fn insert(&mut self, pattern: &str) -> Result<(), RegexTrieError> {
+        Self::insert(self, pattern)
+    }
+
+    fn insert_many(&mut self, patterns: &[String]) -> Result<(), RegexTrieError> {
+        Self::insert_many(self, patterns)
+    }
+
+    fn find_matches(&self, input: &str) -> Vec<String> {
+        Self::find_matches(self, input)
+    }
+
+    fn find_best_match(&self, input: &str) -> Option<String> {
+        Self::find_best_match(self, input)
+    }
+}
+
+impl PatternMatcher for NaiveMatcher {
+    fn insert(&mut self, pattern: &str) -> Result<(), RegexTrieError> {
+        Self::insert(self, pattern)
+    }
+
+    fn insert_many(&mut self, patterns: &[String]) -> Result<(), RegexTrieError> {
+        Self::insert_many(self, patterns)
+    }
+
+    fn find_matches(&self, input: &str) -> Vec<String> {
+        Self::find_matches(self, input)
+    }
+
+    fn find_best_match(&self, input: &str) -> Option<String> {
+        Self::find_best_match(self, input)
+    }
+}
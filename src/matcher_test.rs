@@ -0,0 +1,37 @@
+use pretty_assertions::assert_eq;
+
+use crate::{PathSegmentTrie, PatternMatcher, RegexTrie, reference::NaiveMatcher};
+
+/// Generic helper, only relying on the trait
+fn best_of<M: PatternMatcher + Default>(patterns: &[String], input: &str) -> Option<String> {
+    let mut matcher = M::from_patterns(&patterns[1..]).expect("can't init matcher");
+    matcher.insert(&patterns[0]).expect("should insert");
+    matcher.find_best_match(input)
+}
+
+/// Test both backends can be swapped behind a generic
+#[test]
+fn test_swappable_backends() {
+    let patterns = vec![
+        "a.*".to_string(),
+        "a[0-9]+b.*".to_string(),
+        "a123bbb".to_string(),
+    ];
+
+    for input in ["a123bbb", "a1b", "b"] {
+        assert_eq!(
+            best_of::<NaiveMatcher>(&patterns, input),
+            best_of::<RegexTrie>(&patterns, input),
+            "backends disagree on {input}"
+        );
+        assert_eq!(
+            best_of::<PathSegmentTrie>(&patterns, input),
+            best_of::<RegexTrie>(&patterns, input),
+            "backends disagree on {input}"
+        );
+    }
+    assert!(
+        RegexTrie::from_patterns(&["[".to_string()]).is_err(),
+        "should have failed"
+    );
+}
@@ -0,0 +1,119 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, PoisonError, RwLock},
+};
+
+use crate::{
+    RegexTrie,
+    regex_trie::{Scratch, TrieNode},
+};
+
+/// Candidates of the deepest nodes reached by the previous queries, i.e. the
+/// sorted indices of the patterns stored along their path, keyed by node
+/// address. Addresses only change when the trie is modified, which clears
+/// the memo.
+#[derive(Debug, Default)]
+pub(crate) struct CandidateMemo {
+    /// Maximum number of memoized nodes, the memo being cleared once reached
+    capacity: usize,
+    /// Candidates of every memoized node
+    nodes: RwLock<HashMap<usize, Arc<[usize]>>>,
+}
+
+impl CandidateMemo {
+    /// Empty memo of up to `capacity` nodes.
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            nodes: RwLock::default(),
+        }
+    }
+
+    /// Maximum number of memoized nodes.
+    pub(crate) const fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Forgets every memoized node, once the trie has been modified.
+    pub(crate) fn clear(&mut self) {
+        self.nodes
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+    }
+}
+
+/// Key of a node in the memo.
+fn node_key(node: &TrieNode) -> usize {
+    std::ptr::from_ref(node).addr()
+}
+
+impl RegexTrie {
+    /// Enables memoizing the candidates of up to `capacity` trie nodes (or
+    /// disables it with 0): queries ending their walk on an already reached
+    /// node, e.g. URLs sharing a long prefix, get its candidates at once
+    /// instead of collecting them node by node. The memo is cleared whenever
+    /// the trie is modified, or once full.
+    #[must_use]
+    pub fn with_candidate_memoization(mut self, capacity: usize) -> Self {
+        self.candidate_memo = (capacity > 0).then(|| CandidateMemo::new(capacity));
+        self
+    }
+
+    /// Number of trie nodes whose candidates are memoized.
+    #[must_use]
+    pub fn memoized_nodes(&self) -> usize {
+        self.candidate_memo.as_ref().map_or(0, |memo| {
+            memo.nodes
+                .read()
+                .unwrap_or_else(PoisonError::into_inner)
+                .len()
+        })
+    }
+
+    /// Forgets the memoized candidates, before modifying the trie.
+    pub(crate) fn invalidate_candidate_memo(&mut self) {
+        if let Some(memo) = &mut self.candidate_memo {
+            memo.clear();
+        }
+    }
+
+    /// Same as `candidates_into`, with the candidates of the deepest node
+    /// taken from the memo, or memoized once collected.
+    pub(crate) fn memoized_candidates_into(
+        &self,
+        input: &str,
+        scratch: &mut Scratch,
+        memo: &CandidateMemo,
+    ) -> (&TrieNode, bool) {
+        let literal_ends = &mut scratch.literal_ends;
+        literal_ends.clear();
+        let walk = self.walk_into(input, &mut scratch.escaped_pattern, |_, node, path| {
+            if node.contains_non_regex_prefix {
+                literal_ends.push(path.len());
+            }
+        });
+
+        let key = node_key(walk.0);
+        let memoized = memo
+            .nodes
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(&key)
+            .cloned();
+        scratch.candidate_indices.clear();
+        if let Some(candidates) = memoized {
+            scratch.candidate_indices.extend_from_slice(&candidates);
+            return walk;
+        }
+
+        // Same path, so the same deepest node.
+        let walk = self.candidates_into(input, scratch);
+        let mut nodes = memo.nodes.write().unwrap_or_else(PoisonError::into_inner);
+        if nodes.len() >= memo.capacity {
+            nodes.clear();
+        }
+        nodes.insert(key, Arc::from(scratch.candidate_indices.as_slice()));
+        walk
+    }
+}
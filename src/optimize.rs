@@ -0,0 +1,55 @@
+use std::collections::HashMap;
+
+use crate::{MatchStats, RegexTrie, memo::CandidateMemo};
+
+/// Capacity of the candidate memo enabled by `RegexTrie::optimize`.
+const OPTIMIZED_MEMO_CAPACITY: usize = 4096;
+
+/// Outcome of `RegexTrie::optimize`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct OptimizeReport {
+    /// Number of regexes which matched at least once, now tried first
+    pub hot_regexes: usize,
+    /// Patterns which never matched, in insertion order, now tried last
+    pub dead_patterns: Vec<String>,
+}
+
+impl RegexTrie {
+    /// Restructures the query path of the trie for the traffic described by
+    /// the statistics, e.g. collected by `stats` on this trie or on replicas
+    /// of it, and merged:
+    /// - the regex candidates of `is_match` are tried by decreasing number of
+    ///   matches, so the hot ones come first and the dead ones last (see
+    ///   `order_candidates_by_hits`),
+    /// - the candidates of the most reached trie nodes are memoized, if they
+    ///   aren't already (see `with_candidate_memoization`).
+    ///
+    /// Patterns unknown to the statistics are considered dead. Results of
+    /// the queries don't change.
+    pub fn optimize(&mut self, stats: &MatchStats) -> OptimizeReport {
+        let matches: HashMap<&str, u64> = stats
+            .matches
+            .iter()
+            .map(|(pattern, count)| (pattern.as_str(), *count))
+            .collect();
+
+        let mut report = OptimizeReport::default();
+        let mut candidate_priority = vec![0; self.compiled_patterns.len()];
+        for (pattern, compiled_index) in self.patterns.iter().flatten() {
+            let count = matches.get(&**pattern).copied().unwrap_or(0);
+            if count == 0 {
+                report.dead_patterns.push(pattern.to_string());
+            }
+            if let Some(compiled_index) = compiled_index {
+                candidate_priority[*compiled_index] = count;
+                report.hot_regexes += usize::from(count > 0);
+            }
+        }
+        self.candidate_priority = candidate_priority;
+
+        if self.candidate_memo.is_none() && stats.queries > 0 {
+            self.candidate_memo = Some(CandidateMemo::new(OPTIMIZED_MEMO_CAPACITY));
+        }
+        report
+    }
+}
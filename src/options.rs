@@ -0,0 +1,159 @@
+use std::borrow::Cow;
+
+use crate::{
+    PatternId, RegexTrie, RegexTrieError,
+    dedup::canonical_form,
+    engine::{CompiledRegex, compile},
+    regex_trie::{PreparedBatch, parse_literal_prefix_with},
+};
+
+/// Settings of a single pattern, as given to `RegexTrie::insert_with_options`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct InsertOptions {
+    /// Whether the pattern ignores case (Unicode simple case folding), while
+    /// the other patterns of the trie stay case-sensitive
+    pub case_insensitive: bool,
+}
+
+/// A pattern parsed and compiled by `RegexTrie::prepare_with_options`, ready
+/// to be added to the trie.
+pub(crate) enum PreparedInsert<'pattern> {
+    /// A case-sensitive pattern, prepared like with `insert`
+    Batch(PreparedBatch<'pattern>),
+    /// A case-insensitive pattern, with its DFA and score
+    CaseInsensitive(&'pattern str, CompiledRegex, usize),
+    /// A pattern already known, or equivalent to a known one
+    Known,
+}
+
+/// Literal prefix under which a pattern is stored in the trie. A
+/// case-insensitive pattern only keeps the characters before its first ASCII
+/// letter or non-ASCII character, which are the only ones matching a single
+/// input character whatever the case.
+pub(crate) fn stored_prefix(
+    pattern: &str,
+    case_insensitive: bool,
+    extra_specials: &str,
+) -> Vec<(char, bool)> {
+    let (mut prefix, _) = parse_literal_prefix_with(pattern, extra_specials);
+    if case_insensitive {
+        let caseless = prefix
+            .iter()
+            .take_while(|(ch, _)| ch.is_ascii() && !ch.is_ascii_alphabetic())
+            .count();
+        prefix.truncate(caseless);
+    }
+    prefix
+}
+
+/// Regex a pattern is compiled from: the pattern itself if it's a regex, or
+/// its escaped literal if it's a plain one, according to the extra
+/// meta-characters.
+pub(crate) fn regex_source<'pattern>(
+    pattern: &'pattern str,
+    extra_specials: &str,
+) -> Cow<'pattern, str> {
+    let (literal, is_regex) = parse_literal_prefix_with(pattern, extra_specials);
+    if is_regex {
+        Cow::Borrowed(pattern)
+    } else {
+        Cow::Owned(regex_syntax::escape(
+            &literal.into_iter().map(|(ch, _)| ch).collect::<String>(),
+        ))
+    }
+}
+
+impl RegexTrie {
+    /// Same as `insert`, with settings specific to this pattern, e.g. to
+    /// ignore case for some rules only. A case-insensitive pattern is always
+    /// compiled into a DFA, even a plain one (which keeps its plain score),
+    /// and is stored higher in the trie, so it's a candidate for more inputs.
+    /// Inserting an already known pattern does nothing, whatever the
+    /// options.
+    ///
+    /// ## Errors
+    ///
+    /// If the regex pattern can't be compiled, or if the pattern capacity or
+    /// the DFA memory budget would be exceeded
+    pub fn insert_with_options(
+        &mut self,
+        pattern: &str,
+        options: InsertOptions,
+    ) -> Result<(), RegexTrieError> {
+        let prepared = self.prepare_with_options(pattern, options)?;
+        self.commit_with_options(prepared);
+        Ok(())
+    }
+
+    /// First stage of `insert_with_options`, which doesn't modify the trie:
+    /// parses and compiles the pattern, and checks it can be inserted.
+    ///
+    /// ## Errors
+    ///
+    /// If the regex pattern can't be compiled, or if the pattern capacity or
+    /// the DFA memory budget would be exceeded
+    pub(crate) fn prepare_with_options<'pattern>(
+        &self,
+        pattern: &'pattern str,
+        options: InsertOptions,
+    ) -> Result<PreparedInsert<'pattern>, RegexTrieError> {
+        if !options.case_insensitive {
+            return Ok(PreparedInsert::Batch(
+                self.prepare_many(vec![Cow::Borrowed(pattern)])?,
+            ));
+        }
+        if self.ids.contains_key(pattern) {
+            return Ok(PreparedInsert::Known);
+        }
+
+        self.check_capacity([pattern])?;
+        self.check_literal_detection([pattern])?;
+        let (_, is_regex) = self.parse_prefix(pattern);
+        let source = regex_source(pattern, &self.extra_specials);
+        if let Some(canonical_regexes) = &self.canonical_regexes {
+            if canonical_form(&source, true)
+                .is_some_and(|canonical| canonical_regexes.contains_key(&canonical))
+            {
+                return Ok(PreparedInsert::Known);
+            }
+        }
+
+        let dfa = compile(&source, true, self.fallback_engine, &self.dfa_config)?;
+        self.check_empty_patterns([(pattern, true)])?;
+        self.check_dfa_budget([(pattern, &dfa)])?;
+        let score = (self.scorer)(pattern, is_regex);
+        Ok(PreparedInsert::CaseInsensitive(pattern, dfa, score))
+    }
+
+    /// Second stage of `insert_with_options`: adds the prepared pattern to
+    /// the trie.
+    pub(crate) fn commit_with_options(&mut self, prepared: PreparedInsert<'_>) {
+        match prepared {
+            PreparedInsert::Batch(prepared) => self.commit_many(prepared),
+            PreparedInsert::CaseInsensitive(pattern, dfa, score) => {
+                self.add_pattern(
+                    Cow::Borrowed(pattern),
+                    &stored_prefix(pattern, true, &self.extra_specials),
+                    Some((dfa, score)),
+                    InsertOptions {
+                        case_insensitive: true,
+                    },
+                );
+                self.refresh_alternations();
+            }
+            PreparedInsert::Known => {}
+        }
+    }
+
+    /// Whether the pattern of the given id ignores case.
+    pub(crate) fn is_case_insensitive(&self, id: PatternId) -> bool {
+        self.case_insensitive.contains(&id)
+    }
+
+    /// Options the pattern of the given id has been inserted with.
+    pub(crate) fn options(&self, id: PatternId) -> InsertOptions {
+        InsertOptions {
+            case_insensitive: self.is_case_insensitive(id),
+        }
+    }
+}
@@ -0,0 +1,243 @@
+use std::{iter::Peekable, str::Chars};
+
+use crate::{
+    RegexTrie, RegexTrieError,
+    regex_trie::{SPECIALS, group_after_prefix},
+};
+
+/// Error of a PCRE construct which can't be translated.
+fn unsupported(pattern: &str, reason: &str) -> RegexTrieError {
+    RegexTrieError::InvalidPattern(format!(
+        "{pattern:?} isn't a supported PCRE pattern: {reason}"
+    ))
+}
+
+/// Copies a character class, whose opening `[` has been consumed, as is:
+/// both syntaxes agree on them.
+fn copy_class(
+    pattern: &str,
+    chars: &mut Peekable<Chars<'_>>,
+    out: &mut String,
+) -> Result<(), RegexTrieError> {
+    out.push('[');
+    if let Some(negation) = chars.next_if_eq(&'^') {
+        out.push(negation);
+    }
+    // A leading `]` is a literal one.
+    if chars.next_if_eq(&']').is_some() {
+        out.push_str(r"\]");
+    }
+    while let Some(ch) = chars.next() {
+        out.push(ch);
+        match ch {
+            '\\' => out.extend(chars.next()),
+            ']' => return Ok(()),
+            _ => {}
+        }
+    }
+    Err(unsupported(pattern, "unterminated character class"))
+}
+
+/// Copies a `{n}`, `{n,}` or `{n,m}` quantifier, whose opening `{` has been
+/// consumed, or escapes a literal `{`. Returns false if it's a literal one.
+fn copy_counted_quantifier(chars: &mut Peekable<Chars<'_>>, out: &mut String) -> bool {
+    let quantifier: String = chars
+        .clone()
+        .take_while(|&ch| ch != '}')
+        .collect::<String>();
+    let (min, max) = quantifier.split_once(',').unwrap_or((&quantifier, "0"));
+    let is_quantifier = !min.is_empty()
+        && min.chars().all(|ch| ch.is_ascii_digit())
+        && max.chars().all(|ch| ch.is_ascii_digit())
+        && chars.clone().nth(quantifier.chars().count()) == Some('}');
+    if is_quantifier {
+        out.push('{');
+        out.push_str(&quantifier);
+        out.push('}');
+        chars.nth(quantifier.chars().count());
+    } else {
+        out.push_str(r"\{");
+    }
+    is_quantifier
+}
+
+/// Translates an escape sequence, whose `\` has been consumed. Returns
+/// whether the pattern has to be grouped to be read as a regex: for an
+/// anchor, or an escape a plain pattern would read as a literal `\` (e.g.
+/// `\d`).
+fn translate_escape(
+    pattern: &str,
+    chars: &mut Peekable<Chars<'_>>,
+    out: &mut String,
+) -> Result<bool, RegexTrieError> {
+    match chars.next() {
+        None => return Err(unsupported(pattern, "trailing backslash")),
+        Some('A') => out.push('^'),
+        Some('z') => out.push('$'),
+        Some('Z') => out.push_str(r"\n?$"),
+        Some('Q') => {
+            let mut quoted = String::new();
+            while let Some(ch) = chars.next() {
+                if ch == '\\' && chars.next_if_eq(&'E').is_some() {
+                    break;
+                }
+                quoted.push(ch);
+            }
+            regex_syntax::escape_into(&quoted, out);
+            return Ok(quoted
+                .chars()
+                .any(|ch| regex_syntax::is_meta_character(ch) && !SPECIALS.contains(ch)));
+        }
+        Some('1'..='9' | 'g' | 'k') => return Err(unsupported(pattern, "backreferences")),
+        Some(escaped @ ('G' | 'K')) => {
+            return Err(unsupported(pattern, &format!("\\{escaped}")));
+        }
+        Some(escaped) => {
+            out.push('\\');
+            out.push(escaped);
+            // Braces of `\x{...}` or `\p{...}` aren't a quantifier.
+            if matches!(escaped, 'x' | 'p' | 'P') && chars.peek() == Some(&'{') {
+                out.extend(chars.by_ref().take_while(|&ch| ch != '}'));
+                out.push('}');
+            }
+            return Ok(!SPECIALS.contains(escaped));
+        }
+    }
+    Ok(true)
+}
+
+/// Translates a `(?...)` group, whose `(?` has been consumed. Returns
+/// whether a group has been opened (i.e. it's not a comment).
+fn translate_extension(
+    pattern: &str,
+    chars: &mut Peekable<Chars<'_>>,
+    out: &mut String,
+) -> Result<bool, RegexTrieError> {
+    let mut next = chars.clone().take(2);
+    match (next.next(), next.next()) {
+        (Some('#'), _) => {
+            if !chars.any(|ch| ch == ')') {
+                return Err(unsupported(pattern, "unterminated comment"));
+            }
+            return Ok(false);
+        }
+        (Some('>'), _) => {
+            chars.next();
+            out.push_str("(?:");
+        }
+        (Some('=' | '!'), _) | (Some('<'), Some('=' | '!')) => {
+            return Err(unsupported(pattern, "look-around"));
+        }
+        (Some('('), _) => return Err(unsupported(pattern, "conditionals")),
+        (Some('R' | '0'..='9' | '&'), _)
+        | (Some('+' | '-'), Some('0'..='9'))
+        | (Some('P'), Some('>')) => {
+            return Err(unsupported(pattern, "recursion"));
+        }
+        (Some('P'), Some('=')) => return Err(unsupported(pattern, "backreferences")),
+        (Some('|'), _) => return Err(unsupported(pattern, "branch reset")),
+        _ => out.push_str("(?"),
+    }
+    Ok(true)
+}
+
+impl RegexTrie {
+    /// Best-effort translation of a PCRE pattern to the native syntax:
+    /// `\A` and a leading `^` are dropped since patterns are anchored anyway,
+    /// `\z` and `\Z` become end anchors, possessive quantifiers and atomic
+    /// groups are downgraded to greedy ones (they only differ on
+    /// backtracking, which the DFAs don't do), `(?#...)` comments and `\Q`
+    /// ... `\E` quoting are rewritten. Patterns with other anchors, a
+    /// top-level alternation or escapes like `\d` are grouped after their
+    /// literal prefix (or as a whole for an alternation), so they're compiled
+    /// as regexes.
+    ///
+    /// ## Errors
+    ///
+    /// If the pattern uses a construct which has no equivalent: look-around,
+    /// backreferences, recursion, conditionals, `\G`, `\K` or backtracking
+    /// verbs, or if it's malformed (e.g. an unterminated group comment)
+    pub fn translate_pcre(pattern: &str) -> Result<String, RegexTrieError> {
+        let mut out = String::with_capacity(pattern.len());
+        let mut needs_group = false;
+        let mut after_quantifier = false;
+        // Depth of the current group
+        let mut depth = 0_usize;
+        let mut chars = pattern.chars().peekable();
+        if chars.next_if_eq(&'^').is_none() && pattern.starts_with(r"\A") {
+            chars.nth(1);
+        }
+
+        while let Some(ch) = chars.next() {
+            let is_quantifier = match ch {
+                // Possessive quantifier
+                '+' if after_quantifier => false,
+                '*' | '+' | '?' => {
+                    out.push(ch);
+                    true
+                }
+                '{' => copy_counted_quantifier(&mut chars, &mut out),
+                '[' => {
+                    copy_class(pattern, &mut chars, &mut out)?;
+                    false
+                }
+                '|' => {
+                    needs_group |= depth == 0;
+                    out.push(ch);
+                    false
+                }
+                '^' | '$' => {
+                    needs_group = true;
+                    out.push(ch);
+                    false
+                }
+                '\\' => {
+                    needs_group |= translate_escape(pattern, &mut chars, &mut out)?;
+                    false
+                }
+                '(' if chars.peek() == Some(&'*') => {
+                    return Err(unsupported(pattern, "backtracking verbs"));
+                }
+                '(' if chars.next_if_eq(&'?').is_some() => {
+                    if translate_extension(pattern, &mut chars, &mut out)? {
+                        depth += 1;
+                    }
+                    false
+                }
+                '(' | ')' => {
+                    depth = if ch == '(' {
+                        depth + 1
+                    } else {
+                        depth.saturating_sub(1)
+                    };
+                    out.push(ch);
+                    false
+                }
+                _ => {
+                    out.push(ch);
+                    false
+                }
+            };
+            // A lazy `?` ends the quantifier, like a possessive `+`.
+            after_quantifier = is_quantifier && !(after_quantifier && ch == '?');
+        }
+
+        if needs_group {
+            out = group_after_prefix(&out);
+        }
+        Ok(out)
+    }
+
+    /// Translates a PCRE pattern with `translate_pcre`, and inserts it. The
+    /// trie only knows the translated pattern, which is returned, e.g. to map
+    /// the matches back to the original rules.
+    ///
+    /// ## Errors
+    ///
+    /// If the pattern uses an unsupported construct, or can't be compiled
+    pub fn insert_pcre(&mut self, pattern: &str) -> Result<String, RegexTrieError> {
+        let translated = Self::translate_pcre(pattern)?;
+        self.insert(&translated)?;
+        Ok(translated)
+    }
+}
@@ -0,0 +1,191 @@
+use std::{iter::Peekable, str::Chars};
+
+use crate::{
+    RegexTrie, RegexTrieError,
+    regex_trie::{SPECIALS, group_after_prefix},
+};
+
+/// Character classes of POSIX bracket expressions, also supported natively.
+const POSIX_CLASSES: [&str; 12] = [
+    "alnum", "alpha", "blank", "cntrl", "digit", "graph", "lower", "print", "punct", "space",
+    "upper", "xdigit",
+];
+
+/// Error of a pattern which isn't valid POSIX ERE.
+fn invalid(pattern: &str, reason: &str) -> RegexTrieError {
+    RegexTrieError::InvalidPattern(format!("{pattern:?} isn't a valid POSIX ERE: {reason}"))
+}
+
+/// Reads the name of a `[:class:]`, `[=equivalence=]` or `[.collating.]`
+/// element, up to its closing `delimiter` followed by `]`.
+fn read_element(chars: &mut Peekable<Chars<'_>>, delimiter: char) -> Option<String> {
+    let mut name = String::new();
+    while let Some(ch) = chars.next() {
+        if ch == delimiter && chars.peek() == Some(&']') {
+            chars.next();
+            return Some(name);
+        }
+        name.push(ch);
+    }
+    None
+}
+
+/// Translates a bracket expression, whose opening `[` has been consumed.
+/// Every character is literal there (including `\`), except the range `-`
+/// and the nested `[:class:]`, `[=c=]` and `[.c.]` elements.
+fn translate_bracket(
+    pattern: &str,
+    chars: &mut Peekable<Chars<'_>>,
+    out: &mut String,
+) -> Result<(), RegexTrieError> {
+    out.push('[');
+    if chars.next_if_eq(&'^').is_some() {
+        out.push('^');
+    }
+
+    let mut first = true;
+    loop {
+        let Some(ch) = chars.next() else {
+            return Err(invalid(pattern, "unterminated bracket expression"));
+        };
+        match ch {
+            // A leading `]` is a literal one.
+            ']' if !first => break,
+            '[' if chars.peek() == Some(&':') => {
+                chars.next();
+                let name = read_element(chars, ':')
+                    .ok_or_else(|| invalid(pattern, "unterminated character class"))?;
+                if !POSIX_CLASSES.contains(&name.as_str()) {
+                    return Err(invalid(pattern, &format!("unknown class [:{name}:]")));
+                }
+                out.push_str(&format!("[:{name}:]"));
+            }
+            '[' if matches!(chars.peek(), Some('=' | '.')) => {
+                let delimiter = chars.next().unwrap_or_default();
+                let name = read_element(chars, delimiter)
+                    .ok_or_else(|| invalid(pattern, "unterminated collating element"))?;
+                // Only single characters are supported, without locale.
+                let mut name_chars = name.chars();
+                let (Some(ch), None) = (name_chars.next(), name_chars.next()) else {
+                    return Err(invalid(pattern, &format!("unsupported element {name:?}")));
+                };
+                regex_syntax::escape_into(&ch.to_string(), out);
+            }
+            // A `-` is literal first or last, and a range otherwise.
+            '-' if !first && chars.peek() != Some(&']') => out.push('-'),
+            _ => regex_syntax::escape_into(&ch.to_string(), out),
+        }
+        first = false;
+    }
+
+    out.push(']');
+    Ok(())
+}
+
+/// Part of a translated pattern.
+enum Token {
+    /// Literal character
+    Literal(char),
+    /// Native regex syntax
+    Regex(String),
+}
+
+impl RegexTrie {
+    /// Translates a POSIX extended regex (ERE) to the native syntax: bracket
+    /// expressions (with their `[:alpha:]`-like classes, literal `\` and
+    /// leading `]`) are rewritten, escaped characters are always literal (only
+    /// the non alphanumeric ones can be escaped), and
+    /// a leading `^` is dropped since patterns are anchored anyway. Patterns
+    /// with other anchors or a top-level alternation are grouped after their
+    /// literal prefix (see `RegexTrie::literal_prefix`), or as a whole for an
+    /// alternation, and ones without any operator become plain patterns. Matching stays
+    /// leftmost-first, not leftmost-longest.
+    ///
+    /// ## Errors
+    ///
+    /// If the pattern isn't valid ERE (e.g. an unterminated bracket
+    /// expression, an unknown class, or an escaped letter or digit, like the
+    /// `\w` or backreferences of other syntaxes)
+    pub fn translate_posix_ere(pattern: &str) -> Result<String, RegexTrieError> {
+        let mut tokens = Vec::new();
+        let mut needs_group = false;
+        // Depth of the current group
+        let mut depth = 0_usize;
+        let mut chars = pattern.chars().peekable();
+        chars.next_if_eq(&'^');
+        while let Some(ch) = chars.next() {
+            tokens.push(match ch {
+                '\\' => match chars.next() {
+                    None => return Err(invalid(pattern, "trailing backslash")),
+                    Some('0'..='9') => return Err(invalid(pattern, "backreferences")),
+                    // ERE doesn't define any escape sequence like `\w`.
+                    Some(escaped) if escaped.is_ascii_alphabetic() => {
+                        return Err(invalid(pattern, &format!("undefined escape \\{escaped}")));
+                    }
+                    Some(escaped) => Token::Literal(escaped),
+                },
+                '[' => {
+                    let mut class = String::new();
+                    translate_bracket(pattern, &mut chars, &mut class)?;
+                    Token::Regex(class)
+                }
+                '|' | '^' | '$' => {
+                    needs_group |= ch != '|' || depth == 0;
+                    Token::Regex(ch.to_string())
+                }
+                '(' | ')' => {
+                    depth = if ch == '(' {
+                        depth + 1
+                    } else {
+                        depth.saturating_sub(1)
+                    };
+                    Token::Regex(ch.to_string())
+                }
+                _ if SPECIALS.contains(ch) => Token::Regex(ch.to_string()),
+                _ => Token::Literal(ch),
+            });
+        }
+
+        // A literal `\` could start an assertion (e.g. `\b`) in a plain
+        // pattern, so it's only written in a regex.
+        let is_regex = tokens
+            .iter()
+            .any(|token| matches!(token, Token::Regex(_) | Token::Literal('\\')));
+        let mut out = String::with_capacity(pattern.len());
+        for token in tokens {
+            match token {
+                Token::Regex(regex) => out.push_str(&regex),
+                // The trie only unescapes the special characters, so other
+                // meta-characters go in a class.
+                Token::Literal(ch) if SPECIALS.contains(ch) => {
+                    out.push('\\');
+                    out.push(ch);
+                }
+                Token::Literal(ch @ ('\\' | '|' | '^' | '$')) if is_regex => {
+                    out.push('[');
+                    regex_syntax::escape_into(&ch.to_string(), &mut out);
+                    out.push(']');
+                }
+                Token::Literal(ch) => out.push(ch),
+            }
+        }
+
+        if needs_group {
+            out = group_after_prefix(&out);
+        }
+        Ok(out)
+    }
+
+    /// Translates a POSIX extended regex with `translate_posix_ere`, and
+    /// inserts it. The trie only knows the translated pattern, which is
+    /// returned, e.g. to map the matches back to the original rules.
+    ///
+    /// ## Errors
+    ///
+    /// If the pattern isn't valid ERE, or can't be compiled
+    pub fn insert_posix_ere(&mut self, pattern: &str) -> Result<String, RegexTrieError> {
+        let translated = Self::translate_posix_ere(pattern)?;
+        self.insert(&translated)?;
+        Ok(translated)
+    }
+}
@@ -0,0 +1,147 @@
+use memchr::memmem::Finder;
+use regex_syntax::{
+    Parser,
+    hir::{Hir, HirKind},
+};
+
+use crate::{MatchMode, RegexTrie};
+
+/// Longest literal every match of a regex must contain, after its first
+/// piece (usually its literal prefix, which the trie already checks), e.g.
+/// `.log` for `/var/.*\.log`, as a substring searcher. `None` if there's no
+/// such literal, or if the regex can't be parsed.
+pub(crate) fn required_literal(pattern: &str) -> Option<Finder<'static>> {
+    let hir = Parser::new().parse(pattern).ok()?;
+    let HirKind::Concat(pieces) = hir.kind() else {
+        return None;
+    };
+    pieces
+        .iter()
+        .skip(1)
+        .filter_map(literal_bytes)
+        .max_by_key(|literal| literal.len())
+        .map(|literal| Finder::new(literal).into_owned())
+}
+
+/// Longest literal every match of a regex must contain, wherever it is in
+/// the regex, e.g. `timeout` for `.*ERROR.*timeout.*`. `None` if there's no
+/// such literal, or if the regex can't be parsed.
+pub(crate) fn longest_required_literal(pattern: &str) -> Option<String> {
+    let hir = Parser::new().parse(pattern).ok()?;
+    let mut literals = Vec::new();
+    collect_required_literals(&hir, &mut literals);
+    literals
+        .into_iter()
+        .filter_map(|literal| std::str::from_utf8(literal).ok())
+        .max_by_key(|literal| literal.len())
+        .map(ToString::to_string)
+}
+
+/// Literals of the regex (or of the sub-regexes every match goes through),
+/// without looking into the alternations and optional repetitions.
+pub(crate) fn collect_required_literals<'hir>(hir: &'hir Hir, literals: &mut Vec<&'hir [u8]>) {
+    match hir.kind() {
+        HirKind::Literal(literal) => literals.push(&literal.0),
+        HirKind::Concat(pieces) => {
+            for piece in pieces {
+                collect_required_literals(piece, literals);
+            }
+        }
+        HirKind::Capture(capture) => collect_required_literals(&capture.sub, literals),
+        HirKind::Repetition(repetition) if repetition.min > 0 => {
+            collect_required_literals(&repetition.sub, literals);
+        }
+        _ => {}
+    }
+}
+
+/// Minimum and maximum (if bounded) length in bytes of the matches of a
+/// regex, e.g. 3 and 5 for `[a-z]{3,5}`, and 1 and none for `a+`. 0 and none
+/// if the regex can't be parsed.
+pub(crate) fn match_length_range(pattern: &str, case_insensitive: bool) -> (usize, Option<usize>) {
+    let parsed = if case_insensitive {
+        Parser::new().parse(&format!("(?i:{pattern})"))
+    } else {
+        Parser::new().parse(pattern)
+    };
+    parsed.map_or((0, None), |hir| {
+        let properties = hir.properties();
+        (
+            properties.minimum_len().unwrap_or(0),
+            properties.maximum_len(),
+        )
+    })
+}
+
+/// Bytes of a literal piece of a regex.
+fn literal_bytes(hir: &Hir) -> Option<&[u8]> {
+    match hir.kind() {
+        HirKind::Literal(literal) => Some(&literal.0),
+        _ => None,
+    }
+}
+
+/// Next occurrence of a required literal in the haystack.
+#[derive(Debug, Clone, Copy, Default)]
+enum Occurrence {
+    /// Not searched yet
+    #[default]
+    Unsearched,
+    /// Starting at the given offset
+    At(usize),
+    /// None left
+    Missing,
+}
+
+/// Positions of the required literals of the candidates of a scan, so each
+/// one is only searched again once the scan has gone past its last
+/// occurrence.
+#[derive(Debug, Default)]
+pub(crate) struct LiteralPositions {
+    /// Next occurrence of the required literal of every compiled pattern
+    next: Vec<Occurrence>,
+}
+
+impl LiteralPositions {
+    /// Whether the required literal of the compiled pattern, if any, occurs
+    /// in the haystack from the `start` offset. A regex can't match from
+    /// there otherwise.
+    pub(crate) fn may_match(
+        &mut self,
+        index: usize,
+        finder: Option<&Finder<'_>>,
+        haystack: &[u8],
+        start: usize,
+    ) -> bool {
+        let Some(finder) = finder else {
+            return true;
+        };
+        if self.next.len() <= index {
+            self.next.resize(index + 1, Occurrence::Unsearched);
+        }
+        match self.next.get(index) {
+            Some(Occurrence::Missing) => return false,
+            Some(&Occurrence::At(next)) if next >= start => return true,
+            _ => {}
+        }
+        let next = finder.find(&haystack[start..]).map(|offset| start + offset);
+        self.next[index] = next.map_or(Occurrence::Missing, Occurrence::At);
+        next.is_some()
+    }
+}
+
+impl RegexTrie {
+    /// Whether the input length fits the lengths of the matches of the
+    /// compiled pattern of the given index: between its minimum and maximum
+    /// in full mode, at least its minimum in prefix mode. It can't match
+    /// otherwise.
+    pub(crate) fn length_fits(&self, index: usize, input: &[u8]) -> bool {
+        let Some(&(min, max)) = self.match_lengths.get(index) else {
+            return true;
+        };
+        match self.match_mode {
+            MatchMode::Full => input.len() >= min && max.is_none_or(|max| input.len() <= max),
+            MatchMode::Prefix => input.len() >= min,
+        }
+    }
+}
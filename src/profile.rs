@@ -0,0 +1,60 @@
+use std::{cmp::Reverse, sync::atomic::Ordering};
+
+use crate::{MatchMode, RegexTrie, regex_trie::Scratch};
+
+impl RegexTrie {
+    /// Orders the regex candidates of `is_match` by their number of matches
+    /// so far (see `with_hit_counting`), the most frequently matching first,
+    /// so it exits early on skewed traffic. Ties keep the insertion order.
+    /// The order is a snapshot, kept until the next call. Does nothing and
+    /// returns false if counting isn't enabled.
+    ///
+    /// Only the evaluation order changes: all the other queries still report
+    /// the patterns in insertion order.
+    pub fn order_candidates_by_hits(&mut self) -> bool {
+        let Some(hit_counters) = &self.hit_counters else {
+            return false;
+        };
+        let mut candidate_priority = vec![0; self.compiled_patterns.len()];
+        for (slot, count) in self.patterns.iter().zip(&hit_counters.patterns) {
+            if let Some((_, Some(compiled_index))) = slot {
+                candidate_priority[*compiled_index] = count.load(Ordering::Relaxed);
+            }
+        }
+        self.candidate_priority = candidate_priority;
+        true
+    }
+
+    /// Whether any pattern matches the input. Stops at the first match,
+    /// trying the plain patterns first, then the regexes in the order set by
+    /// `order_candidates_by_hits`, if any. Matches aren't counted, as they
+    /// aren't all evaluated.
+    #[must_use]
+    pub fn is_match(&self, input: &str) -> bool {
+        if !self.input_allowed(input.as_bytes()) {
+            return false;
+        }
+        let mut scratch = Scratch::default();
+        let literal_match = self.collect_candidates(input, &mut scratch);
+        let any_literal = match self.match_mode {
+            MatchMode::Full => literal_match,
+            MatchMode::Prefix => !scratch.literal_ends.is_empty(),
+        };
+        if any_literal {
+            return true;
+        }
+
+        if !self.candidate_priority.is_empty() {
+            // Stable sort, to keep the insertion order on ties.
+            scratch.candidate_indices.sort_by_key(|&index| {
+                Reverse(self.candidate_priority.get(index).copied().unwrap_or(0))
+            });
+        }
+        scratch.candidate_indices.iter().any(|&index| {
+            let (pattern, dfa, _) = &self.compiled_patterns[index];
+            self.length_fits(index, input.as_bytes())
+                && self.suffix_gate(index, input.as_bytes())
+                && self.is_match_cached(pattern, dfa, input.as_bytes(), &scratch.meta_caches)
+        })
+    }
+}
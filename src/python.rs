@@ -0,0 +1,78 @@
+use pyo3::{exceptions::PyValueError, prelude::*};
+
+use crate::RegexTrie;
+
+/// Python facing wrapper around a `RegexTrie`.
+#[pyclass(name = "RegexTrie", module = "regextrie")]
+#[derive(Debug, Default)]
+pub struct PyRegexTrie {
+    /// Wrapped trie
+    inner: RegexTrie,
+}
+
+#[pymethods]
+impl PyRegexTrie {
+    /// Creates a new trie with the default scorer, optionally filled with
+    /// the given patterns.
+    ///
+    /// ## Errors
+    ///
+    /// Raises `ValueError` if any of the regex pattern can't be compiled
+    #[new]
+    #[pyo3(signature = (patterns = None))]
+    pub fn new(patterns: Option<Vec<String>>) -> PyResult<Self> {
+        let mut trie = Self::default();
+        if let Some(patterns) = patterns {
+            trie.insert_many(patterns)?;
+        }
+        Ok(trie)
+    }
+
+    /// Compiles a regex pattern and inserts it into the trie.
+    ///
+    /// ## Errors
+    ///
+    /// Raises `ValueError` if the regex pattern can't be compiled
+    pub fn insert(&mut self, pattern: &str) -> PyResult<()> {
+        self.inner
+            .insert(pattern)
+            .map_err(|err| PyValueError::new_err(err.to_string()))
+    }
+
+    /// Insert many pattern at once. Regex compilation are parallelized.
+    ///
+    /// ## Errors
+    ///
+    /// Raises `ValueError` if any regex pattern can't be compiled
+    #[expect(
+        clippy::needless_pass_by_value,
+        reason = "python arguments are extracted by value"
+    )]
+    pub fn insert_many(&mut self, patterns: Vec<String>) -> PyResult<()> {
+        self.inner
+            .insert_many(&patterns)
+            .map_err(|err| PyValueError::new_err(err.to_string()))
+    }
+
+    /// Finds all patterns fully matching the given input.
+    #[must_use]
+    pub fn find_matches(&self, input: &str) -> Vec<String> {
+        self.inner.find_matches(input)
+    }
+
+    /// Finds the best pattern matching the given input, or `None`.
+    #[must_use]
+    pub fn find_best_match(&self, input: &str) -> Option<String> {
+        self.inner.find_best_match(input)
+    }
+}
+
+/// Python module exposing the trie.
+///
+/// ## Errors
+///
+/// If the class can't be registered
+#[pymodule]
+fn regextrie(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<PyRegexTrie>()
+}
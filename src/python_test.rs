@@ -0,0 +1,20 @@
+use pretty_assertions::assert_eq;
+
+use crate::PyRegexTrie;
+
+/// Test the python facing wrapper forwards to the trie
+#[test]
+fn test_python_wrapper() {
+    let mut trie = PyRegexTrie::new(Some(vec!["a.*".to_string(), "a[0-9]+b.*".to_string()]))
+        .expect("can't init regex trie");
+    trie.insert("abc").expect("should insert");
+
+    let mut matches = trie.find_matches("a123bbb");
+    matches.sort();
+    assert_eq!(vec!["a.*", "a[0-9]+b.*"], matches, "wrong matches");
+    assert_eq!(
+        Some("abc".to_string()),
+        trie.find_best_match("abc"),
+        "plain match should win"
+    );
+}
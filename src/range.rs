@@ -0,0 +1,154 @@
+use crate::{RegexTrie, RegexTrieError};
+
+/// Largest bound of a numeric range token, so the sub-range computations
+/// can't overflow.
+const MAX_BOUND: u64 = 999_999_999_999_999_999;
+
+/// `n` with its last `count` digits set to 9.
+const fn fill_by_nines(n: u64, count: u32) -> u64 {
+    let power = 10_u64.pow(count);
+    n / power * power + power - 1
+}
+
+/// `n` with its last `count` digits set to 0.
+const fn fill_by_zeros(n: u64, count: u32) -> u64 {
+    n - n % 10_u64.pow(count)
+}
+
+/// Regex of a sub-range whose bounds have the same number of digits, and
+/// only differ on a single digit followed by full `[0-9]` ones.
+fn sub_range_regex(start: u64, stop: u64, out: &mut String) {
+    for (low, high) in start.to_string().chars().zip(stop.to_string().chars()) {
+        if low == high {
+            out.push(low);
+        } else {
+            out.push_str(&format!("[{low}-{high}]"));
+        }
+    }
+}
+
+/// Regex matching the decimal integers of `[min, max]`, without leading
+/// zeros, as an alternation of simpler sub-ranges, e.g. `1[0-9][0-9]` for
+/// 100 to 199. The longest numbers come first, as alternatives are tried
+/// leftmost-first.
+pub(crate) fn range_regex(min: u64, max: u64) -> String {
+    // Bounds of the sub-ranges, split where the number of digits changes
+    // or where a digit can no longer span 0 to 9.
+    let mut stops = vec![max];
+    let mut count = 1;
+    let mut stop = fill_by_nines(min, count);
+    while min <= stop && stop <= max {
+        stops.push(stop);
+        count += 1;
+        stop = fill_by_nines(min, count);
+    }
+    count = 1;
+    while let Some(stop) = fill_by_zeros(max + 1, count)
+        .checked_sub(1)
+        .filter(|&stop| min < stop && stop <= max)
+    {
+        stops.push(stop);
+        count += 1;
+    }
+    stops.sort_unstable();
+    stops.dedup();
+
+    let mut start = min;
+    let mut sub_ranges: Vec<String> = stops
+        .into_iter()
+        .map(|stop| {
+            let mut sub_range = String::new();
+            sub_range_regex(start, stop, &mut sub_range);
+            start = stop + 1;
+            sub_range
+        })
+        .collect();
+    sub_ranges.reverse();
+    sub_ranges.join("|")
+}
+
+/// Parses the `min-max` bounds of a range token, between the `<` and `>`.
+/// Returns `None` if it's not a range token, e.g. a literal `<`.
+fn parse_token(pattern: &str, token: &str) -> Result<Option<(u64, u64)>, RegexTrieError> {
+    let Some((min, max)) = token.split_once('-') else {
+        return Ok(None);
+    };
+    let is_number = |bound: &str| !bound.is_empty() && bound.chars().all(|ch| ch.is_ascii_digit());
+    if !is_number(min) || !is_number(max) {
+        return Ok(None);
+    }
+
+    let invalid = |reason: &str| {
+        RegexTrieError::InvalidPattern(format!("invalid range <{token}> in {pattern:?}: {reason}"))
+    };
+    let parse = |bound: &str| {
+        bound
+            .parse::<u64>()
+            .ok()
+            .filter(|&bound| bound <= MAX_BOUND)
+            .ok_or_else(|| invalid("bound too large"))
+    };
+    let (min, max) = (parse(min)?, parse(max)?);
+    if min > max {
+        return Err(invalid("min is greater than max"));
+    }
+    Ok(Some((min, max)))
+}
+
+impl RegexTrie {
+    /// Translates the numeric range tokens of a pattern, like `<100-255>`,
+    /// to a regex alternation matching the integers of the range, without
+    /// leading zeros, e.g. `(?:25[0-5]|2[0-4][0-9]|1[0-9][0-9])`. Handy for
+    /// ports, status codes or ids. Tokens in character classes, or whose `<`
+    /// is escaped, are left as is, as is anything else between `<` and `>`.
+    ///
+    /// ## Errors
+    ///
+    /// If a range is reversed (e.g. `<9-1>`), or a bound is too large (more
+    /// than 18 digits)
+    pub fn translate_numeric_ranges(pattern: &str) -> Result<String, RegexTrieError> {
+        let mut out = String::with_capacity(pattern.len());
+        let mut in_class = false;
+        let mut chars = pattern.char_indices();
+        while let Some((position, ch)) = chars.next() {
+            match ch {
+                '\\' => {
+                    out.push(ch);
+                    out.extend(chars.next().map(|(_, escaped)| escaped));
+                    continue;
+                }
+                '[' => in_class = true,
+                ']' => in_class = false,
+                '<' if !in_class => {
+                    let rest = &pattern[position + 1..];
+                    let token = rest.find('>').map(|end| &rest[..end]);
+                    if let Some(token) = token {
+                        if let Some((min, max)) = parse_token(pattern, token)? {
+                            out.push_str(&format!("(?:{})", range_regex(min, max)));
+                            // Skip the token and its closing `>`
+                            chars.nth(token.len());
+                            continue;
+                        }
+                    }
+                }
+                _ => {}
+            }
+            out.push(ch);
+        }
+        Ok(out)
+    }
+
+    /// Translates the numeric range tokens of a pattern with
+    /// `translate_numeric_ranges`, and inserts it. The trie only knows the
+    /// translated pattern, which is returned, e.g. to map the matches back to
+    /// the original rules.
+    ///
+    /// ## Errors
+    ///
+    /// If a range token is invalid, or the pattern can't be compiled
+    pub fn insert_with_numeric_ranges(&mut self, pattern: &str) -> Result<String, RegexTrieError> {
+        let translated = Self::translate_numeric_ranges(pattern)?;
+        self.insert(&translated)?;
+        Ok(translated)
+    }
+}
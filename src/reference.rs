@@ -0,0 +1,167 @@
+use regex_automata::dfa::regex::Regex;
+
+use crate::{
+    RegexTrieError,
+    regex_trie::{ScorerFuncType, default_scorer, parse_literal_prefix},
+};
+
+/// How a pattern of a `NaiveMatcher` is checked against an input.
+#[derive(Debug)]
+enum Check {
+    /// Plain pattern, equal to its unescaped literal only
+    Literal(String),
+    /// Regex pattern, with its compiled DFA
+    Regex(Box<Regex>),
+}
+
+/// Naive reference implementation of the matching semantics of `RegexTrie`.
+///
+/// Every pattern is kept in a flat list and checked one by one against the
+/// input, without any indexing. It tells the plain patterns from the regexes
+/// like the trie does (e.g. `id\d` or `a|b` are plain, matching only
+/// themselves), and uses the same regex engine and the same scorer semantics,
+/// so any difference between the two comes from the trie indexing itself.
+/// Useful for differential testing, not for production use.
+pub struct NaiveMatcher {
+    /// Original pattern string, how it's checked and its score, in insertion
+    /// order
+    compiled_patterns: Vec<(String, Check, usize)>,
+    /// Scorer function
+    scorer: ScorerFuncType,
+}
+
+impl Default for NaiveMatcher {
+    fn default() -> Self {
+        Self::new_with_custom_scorer(Box::new(default_scorer))
+    }
+}
+
+impl std::fmt::Debug for NaiveMatcher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NaiveMatcher")
+            .field("compiled_patterns", &self.compiled_patterns)
+            .finish_non_exhaustive()
+    }
+}
+
+impl NaiveMatcher {
+    /// Creates a new, empty matcher with default scorer.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new, empty matcher with a custom scorer.
+    #[must_use]
+    pub fn new_with_custom_scorer(scorer: ScorerFuncType) -> Self {
+        Self {
+            compiled_patterns: Vec::default(),
+            scorer,
+        }
+    }
+
+    /// Creates a new matcher from a set of patterns.
+    ///
+    /// ## Errors
+    ///
+    /// If any of the regex pattern can't be compiled
+    pub fn from(patterns: &[String]) -> Result<Self, RegexTrieError> {
+        let mut matcher = Self::new();
+        matcher.insert_many(patterns)?;
+        Ok(matcher)
+    }
+
+    /// Creates a new matcher from a set of patterns, with a custom scorer.
+    ///
+    /// ## Errors
+    ///
+    /// If any of the regex pattern can't be compiled
+    pub fn from_with_scorer(
+        patterns: &[String],
+        scorer: ScorerFuncType,
+    ) -> Result<Self, RegexTrieError> {
+        let mut matcher = Self::new_with_custom_scorer(scorer);
+        matcher.insert_many(patterns)?;
+        Ok(matcher)
+    }
+
+    /// Compiles a pattern and appends it to the list.
+    ///
+    /// ## Errors
+    ///
+    /// If the regex pattern can't be compiled
+    pub fn insert(&mut self, pattern: &str) -> Result<(), RegexTrieError> {
+        self.insert_many(&[pattern.to_string()])
+    }
+
+    /// Insert many patterns at once. Nothing is inserted if any pattern
+    /// can't be compiled.
+    ///
+    /// ## Errors
+    ///
+    /// If any regex pattern can't be compiled
+    pub fn insert_many(&mut self, patterns: &[String]) -> Result<(), RegexTrieError> {
+        let compiled = patterns
+            .iter()
+            .map(|pattern| {
+                let (literal, is_regex) = parse_literal_prefix(pattern);
+                if !is_regex {
+                    return Ok(Check::Literal(
+                        literal.into_iter().map(|(ch, _)| ch).collect(),
+                    ));
+                }
+                Regex::new(pattern)
+                    .map(|dfa| Check::Regex(Box::new(dfa)))
+                    .map_err(|err| RegexTrieError::RegexCompilationFailed(Box::new(err)))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        for (pattern, check) in patterns.iter().zip(compiled) {
+            let score = (self.scorer)(pattern, matches!(check, Check::Regex(_)));
+            self.compiled_patterns.push((pattern.clone(), check, score));
+        }
+
+        Ok(())
+    }
+
+    /// Finds all patterns fully matching the given input, in insertion
+    /// order.
+    #[must_use]
+    pub fn find_matches(&self, input: &str) -> Vec<String> {
+        self.matching(input)
+            .map(|(pattern, _)| pattern.to_string())
+            .collect()
+    }
+
+    /// Finds the pattern with the lowest score fully matching the given input.
+    /// On equal scores, the first inserted pattern wins.
+    #[must_use]
+    pub fn find_best_match(&self, input: &str) -> Option<String> {
+        let mut best_match: Option<(&str, usize)> = None;
+        for (pattern, score) in self.matching(input) {
+            if best_match.is_none_or(|(_, best_score)| score < best_score) {
+                best_match = Some((pattern, score));
+            }
+        }
+
+        best_match.map(|(pattern, _)| pattern.to_string())
+    }
+
+    /// Iterates over all the patterns fully matching the input, with their
+    /// score.
+    fn matching<'matcher>(
+        &'matcher self,
+        input: &'matcher str,
+    ) -> impl Iterator<Item = (&'matcher str, usize)> {
+        let input_bytes = input.as_bytes();
+        self.compiled_patterns
+            .iter()
+            .filter(move |(_, check, _)| match check {
+                Check::Literal(literal) => literal == input,
+                Check::Regex(dfa) => dfa
+                    .find(input_bytes)
+                    .is_some_and(|m| m.start() == 0 && m.end() == input_bytes.len()),
+            })
+            .map(|(pattern, _, score)| (pattern.as_str(), *score))
+    }
+}
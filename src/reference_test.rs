@@ -0,0 +1,91 @@
+use pretty_assertions::assert_eq;
+
+use crate::{RegexTrie, reference::NaiveMatcher};
+
+/// Test set
+const TEST_SET: &str = include_str!("../assets/small_set.txt");
+
+/// Test the naive matcher uses the same semantics as the trie
+#[test]
+fn test_naive_semantics() {
+    let patterns = vec![
+        "a.*".to_string(),
+        "a[0-9]+b.*".to_string(),
+        "a123bbb".to_string(),
+        "test\\[bracket\\]".to_string(),
+    ];
+    let naive = NaiveMatcher::from(&patterns).expect("can't init naive matcher");
+
+    assert_eq!(
+        vec!["a.*", "a[0-9]+b.*", "a123bbb"],
+        naive.find_matches("a123bbb"),
+        "should be in insertion order"
+    );
+    assert_eq!(
+        Some("a123bbb".to_string()),
+        naive.find_best_match("a123bbb"),
+        "plain match should win"
+    );
+    assert_eq!(
+        Some("test\\[bracket\\]".to_string()),
+        naive.find_best_match("test[bracket]"),
+        "escaped match should work"
+    );
+    assert!(
+        NaiveMatcher::from(&["[".to_string()]).is_err(),
+        "should have failed"
+    );
+}
+
+/// Test the naive matcher tells the plain patterns from the regexes like the
+/// trie
+#[test]
+fn test_naive_plain_patterns() {
+    let patterns = vec![
+        r"id\d".to_string(),
+        "a|b".to_string(),
+        r"C:\dir".to_string(),
+        r"id[0-9]+".to_string(),
+        r"id\d+".to_string(),
+    ];
+    let naive = NaiveMatcher::from(&patterns).expect("can't init naive matcher");
+    let trie = RegexTrie::from(&patterns).expect("can't init regex trie");
+
+    for input in ["id5", r"id\d", "a", "b", "a|b", r"C:\dir", "id55"] {
+        assert_eq!(
+            naive.find_matches(input),
+            trie.find_matches(input),
+            "different matches for {input}"
+        );
+    }
+    assert_eq!(naive.find_matches(r"id\d"), vec![r"id\d"]);
+    assert_eq!(naive.find_matches("id5"), vec!["id[0-9]+", r"id\d+"]);
+    assert_eq!(naive.find_matches("a|b"), vec!["a|b"]);
+}
+
+/// Differential test between the trie and the naive matcher on real like
+/// assets
+#[test]
+fn test_naive_agrees_with_trie() {
+    let patterns = TEST_SET
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
+    let naive = NaiveMatcher::from(&patterns).expect("can't init naive matcher");
+    let trie = RegexTrie::from(&patterns).expect("can't init regex trie");
+
+    for input in [
+        "https://www.google.com/b4a/test/mqgzumi/another/yh936/again/kk839gym/abc123",
+        "https://www.google.com/",
+        "https://www.yahoo.com/test",
+        "nothing",
+        "",
+    ] {
+        let mut expected = naive.find_matches(input);
+        let mut result = trie.find_matches(input);
+        expected.sort();
+        result.sort();
+        assert_eq!(expected, result, "different matches for {input}");
+    }
+}
@@ -1,29 +1,355 @@
+use std::{
+    borrow::Cow,
+    cell::RefCell,
+    cmp::{Ordering, Reverse},
+    collections::{HashMap, HashSet},
+    ops::ControlFlow,
+    sync::{Arc, atomic::AtomicU64},
+    time::Instant,
+};
+
+use memchr::memmem::Finder;
 use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
-use regex_automata::dfa::regex::Regex;
-use std::collections::{HashMap, HashSet};
+use regex_automata::{Anchored, Input, PatternSet};
 
-use crate::RegexTrieError;
+use crate::{
+    InsertOptions, RegexTrieError, Utf8Handling,
+    alternation::NodeAlternations,
+    budget::SharedDfaSizeWarning,
+    empty::EmptyPolicy,
+    engine::{CompiledRegex, DfaConfig, MetaCaches, compile},
+    example::generate_example,
+    first_byte::FirstBytes,
+    hits::HitCounters,
+    literal::{LiteralDetection, VerbatimLiterals},
+    memo::CandidateMemo,
+    options::{regex_source, stored_prefix},
+    prefilter::{match_length_range, required_literal},
+    suffix::{SuffixIndex, literal_suffix},
+    tie_break::{TieBreak, TieBreaker},
+    trigram::{Trigram, TrigramIndex},
+};
 
 /// Special character in a regex
-const SPECIALS: &str = ".?*+()[]{}";
+pub(crate) const SPECIALS: &str = ".?*+()[]{}";
+/// Escaped characters of the assertions (word boundaries, anchors), which
+/// make a pattern a regex
+pub(crate) const ASSERTIONS: &str = "bBAz<>";
 /// Type for the scorer function
 /// 1st arg is the pattern
 /// 2nd arg is if it's a regex or a plain match
-type ScorerFuncType = Box<dyn Fn(&str, bool) -> usize>;
+/// It's `Send + Sync` for the trie to be shared between threads (parallel
+/// compilation, shards, background insertion, Python, http services).
+pub type ScorerFuncType = Box<dyn Fn(&str, bool) -> usize + Send + Sync>;
+/// Scorer function, shared by the tries derived from one another
+pub(crate) type SharedScorer = Arc<dyn Fn(&str, bool) -> usize + Send + Sync>;
+
+/// Compact identifier of a pattern inserted in a trie. Ids are assigned in
+/// insertion order, starting from 0, and are stable for the lifetime of the
+/// trie (including through serialization), so they can be stored in
+/// external indexes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PatternId(usize);
+
+impl PatternId {
+    /// Builds an id from its raw index, e.g. read back from an external
+    /// index.
+    #[must_use]
+    pub const fn new(index: usize) -> Self {
+        Self(index)
+    }
+
+    /// Raw index of the id.
+    #[must_use]
+    pub const fn index(self) -> usize {
+        self.0
+    }
+}
+
+/// Extract the literal prefix of a pattern, as a list of characters with
+/// whether they were escaped, and if the pattern is a regex (i.e. it has an
+/// unescaped meta-character or an assertion like `\b` after its literal
+/// prefix). The prefix of a regex stops before its first escape like `\d`
+/// or anchor, and is empty if it has a top level alternation.
+pub(crate) fn parse_literal_prefix(pattern: &str) -> (Vec<(char, bool)>, bool) {
+    parse_literal_prefix_with(pattern, "")
+}
+
+/// Same as `parse_literal_prefix`, the extra characters being meta-characters
+/// as well.
+pub(crate) fn parse_literal_prefix_with(
+    pattern: &str,
+    extra_specials: &str,
+) -> (Vec<(char, bool)>, bool) {
+    let is_special = |ch: char| SPECIALS.contains(ch) || extra_specials.contains(ch);
+    let mut prefix = Vec::with_capacity(pattern.len());
+    let mut previous_char = None;
+    // Length of the prefix before its first character reading differently
+    // in a regex (an escape like `\d`, an anchor), which only matters if the
+    // pattern is a regex. A top level alternation has no common prefix.
+    let mut regex_len = None;
+    let regex_prefix = |mut prefix: Vec<(char, bool)>, regex_len: Option<usize>| {
+        if has_top_level_alternation(pattern) {
+            prefix.clear();
+        } else if let Some(len) = regex_len {
+            prefix.truncate(len);
+        }
+        (prefix, true)
+    };
+    let mut chars = pattern.chars().peekable();
+    while let Some(ch) = chars.next() {
+        if ch == '\\' && matches!(chars.peek(), Some(&next) if is_special(next)) {
+            // Unpop the escape character
+            previous_char = Some(ch);
+            continue;
+        }
+        // An assertion is a regex, unless the backslash is a literal one.
+        if ch == '\\'
+            && previous_char != Some('\\')
+            && matches!(chars.peek(), Some(next) if ASSERTIONS.contains(*next))
+        {
+            return regex_prefix(prefix, regex_len);
+        }
+
+        // Stop at the first non escaped regex meta-character.
+        let mut is_escaped = false;
+        if is_special(ch) {
+            // Escaped means we should represent the pattern as escaped
+            if previous_char == Some('\\') {
+                is_escaped = true;
+            } else {
+                // This is a regex, we can stop. A quantifier allowing zero
+                // repetition makes the previous character optional.
+                if matches!(ch, '*' | '?' | '{') {
+                    prefix.pop();
+                }
+                return regex_prefix(prefix, regex_len);
+            }
+        } else if matches!(ch, '\\' | '^' | '$' | '|') {
+            regex_len.get_or_insert(prefix.len());
+        }
+
+        prefix.push((ch, is_escaped));
+        previous_char = Some(ch);
+    }
+
+    (prefix, false)
+}
+
+/// Groups the part of a regex after its literal prefix, e.g. `/api/x(?:$)`
+/// for `/api/x$`, so it's read as a regex while keeping its prefix in the
+/// trie, or the whole regex if it has a top level alternation. A character
+/// followed by a quantifier goes in the group.
+pub(crate) fn group_after_prefix(regex: &str) -> String {
+    if has_top_level_alternation(regex) {
+        return format!("(?:{regex})");
+    }
+    // Start of the last literal character, and end of the literal prefix
+    let mut last_start = 0;
+    let mut end = 0;
+    let mut chars = regex.char_indices().peekable();
+    while let Some((offset, ch)) = chars.next() {
+        let len = if ch == '\\' {
+            match chars.next_if(|&(_, next)| SPECIALS.contains(next)) {
+                Some((_, next)) => 1 + next.len_utf8(),
+                None => break,
+            }
+        } else if SPECIALS.contains(ch) || matches!(ch, '^' | '$') {
+            if matches!(ch, '*' | '+' | '?' | '{') {
+                end = last_start;
+            }
+            break;
+        } else {
+            ch.len_utf8()
+        };
+        last_start = offset;
+        end = offset + len;
+    }
+    format!("{}(?:{})", &regex[..end], &regex[end..])
+}
+
+/// Whether a regex has an alternation outside of any group or class, like
+/// `ab|cd`.
+fn has_top_level_alternation(regex: &str) -> bool {
+    let mut depth = 0_usize;
+    let mut in_class = false;
+    let mut chars = regex.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => {
+                chars.next();
+            }
+            // A leading `]` is a literal one.
+            '[' if !in_class => {
+                in_class = true;
+                chars.next_if_eq(&'^');
+                chars.next_if_eq(&']');
+            }
+            ']' => in_class = false,
+            '(' if !in_class => depth += 1,
+            ')' if !in_class => depth = depth.saturating_sub(1),
+            '|' if !in_class && depth == 0 => return true,
+            _ => {}
+        }
+    }
+    false
+}
+
+/// Whether the leftmost-first match of the DFA spans the whole input.
+pub(crate) fn is_full_match(dfa: &CompiledRegex, input: &[u8]) -> bool {
+    dfa.find(input)
+        .is_some_and(|m| m.start() == 0 && m.end() == input.len())
+}
+
+/// Whether the DFA matches a prefix of the input, i.e. starting at 0 and
+/// ending anywhere.
+pub(crate) fn is_prefix_match(dfa: &CompiledRegex, input: &[u8]) -> bool {
+    dfa.find(Input::new(input).anchored(Anchored::Yes))
+        .is_some()
+}
+
+/// How patterns are matched against an input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MatchMode {
+    /// The pattern must match the whole input
+    #[default]
+    Full,
+    /// The pattern must match a prefix of the input, as if it ended with
+    /// `.*`. Handy for URL-prefix rules like `https://google.com/user/`.
+    Prefix,
+}
+
+/// How the best match is selected among all the matching patterns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SelectionPolicy {
+    /// The pattern with the lowest score, as given by the scorer
+    #[default]
+    LowestScore,
+    /// The most specific pattern, whatever the scorer: the one whose match
+    /// consumes the most of the input (only relevant in prefix mode), then
+    /// the one with the longest literal prefix
+    LongestMatch,
+}
+
+/// A pattern matching an input, as found by `RegexTrie::for_each_match`.
+pub(crate) enum Matched<'query> {
+    /// Plain pattern, from its escaped path in the trie
+    Literal(&'query str),
+    /// Regex pattern, with its DFA and its score
+    Regex(&'query Arc<str>, &'query CompiledRegex, usize),
+}
+
+/// Rank of a matching pattern, the lowest being the best: how much of the
+/// input its match consumes, the length of its literal prefix, and its
+/// score, as relevant for the selection policy.
+type Rank = (Reverse<usize>, Reverse<usize>, usize);
+
+/// What to do once a pattern matched: stop, or go on with the candidates
+/// whose score is at most the given one, the others being skipped without
+/// running their regex.
+pub(crate) type NextMatch = ControlFlow<(), usize>;
+
+/// Human readable form of a path in the trie, for error messages.
+fn location(path: &[(char, bool)]) -> String {
+    path.iter().map(|(ch, _)| ch).collect()
+}
+
+/// How a pattern is matched.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PatternKind {
+    /// Plain pattern, matched by walking the trie only
+    Literal,
+    /// Regex pattern, matched with its compiled DFA
+    Regex,
+}
+
+/// A pattern of the trie, as exposed by `RegexTrie::iter`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PatternEntry<'trie> {
+    /// Id of the pattern
+    pub id: PatternId,
+    /// Pattern, as it was inserted
+    pub pattern: &'trie str,
+    /// Score of the pattern, as given by the scorer
+    pub score: usize,
+    /// Whether the pattern is plain or a compiled regex
+    pub kind: PatternKind,
+}
 
 /// Represents a node in the Regex Trie.
 /// Each node has a map of children for subsequent characters. It stores the
 /// indices of patterns that have this node's path as their literal prefix.
-#[derive(Debug, Default)]
-struct TrieNode {
+#[derive(Debug)]
+pub(crate) struct TrieNode {
     /// List of all children
-    children: HashMap<char, TrieNode>,
+    pub(crate) children: HashMap<char, TrieNode>,
     /// On which compiled pattern it should point
-    pattern_indices: Vec<usize>,
+    pub(crate) pattern_indices: Vec<usize>,
     /// Indicate this node also count has a prefix without any regex
-    contains_non_regex_prefix: bool,
+    pub(crate) contains_non_regex_prefix: bool,
     /// If this node is an escaped node
-    is_escaped: bool,
+    pub(crate) is_escaped: bool,
+    /// Lowest score of the patterns of the subtree, plain or regex. Only a
+    /// lower bound once patterns have been removed, until `compact`.
+    pub(crate) min_score: usize,
+}
+
+impl Default for TrieNode {
+    fn default() -> Self {
+        Self {
+            children: HashMap::default(),
+            pattern_indices: Vec::new(),
+            contains_non_regex_prefix: false,
+            is_escaped: false,
+            min_score: usize::MAX,
+        }
+    }
+}
+
+/// Outcome of walking the trie along an input.
+pub(crate) struct Walk<'trie> {
+    /// Deepest node reached
+    pub(crate) node: &'trie TrieNode,
+    /// Whether the whole input has been consumed
+    pub(crate) input_match_entirely: bool,
+    /// Path leading to the deepest node, escaped as it was inserted
+    pub(crate) escaped_pattern: String,
+}
+
+/// Buffers reused from one query to the other, to avoid allocating them for
+/// every input when matching many of them.
+#[derive(Debug, Default)]
+pub(crate) struct Scratch {
+    /// Indices of the candidate patterns
+    pub(crate) candidate_indices: Vec<usize>,
+    /// Path walked in the trie, escaped as it was inserted
+    pub(crate) escaped_pattern: String,
+    /// Length of `escaped_pattern` at every node holding a plain pattern
+    pub(crate) literal_ends: Vec<usize>,
+    /// Search caches of the fallback engine, disabled by default
+    pub(crate) meta_caches: MetaCaches,
+    /// Patterns matched by the combined regexes of nodes run by the query,
+    /// by id
+    pub(crate) alternation_sets: RefCell<Vec<(usize, PatternSet)>>,
+    /// Trigrams of the input, computed once per query by the first candidate
+    /// with required trigrams
+    pub(crate) input_trigrams: RefCell<Option<HashSet<Trigram>>>,
+    /// Sorted compiled indices of the literal suffixes the input ends with,
+    /// computed once per query by the first candidate with one
+    pub(crate) input_suffixes: RefCell<Option<Vec<usize>>>,
+}
+
+/// Pattern with its literal prefix, and whether it's a regex, see
+/// `parse_literal_prefix`.
+type ParsedPattern<'pattern> = (Cow<'pattern, str>, (Vec<(char, bool)>, bool));
+
+/// Patterns parsed and compiled by `RegexTrie::prepare_many`, ready to be
+/// added to the trie.
+pub(crate) struct PreparedBatch<'pattern> {
+    /// Patterns, with their literal prefix and whether they're regexes
+    pub(crate) parsed: Vec<ParsedPattern<'pattern>>,
+    /// Compiled regex of every pattern, `None` for the plain ones
+    pub(crate) compiled: Vec<Option<CompiledRegex>>,
 }
 
 /// The `RegexTrie` structure.
@@ -31,24 +357,100 @@ struct TrieNode {
 /// (DFAs).
 pub struct RegexTrie {
     /// Head of the trie tree
-    root: TrieNode,
+    pub(crate) root: TrieNode,
     /// Stores the original pattern string and its compiled DFA, with an
     /// optional score
-    compiled_patterns: Vec<(String, Regex, usize)>,
+    pub(crate) compiled_patterns: Vec<(Arc<str>, CompiledRegex, usize)>,
+    /// Every inserted pattern, indexed by its `PatternId`, with the index of
+    /// its compiled DFA if it's a regex, or `None` once removed. Pattern
+    /// strings are shared between all the fields, and with the matching
+    /// results.
+    pub(crate) patterns: Vec<Option<(Arc<str>, Option<usize>)>>,
+    /// Reverse lookup of `patterns`
+    pub(crate) ids: HashMap<Arc<str>, PatternId>,
+    /// Tags of the tagged patterns
+    pub(crate) tags: HashMap<PatternId, Vec<Arc<str>>>,
+    /// Strings of the plain patterns inserted escaped
+    pub(crate) verbatim_literals: VerbatimLiterals,
     /// Scorer function
-    scorer: ScorerFuncType,
+    pub(crate) scorer: SharedScorer,
+    /// Size above which inserting a regex calls the warning callback
+    pub(crate) dfa_size_warning: Option<(usize, SharedDfaSizeWarning)>,
+    /// How patterns are matched
+    pub(crate) match_mode: MatchMode,
+    /// How the best match is selected
+    pub(crate) selection_policy: SelectionPolicy,
+    /// How ties between best matches are broken
+    pub(crate) tie_breaker: TieBreaker,
+    /// Whether a plain pattern equal to the input is the best match, without
+    /// running any regex
+    pub(crate) prefer_exact_literal: bool,
+    /// Whether the patterns matching the empty string can be inserted
+    pub(crate) empty_patterns: EmptyPolicy,
+    /// Whether the empty input can match patterns
+    pub(crate) empty_inputs: EmptyPolicy,
+    /// How the plain patterns are told from the regexes
+    pub(crate) literal_detection: LiteralDetection,
+    /// How invalid UTF-8 inputs are handled
+    pub(crate) utf8_handling: Utf8Handling,
+    /// Number of matches of every pattern, indexed by its `PatternId`, if
+    /// counting is enabled
+    pub(crate) hit_counters: Option<HitCounters>,
+    /// Known regexes by canonical form, if equivalent ones are deduplicated
+    pub(crate) canonical_regexes: Option<HashMap<String, PatternId>>,
+    /// Patterns ignoring case
+    pub(crate) case_insensitive: HashSet<PatternId>,
+    /// Expiration time of the patterns inserted with a TTL
+    pub(crate) expirations: HashMap<PatternId, Instant>,
+    /// Maximum memory of all the compiled DFAs, in bytes, if limited
+    pub(crate) max_total_dfa_bytes: Option<usize>,
+    /// Maximum number of patterns, if limited
+    pub(crate) max_patterns: Option<usize>,
+    /// Whether the patterns no dense DFA can be built for use a slower
+    /// engine instead of being rejected
+    pub(crate) fallback_engine: bool,
+    /// Settings of the dense DFAs of the inserted patterns
+    pub(crate) dfa_config: DfaConfig,
+    /// Meta-characters ending the literal prefixes, on top of `SPECIALS`
+    pub(crate) extra_specials: Box<str>,
+    /// Candidates of the nodes reached by the previous queries, if memoized
+    pub(crate) candidate_memo: Option<CandidateMemo>,
+    /// Combined regexes of the nodes holding many of them, if enabled
+    pub(crate) node_alternations: Option<NodeAlternations>,
+    /// Required trigrams of the regexes, if indexed
+    pub(crate) trigram_index: Option<TrigramIndex>,
+    /// Reversed literal suffixes of the regexes, if indexed
+    pub(crate) suffix_index: Option<SuffixIndex>,
+    /// First bytes of the non-empty literal prefixes
+    pub(crate) first_bytes: FirstBytes,
+    /// Number of matches of every compiled pattern when the candidates were
+    /// last ordered by hits, empty if they never were
+    pub(crate) candidate_priority: Vec<u64>,
+    /// Searcher of the required literal of every compiled pattern, indexed
+    /// like `compiled_patterns`, for the substring mode
+    pub(crate) required_literals: Vec<Option<Finder<'static>>>,
+    /// Literal suffix of every compiled pattern, indexed like
+    /// `compiled_patterns`, checked on the input before running the DFA
+    pub(crate) literal_suffixes: Vec<Option<Box<[u8]>>>,
+    /// Minimum and maximum (if bounded) length of the matches of every
+    /// compiled pattern, indexed like `compiled_patterns`
+    pub(crate) match_lengths: Vec<(usize, Option<usize>)>,
+}
+
+/// Default scorer: the shortest regex wins, and plain matches win over any
+/// regex.
+pub(crate) const fn default_scorer(pattern: &str, is_regex: bool) -> usize {
+    if is_regex {
+        pattern.len()
+    } else {
+        // 0 score means it take priority over any regex
+        0
+    }
 }
 
 impl Default for RegexTrie {
     fn default() -> Self {
-        Self::new_with_custom_scorer(Box::new(|pattern: &str, is_regex| {
-            if is_regex {
-                pattern.len()
-            } else {
-                // 0 score means it take priority over any regex
-                0
-            }
-        }))
+        Self::new_with_custom_scorer(Box::new(default_scorer))
     }
 }
 
@@ -57,7 +459,25 @@ impl std::fmt::Debug for RegexTrie {
         f.debug_struct("RegexTrie")
             .field("root", &self.root)
             .field("compiled_patterns", &self.compiled_patterns)
-            .finish()
+            .field("patterns", &self.patterns)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Semantic equality: both tries hold the same set of patterns, with the same
+/// scores, whatever their insertion order (and so their ids).
+impl PartialEq for RegexTrie {
+    fn eq(&self, other: &Self) -> bool {
+        self.ids.len() == other.ids.len()
+            && self.iter().all(|entry| {
+                other
+                    .id_of(entry.pattern)
+                    .and_then(|id| other.entry(id))
+                    .is_some_and(|other_entry| {
+                        other_entry.score == entry.score
+                            && other.options(other_entry.id) == self.options(entry.id)
+                    })
+            })
     }
 }
 
@@ -71,10 +491,120 @@ impl RegexTrie {
     /// Creates a new, empty `RegexTrie` with a custom scorer.
     #[must_use]
     pub fn new_with_custom_scorer(scorer: ScorerFuncType) -> Self {
+        Self::new_with_shared_scorer(Arc::from(scorer))
+    }
+
+    /// Creates a new, empty `RegexTrie` sharing the given scorer.
+    pub(crate) fn new_with_shared_scorer(scorer: SharedScorer) -> Self {
         Self {
             root: TrieNode::default(),
             compiled_patterns: Vec::default(),
+            patterns: Vec::default(),
+            ids: HashMap::default(),
+            tags: HashMap::default(),
+            verbatim_literals: VerbatimLiterals::default(),
             scorer,
+            dfa_size_warning: None,
+            match_mode: MatchMode::default(),
+            selection_policy: SelectionPolicy::default(),
+            tie_breaker: TieBreaker::default(),
+            prefer_exact_literal: false,
+            empty_patterns: EmptyPolicy::default(),
+            empty_inputs: EmptyPolicy::default(),
+            literal_detection: LiteralDetection::default(),
+            utf8_handling: Utf8Handling::default(),
+            hit_counters: None,
+            canonical_regexes: None,
+            case_insensitive: HashSet::default(),
+            expirations: HashMap::default(),
+            max_total_dfa_bytes: None,
+            max_patterns: None,
+            fallback_engine: false,
+            dfa_config: DfaConfig::default(),
+            extra_specials: Box::default(),
+            candidate_memo: None,
+            node_alternations: None,
+            trigram_index: None,
+            suffix_index: None,
+            first_bytes: FirstBytes::default(),
+            candidate_priority: Vec::default(),
+            required_literals: Vec::default(),
+            literal_suffixes: Vec::default(),
+            match_lengths: Vec::default(),
+        }
+    }
+
+    /// Sets how patterns are matched against the inputs, see `MatchMode`.
+    /// It only applies to the anchored queries (`find_matches`,
+    /// `find_best_match`, `explain`, ...), and isn't serialized, like the
+    /// scorer.
+    #[must_use]
+    pub const fn with_match_mode(mut self, match_mode: MatchMode) -> Self {
+        self.match_mode = match_mode;
+        self
+    }
+
+    /// How patterns are matched against the inputs.
+    #[must_use]
+    pub const fn match_mode(&self) -> MatchMode {
+        self.match_mode
+    }
+
+    /// Sets how the best match is selected, see `SelectionPolicy`. Like the
+    /// match mode, it isn't serialized.
+    #[must_use]
+    pub const fn with_selection_policy(mut self, selection_policy: SelectionPolicy) -> Self {
+        self.selection_policy = selection_policy;
+        self
+    }
+
+    /// How the best match is selected.
+    #[must_use]
+    pub const fn selection_policy(&self) -> SelectionPolicy {
+        self.selection_policy
+    }
+
+    /// Makes a plain pattern equal to the input the best match, whatever the
+    /// selection policy, the scorer and the tie break: `find_best_match`
+    /// then returns it without running any regex candidate (unless hits are
+    /// counted). With the default scorer, the result is the same, only
+    /// faster. Like the selection policy, it isn't serialized.
+    #[must_use]
+    pub const fn with_prefer_exact_literal(mut self, enabled: bool) -> Self {
+        self.prefer_exact_literal = enabled;
+        self
+    }
+
+    /// Whether a plain pattern equal to the input is always the best match.
+    #[must_use]
+    pub const fn prefer_exact_literal(&self) -> bool {
+        self.prefer_exact_literal
+    }
+
+    /// Whether the DFA matches the input, according to the match mode.
+    pub(crate) fn dfa_matches(&self, dfa: &CompiledRegex, input: &[u8]) -> bool {
+        match self.match_mode {
+            MatchMode::Full => is_full_match(dfa, input),
+            MatchMode::Prefix => is_prefix_match(dfa, input),
+        }
+    }
+
+    /// Same as `dfa_matches`, for the regex of a pattern, with the search
+    /// caches of the scratch buffers.
+    pub(crate) fn is_match_cached(
+        &self,
+        pattern: &Arc<str>,
+        dfa: &CompiledRegex,
+        input: &[u8],
+        caches: &MetaCaches,
+    ) -> bool {
+        match self.match_mode {
+            MatchMode::Full => caches
+                .find(pattern, dfa, Input::new(input))
+                .is_some_and(|m| m.start() == 0 && m.end() == input.len()),
+            MatchMode::Prefix => caches
+                .find(pattern, dfa, Input::new(input).anchored(Anchored::Yes))
+                .is_some(),
         }
     }
 
@@ -112,15 +642,91 @@ impl RegexTrie {
         Ok(trie)
     }
 
+    /// The literal prefix of a pattern, unescaped, under which it's stored in
+    /// the trie. Patterns with an empty prefix (e.g. `.*foo`) are candidates
+    /// for every input, so they should be kept rare.
+    #[must_use]
+    pub fn literal_prefix(pattern: &str) -> String {
+        let (prefix, _) = parse_literal_prefix(pattern);
+        prefix.into_iter().map(|(ch, _)| ch).collect()
+    }
+
+    /// Adds meta-characters ending the literal prefixes, on top of the
+    /// default ones (`.?*+()[]{}`), e.g. `|^$` for patterns like `a|b` or
+    /// `^/api$`, which are otherwise stored as plain patterns, or under a
+    /// too long prefix. Escaping them (e.g. `\|`) keeps them literal. They
+    /// replace the previously added ones, and the known patterns are stored
+    /// again under their new literal prefix, those turning into regexes
+    /// being compiled. Like the match mode, they aren't serialized, so the
+    /// patterns only made regexes by them can't be loaded back.
+    ///
+    /// ## Errors
+    ///
+    /// If a plain pattern turning into a regex can't be compiled
+    pub fn with_extra_specials(mut self, specials: &str) -> Result<Self, RegexTrieError> {
+        self.extra_specials = specials.into();
+        for index in 0..self.patterns.len() {
+            let Some((pattern, compiled_index)) = &self.patterns[index] else {
+                continue;
+            };
+            let (_, is_regex) = self.parse_prefix(pattern);
+            let case_insensitive = self.is_case_insensitive(PatternId(index));
+            let compiled_index = match compiled_index {
+                None if is_regex => {
+                    let dfa = compile(pattern, false, self.fallback_engine, &self.dfa_config)?;
+                    let score = (self.scorer)(pattern, true);
+                    self.compiled_patterns
+                        .push((Arc::clone(pattern), dfa, score));
+                    Some(self.compiled_patterns.len() - 1)
+                }
+                Some(_) if !is_regex && !case_insensitive => None,
+                _ => continue,
+            };
+            self.patterns[index] = Some((Arc::clone(pattern), compiled_index));
+        }
+        // Rebuilt from the updated patterns, hits being counted again from 0
+        Ok(self
+            .filtered(|_| true, true)
+            .with_hit_counting(self.hit_counters.is_some()))
+    }
+
+    /// Same as `parse_literal_prefix`, with the extra meta-characters of the
+    /// trie.
+    pub(crate) fn parse_prefix(&self, pattern: &str) -> (Vec<(char, bool)>, bool) {
+        parse_literal_prefix_with(pattern, &self.extra_specials)
+    }
+
+    /// Whether a character is a meta-character, escaped in the trie.
+    pub(crate) fn is_special(&self, ch: char) -> bool {
+        SPECIALS.contains(ch) || self.extra_specials.contains(ch)
+    }
+
+    /// Length of the unescaped literal prefix of a pattern, in bytes.
+    fn literal_prefix_len(&self, pattern: &str) -> usize {
+        let (prefix, _) = self.parse_prefix(pattern);
+        prefix.iter().map(|(ch, _)| ch.len_utf8()).sum()
+    }
+
     /// Compiles a regex pattern and inserts it into the trie.
     /// The trie is built using the literal prefix of the pattern. The
-    /// compilation is done once, upon insertion.
+    /// compilation is done once, upon insertion. Inserting an already known
+    /// pattern does nothing, it keeps its `PatternId`.
     ///
     /// ## Errors
     ///
     /// If the regex pattern can't be compiled
     pub fn insert(&mut self, pattern: &str) -> Result<(), RegexTrieError> {
-        self.insert_many_lazy(&[pattern.to_string()])
+        self.insert_many_lazy(vec![Cow::Borrowed(pattern)])
+    }
+
+    /// Same as `insert`, but takes ownership of the pattern, so it's stored
+    /// without being copied.
+    ///
+    /// ## Errors
+    ///
+    /// If the regex pattern can't be compiled
+    pub fn insert_owned(&mut self, pattern: impl Into<String>) -> Result<(), RegexTrieError> {
+        self.insert_many_lazy(vec![Cow::Owned(pattern.into())])
     }
 
     /// Insert many pattern at once. Regex compilation are parallelized.
@@ -129,87 +735,343 @@ impl RegexTrie {
     ///
     /// If any regex pattern can't be compiled, the whole set will failed
     pub fn insert_many(&mut self, patterns: &[String]) -> Result<(), RegexTrieError> {
-        self.insert_many_lazy(patterns)
+        self.insert_many_lazy(
+            patterns
+                .iter()
+                .map(|pattern| Cow::Borrowed(pattern.as_str()))
+                .collect(),
+        )
     }
 
-    /// Insert many entries, but without compiling any regex immediately.
-    /// Instead, compute it at the end in parallel (because it's costly
-    /// operation).
+    /// Same as `insert_many`, but takes ownership of the patterns, so they're
+    /// stored without being copied. Meant for bulk loads.
     ///
     /// ## Errors
     ///
-    /// If the regex pattern can't be compiled
-    fn insert_many_lazy(&mut self, patterns: &[String]) -> Result<(), RegexTrieError> {
-        // Empty or "$-"" means a regex which never match
-        let placeholder =
-            Regex::new("").map_err(|err| RegexTrieError::RegexCompilationFailed(Box::new(err)))?;
-        let mut to_compile = Vec::default();
-
-        for pattern in patterns {
-            // Traverse the trie using the literal prefix of the pattern.
-            let mut current_node = &mut self.root;
-            let mut previous_char = None;
-            let mut is_regex = false;
-            let mut chars = pattern.chars().peekable();
-            while let Some(ch) = chars.next() {
-                if ch == '\\' && matches!(chars.peek(), Some(next) if SPECIALS.contains(*next)) {
-                    // Unpop the escape character
-                    previous_char = Some(ch);
-                    continue;
-                }
-
-                // Stop at the first non escaped regex meta-character.
-                let mut is_escaped = false;
-                if SPECIALS.contains(ch) {
-                    // Escaped means we should represent the pattern as escaped
-                    if previous_char == Some('\\') {
-                        is_escaped = true;
-                    } else {
-                        // This is a regex, we can stop
-                        is_regex = true;
-                        break;
-                    }
-                }
+    /// If any regex pattern can't be compiled, the whole set will failed
+    pub fn insert_many_owned<S: Into<String>>(
+        &mut self,
+        patterns: impl IntoIterator<Item = S>,
+    ) -> Result<(), RegexTrieError> {
+        self.insert_many_lazy(
+            patterns
+                .into_iter()
+                .map(|pattern| Cow::Owned(pattern.into()))
+                .collect(),
+        )
+    }
 
-                current_node = current_node.children.entry(ch).or_default();
-                current_node.is_escaped = is_escaped;
-                previous_char = Some(ch);
-            }
+    /// Insert many entries. All the regex are compiled first, in parallel
+    /// (because it's costly operation), and the trie is only updated once
+    /// they all succeeded, so a failure leaves it untouched.
+    ///
+    /// ## Errors
+    ///
+    /// If the regex pattern can't be compiled, or if the pattern capacity or
+    /// the DFA memory budget would be exceeded
+    pub(crate) fn insert_many_lazy(
+        &mut self,
+        patterns: Vec<Cow<'_, str>>,
+    ) -> Result<(), RegexTrieError> {
+        let prepared = self.prepare_many(patterns)?;
+        self.commit_many(prepared);
+        Ok(())
+    }
 
-            if is_regex {
-                // Put a placeholder, the regex will be compiled later
-                let pattern_index = self.compiled_patterns.len();
-                let score = (self.scorer)(pattern, true);
-                self.compiled_patterns
-                    .push((pattern.clone(), placeholder.clone(), score));
-                to_compile.push((pattern_index, pattern.clone()));
-
-                // Store the index of the compiled pattern at the node corresponding
-                // to the end of its literal prefix.
-                current_node.pattern_indices.push(pattern_index);
-            } else {
-                // Special value to indicate it's not a regex but a complete string
-                current_node.contains_non_regex_prefix = true;
-            }
-        }
+    /// First stage of `insert_many_lazy`, which doesn't modify the trie:
+    /// parses and compiles the patterns, and checks the pattern capacity and
+    /// the DFA memory budget.
+    ///
+    /// ## Errors
+    ///
+    /// If the regex pattern can't be compiled, or if the pattern capacity or
+    /// the DFA memory budget would be exceeded
+    pub(crate) fn prepare_many<'pattern>(
+        &self,
+        patterns: Vec<Cow<'pattern, str>>,
+    ) -> Result<PreparedBatch<'pattern>, RegexTrieError> {
+        let mut parsed = patterns
+            .into_iter()
+            .map(|pattern| {
+                let parsed = self.parse_prefix(&pattern);
+                (pattern, parsed)
+            })
+            .collect::<Vec<_>>();
+        self.drop_equivalent(&mut parsed, |(_, is_regex)| *is_regex);
+        self.check_capacity(parsed.iter().map(|(pattern, _)| pattern.as_ref()))?;
+        self.check_literal_detection(parsed.iter().map(|(pattern, _)| pattern.as_ref()))?;
 
         // Each pattern is compiled on a separate worker thread.
-        let compiled = to_compile
+        let compiled = parsed
             .par_iter()
-            .map(|(idx, pattern)| {
+            .map(|(pattern, (_, is_regex))| {
                 // Compile the pattern into a DFA. Return an error on failure.
-                let dfa = Regex::new(pattern)
-                    .map_err(|err| RegexTrieError::RegexCompilationFailed(Box::new(err)))?;
-                Ok::<_, RegexTrieError>((*idx, dfa))
+                is_regex
+                    .then(|| compile(pattern, false, self.fallback_engine, &self.dfa_config))
+                    .transpose()
             })
             .collect::<Result<Vec<_>, _>>()?;
+        self.check_empty_patterns(parsed.iter().map(|(pattern, _)| (pattern.as_ref(), false)))?;
+        self.check_dfa_budget(
+            parsed
+                .iter()
+                .zip(&compiled)
+                .filter_map(|((pattern, _), dfa)| Some((pattern.as_ref(), dfa.as_ref()?))),
+        )?;
 
-        // Overwrite all placeholders
-        for (idx, dfa) in compiled {
-            self.compiled_patterns[idx].1 = dfa;
+        Ok(PreparedBatch { parsed, compiled })
+    }
+
+    /// Second stage of `insert_many_lazy`: adds the prepared patterns to the
+    /// trie.
+    pub(crate) fn commit_many(&mut self, prepared: PreparedBatch<'_>) {
+        for ((pattern, (prefix, _)), dfa) in prepared.parsed.into_iter().zip(prepared.compiled) {
+            let compiled = dfa.map(|dfa| (dfa, (self.scorer)(&pattern, true)));
+            self.add_pattern(pattern, &prefix, compiled, InsertOptions::default());
         }
+        self.refresh_alternations();
+    }
 
-        Ok(())
+    /// Adds an already compiled pattern to the trie, at the node of its
+    /// literal prefix, with its DFA and score. `None` means the pattern is
+    /// plain, not a regex. Already known patterns are left as is, and new
+    /// ones are only copied if they're borrowed.
+    pub(crate) fn add_pattern(
+        &mut self,
+        pattern: Cow<'_, str>,
+        prefix: &[(char, bool)],
+        compiled: Option<(CompiledRegex, usize)>,
+        options: InsertOptions,
+    ) {
+        if self.ids.contains_key(pattern.as_ref()) {
+            return;
+        }
+        let pattern: Arc<str> = Arc::from(pattern.into_owned());
+        self.invalidate_candidate_memo();
+
+        if let Some(&(ch, _)) = prefix.first() {
+            self.first_bytes.insert(ch);
+        }
+
+        let mut compiled_index = None;
+        if let Some((dfa, score)) = compiled {
+            self.warn_dfa_size(&pattern, &dfa);
+            let pattern_index = self.compiled_patterns.len();
+            self.required_literals.push(if options.case_insensitive {
+                None
+            } else {
+                required_literal(&pattern)
+            });
+            self.literal_suffixes.push(if options.case_insensitive {
+                None
+            } else {
+                literal_suffix(&regex_source(&pattern, &self.extra_specials))
+                    .map(Vec::into_boxed_slice)
+            });
+            self.match_lengths.push(match_length_range(
+                &regex_source(&pattern, &self.extra_specials),
+                options.case_insensitive,
+            ));
+            self.compiled_patterns
+                .push((Arc::clone(&pattern), dfa, score));
+            self.index_trigrams(pattern_index, options.case_insensitive);
+            self.index_suffix(pattern_index, options.case_insensitive);
+            compiled_index = Some(pattern_index);
+        }
+        // Traverse the trie using the literal prefix of the pattern.
+        let mut current_node = &mut self.root;
+        for &(ch, is_escaped) in prefix {
+            current_node = current_node.children.entry(ch).or_default();
+            current_node.is_escaped = is_escaped;
+        }
+        match compiled_index {
+            // Store the index of the compiled pattern at the node corresponding
+            // to the end of its literal prefix.
+            Some(pattern_index) => current_node.pattern_indices.push(pattern_index),
+            // Special value to indicate it's not a regex but a complete string
+            None => current_node.contains_non_regex_prefix = true,
+        }
+        if compiled_index.is_some() {
+            self.mark_alternation_stale(prefix);
+        }
+        let score = compiled_index.map_or_else(
+            || (self.scorer)(&pattern, false),
+            |compiled_index| self.compiled_patterns[compiled_index].2,
+        );
+        self.root.lower_min_score(prefix, score);
+
+        let id = PatternId(self.patterns.len());
+        self.patterns
+            .push(Some((Arc::clone(&pattern), compiled_index)));
+        if let Some(hit_counters) = &mut self.hit_counters {
+            hit_counters.patterns.push(AtomicU64::new(0));
+        }
+        self.ids.insert(pattern, id);
+        if options.case_insensitive {
+            self.case_insensitive.insert(id);
+        }
+        if compiled_index.is_some() {
+            self.register_canonical(id);
+        }
+    }
+
+    /// Reserves the id of a removed pattern, so the next ones keep theirs
+    /// (e.g. when loading a serialized trie).
+    pub(crate) fn add_removed_pattern(&mut self) {
+        self.patterns.push(None);
+        if let Some(hit_counters) = &mut self.hit_counters {
+            hit_counters.patterns.push(AtomicU64::new(0));
+        }
+    }
+
+    /// Pattern of the given id, as it was inserted.
+    #[must_use]
+    pub fn pattern(&self, id: PatternId) -> Option<&str> {
+        self.patterns
+            .get(id.0)?
+            .as_ref()
+            .map(|(pattern, _)| &**pattern)
+    }
+
+    /// Iterates over every pattern of the trie, in insertion order (i.e. by
+    /// id), with its score and kind. Removed patterns are skipped.
+    pub fn iter(&self) -> impl Iterator<Item = PatternEntry<'_>> {
+        (0..self.patterns.len()).filter_map(|index| self.entry(PatternId(index)))
+    }
+
+    /// Pattern of the given id, with its score and kind.
+    pub(crate) fn entry(&self, id: PatternId) -> Option<PatternEntry<'_>> {
+        let (pattern, compiled_index) = self.patterns.get(id.0)?.as_ref()?;
+        let (score, kind) = compiled_index.map_or_else(
+            || ((self.scorer)(pattern, false), PatternKind::Literal),
+            |compiled_index| (self.compiled_patterns[compiled_index].2, PatternKind::Regex),
+        );
+        Some(PatternEntry {
+            id,
+            pattern,
+            score,
+            kind,
+        })
+    }
+
+    /// Id of the given pattern, if it has been inserted. A string inserted
+    /// with `insert_literal` is found from either itself or its escaped
+    /// pattern.
+    #[must_use]
+    pub fn id_of(&self, pattern: &str) -> Option<PatternId> {
+        self.ids
+            .get(pattern)
+            .copied()
+            .or_else(|| self.verbatim_id(pattern))
+    }
+
+    /// Iterates over every pattern which has been compiled as a regex (plain
+    /// patterns only live in the trie nodes), with the regex it's compiled
+    /// from and whether it ignores case.
+    #[cfg(feature = "http")]
+    pub(crate) fn regex_patterns(&self) -> impl Iterator<Item = (&str, Cow<'_, str>, bool)> {
+        self.patterns
+            .iter()
+            .enumerate()
+            .filter_map(|(index, pattern)| match pattern {
+                Some((pattern, Some(_))) => Some((
+                    &**pattern,
+                    regex_source(pattern, &self.extra_specials),
+                    self.is_case_insensitive(PatternId(index)),
+                )),
+                _ => None,
+            })
+    }
+
+    /// Traverse the trie based on the input string, calling `visit` with the
+    /// depth and the escaped path of every node reached, starting with the
+    /// root (which holds the patterns with no literal prefix, e.g. ".*").
+    pub(crate) fn walk_input(
+        &self,
+        input: &str,
+        visit: impl FnMut(usize, &TrieNode, &str),
+    ) -> Walk<'_> {
+        let mut escaped_pattern = String::with_capacity(input.len());
+        let (node, input_match_entirely) = self.walk_into(input, &mut escaped_pattern, visit);
+        Walk {
+            node,
+            input_match_entirely,
+            escaped_pattern,
+        }
+    }
+
+    /// Same as `walk_input`, writing the escaped path in the given buffer instead
+    /// of allocating it. Returns the deepest node reached, and whether the
+    /// whole input has been consumed.
+    pub(crate) fn walk_into(
+        &self,
+        input: &str,
+        escaped_pattern: &mut String,
+        mut visit: impl FnMut(usize, &TrieNode, &str),
+    ) -> (&TrieNode, bool) {
+        let mut current_node = &self.root;
+        escaped_pattern.clear();
+        visit(0, current_node, escaped_pattern);
+        // No literal prefix starts like the input, only the root patterns
+        // are candidates.
+        if input
+            .as_bytes()
+            .first()
+            .is_some_and(|&byte| !self.first_bytes.contains(byte))
+        {
+            return (current_node, false);
+        }
+
+        for (depth, ch) in input.chars().enumerate() {
+            let Some(node) = current_node.children.get(&ch) else {
+                // No further path in the trie, so no more candidates can be found this way.
+                return (current_node, false);
+            };
+            if node.is_escaped {
+                escaped_pattern.push('\\');
+            }
+            escaped_pattern.push(ch);
+
+            current_node = node;
+            visit(depth + 1, current_node, escaped_pattern);
+        }
+
+        (current_node, true)
+    }
+
+    /// Shared string of a plain pattern, from its path in the trie: the
+    /// string it has been escaped from if it's been inserted with
+    /// `insert_literal`.
+    pub(crate) fn shared_literal(&self, escaped_pattern: &str) -> Arc<str> {
+        if let Some(string) = self.verbatim_literals.string(escaped_pattern) {
+            return Arc::clone(string);
+        }
+        self.ids.get_key_value(escaped_pattern).map_or_else(
+            || Arc::from(escaped_pattern),
+            |(pattern, _)| Arc::clone(pattern),
+        )
+    }
+
+    /// Walks the trie along the input, collecting in the scratch buffers the
+    /// indices of all the patterns whose literal prefix matches it, and the
+    /// plain patterns met along the way. Indices are sorted, i.e. in
+    /// insertion order, so results don't depend on the trie layout. Returns
+    /// the deepest node reached, and whether the whole input has been
+    /// consumed.
+    pub(crate) fn candidates_into(&self, input: &str, scratch: &mut Scratch) -> (&TrieNode, bool) {
+        let candidate_indices = &mut scratch.candidate_indices;
+        let literal_ends = &mut scratch.literal_ends;
+        candidate_indices.clear();
+        literal_ends.clear();
+        let walk = self.walk_into(input, &mut scratch.escaped_pattern, |_, node, path| {
+            candidate_indices.extend_from_slice(&node.pattern_indices);
+            if node.contains_non_regex_prefix {
+                literal_ends.push(path.len());
+            }
+        });
+        // Every pattern is stored in a single node, so there's no duplicate.
+        candidate_indices.sort_unstable();
+        walk
     }
 
     /// Finds all regex patterns in the trie that fully match the given input
@@ -224,129 +1086,567 @@ impl RegexTrie {
     /// **DFA Matching:** It iterates through the candidate patterns. For each
     /// one, it retrieves its pre-compiled DFA and runs a match against the
     /// entire input string. This is very fast as the DFA is already built.
+    ///
+    /// The order is stable: the plain pattern equal to the input comes first
+    /// if any, then the matching regexes in insertion order.
     #[must_use]
     pub fn find_matches(&self, input: &str) -> Vec<String> {
-        let mut candidate_indices = HashSet::new();
-        let mut current_node = &self.root;
+        self.find_matches_shared(input)
+            .iter()
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    /// Same as `find_matches`, but returns the patterns as they're stored in
+    /// the trie, shared instead of copied. Cheaper with many long patterns.
+    #[must_use]
+    pub fn find_matches_shared(&self, input: &str) -> Vec<Arc<str>> {
+        let mut matching_patterns = Vec::new();
+        self.find_matches_into(input, &mut Scratch::default(), &mut matching_patterns);
+        matching_patterns
+    }
+
+    /// Same as `find_matches_shared`, appending the matches to `out`, and
+    /// reusing the scratch buffers of the previous queries.
+    pub(crate) fn find_matches_into(
+        &self,
+        input: &str,
+        scratch: &mut Scratch,
+        out: &mut Vec<Arc<str>>,
+    ) {
+        self.for_each_match(input, scratch, |matched| match matched {
+            Matched::Literal(pattern) => out.push(self.shared_literal(pattern)),
+            Matched::Regex(pattern, _, _) => out.push(Arc::clone(pattern)),
+        });
+    }
+
+    /// Calls `on_match` for every pattern matching the input, according to
+    /// the match mode: the plain patterns first, then the regexes in
+    /// insertion order. Matches are counted if enabled.
+    pub(crate) fn for_each_match(
+        &self,
+        input: &str,
+        scratch: &mut Scratch,
+        on_match: impl FnMut(Matched<'_>),
+    ) {
+        let literal_match = self.collect_candidates(input, scratch);
+        self.match_candidates(input.as_bytes(), scratch, literal_match, on_match);
+    }
+
+    /// Same as `for_each_match` for a best match query, `on_match` telling
+    /// what to do next, unless the matches are counted.
+    pub(crate) fn try_for_each_match(
+        &self,
+        input: &str,
+        scratch: &mut Scratch,
+        on_match: impl FnMut(Matched<'_>) -> NextMatch,
+    ) {
+        let literal_match = self.collect_best_candidates(input, scratch);
+        self.try_match_candidates(input.as_bytes(), scratch, literal_match, on_match);
+    }
+
+    /// First stage of `for_each_match`: collects in the scratch buffers all
+    /// the patterns whose literal prefix matches the input, from the memo if
+    /// enabled. Returns whether a plain pattern is equal to the input.
+    pub(crate) fn collect_candidates(&self, input: &str, scratch: &mut Scratch) -> bool {
+        let (node, input_match_entirely) = match &self.candidate_memo {
+            Some(memo) => self.memoized_candidates_into(input, scratch, memo),
+            None => self.candidates_into(input, scratch),
+        };
+        input_match_entirely && node.contains_non_regex_prefix
+    }
+
+    /// Second stage of `for_each_match`, once the candidates have been
+    /// collected in the scratch buffers, `literal_match` telling whether a
+    /// plain pattern is equal to the input. Matches are counted if enabled.
+    pub(crate) fn match_candidates(
+        &self,
+        input: &[u8],
+        scratch: &Scratch,
+        literal_match: bool,
+        mut on_match: impl FnMut(Matched<'_>),
+    ) {
+        self.try_match_candidates(input, scratch, literal_match, |matched| {
+            on_match(matched);
+            ControlFlow::Continue(usize::MAX)
+        });
+    }
 
-        // Always include patterns with no literal prefix (e.g., ".*"), which
-        // are stored at the root.
-        for &index in &current_node.pattern_indices {
-            candidate_indices.insert(index);
+    /// Same as `match_candidates`, `on_match` telling what to do next.
+    /// Counted queries still run every candidate, so the counts don't depend
+    /// on the skipped ones, but `on_match` isn't called anymore once it
+    /// breaks.
+    pub(crate) fn try_match_candidates(
+        &self,
+        input: &[u8],
+        scratch: &Scratch,
+        literal_match: bool,
+        mut on_match: impl FnMut(Matched<'_>) -> NextMatch,
+    ) {
+        if self.hit_counters.is_none() {
+            let _ = self.match_candidates_uncounted(input, scratch, literal_match, on_match);
+            return;
         }
 
-        // Traverse the trie based on the input string to find more candidates.
-        let mut input_match_entirely = true;
-        let mut escaped_pattern = String::with_capacity(input.len());
-        for ch in input.chars() {
-            if let Some(node) = current_node.children.get(&ch) {
-                if node.is_escaped {
-                    escaped_pattern.push('\\');
-                }
-                escaped_pattern.push(ch);
+        let mut any_match = false;
+        let mut stopped = false;
+        let _ = self.match_candidates_uncounted(input, scratch, literal_match, |matched| {
+            any_match = true;
+            self.count_hit(&matched);
+            if !stopped {
+                stopped = on_match(matched).is_break();
+            }
+            ControlFlow::Continue(usize::MAX)
+        });
+        self.count_query(scratch, any_match);
+    }
 
-                current_node = node;
-                // Collect all patterns whose literal prefix matches what we've seen so far.
-                for &index in &current_node.pattern_indices {
-                    candidate_indices.insert(index);
+    /// Same as `try_match_candidates`, without counting the matches.
+    fn match_candidates_uncounted(
+        &self,
+        input: &[u8],
+        scratch: &Scratch,
+        literal_match: bool,
+        mut on_match: impl FnMut(Matched<'_>) -> NextMatch,
+    ) -> ControlFlow<()> {
+        scratch.alternation_sets.borrow_mut().clear();
+        if !self.input_allowed(input) {
+            return ControlFlow::Continue(());
+        }
+        scratch.input_trigrams.borrow_mut().take();
+        scratch.input_suffixes.borrow_mut().take();
+        let mut max_score = usize::MAX;
+        match self.match_mode {
+            // If we match the input exactly, it means there's no regex
+            // involved here. We can directly return it.
+            MatchMode::Full => {
+                if literal_match {
+                    max_score = on_match(Matched::Literal(&scratch.escaped_pattern))?;
+                }
+            }
+            // Any plain pattern along the way is a prefix of the input.
+            MatchMode::Prefix => {
+                for &end in &scratch.literal_ends {
+                    max_score = on_match(Matched::Literal(&scratch.escaped_pattern[..end]))?;
                 }
-            } else {
-                // No further path in the trie, so no more candidates can be found this way.
-                input_match_entirely = false;
-                break;
             }
         }
 
-        let mut matching_patterns = Vec::new();
+        // DFA Matching, skipping the candidates whose score is already known
+        // to be too high.
+        for &index in &scratch.candidate_indices {
+            let (pattern_str, dfa, score) = &self.compiled_patterns[index];
 
-        // If we match the input exactly, it means there's no regex involved
-        // here. We can directly return it.
-        if input_match_entirely && current_node.contains_non_regex_prefix {
-            matching_patterns.push(escaped_pattern);
+            if *score <= max_score
+                && self.length_fits(index, input)
+                && self.suffix_gate(index, input)
+                && self.suffix_occurs(index, input, scratch)
+                && self.trigrams_occur(index, input, scratch)
+                && self.candidate_matches(index, input, scratch)
+            {
+                max_score = on_match(Matched::Regex(pattern_str, dfa, *score))?;
+            }
         }
+        ControlFlow::Continue(())
+    }
+
+    /// Same as `find_matches`, but each pattern comes with its score, sorted
+    /// best-first (lowest score first). Equal scores keep the `find_matches`
+    /// order, so the first entry is the one `find_best_match` returns.
+    #[must_use]
+    pub fn find_matches_with_scores(&self, input: &str) -> Vec<(String, usize)> {
+        let mut matching_patterns = Vec::new();
+        self.for_each_match(input, &mut Scratch::default(), |matched| {
+            matching_patterns.push(match matched {
+                Matched::Literal(pattern) => (
+                    self.shared_literal(pattern).to_string(),
+                    (self.scorer)(pattern, false),
+                ),
+                Matched::Regex(pattern, _, score) => (pattern.to_string(), score),
+            });
+        });
+
+        // Stable sort, to keep the insertion order on ties.
+        matching_patterns.sort_by_key(|(_, score)| *score);
+        matching_patterns
+    }
+
+    /// Same as finding all the matches, but only keep the "best" match,
+    /// according to the selection policy. By default, it's the one with the
+    /// lowest score (see `scorer_func` in the init), i.e. the shortest
+    /// pattern. On ties, the plain pattern wins, then the first inserted
+    /// regex, unless another `TieBreak` is set.
+    ///
+    /// The search stops as soon as a match nothing can beat is found, e.g. a
+    /// plain pattern with a score of 0 for the default scorer, without
+    /// running the remaining candidates (unless ties are broken otherwise
+    /// than by `TieBreak::First`, or hits are counted). Until then, the
+    /// candidates whose score can't beat the best match so far are skipped
+    /// without running their regex.
+    ///
+    /// See `find_matches` for explanation.
+    #[must_use]
+    pub fn find_best_match(&self, input: &str) -> Option<String> {
+        self.find_best_match_shared(input)
+            .map(|pattern| pattern.to_string())
+    }
+
+    /// Same as `find_best_match`, but returns the pattern as it's stored in
+    /// the trie, shared instead of copied.
+    #[must_use]
+    pub fn find_best_match_shared(&self, input: &str) -> Option<Arc<str>> {
+        self.best_match_among(input, |on_match| {
+            self.try_for_each_match(input, &mut Scratch::default(), on_match);
+        })
+    }
 
-        // DFA Matching
-        let input_bytes = input.as_bytes();
+    /// Best of the matches of the input, as enumerated by
+    /// `try_for_each_match`, according to the selection policy and the tie
+    /// break. The enumeration is stopped once a match nothing can beat is
+    /// found, e.g. a plain pattern with a score of 0, and ties are won by the
+    /// first one, and the candidates whose score can't beat the best match
+    /// are skipped.
+    pub(crate) fn best_match_among(
+        &self,
+        input: &str,
+        for_each_match: impl FnOnce(&mut dyn FnMut(Matched<'_>) -> NextMatch),
+    ) -> Option<Arc<str>> {
+        let unbeatable_rank = self.unbeatable_rank(input);
+        let mut best_match: Option<(Arc<str>, _)> = None;
+        // Other patterns ranked like the best one, when ties are broken.
+        let mut ties = Vec::new();
+        for_each_match(&mut |matched| {
+            let rank = self.rank(input, &matched);
+            if let Some(pattern) = self.preferred_literal(input, &matched) {
+                ties.clear();
+                best_match = Some((self.shared_literal(pattern), rank));
+                return ControlFlow::Break(());
+            }
+            let ordering = best_match
+                .as_ref()
+                .map_or(Ordering::Less, |(_, best_rank)| rank.cmp(best_rank));
+            // On ties, the first one wins, unless told otherwise.
+            if ordering == Ordering::Greater
+                || (ordering == Ordering::Equal && self.tie_breaker.policy == TieBreak::First)
+            {
+                return ControlFlow::Continue(
+                    best_match.as_ref().map_or(usize::MAX, |(_, best_rank)| {
+                        self.max_useful_score(best_rank)
+                    }),
+                );
+            }
 
-        for index in candidate_indices {
-            let (pattern_str, dfa, _) = &self.compiled_patterns[index];
+            let pattern = match matched {
+                Matched::Literal(pattern) => self.shared_literal(pattern),
+                Matched::Regex(pattern, _, _) => Arc::clone(pattern),
+            };
+            if ordering == Ordering::Equal {
+                ties.push(pattern);
+            } else {
+                ties.clear();
+                best_match = Some((pattern, rank));
+            }
+            if rank <= unbeatable_rank && self.tie_breaker.policy == TieBreak::First {
+                return ControlFlow::Break(());
+            }
+            ControlFlow::Continue(self.max_useful_score(&rank))
+        });
 
-            if let Some(m) = dfa.find(input_bytes) {
-                if m.start() == 0 && m.end() == input_bytes.len() {
-                    matching_patterns.push(pattern_str.clone());
+        let (best, _) = best_match?;
+        if ties.is_empty() {
+            return Some(best);
+        }
+        ties.insert(0, best);
+        self.break_tie(ties)
+    }
+
+    /// The matching plain pattern, if it's equal to the input and such
+    /// patterns are preferred.
+    fn preferred_literal<'matched>(
+        &self,
+        input: &str,
+        matched: &Matched<'matched>,
+    ) -> Option<&'matched str> {
+        match matched {
+            Matched::Literal(pattern) if self.prefer_exact_literal => match self.match_mode {
+                // Only a plain pattern equal to the input matches.
+                MatchMode::Full => Some(pattern),
+                MatchMode::Prefix => {
+                    (self.literal_prefix_len(pattern) == input.len()).then_some(pattern)
                 }
+            },
+            _ => None,
+        }
+    }
+
+    /// Rank of a matching pattern according to the selection policy, the
+    /// lowest being the best.
+    fn rank(&self, input: &str, matched: &Matched<'_>) -> Rank {
+        match (self.selection_policy, matched) {
+            (SelectionPolicy::LowestScore, Matched::Literal(pattern)) => {
+                (Reverse(0), Reverse(0), (self.scorer)(pattern, false))
+            }
+            (SelectionPolicy::LowestScore, Matched::Regex(_, _, score)) => {
+                (Reverse(0), Reverse(0), *score)
+            }
+            (SelectionPolicy::LongestMatch, Matched::Literal(pattern)) => {
+                let len = self.literal_prefix_len(pattern);
+                (Reverse(len), Reverse(len), 0)
+            }
+            (SelectionPolicy::LongestMatch, Matched::Regex(pattern, dfa, _)) => {
+                let end = match self.match_mode {
+                    MatchMode::Full => input.len(),
+                    MatchMode::Prefix => dfa
+                        .find(Input::new(input).anchored(Anchored::Yes))
+                        .map_or(0, |found| found.end()),
+                };
+                (Reverse(end), Reverse(self.literal_prefix_len(pattern)), 0)
             }
         }
+    }
 
-        matching_patterns
+    /// Highest score a match can have to still beat (or tie with, if ties
+    /// aren't won by the first one) the best match so far.
+    pub(crate) const fn max_useful_score(&self, best_rank: &Rank) -> usize {
+        match (self.selection_policy, self.tie_breaker.policy) {
+            (SelectionPolicy::LowestScore, TieBreak::First) => best_rank.2.saturating_sub(1),
+            (SelectionPolicy::LowestScore, _) => best_rank.2,
+            // Scores aren't compared.
+            (SelectionPolicy::LongestMatch, _) => usize::MAX,
+        }
+    }
+
+    /// Best rank a match of the input can get: no other match can beat it.
+    const fn unbeatable_rank(&self, input: &str) -> Rank {
+        match self.selection_policy {
+            SelectionPolicy::LowestScore => (Reverse(0), Reverse(0), 0),
+            SelectionPolicy::LongestMatch => (Reverse(input.len()), Reverse(input.len()), 0),
+        }
     }
 
-    /// Same as finding all the matches, but only keep the "best" match.
-    /// See `scorer_func` in the init. By default, take the shortest pattern.
+    /// Synthesizes a string matching the given pattern, which must have been
+    /// inserted in the trie. Useful to show a concrete example of what a rule
+    /// matches. Plain patterns are their own example, and regex ones go
+    /// through a bounded expansion of their classes and quantifiers.
     ///
-    /// See `find_matches` for explanation.
+    /// Returns `None` if the pattern isn't in the trie, or if no example
+    /// could be found (e.g. look-around assertions making the naive expansion
+    /// fail).
     #[must_use]
-    pub fn find_best_match(&self, input: &str) -> Option<String> {
-        let mut candidate_indices = HashSet::new();
-        let mut current_node = &self.root;
+    pub fn generate_example(&self, pattern: &str) -> Option<String> {
+        let (prefix, is_regex) = self.parse_prefix(pattern);
+        if !is_regex
+            && self
+                .id_of(pattern)
+                .is_none_or(|id| !self.is_case_insensitive(id))
+        {
+            let mut node = &self.root;
+            for (ch, _) in &prefix {
+                node = node.children.get(ch)?;
+            }
+            return node
+                .contains_non_regex_prefix
+                .then(|| prefix.iter().map(|(ch, _)| ch).collect());
+        }
+
+        let (_, dfa, _) = self
+            .compiled_patterns
+            .iter()
+            .find(|(candidate, _, _)| **candidate == *pattern)?;
+        generate_example(pattern).filter(|example| is_full_match(dfa, example.as_bytes()))
+    }
 
-        // Always include patterns with no literal prefix (e.g., ".*"), which
-        // are stored at the root.
-        for &index in &current_node.pattern_indices {
-            candidate_indices.insert(index);
+    /// Walks the whole trie and verifies its invariants. Meant for debugging
+    /// and tests, as it visits every node:
+    ///  - every `pattern_indices` entry points at a live compiled pattern,
+    ///    referenced exactly once, and the ones of removed patterns aren't
+    ///    referenced,
+    ///  - the path leading to a pattern reconstructs its literal prefix,
+    ///  - only meta-characters are escaped, and they always are,
+    ///  - no orphan node, i.e. every leaf holds at least one pattern,
+    ///  - the minimum score of a node is at most the ones of its patterns
+    ///    and of its children,
+    ///  - every `PatternId` resolves back to its pattern, and regex ones to
+    ///    their compiled pattern.
+    ///
+    /// ## Errors
+    ///
+    /// Describes the first broken invariant found
+    pub fn self_check(&self) -> Result<(), RegexTrieError> {
+        let mut references = vec![0_usize; self.compiled_patterns.len()];
+        let mut path = Vec::new();
+        self.check_node(&self.root, &mut path, &mut references)?;
+        let live = self.check_ids()?;
+        if self.required_literals.len() != self.compiled_patterns.len() {
+            return Err(RegexTrieError::Inconsistent(format!(
+                "{} required literals for {} compiled patterns",
+                self.required_literals.len(),
+                self.compiled_patterns.len(),
+            )));
+        }
+        if self.literal_suffixes.len() != self.compiled_patterns.len() {
+            return Err(RegexTrieError::Inconsistent(format!(
+                "{} literal suffixes for {} compiled patterns",
+                self.literal_suffixes.len(),
+                self.compiled_patterns.len(),
+            )));
+        }
+        if self.match_lengths.len() != self.compiled_patterns.len() {
+            return Err(RegexTrieError::Inconsistent(format!(
+                "{} match lengths for {} compiled patterns",
+                self.match_lengths.len(),
+                self.compiled_patterns.len(),
+            )));
+        }
+        if let Some(trigram_index) = self
+            .trigram_index
+            .as_ref()
+            .filter(|trigram_index| trigram_index.len() != self.compiled_patterns.len())
+        {
+            return Err(RegexTrieError::Inconsistent(format!(
+                "{} indexed trigram sets for {} compiled patterns",
+                trigram_index.len(),
+                self.compiled_patterns.len(),
+            )));
+        }
+        if let Some(suffix_index) = self
+            .suffix_index
+            .as_ref()
+            .filter(|suffix_index| suffix_index.len() != self.compiled_patterns.len())
+        {
+            return Err(RegexTrieError::Inconsistent(format!(
+                "{} indexed suffixes for {} compiled patterns",
+                suffix_index.len(),
+                self.compiled_patterns.len(),
+            )));
         }
 
-        // Traverse the trie based on the input string to find more candidates.
-        let mut input_match_entirely = true;
-        let mut escaped_pattern = String::with_capacity(input.len());
-        for ch in input.chars() {
-            if let Some(node) = current_node.children.get(&ch) {
-                if node.is_escaped {
-                    escaped_pattern.push('\\');
-                }
-                escaped_pattern.push(ch);
+        // Compiled patterns of the removed ones are left unreferenced.
+        if let Some(index) =
+            (0..references.len()).find(|&index| references[index] != usize::from(live[index]))
+        {
+            return Err(RegexTrieError::Inconsistent(format!(
+                "pattern #{index} is referenced {} times",
+                references[index]
+            )));
+        }
 
-                current_node = node;
-                // Collect all patterns whose literal prefix matches what we've seen so far.
-                for &index in &current_node.pattern_indices {
-                    candidate_indices.insert(index);
-                }
-            } else {
-                // No further path in the trie, so no more candidates can be found this way.
-                input_match_entirely = false;
-                break;
+        Ok(())
+    }
+
+    /// Part of `self_check` verifying the pattern ids. Returns which compiled
+    /// patterns belong to a live (i.e. not removed) pattern.
+    ///
+    /// ## Errors
+    ///
+    /// Describes the first broken invariant found
+    fn check_ids(&self) -> Result<Vec<bool>, RegexTrieError> {
+        let patterns = self.patterns.iter().flatten().count();
+        if self.ids.len() != patterns {
+            return Err(RegexTrieError::Inconsistent(format!(
+                "{} ids for {patterns} patterns",
+                self.ids.len(),
+            )));
+        }
+
+        let mut live = vec![false; self.compiled_patterns.len()];
+        for (index, slot) in self.patterns.iter().enumerate() {
+            let Some((pattern, compiled_index)) = slot else {
+                continue;
+            };
+            if self.id_of(pattern) != Some(PatternId(index)) {
+                return Err(RegexTrieError::Inconsistent(format!(
+                    "pattern {pattern:?} doesn't resolve to id #{index}"
+                )));
             }
+            let Some(compiled_index) = compiled_index else {
+                continue;
+            };
+            if self
+                .compiled_patterns
+                .get(*compiled_index)
+                .is_none_or(|(compiled, _, _)| compiled != pattern)
+                || live[*compiled_index]
+            {
+                return Err(RegexTrieError::Inconsistent(format!(
+                    "pattern {pattern:?} doesn't resolve to its compiled pattern"
+                )));
+            }
+            live[*compiled_index] = true;
         }
 
-        let mut best_match = None;
+        Ok(live)
+    }
 
-        // If we match the input exactly, it means there's no regex involved
-        // here. We can directly return it.
-        if input_match_entirely && current_node.contains_non_regex_prefix {
-            let score = (self.scorer)(&escaped_pattern, false);
-            best_match = Some((escaped_pattern, score));
+    /// Recursive part of `self_check`, `path` being the characters leading to
+    /// `node`.
+    ///
+    /// ## Errors
+    ///
+    /// Describes the first broken invariant found
+    fn check_node(
+        &self,
+        node: &TrieNode,
+        path: &mut Vec<(char, bool)>,
+        references: &mut [usize],
+    ) -> Result<(), RegexTrieError> {
+        if node.children.is_empty()
+            && node.pattern_indices.is_empty()
+            && !node.contains_non_regex_prefix
+            && !path.is_empty()
+        {
+            return Err(RegexTrieError::Inconsistent(format!(
+                "orphan node at {:?}",
+                location(path)
+            )));
         }
 
-        // DFA Matching
-        let input_bytes = input.as_bytes();
+        for &index in &node.pattern_indices {
+            let Some((pattern, _, score)) = self.compiled_patterns.get(index) else {
+                return Err(RegexTrieError::Inconsistent(format!(
+                    "dangling pattern #{index} at {:?}",
+                    location(path)
+                )));
+            };
+            references[index] += 1;
 
-        for index in candidate_indices {
-            let (pattern_str, dfa, score) = &self.compiled_patterns[index];
+            let case_insensitive = self
+                .id_of(pattern)
+                .is_some_and(|id| self.is_case_insensitive(id));
+            if stored_prefix(pattern, case_insensitive, &self.extra_specials) != *path {
+                return Err(RegexTrieError::Inconsistent(format!(
+                    "pattern {pattern:?} stored at {:?}",
+                    location(path)
+                )));
+            }
+            if *score < node.min_score {
+                return Err(RegexTrieError::Inconsistent(format!(
+                    "pattern {pattern:?} scored below the minimum of {:?}",
+                    location(path)
+                )));
+            }
+        }
 
-            if let Some(m) = dfa.find(input_bytes) {
-                if m.start() == 0 && m.end() == input_bytes.len() {
-                    match &best_match {
-                        Some((_, best_score)) => {
-                            if score < best_score {
-                                best_match = Some((pattern_str.clone(), *score));
-                            }
-                        }
-                        None => best_match = Some((pattern_str.clone(), *score)),
-                    }
-                }
+        for (&ch, child) in &node.children {
+            if child.is_escaped != self.is_special(ch) {
+                return Err(RegexTrieError::Inconsistent(format!(
+                    "wrong escaping of {ch:?} after {:?}",
+                    location(path)
+                )));
             }
+
+            if child.min_score < node.min_score {
+                return Err(RegexTrieError::Inconsistent(format!(
+                    "minimum score of {:?} above the one of {ch:?} below",
+                    location(path)
+                )));
+            }
+
+            path.push((ch, child.is_escaped));
+            self.check_node(child, path, references)?;
+            path.pop();
         }
 
-        best_match.map(|(pattern, _)| pattern)
+        Ok(())
     }
 }
@@ -1,6 +1,15 @@
+use std::{sync::Arc, time::Duration};
+
 use pretty_assertions::assert_eq;
 
-use crate::RegexTrie;
+use crate::{
+    BackgroundRegexTrie, CompactReport, ContainsTrie, CostEstimate, DfaConfig, EmptyPolicy, Engine,
+    FrozenRegexTrie, GlobStar, HostnameTrie, InsertOptions, LiteralDetection, LoggedRegexTrie,
+    MatchMode, OptimizeReport, PathSegmentTrie, PatternEntry, PatternId, PatternInfo, PatternKind,
+    QueryCache, RegexTrie, RegexTrieError, ScoreChange, SelectionPolicy, ShardedRegexTrie,
+    TieBreak, TokenTrie, TrieSnapshot, Utf8Handling,
+    regex_trie::{Scratch, parse_literal_prefix},
+};
 
 /// Test set
 const TEST_SET: &str = include_str!("../assets/small_set.txt");
@@ -63,6 +72,64 @@ fn test_quantifiers() {
     );
 }
 
+/// Test a character made optional by a quantifier isn't part of the literal
+/// prefix
+#[test]
+fn test_optional_prefix_character() {
+    for (pattern, prefix) in [
+        ("/ab?", "/a"),
+        ("ab*", "a"),
+        ("ab{0,2}", "a"),
+        ("ab+", "ab"),
+    ] {
+        let (parsed, is_regex) = parse_literal_prefix(pattern);
+        assert!(is_regex, "{pattern}");
+        assert_eq!(
+            parsed.into_iter().map(|(ch, _)| ch).collect::<String>(),
+            prefix,
+            "{pattern}"
+        );
+    }
+
+    let patterns = vec!["ab*".to_string(), "ab{0,2}".to_string(), "ab+".to_string()];
+    let tree = RegexTrie::from(&patterns).expect("can't init regex trie");
+    assert_eq_no_sort(
+        vec![patterns[0].clone(), patterns[1].clone()],
+        tree.find_matches("a"),
+    );
+    assert_eq_no_sort(patterns.clone(), tree.find_matches("abb"));
+    assert_eq_no_sort(
+        vec![patterns[0].clone(), patterns[2].clone()],
+        tree.find_matches("abbb"),
+    );
+}
+
+/// Test the regexes whose literal prefix is cut by an escape, an anchor or
+/// an alternation
+#[test]
+fn test_regex_prefix_escape() {
+    let patterns = vec![
+        r"id\d+".to_string(),
+        "ab|cd.*".to_string(),
+        "^a.*".to_string(),
+        "b+|cd".to_string(),
+    ];
+    let tree = RegexTrie::from(&patterns).expect("can't init regex trie");
+
+    assert_eq_no_sort(vec![patterns[0].clone()], tree.find_matches("id5"));
+    assert_eq_no_sort(
+        vec![patterns[1].clone(), patterns[3].clone()],
+        tree.find_matches("cd"),
+    );
+    assert_eq_no_sort(vec![patterns[1].clone()], tree.find_matches("cd1"));
+    assert_eq_no_sort(
+        vec![patterns[1].clone(), patterns[2].clone()],
+        tree.find_matches("ab"),
+    );
+    assert_eq_no_sort(vec![patterns[2].clone()], tree.find_matches("a"));
+    assert!(tree.find_matches(r"id\d").is_empty());
+}
+
 /// Test a non matching input
 #[test]
 fn test_no_regex_match() {
@@ -296,3 +363,3200 @@ where
     rhs.sort();
     assert_eq!(lhs, rhs, "should be equal");
 }
+
+/// Test the consistency validator on a real like trie
+#[test]
+fn test_self_check() {
+    let patterns = TEST_SET
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(ToString::to_string)
+        .chain(["test\\[bracket\\]".to_string(), ".*".to_string()])
+        .collect::<Vec<_>>();
+    let mut tree = RegexTrie::from(&patterns).expect("can't init regex trie");
+    assert!(tree.self_check().is_ok(), "should be consistent");
+
+    tree.insert("https://www\\.google\\.com/a\\.b")
+        .expect("should insert");
+    assert!(tree.self_check().is_ok(), "should be consistent");
+}
+
+/// Test generating examples matching the inserted patterns
+#[test]
+fn test_generate_example() {
+    let patterns = vec![
+        "https://www\\.google\\.com/[a-z0-9]+/test/.*".to_string(),
+        "test\\[bracket\\]".to_string(),
+        "id-[0-9]{3}(abc|def)?".to_string(),
+        "[^a-z]+".to_string(),
+    ];
+    let tree = RegexTrie::from(&patterns).expect("can't init regex trie");
+
+    for pattern in &patterns {
+        let example = tree
+            .generate_example(pattern)
+            .expect("should generate an example");
+        assert_eq!(
+            Some(pattern.clone()),
+            tree.find_matches(&example)
+                .into_iter()
+                .find(|matched| matched == pattern),
+            "{example} should match {pattern}"
+        );
+    }
+    assert_eq!(
+        Some("id-000abc".to_string()),
+        tree.generate_example("id-[0-9]{3}(abc|def)?"),
+        "should be readable"
+    );
+    assert_eq!(None, tree.generate_example("unknown.*"), "not in the trie");
+}
+
+/// Test the explain trace of a match
+#[test]
+fn test_explain() {
+    let patterns = vec![
+        ".*".to_string(),
+        "a.*".to_string(),
+        "a[0-9]+b.*".to_string(),
+        "abc".to_string(),
+    ];
+    let tree = RegexTrie::from(&patterns).expect("can't init regex trie");
+
+    let trace = tree.explain("a123bbb");
+    assert_eq!("a", trace.walked_prefix, "should stop after the 'a'");
+    assert_eq!(None, trace.literal_match, "no plain match");
+    assert_eq!(
+        vec![(".*", 0, true), ("a.*", 1, true), ("a[0-9]+b.*", 1, true)],
+        trace
+            .candidates
+            .iter()
+            .map(|candidate| (
+                candidate.pattern.as_str(),
+                candidate.depth,
+                candidate.matched
+            ))
+            .collect::<Vec<_>>(),
+        "wrong candidates"
+    );
+    assert_eq!(tree.find_best_match("a123bbb"), trace.best, "wrong best");
+
+    let trace = tree.explain("abc");
+    assert_eq!(
+        Some(("abc".to_string(), 0)),
+        trace.literal_match,
+        "plain match"
+    );
+    assert_eq!(
+        Some("abc".to_string()),
+        trace.best,
+        "plain match should win"
+    );
+}
+
+/// Test serializing and loading back a compiled trie
+#[test]
+fn test_serialization_roundtrip() {
+    let patterns = TEST_SET
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(ToString::to_string)
+        .chain(["test\\[bracket\\]".to_string(), "plain".to_string()])
+        .collect::<Vec<_>>();
+    let tree = RegexTrie::from(&patterns).expect("can't init regex trie");
+
+    let bytes = tree.to_bytes();
+    let loaded = RegexTrie::from_bytes(&bytes).expect("can't load regex trie");
+    assert!(loaded.self_check().is_ok(), "should be consistent");
+    for input in [
+        "https://www.google.com/b4a/test/mqgzumi/another/yh936/again/kk839gym/abc123",
+        "test[bracket]",
+        "plain",
+        "nothing",
+    ] {
+        let mut expected = tree.find_matches(input);
+        let mut result = loaded.find_matches(input);
+        expected.sort();
+        result.sort();
+        assert_eq!(expected, result, "different matches for {input}");
+        assert_eq!(
+            tree.find_best_match(input),
+            loaded.find_best_match(input),
+            "different best match for {input}"
+        );
+    }
+
+    assert!(
+        RegexTrie::from_bytes(&bytes[..bytes.len() - 1]).is_err(),
+        "truncated data should fail"
+    );
+    assert!(
+        RegexTrie::from_bytes(b"garbage").is_err(),
+        "garbage should fail"
+    );
+}
+
+/// Test the literal prefix extraction
+#[test]
+fn test_literal_prefix() {
+    assert_eq!(RegexTrie::literal_prefix("hello.*"), "hello");
+    assert_eq!(RegexTrie::literal_prefix("plain"), "plain");
+    assert_eq!(RegexTrie::literal_prefix(r"a\.b\(c[0-9]+"), "a.b(c");
+    assert_eq!(RegexTrie::literal_prefix(".*foo"), "");
+    assert_eq!(RegexTrie::literal_prefix(""), "");
+}
+
+/// Test the matches are returned in a stable order: plain pattern first,
+/// then the regexes in insertion order, whatever the trie layout
+#[test]
+fn test_deterministic_order() {
+    let patterns = vec![
+        "ab.*".to_string(),
+        ".*".to_string(),
+        "abc".to_string(),
+        "a.*".to_string(),
+        "abc.*".to_string(),
+        "a[a-z]c".to_string(),
+    ];
+    let tree = RegexTrie::from(&patterns).expect("can't init regex trie");
+
+    for _ in 0..10 {
+        assert_eq!(
+            vec!["abc", "ab.*", ".*", "a.*", "abc.*", "a[a-z]c"],
+            tree.find_matches("abc")
+        );
+    }
+
+    // On ties, the first inserted regex wins
+    let patterns = vec!["b.c".to_string(), "..c".to_string(), "a.c".to_string()];
+    let tree =
+        RegexTrie::from_with_scorer(&patterns, Box::new(|_, _| 1)).expect("can't init regex trie");
+    assert_eq!(Some("..c".to_string()), tree.find_best_match("abc"));
+    assert_eq!(Some("..c".to_string()), tree.explain("abc").best);
+    let tree =
+        RegexTrie::from_with_scorer(&["a.c".to_string(), "..c".to_string()], Box::new(|_, _| 1))
+            .expect("can't init regex trie");
+    assert_eq!(Some("a.c".to_string()), tree.find_best_match("abc"));
+    assert_eq!(Some("a.c".to_string()), tree.explain("abc").best);
+}
+
+/// Test all the matches are returned with their score, best-first
+#[test]
+fn test_find_matches_with_scores() {
+    let patterns = vec![
+        "a.*".to_string(),
+        "abc".to_string(),
+        "a[a-z]+".to_string(),
+        "ab.".to_string(),
+        "x.*".to_string(),
+    ];
+    let tree = RegexTrie::from(&patterns).expect("can't init regex trie");
+
+    assert_eq!(
+        vec![
+            ("abc".to_string(), 0),
+            ("a.*".to_string(), 3),
+            ("ab.".to_string(), 3),
+            ("a[a-z]+".to_string(), 7),
+        ],
+        tree.find_matches_with_scores("abc")
+    );
+    assert_eq!(
+        tree.find_best_match("abc"),
+        tree.find_matches_with_scores("abc")
+            .first()
+            .map(|(pattern, _)| pattern.clone())
+    );
+    assert!(tree.find_matches_with_scores("nothing").is_empty());
+}
+
+/// Test the lookups between patterns and their ids
+#[test]
+fn test_pattern_ids() {
+    let patterns = vec![
+        "a.*".to_string(),
+        r"plain\.txt".to_string(),
+        "b[0-9]+".to_string(),
+    ];
+    let mut tree = RegexTrie::from(&patterns).expect("can't init regex trie");
+
+    for (index, pattern) in patterns.iter().enumerate() {
+        let id = tree.id_of(pattern).expect("pattern should have an id");
+        assert_eq!(index, id.index());
+        assert_eq!(Some(pattern.as_str()), tree.pattern(id));
+    }
+    assert_eq!(None, tree.id_of("unknown"));
+    assert_eq!(None, tree.pattern(PatternId::new(3)));
+
+    // Re-inserting a known pattern keeps its id, and doesn't duplicate it
+    tree.insert("b[0-9]+").expect("should have worked");
+    tree.insert("c").expect("should have worked");
+    assert_eq!(Some(PatternId::new(2)), tree.id_of("b[0-9]+"));
+    assert_eq!(Some(PatternId::new(3)), tree.id_of("c"));
+    assert_eq!(vec!["b[0-9]+"], tree.find_matches("b42"));
+    assert!(tree.self_check().is_ok(), "should be consistent");
+
+    // Ids survive serialization
+    let loaded = RegexTrie::from_bytes(&tree.to_bytes()).expect("can't load regex trie");
+    for pattern in ["a.*", r"plain\.txt", "b[0-9]+", "c"] {
+        assert_eq!(tree.id_of(pattern), loaded.id_of(pattern));
+    }
+}
+
+/// Test iterating over all the patterns
+#[test]
+fn test_iter() {
+    let patterns = vec![
+        "a.*".to_string(),
+        "plain".to_string(),
+        "b[0-9]+".to_string(),
+    ];
+    let tree = RegexTrie::from(&patterns).expect("can't init regex trie");
+
+    assert_eq!(
+        vec![
+            PatternEntry {
+                id: PatternId::new(0),
+                pattern: "a.*",
+                score: 3,
+                kind: PatternKind::Regex,
+            },
+            PatternEntry {
+                id: PatternId::new(1),
+                pattern: "plain",
+                score: 0,
+                kind: PatternKind::Literal,
+            },
+            PatternEntry {
+                id: PatternId::new(2),
+                pattern: "b[0-9]+",
+                score: 7,
+                kind: PatternKind::Regex,
+            },
+        ],
+        tree.iter().collect::<Vec<_>>()
+    );
+    assert_eq!(0, RegexTrie::new().iter().count());
+}
+
+/// Test the introspection of the inserted patterns
+#[test]
+fn test_pattern_info() {
+    let mut tree = RegexTrie::from(&[
+        "/api/v[0-9]/(?i)users".to_string(),
+        "/plain".to_string(),
+        "(?s-i:.)+".to_string(),
+    ])
+    .expect("can't init regex trie");
+    tree.insert_with_options(
+        "/Docs/.*",
+        InsertOptions {
+            case_insensitive: true,
+        },
+    )
+    .expect("can't insert");
+
+    let info = tree.pattern_info(PatternId::new(0)).expect("known id");
+    assert_eq!(info.literal_prefix, "/api/v");
+    assert_eq!(info.kind, PatternKind::Regex);
+    assert_eq!(info.engine, Some(Engine::Dfa));
+    assert!(info.dfa_bytes > 0);
+    // The long s folds to s, and takes 2 bytes
+    assert_eq!((info.min_len, info.max_len), (13, Some(15)));
+    assert!(!info.case_insensitive);
+    assert_eq!(info.inline_flags, "i");
+
+    assert_eq!(
+        tree.pattern_info(PatternId::new(1)),
+        Some(PatternInfo {
+            literal_prefix: "/plain".to_string(),
+            kind: PatternKind::Literal,
+            engine: None,
+            dfa_bytes: 0,
+            min_len: 6,
+            max_len: Some(6),
+            case_insensitive: false,
+            inline_flags: String::new(),
+        })
+    );
+
+    let info = tree.pattern_info(PatternId::new(2)).expect("known id");
+    assert_eq!(info.literal_prefix, "");
+    assert_eq!((info.min_len, info.max_len), (1, None));
+    assert_eq!(info.inline_flags, "s");
+
+    // Only the characters matching themselves whatever the case are kept.
+    let info = tree.pattern_info(PatternId::new(3)).expect("known id");
+    assert_eq!(info.literal_prefix, "/");
+    assert!(info.case_insensitive);
+
+    assert_eq!(tree.remove_with_prefix("/plain"), 1);
+    assert_eq!(tree.pattern_info(PatternId::new(1)), None);
+    assert_eq!(tree.pattern_info(PatternId::new(9)), None);
+}
+
+/// Test the cost estimation of the patterns before insertion
+#[test]
+fn test_estimate_cost() {
+    let tree = RegexTrie::new();
+    assert_eq!(
+        tree.estimate_cost("/plain").expect("valid pattern"),
+        CostEstimate {
+            engine: None,
+            nfa_states: 0,
+            memory: 0,
+            unbounded_repetition: false,
+        }
+    );
+
+    let small = tree.estimate_cost("/id/[0-9]{3}").expect("valid regex");
+    assert_eq!(small.engine, Some(Engine::Dfa));
+    assert!(small.nfa_states > 0);
+    assert!(!small.unbounded_repetition);
+    let large = tree.estimate_cost("/.*a.{6}").expect("valid regex");
+    assert!(large.unbounded_repetition);
+    assert!(large.nfa_states > small.nfa_states);
+    assert!(large.memory > small.memory);
+
+    // Nothing is inserted
+    assert_eq!(tree.iter().count(), 0);
+    assert!(matches!(
+        tree.estimate_cost("/id/["),
+        Err(RegexTrieError::RegexCompilationFailed(_))
+    ));
+    let fallback = RegexTrie::new().with_fallback_engine(true);
+    assert_eq!(
+        fallback
+            .estimate_cost(r"\bword\b")
+            .expect("valid regex")
+            .engine,
+        Some(Engine::Meta)
+    );
+}
+
+/// Test the patterns and inputs matching the empty string
+#[test]
+fn test_empty_patterns_and_inputs() {
+    let patterns = [String::new(), "a*".to_string(), "/x".to_string()];
+    let tree = RegexTrie::from(&patterns).expect("can't init regex trie");
+    assert_eq!(tree.empty_patterns(), EmptyPolicy::Allow);
+    assert_eq!(tree.empty_inputs(), EmptyPolicy::Allow);
+    // `""` is a plain pattern like any other: it matches the empty input only
+    assert_eq!(tree.find_matches(""), vec!["", "a*"]);
+    assert_eq!(tree.find_best_match(""), Some(String::new()));
+    assert_eq!(tree.find_matches("aa"), vec!["a*"]);
+    // ... and every input in prefix mode, as a prefix of it
+    let prefix = RegexTrie::from(&patterns)
+        .expect("can't init regex trie")
+        .with_match_mode(MatchMode::Prefix);
+    assert_eq!(prefix.find_matches("/x"), vec!["", "/x", "a*"]);
+
+    let denied = tree.with_empty_inputs(EmptyPolicy::Deny);
+    assert!(denied.find_matches("").is_empty());
+    assert_eq!(denied.find_best_match(""), None);
+    assert!(!denied.is_match(""));
+    assert_eq!(denied.find_matches("aa"), vec!["a*"]);
+
+    let mut tree = RegexTrie::new().with_empty_patterns(EmptyPolicy::Deny);
+    for pattern in ["", "a*", "(x|)", "[0-9]{0,3}"] {
+        assert!(
+            matches!(
+                tree.insert(pattern),
+                Err(RegexTrieError::EmptyPattern(rejected)) if rejected == pattern
+            ),
+            "{pattern} accepted"
+        );
+    }
+    tree.insert_with_options(
+        "(?:)",
+        InsertOptions {
+            case_insensitive: true,
+        },
+    )
+    .expect_err("empty pattern");
+    // Nothing of a rejected batch is inserted
+    tree.insert_many(&["/a".to_string(), "b?".to_string()])
+        .expect_err("empty pattern");
+    assert_eq!(tree.iter().count(), 0);
+    tree.insert_many(&["/a".to_string(), "a+".to_string()])
+        .expect("can't insert");
+    tree.insert("(").expect_err("invalid regex");
+    assert!(matches!(
+        tree.insert("("),
+        Err(RegexTrieError::RegexCompilationFailed(_))
+    ));
+}
+
+/// Test the semantic equality between tries
+#[test]
+fn test_equality() {
+    let patterns = vec![
+        "a.*".to_string(),
+        "plain".to_string(),
+        "b[0-9]+".to_string(),
+    ];
+    let tree = RegexTrie::from(&patterns).expect("can't init regex trie");
+
+    // Insertion order doesn't matter
+    let mut reversed = patterns.clone();
+    reversed.reverse();
+    let other = RegexTrie::from(&reversed).expect("can't init regex trie");
+    assert_eq!(tree, other);
+    assert_eq!(
+        tree,
+        RegexTrie::from_bytes(&tree.to_bytes()).expect("can't load regex trie")
+    );
+
+    // Different patterns
+    let mut other = RegexTrie::from(&patterns).expect("can't init regex trie");
+    other.insert("c").expect("should have worked");
+    assert_ne!(tree, other);
+    assert_ne!(other, tree);
+    let other = RegexTrie::from(&patterns[..2]).expect("can't init regex trie");
+    assert_ne!(tree, other);
+
+    // Different scores
+    let other = RegexTrie::from_with_scorer(&patterns, Box::new(|pattern, _| pattern.len()))
+        .expect("can't init regex trie");
+    assert_ne!(tree, other);
+}
+
+/// Test inserting owned patterns works like borrowed ones
+#[test]
+fn test_insert_owned() {
+    let patterns = vec![
+        "a.*".to_string(),
+        "plain".to_string(),
+        "b[0-9]+".to_string(),
+    ];
+    let expected = RegexTrie::from(&patterns).expect("can't init regex trie");
+
+    let mut tree = RegexTrie::new();
+    tree.insert_many_owned(patterns.clone())
+        .expect("should have worked");
+    assert_eq!(expected, tree);
+
+    let mut tree = RegexTrie::new();
+    for pattern in patterns {
+        tree.insert_owned(pattern).expect("should have worked");
+    }
+    tree.insert_owned("c").expect("should have worked");
+    assert_eq!(vec!["c"], tree.find_matches("c"));
+    assert!(tree.insert_many_owned(["d", "e(", "f"]).is_err());
+    assert_eq!(None, tree.id_of("d"));
+    assert!(tree.self_check().is_ok(), "should be consistent");
+}
+
+/// Test the matched patterns are shared with the trie, not copied
+#[test]
+fn test_shared_matches() {
+    let patterns = vec!["a.*".to_string(), "abc".to_string(), "a[a-z]+".to_string()];
+    let tree = RegexTrie::from(&patterns).expect("can't init regex trie");
+
+    let first = tree.find_matches_shared("abc");
+    let second = tree.find_matches_shared("abc");
+    assert_eq!(
+        tree.find_matches("abc"),
+        first.iter().map(ToString::to_string).collect::<Vec<_>>()
+    );
+    assert!(
+        first
+            .iter()
+            .zip(&second)
+            .all(|(lhs, rhs)| Arc::ptr_eq(lhs, rhs)),
+        "patterns should be shared"
+    );
+
+    let best = tree.find_best_match_shared("abc").expect("should match");
+    assert!(Arc::ptr_eq(&first[0], &best), "best should be shared");
+    assert_eq!(
+        tree.find_best_match("abz").as_deref(),
+        tree.find_best_match_shared("abz").as_deref()
+    );
+    assert_eq!(None, tree.find_best_match_shared("nothing"));
+}
+
+/// Test scanning a whole text, line by line
+#[test]
+fn test_scan_text() {
+    let patterns = vec![
+        "GET /.*".to_string(),
+        "GET /health".to_string(),
+        "POST /api/[a-z]+".to_string(),
+    ];
+    let tree = RegexTrie::from(&patterns).expect("can't init regex trie");
+
+    let text = "GET /health\r\nPUT /nothing\nPOST /api/users\n\nGET /index.html\n";
+    let result = tree.scan_text(text);
+    assert_eq!(
+        vec![0, 2, 4],
+        result.iter().map(|line| line.line).collect::<Vec<_>>()
+    );
+    for line_matches in &result {
+        let line = text
+            .lines()
+            .nth(line_matches.line)
+            .expect("line should exist");
+        assert_eq!(
+            tree.find_matches_shared(line),
+            line_matches.matches,
+            "different matches for {line}"
+        );
+    }
+    assert!(tree.scan_text("").is_empty());
+}
+
+/// Test the prefix match mode
+#[test]
+fn test_prefix_match_mode() {
+    let patterns = vec![
+        r"https://google\.com/user/".to_string(),
+        r"https://google\.com/".to_string(),
+        "https://google.com/user/[0-9]+/".to_string(),
+        "https://google.com/admin".to_string(),
+        "https://yahoo.com/.+".to_string(),
+    ];
+    let tree = RegexTrie::from(&patterns)
+        .expect("can't init regex trie")
+        .with_match_mode(MatchMode::Prefix);
+    assert_eq!(MatchMode::Prefix, tree.match_mode());
+
+    assert_eq!(
+        vec![
+            r"https://google\.com/",
+            r"https://google\.com/user/",
+            "https://google.com/user/[0-9]+/",
+        ],
+        tree.find_matches("https://google.com/user/42/profile")
+    );
+    assert_eq!(
+        Some(r"https://google\.com/".to_string()),
+        tree.find_best_match("https://google.com/user/42/profile")
+    );
+    assert_eq!(
+        tree.find_best_match("https://google.com/user/42/profile"),
+        tree.explain("https://google.com/user/42/profile").best
+    );
+    assert_eq!(
+        vec!["https://yahoo.com/.+"],
+        tree.find_matches("https://yahoo.com/a")
+    );
+    assert!(tree.find_matches("https://yahoo.com/").is_empty());
+    assert!(tree.find_matches("https://google.co").is_empty());
+
+    // The default mode still requires full matches
+    let tree = RegexTrie::from(&patterns).expect("can't init regex trie");
+    assert_eq!(MatchMode::Full, tree.match_mode());
+    assert!(
+        tree.find_matches("https://google.com/user/42/profile")
+            .is_empty()
+    );
+}
+
+/// Test the longest match selection policy ignores the scorer
+#[test]
+fn test_longest_match_policy() {
+    let patterns = vec![
+        "/api/.*".to_string(),
+        "/api/users/[0-9]+".to_string(),
+        "/.*".to_string(),
+        "/api/users/42".to_string(),
+    ];
+    let tree = RegexTrie::from(&patterns)
+        .expect("can't init regex trie")
+        .with_selection_policy(SelectionPolicy::LongestMatch);
+    assert_eq!(SelectionPolicy::LongestMatch, tree.selection_policy());
+
+    // Longest literal prefix wins, the exact literal being the longest
+    assert_eq!(
+        Some("/api/users/[0-9]+".to_string()),
+        tree.find_best_match("/api/users/7")
+    );
+    assert_eq!(
+        Some("/api/users/42".to_string()),
+        tree.find_best_match("/api/users/42")
+    );
+    assert_eq!(
+        Some("/api/.*".to_string()),
+        tree.find_best_match("/api/other")
+    );
+    assert_eq!(
+        tree.find_best_match("/api/users/7"),
+        tree.explain("/api/users/7").best
+    );
+
+    // In prefix mode, the longest match wins first
+    let patterns = vec![
+        "/static/[a-z]+".to_string(),
+        "/static/[a-z]+/[a-z]+\\.css".to_string(),
+    ];
+    let tree = RegexTrie::from(&patterns)
+        .expect("can't init regex trie")
+        .with_match_mode(MatchMode::Prefix)
+        .with_selection_policy(SelectionPolicy::LongestMatch);
+    assert_eq!(
+        Some("/static/[a-z]+/[a-z]+\\.css".to_string()),
+        tree.find_best_match("/static/theme/main.css?v=2")
+    );
+
+    // The default policy keeps the lowest score
+    let tree = RegexTrie::from(&patterns).expect("can't init regex trie");
+    assert_eq!(SelectionPolicy::LowestScore, tree.selection_policy());
+}
+
+/// Test whether inputs could be completed into a match
+#[test]
+fn test_input_could_extend_to_match() {
+    let patterns = vec![
+        "https://www\\.google\\.com/user/".to_string(),
+        "https://[a-z]+\\.org/[0-9]+".to_string(),
+        "ftp".to_string(),
+    ];
+    let tree = RegexTrie::from(&patterns).expect("can't init regex trie");
+
+    for input in [
+        "",
+        "h",
+        "https://www.goo",
+        "https://www.google.com/user/",
+        "https://wikipedia",
+        "https://wikipedia.org/12",
+        "ft",
+        "ftp",
+    ] {
+        assert!(
+            tree.input_could_extend_to_match(input),
+            "{input:?} should extend"
+        );
+    }
+    for input in [
+        "x",
+        "https://www.google.com/user/x",
+        "https://wikipedia.org/12a",
+        "https://WIKI",
+        "ftp:",
+    ] {
+        assert!(
+            !tree.input_could_extend_to_match(input),
+            "{input:?} shouldn't extend"
+        );
+    }
+
+    // In prefix mode, anything after a match extends it
+    let tree = tree.with_match_mode(MatchMode::Prefix);
+    assert!(tree.input_could_extend_to_match("ftp://anything"));
+    assert!(tree.input_could_extend_to_match("https://wikipedia.org/12a"));
+    assert!(!tree.input_could_extend_to_match("https://WIKI"));
+}
+
+#[test]
+fn test_walk() {
+    let tree = RegexTrie::from(&[
+        "ab.*".to_string(),
+        "a".to_string(),
+        "[0-9]+".to_string(),
+        r"a\.c".to_string(),
+        "ab".to_string(),
+    ])
+    .expect("can't build trie");
+    let id = |pattern: &str| tree.id_of(pattern).expect("unknown pattern");
+
+    let mut nodes = Vec::new();
+    tree.walk(|prefix, ids, depth| nodes.push((prefix.to_string(), ids.to_vec(), depth)));
+    assert_eq!(
+        nodes,
+        vec![
+            (String::new(), vec![id("[0-9]+")], 0),
+            ("a".to_string(), vec![id("a")], 1),
+            (r"a\.".to_string(), vec![], 2),
+            (r"a\.c".to_string(), vec![id(r"a\.c")], 3),
+            ("ab".to_string(), vec![id("ab"), id("ab.*")], 2),
+        ]
+    );
+
+    let mut count = 0;
+    RegexTrie::new().walk(|prefix, ids, depth| {
+        assert!(prefix.is_empty() && ids.is_empty() && depth == 0);
+        count += 1;
+    });
+    assert_eq!(count, 1);
+}
+
+#[test]
+fn test_diff() {
+    let tree = RegexTrie::from(&[
+        "a.*".to_string(),
+        "plain".to_string(),
+        "b[0-9]+".to_string(),
+    ])
+    .expect("can't init regex trie");
+    assert!(tree.diff(&tree).is_empty());
+    assert_eq!(tree.diff(&tree).to_string(), "");
+
+    let other = RegexTrie::from_with_scorer(
+        &[
+            "c".to_string(),
+            "b[0-9]+".to_string(),
+            "plain".to_string(),
+            "d.*".to_string(),
+        ],
+        Box::new(|pattern, is_regex| if is_regex { pattern.len() } else { 100 }),
+    )
+    .expect("can't init regex trie");
+    let diff = tree.diff(&other);
+    assert_eq!(diff.only_in_self, vec!["a.*".to_string()]);
+    assert_eq!(diff.only_in_other, vec!["c".to_string(), "d.*".to_string()]);
+    let old_score = tree
+        .iter()
+        .find(|entry| entry.pattern == "plain")
+        .expect("missing pattern")
+        .score;
+    assert_eq!(
+        diff.changed,
+        vec![ScoreChange {
+            pattern: "plain".to_string(),
+            old_score,
+            new_score: 100,
+        }]
+    );
+    assert_eq!(
+        diff.to_string(),
+        format!("- \"a.*\"\n+ \"c\"\n+ \"d.*\"\n~ \"plain\": score {old_score} -> 100\n")
+    );
+
+    // Reversed
+    let diff = other.diff(&tree);
+    assert_eq!(diff.only_in_self, vec!["c".to_string(), "d.*".to_string()]);
+    assert_eq!(diff.only_in_other, vec!["a.*".to_string()]);
+    assert_eq!(diff.changed[0].old_score, 100);
+}
+
+#[test]
+fn test_intersection_difference() {
+    let tree = RegexTrie::from_with_scorer(
+        &[
+            "a.*".to_string(),
+            "plain".to_string(),
+            "b[0-9]+".to_string(),
+        ],
+        Box::new(|pattern, _| pattern.len()),
+    )
+    .expect("can't init regex trie")
+    .with_match_mode(MatchMode::Prefix);
+    let other = RegexTrie::from(&["b[0-9]+".to_string(), "c".to_string(), "plain".to_string()])
+        .expect("can't init regex trie");
+
+    let intersection = tree.intersection(&other);
+    intersection.self_check().expect("broken trie");
+    let patterns: Vec<_> = intersection.iter().map(|entry| entry.pattern).collect();
+    assert_eq!(patterns, vec!["plain", "b[0-9]+"]);
+    assert_eq!(intersection.match_mode(), MatchMode::Prefix);
+    // Scores and scorer are the ones of self
+    assert_eq!(
+        intersection.find_matches_with_scores("plain!"),
+        vec![("plain".to_string(), 5)]
+    );
+    assert_eq!(
+        intersection.find_matches_with_scores("b12c"),
+        vec![("b[0-9]+".to_string(), 7)]
+    );
+
+    let difference = tree.difference(&other);
+    difference.self_check().expect("broken trie");
+    assert_eq!(difference.find_matches("abc"), vec!["a.*"]);
+    assert_eq!(difference.find_matches("plain"), vec!["a.*"; 0]);
+    assert_eq!(difference.iter().count(), 1);
+
+    let difference = other.difference(&tree);
+    assert_eq!(difference.find_matches("c"), vec!["c"]);
+    assert!(tree.intersection(&RegexTrie::new()).iter().next().is_none());
+    assert_eq!(tree.difference(&RegexTrie::new()), tree);
+}
+
+#[test]
+fn test_find_matches_grouped() {
+    let mut tree = RegexTrie::new();
+    assert!(tree.find_matches_grouped("/ads/1").is_empty());
+    tree.insert_tagged("/ads/.*", "blocklist")
+        .expect("can't insert");
+    tree.insert_tagged("/ads/[0-9]+", "analytics")
+        .expect("can't insert");
+    tree.insert_tagged("/ads/1", "analytics")
+        .expect("can't insert");
+    tree.insert_tagged("/ads/1", "blocklist")
+        .expect("can't insert");
+    tree.insert_tagged("/ads/1", "blocklist")
+        .expect("can't insert");
+    tree.insert("/.*").expect("can't insert");
+    tree.insert_tagged("/a[", "broken")
+        .expect_err("invalid pattern inserted");
+
+    let id = |pattern: &str| tree.id_of(pattern).expect("unknown pattern");
+    assert_eq!(
+        tree.tags(id("/ads/1")),
+        [Arc::from("analytics"), Arc::from("blocklist")]
+    );
+    assert!(tree.tags(id("/.*")).is_empty());
+
+    let groups = tree.find_matches_grouped("/ads/1");
+    assert_eq!(groups.len(), 2);
+    assert_eq!(groups["analytics"], vec![id("/ads/1"), id("/ads/[0-9]+")]);
+    assert_eq!(groups["blocklist"], vec![id("/ads/1"), id("/ads/.*")]);
+
+    let groups = tree.find_matches_grouped("/ads/x");
+    assert_eq!(groups.len(), 1);
+    assert_eq!(groups["blocklist"], vec![id("/ads/.*")]);
+    assert!(tree.find_matches_grouped("/other").is_empty());
+
+    // Tags follow the patterns in derived tries
+    let derived = tree.difference(&RegexTrie::from(&["/ads/.*".to_string()]).expect("can't init"));
+    let derived_id = derived.id_of("/ads/1").expect("unknown pattern");
+    assert_eq!(derived.tags(derived_id), tree.tags(id("/ads/1")));
+    assert!(
+        !derived
+            .find_matches_grouped("/ads/x")
+            .contains_key("blocklist")
+    );
+}
+
+#[test]
+fn test_tie_break() {
+    let patterns = vec![
+        "pool-[ab]".to_string(),
+        "pool-[a-c]".to_string(),
+        "pool-.".to_string(),
+        "pool-a+".to_string(),
+    ];
+    let scorer = || -> crate::ScorerFuncType { Box::new(|_, _| 1) };
+    let picks = |tree: &RegexTrie, count: usize| -> Vec<String> {
+        (0..count)
+            .map(|_| tree.find_best_match("pool-a").expect("no match"))
+            .collect()
+    };
+
+    // By default, the first one wins
+    let tree = RegexTrie::from_with_scorer(&patterns, scorer()).expect("can't init regex trie");
+    assert_eq!(tree.tie_break(), TieBreak::First);
+    assert_eq!(picks(&tree, 3), vec!["pool-[ab]"; 3]);
+
+    // Weighted round robin
+    let mut tree = RegexTrie::from_with_scorer(&patterns, scorer())
+        .expect("can't init regex trie")
+        .with_tie_break(TieBreak::RoundRobin);
+    let id = |tree: &RegexTrie, pattern: &str| tree.id_of(pattern).expect("unknown pattern");
+    assert!(tree.set_weight(id(&tree, "pool-[a-c]"), 2));
+    assert!(tree.set_weight(id(&tree, "pool-."), 0));
+    assert!(!tree.set_weight(PatternId::new(42), 2));
+    assert_eq!(tree.weight(id(&tree, "pool-[a-c]")), 2);
+    assert_eq!(tree.weight(id(&tree, "pool-a+")), 1);
+    assert_eq!(
+        picks(&tree, 8),
+        vec![
+            "pool-[ab]",
+            "pool-[a-c]",
+            "pool-[a-c]",
+            "pool-a+",
+            "pool-[ab]",
+            "pool-[a-c]",
+            "pool-[a-c]",
+            "pool-a+",
+        ]
+    );
+    // No tie, no turn
+    assert_eq!(
+        tree.find_best_match("pool-c"),
+        Some("pool-[a-c]".to_string())
+    );
+    assert_eq!(tree.find_best_match("pool-d"), Some("pool-.".to_string()));
+
+    // Weighted random, never picking a pattern of weight 0
+    let tree = tree.with_tie_break(TieBreak::WeightedRandom);
+    let picked = picks(&tree, 200);
+    for pattern in ["pool-[ab]", "pool-[a-c]", "pool-a+"] {
+        assert!(
+            picked.iter().any(|pick| pick == pattern),
+            "{pattern} never picked"
+        );
+    }
+    assert!(!picked.iter().any(|pick| pick == "pool-."));
+
+    // Patterns of weight 0 are picked if there's nothing else
+    let mut tree = RegexTrie::from_with_scorer(&patterns[..2], scorer())
+        .expect("can't init regex trie")
+        .with_tie_break(TieBreak::WeightedRandom);
+    for pattern in &patterns[..2] {
+        tree.set_weight(id(&tree, pattern), 0);
+    }
+    assert_eq!(picks(&tree, 3), vec!["pool-[ab]"; 3]);
+}
+
+#[test]
+fn test_hit_counts() {
+    let patterns = vec![
+        "/api/.*".to_string(),
+        "/health".to_string(),
+        "/never/[0-9]+".to_string(),
+    ];
+    let tree = RegexTrie::from(&patterns).expect("can't init regex trie");
+    assert!(tree.hit_counts().is_empty());
+    assert_eq!(tree.find_matches("/api/users").len(), 1);
+
+    let mut tree = tree.with_hit_counting(true);
+    assert_eq!(tree.find_matches("/api/users").len(), 1);
+    assert!(tree.find_best_match("/api/health").is_some());
+    assert_eq!(tree.find_matches("/health").len(), 1);
+    assert!(tree.find_matches("/other").is_empty());
+    tree.insert("/late").expect("can't insert");
+    assert_eq!(tree.scan_text("/late\n/late\n/nope").len(), 2);
+    let id = |pattern: &str| tree.id_of(pattern).expect("unknown pattern");
+    assert_eq!(
+        tree.hit_counts(),
+        vec![
+            (id("/api/.*"), 2),
+            (id("/health"), 1),
+            (id("/never/[0-9]+"), 0),
+            (id("/late"), 2),
+        ]
+    );
+
+    tree.reset_hit_counts();
+    assert!(tree.hit_counts().iter().all(|&(_, count)| count == 0));
+    let tree = tree.with_hit_counting(false);
+    assert_eq!(tree.find_matches("/health").len(), 1);
+    assert!(tree.hit_counts().is_empty());
+}
+
+#[test]
+fn test_match_stats() {
+    let patterns = vec![
+        "/api/.*".to_string(),
+        "/api/[a-z]+".to_string(),
+        "/health".to_string(),
+    ];
+    let tree = RegexTrie::from(&patterns).expect("can't init regex trie");
+    assert_eq!(tree.stats(), None);
+
+    let tree = tree.with_hit_counting(true);
+    let stats = tree.stats().expect("counting enabled");
+    assert_eq!((stats.queries, stats.average_candidates), (0, 0.0));
+
+    assert_eq!(tree.find_matches("/api/users").len(), 2);
+    assert_eq!(tree.find_matches("/health").len(), 1);
+    assert_eq!(tree.find_matches("/api/1").len(), 1);
+    assert!(tree.find_best_match("/other").is_none());
+    let stats = tree.stats().expect("counting enabled");
+    assert_eq!(stats.queries, 4);
+    assert_eq!(stats.queries_without_match, 1);
+    // 2 regex candidates for each /api/ query
+    assert!((stats.average_candidates - 1.0).abs() < f64::EPSILON);
+    assert_eq!(
+        stats.matches,
+        vec![
+            ("/api/.*".to_string(), 2),
+            ("/api/[a-z]+".to_string(), 1),
+            ("/health".to_string(), 1),
+        ]
+    );
+
+    tree.reset_hit_counts();
+    let stats = tree.stats().expect("counting enabled");
+    assert_eq!((stats.queries, stats.queries_without_match), (0, 0));
+}
+
+#[test]
+fn test_remove_with_prefix() {
+    let patterns = vec![
+        "https://old.com/.*".to_string(),
+        r"https://old\.com/[a-z]+".to_string(),
+        r"https://old\.com/home".to_string(),
+        r"https://old\.co".to_string(),
+        "https://new.com/.*".to_string(),
+        "[a-z]+".to_string(),
+    ];
+    let mut tree = RegexTrie::from(&patterns)
+        .expect("can't init regex trie")
+        .with_hit_counting(true);
+    let id = |tree: &RegexTrie, pattern: &str| tree.id_of(pattern);
+    let new_id = id(&tree, "https://new.com/.*");
+    tree.insert_tagged(r"https://old\.com/home", "old")
+        .expect("can't insert");
+
+    assert_eq!(tree.remove_with_prefix("https://old.com/"), 2);
+    tree.self_check().expect("broken trie");
+    assert_eq!(id(&tree, r"https://old\.com/home"), None);
+    assert_eq!(id(&tree, r"https://old\.com/[a-z]+"), None);
+    assert_eq!(id(&tree, "https://new.com/.*"), new_id);
+    assert_eq!(tree.iter().count(), 4);
+    assert_eq!(
+        tree.find_matches("https://old.com/home"),
+        vec!["https://old.com/.*"]
+    );
+    assert_eq!(
+        tree.find_matches("https://old.co"),
+        vec![r"https://old\.co"]
+    );
+    assert_eq!(tree.hit_counts().len(), 4);
+    assert!(tree.find_matches_grouped("https://old.com/home").is_empty());
+
+    // Nothing under the prefix
+    assert_eq!(tree.remove_with_prefix("https://old.com/"), 0);
+    assert_eq!(tree.remove_with_prefix("ftp"), 0);
+
+    // Ids are kept by a serialization round trip
+    let loaded = RegexTrie::from_bytes(&tree.to_bytes()).expect("can't load trie");
+    loaded.self_check().expect("broken trie");
+    assert_eq!(loaded, tree);
+    assert_eq!(id(&loaded, "https://new.com/.*"), new_id);
+
+    // A removed pattern can be inserted again, with a new id
+    tree.insert(r"https://old\.com/home").expect("can't insert");
+    tree.self_check().expect("broken trie");
+    assert_eq!(
+        id(&tree, r"https://old\.com/home"),
+        Some(PatternId::new(patterns.len()))
+    );
+
+    assert_eq!(tree.remove_with_prefix(""), 5);
+    tree.self_check().expect("broken trie");
+    assert!(tree.find_matches("abc").is_empty());
+    assert_eq!(tree.iter().count(), 0);
+
+    // Case-insensitive patterns are stored higher in the trie, but selected
+    // by their whole literal prefix
+    let case_insensitive = InsertOptions {
+        case_insensitive: true,
+    };
+    tree.insert_with_options("/old/Home", case_insensitive)
+        .expect("can't insert");
+    tree.insert_with_options("/old/[a-z]+", case_insensitive)
+        .expect("can't insert");
+    tree.insert_with_options("/older", case_insensitive)
+        .expect("can't insert");
+    tree.insert("/old/static").expect("can't insert");
+    assert_eq!(tree.remove_with_prefix("/old/"), 3);
+    tree.self_check().expect("broken trie");
+    assert!(tree.find_matches("/OLD/home").is_empty());
+    assert_eq!(tree.find_matches("/OLDER"), vec!["/older"]);
+    assert_eq!(tree.iter().count(), 1);
+}
+
+#[test]
+fn test_compact() {
+    let patterns = vec![
+        "a[0-9]+".to_string(),
+        "b[0-9]+".to_string(),
+        "b".to_string(),
+        "c[0-9]+".to_string(),
+        "d.*".to_string(),
+    ];
+    let mut tree = RegexTrie::from(&patterns).expect("can't init regex trie");
+    assert_eq!(tree.compact(), CompactReport::default());
+
+    assert_eq!(tree.remove_with_prefix("b"), 2);
+    assert_eq!(tree.remove_with_prefix("d"), 1);
+    let ids: Vec<_> = tree.iter().map(|entry| entry.id).collect();
+    let report = tree.compact();
+    assert_eq!(report.freed_dfas, 2);
+    assert!(report.freed_bytes > 0);
+    assert_eq!(tree.compiled_patterns.len(), 2);
+    tree.self_check().expect("broken trie");
+
+    assert_eq!(tree.iter().map(|entry| entry.id).collect::<Vec<_>>(), ids);
+    assert_eq!(tree.find_matches("c12"), vec!["c[0-9]+"]);
+    assert_eq!(tree.find_matches("a1"), vec!["a[0-9]+"]);
+    assert!(tree.find_matches("b1").is_empty());
+    assert_eq!(tree.compact(), CompactReport::default());
+
+    tree.insert("e[0-9]+").expect("can't insert");
+    tree.self_check().expect("broken trie");
+    assert_eq!(tree.find_matches("e1"), vec!["e[0-9]+"]);
+}
+
+#[test]
+fn test_regex_dedup() {
+    let patterns = vec![
+        "ab[0-9]+".to_string(),
+        "(?:ab)[0-9]+".to_string(),
+        "ab[0-9]{1,}".to_string(),
+        "(ab)[0-9]+".to_string(),
+        "a{1}b[0-9]+".to_string(),
+        "ab".to_string(),
+    ];
+    let tree = RegexTrie::from(&patterns).expect("can't init regex trie");
+    assert_eq!(tree.find_matches("ab1").len(), 5);
+
+    let mut tree = RegexTrie::new().with_regex_dedup(true);
+    tree.insert_many(&patterns).expect("can't insert");
+    tree.self_check().expect("broken trie");
+    // Capture groups aren't equivalent to plain ones
+    assert_eq!(tree.find_matches("ab1"), vec!["ab[0-9]+", "(ab)[0-9]+"]);
+    assert_eq!(tree.id_of("(?:ab)[0-9]+"), None);
+    tree.insert("ab[[:digit:]]+").expect("can't insert");
+    assert_eq!(tree.iter().count(), 3);
+    tree.insert("ab[").expect_err("invalid pattern inserted");
+
+    // Removed regexes can be inserted again with another spelling
+    assert_eq!(tree.remove_with_prefix("ab"), 2);
+    tree.insert("a{1}b[0-9]+").expect("can't insert");
+    assert_eq!(tree.find_matches("ab1"), vec!["(ab)[0-9]+", "a{1}b[0-9]+"]);
+
+    // Enabled afterward, or on derived tries
+    let tree = RegexTrie::from(&patterns[..1])
+        .expect("can't init regex trie")
+        .with_regex_dedup(true);
+    let mut derived = tree.difference(&RegexTrie::new());
+    derived.insert("ab[0-9]{1,}").expect("can't insert");
+    assert_eq!(derived.iter().count(), 1);
+}
+
+#[test]
+fn test_freeze() {
+    let mut patterns = Vec::new();
+    for domain in ["alpha", "beta", "gamma"] {
+        for path in ["/index", "/login", "/api/v1"] {
+            patterns.push(format!("{domain}{path}"));
+        }
+    }
+    patterns.push("beta/api/v[0-9]+".to_string());
+    patterns.push(".*/logout".to_string());
+    let tree = RegexTrie::from(&patterns).expect("can't init regex trie");
+    let mut node_count = 0;
+    tree.walk(|_, _, _| node_count += 1);
+
+    let inputs = [
+        "alpha/index",
+        "beta/api/v1",
+        "beta/api/v2",
+        "gamma/login",
+        "gamma/logout",
+        "gamma/api",
+        "delta/index",
+        "",
+    ];
+    let expected: Vec<_> = inputs
+        .iter()
+        .map(|input| (tree.find_matches(input), tree.find_best_match(input)))
+        .collect();
+
+    let frozen: FrozenRegexTrie = tree.freeze();
+    // The paths are stored once per distinct subtree: "alpha" and "gamma"
+    // share theirs, "beta" holds a regex.
+    assert!(frozen.node_count() < node_count);
+    for (input, (matches, best_match)) in inputs.iter().zip(expected) {
+        assert_eq!(frozen.find_matches(input), matches, "input {input:?}");
+        assert_eq!(frozen.find_best_match(input), best_match, "input {input:?}");
+    }
+    assert_eq!(
+        frozen
+            .id_of("gamma/login")
+            .and_then(|id| frozen.pattern(id)),
+        Some("gamma/login")
+    );
+
+    let prefixes = RegexTrie::from(&["ab".to_string(), "abc".to_string()])
+        .expect("can't init regex trie")
+        .with_match_mode(MatchMode::Prefix)
+        .freeze();
+    assert_eq!(prefixes.find_matches("abcd"), vec!["ab", "abc"]);
+    assert_eq!(
+        RegexTrie::new().freeze().find_matches("a"),
+        Vec::<String>::new()
+    );
+}
+
+#[test]
+fn test_insert_case_insensitive() {
+    let ignore_case = InsertOptions {
+        case_insensitive: true,
+    };
+    let mut tree = RegexTrie::from(&["/api/users".to_string(), "/api/v[0-9]+".to_string()])
+        .expect("can't init regex trie");
+    tree.insert_with_options("/api/Admin", ignore_case)
+        .expect("can't insert");
+    tree.insert_with_options("/API/v[0-9]+/items", ignore_case)
+        .expect("can't insert");
+    tree.insert_with_options("/api/users", ignore_case)
+        .expect("can't insert");
+    tree.insert_with_options("[", ignore_case)
+        .expect_err("invalid pattern inserted");
+    tree.self_check().expect("broken trie");
+
+    assert_eq!(tree.find_matches("/api/admin"), vec!["/api/Admin"]);
+    assert_eq!(tree.find_matches("/API/ADMIN"), vec!["/api/Admin"]);
+    assert_eq!(
+        tree.find_matches("/api/v2/Items"),
+        vec!["/API/v[0-9]+/items"]
+    );
+    assert_eq!(tree.find_matches("/api/V2"), Vec::<String>::new());
+    // Already known, so still case-sensitive
+    assert_eq!(tree.find_matches("/API/USERS"), Vec::<String>::new());
+    // A plain pattern keeps its plain score
+    tree.insert("/api/[a-z]+").expect("can't insert");
+    assert_eq!(
+        tree.find_best_match("/api/admin").as_deref(),
+        Some("/api/Admin")
+    );
+    assert_eq!(tree.find_matches("/api/Admin"), vec!["/api/Admin"]);
+
+    // Kept through serialization and derived tries
+    let loaded = RegexTrie::from_bytes(&tree.to_bytes()).expect("can't load trie");
+    loaded.self_check().expect("broken trie");
+    assert_eq!(loaded.find_matches("/API/ADMIN"), vec!["/api/Admin"]);
+    assert_eq!(loaded, tree);
+    let derived = tree.difference(&RegexTrie::new());
+    assert_eq!(derived.find_matches("/API/ADMIN"), vec!["/api/Admin"]);
+    let mut case_sensitive = RegexTrie::new();
+    case_sensitive.insert("/api/Admin").expect("can't insert");
+    assert_ne!(case_sensitive, tree.intersection(&case_sensitive));
+
+    // Equivalent only if both ignore case
+    let mut tree = RegexTrie::new().with_regex_dedup(true);
+    tree.insert("ab[0-9]").expect("can't insert");
+    tree.insert_with_options("(?:ab)[0-9]", ignore_case)
+        .expect("can't insert");
+    tree.insert_with_options("a{1}b[0-9]", ignore_case)
+        .expect("can't insert");
+    assert_eq!(tree.find_matches("AB1"), vec!["(?:ab)[0-9]"]);
+    assert_eq!(tree.find_matches("ab1"), vec!["ab[0-9]", "(?:ab)[0-9]"]);
+}
+
+#[test]
+fn test_posix_ere() {
+    let translate = |pattern| RegexTrie::translate_posix_ere(pattern).expect("invalid ERE");
+    assert_eq!(translate("^/api/[[:digit:]]+"), "/api/[[:digit:]]+");
+    assert_eq!(translate("a[]b-]c"), r"a[\]b\-]c");
+    assert_eq!(translate(r"[^\]x"), r"[^\\]x");
+    assert_eq!(translate("[a-z[=e=][.-.]]"), r"[a-ze\-]");
+    assert_eq!(translate(r"a\.b\|c"), r"a\.b|c");
+    assert_eq!(translate(r"a\|b.*"), r"a[\|]b.*");
+    assert_eq!(translate("gif|png$"), "(?:gif|png$)");
+    assert_eq!(translate("a(b|c)"), "a(b|c)");
+    for pattern in ["[abc", "a\\", "[[:word:]]", "[[.ab.]]", "[[:alpha"] {
+        RegexTrie::translate_posix_ere(pattern).expect_err("invalid ERE translated");
+    }
+    for (pattern, reason) in [
+        (r"(a)\1", "backreferences"),
+        (r"a\w", r"undefined escape \w"),
+    ] {
+        let err = RegexTrie::translate_posix_ere(pattern).expect_err("invalid ERE translated");
+        assert!(err.to_string().ends_with(reason), "{pattern:?}: {err}");
+    }
+
+    let mut tree = RegexTrie::new();
+    for pattern in ["^/img/[[:alnum:]_]+\\.(gif|png)$", r"/a\|b", "/login"] {
+        tree.insert_posix_ere(pattern).expect("can't insert");
+    }
+    tree.insert_posix_ere("[[:foo:]]")
+        .expect_err("invalid ERE inserted");
+    tree.self_check().expect("broken trie");
+    assert_eq!(
+        tree.find_matches("/img/logo_2.png"),
+        vec![r"/img/(?:[[:alnum:]_]+\.(gif|png)$)"]
+    );
+    assert_eq!(tree.find_matches("/img/logo.jpg"), Vec::<String>::new());
+
+    // Anchored rules keep their literal prefix
+    for (pattern, translated, prefix) in [
+        (
+            "^/api/users/[0-9]+$",
+            "/api/users/(?:[0-9]+$)",
+            "/api/users/",
+        ),
+        ("/api/x$", "/api/x(?:$)", "/api/x"),
+        ("/api/x*$", "/api/(?:x*$)", "/api/"),
+        ("/a^b", "/a(?:^b)", "/a"),
+    ] {
+        assert_eq!(translate(pattern), translated);
+        assert_eq!(RegexTrie::literal_prefix(translated), prefix);
+        tree.insert_posix_ere(pattern).expect("can't insert");
+    }
+    assert_eq!(
+        tree.find_matches("/api/x"),
+        vec!["/api/x(?:$)", "/api/(?:x*$)"]
+    );
+    tree.self_check().expect("broken trie");
+    assert_eq!(tree.find_matches("/a|b"), vec!["/a|b"]);
+    assert_eq!(tree.find_matches("/login"), vec!["/login"]);
+}
+
+#[test]
+fn test_pcre() {
+    let translate = |pattern| RegexTrie::translate_pcre(pattern).expect("unsupported PCRE");
+    assert_eq!(
+        translate(r"\A/api/v[0-9]++/.*?\z"),
+        r"/api/v(?:[0-9]+/.*?$)"
+    );
+    assert_eq!(
+        translate("^/a(?>b|c)*+(?#comment)d{2,3}+"),
+        "/a(?:b|c)*d{2,3}"
+    );
+    assert_eq!(translate(r"a\+\++x?+y??"), r"a\+\++x?y??");
+    assert_eq!(translate(r"\Q/a.b\E[]x]"), r"/a\.b[\]x]");
+    assert_eq!(
+        translate(r"(?i)(?<name>a)(?-i:b)\x{41}+{"),
+        r"(?:(?i)(?<name>a)(?-i:b)\x{41}+\{)"
+    );
+    assert_eq!(translate("a{"), r"a\{");
+    assert_eq!(translate(r"id\d"), r"id(?:\d)");
+    assert_eq!(translate(r"\Qa-b\E"), r"a(?:\-b)");
+    assert_eq!(translate(r"end\Z"), r"end(?:\n?$)");
+    // Anchored rules keep their literal prefix
+    for (pattern, prefix) in [
+        (r"/api/x\z", "/api/x"),
+        (r"\A/api/users/\d+\z", "/api/users/"),
+        (r"/a|/b\z", ""),
+    ] {
+        assert_eq!(RegexTrie::literal_prefix(&translate(pattern)), prefix);
+    }
+    for (pattern, reason) in [
+        ("a(?=b)", "look-around"),
+        ("(?<!a)b", "look-around"),
+        (r"(a)\1", "backreferences"),
+        ("(?P=name)", "backreferences"),
+        ("(a(?R)?)", "recursion"),
+        ("(?(1)a|b)", "conditionals"),
+        (r"a\Kb", r"\K"),
+        ("(*FAIL)", "backtracking verbs"),
+        ("a(?#comment", "unterminated comment"),
+    ] {
+        let err = RegexTrie::translate_pcre(pattern).expect_err("unsupported PCRE translated");
+        assert!(err.to_string().ends_with(reason), "{pattern:?}: {err}");
+    }
+
+    let mut tree = RegexTrie::new();
+    tree.insert_pcre(r"\A/img/\w++\.(?:gif|png)\z")
+        .expect("can't insert");
+    tree.insert_pcre("/login").expect("can't insert");
+    tree.insert_pcre(r"/id/\d").expect("can't insert");
+    tree.insert_pcre(r"/[a-z]\s\w").expect("can't insert");
+    tree.insert_pcre("/a{").expect("can't insert");
+    tree.insert_pcre("a(?=b)")
+        .expect_err("unsupported PCRE inserted");
+    tree.self_check().expect("broken trie");
+    assert_eq!(
+        tree.find_matches("/img/logo.png"),
+        vec![r"/img/(?:\w+\.(?:gif|png)$)"]
+    );
+    assert_eq!(tree.find_matches("/login"), vec!["/login"]);
+    assert_eq!(tree.find_matches("/id/7"), vec![r"/id/(?:\d)"]);
+    assert_eq!(tree.find_matches("/x y"), vec![r"/(?:[a-z]\s\w)"]);
+    assert_eq!(tree.find_matches("/a{"), vec![r"/a\{"]);
+}
+
+#[test]
+fn test_dfa_budget() {
+    let patterns = ["/a/[0-9]+".to_string(), "/b/[a-z]+".to_string()];
+    let unlimited = RegexTrie::from(&patterns).expect("can't init regex trie");
+    let budget = unlimited.total_dfa_bytes();
+    assert!(budget > 0);
+
+    let mut tree = RegexTrie::new().with_max_total_dfa_bytes(budget);
+    tree.insert_many(&patterns).expect("can't insert");
+    // Known patterns and plain ones don't need any DFA
+    tree.insert("/a/[0-9]+").expect("can't insert");
+    tree.insert("/c/plain").expect("can't insert");
+    let err = tree.insert("/d/.*").expect_err("budget exceeded");
+    assert!(matches!(
+        err,
+        RegexTrieError::BudgetExceeded { required, budget: limit } if required > limit && limit == budget
+    ));
+    tree.insert_with_options(
+        "/e",
+        InsertOptions {
+            case_insensitive: true,
+        },
+    )
+    .expect_err("budget exceeded");
+    assert_eq!(tree.iter().count(), 3);
+    assert_eq!(tree.total_dfa_bytes(), budget);
+
+    // Removed DFAs count until compacted
+    assert_eq!(tree.remove_with_prefix("/b/"), 1);
+    tree.insert("/b/[a-z]+").expect_err("budget exceeded");
+    tree.compact();
+    tree.insert("/b/[a-z]+").expect("can't insert");
+    tree.self_check().expect("broken trie");
+}
+
+#[test]
+fn test_dfa_size_warning() {
+    let small = RegexTrie::from(&["/a/[0-9]+".to_string()]).expect("can't init regex trie");
+    let threshold = small.total_dfa_bytes();
+
+    let warnings = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let sink = Arc::clone(&warnings);
+    let mut tree = RegexTrie::new()
+        .with_hit_counting(true)
+        .with_dfa_size_warning(
+            threshold,
+            Box::new(move |pattern, bytes| {
+                sink.lock()
+                    .expect("poisoned")
+                    .push((pattern.to_string(), bytes));
+            }),
+        );
+    tree.insert_many(&[
+        "/a/[0-9]+".to_string(),
+        "/b/.*x.{4}".to_string(),
+        "/c/plain".to_string(),
+    ])
+    .expect("can't insert");
+
+    let large_id = tree.id_of("/b/.*x.{4}").expect("missing pattern");
+    let dfa_bytes = tree.dfa_bytes();
+    assert_eq!(dfa_bytes[0], (PatternId::new(0), threshold));
+    assert_eq!(dfa_bytes[1].0, large_id);
+    assert!(dfa_bytes[1].1 > threshold);
+    assert_eq!(dfa_bytes.len(), 2);
+    assert_eq!(
+        *warnings.lock().expect("poisoned"),
+        vec![("/b/.*x.{4}".to_string(), dfa_bytes[1].1)]
+    );
+    let stats = tree.stats().expect("hit counting enabled");
+    assert_eq!(
+        stats.dfa_bytes,
+        vec![
+            ("/a/[0-9]+".to_string(), threshold),
+            ("/b/.*x.{4}".to_string(), dfa_bytes[1].1),
+        ]
+    );
+
+    // Derived tries don't warn again about the patterns they copy.
+    let _ = tree.intersection(&tree);
+    assert_eq!(warnings.lock().expect("poisoned").len(), 1);
+}
+
+#[test]
+fn test_fallback_engine() {
+    // No dense DFA supports Unicode word boundaries
+    let pattern = r"foo.*\bbar";
+    let mut tree = RegexTrie::new()
+        .with_fallback_engine(true)
+        .with_hit_counting(true);
+    tree.insert(pattern).expect("can't insert");
+    tree.insert("foo[0-9]+").expect("can't insert");
+    let id = tree.id_of(pattern).expect("missing pattern");
+    assert_eq!(tree.engine(id), Some(Engine::Meta));
+    let dfa_id = tree.id_of("foo[0-9]+").expect("missing pattern");
+    assert_eq!(tree.engine(dfa_id), Some(Engine::Dfa));
+
+    assert_eq!(tree.find_matches("foo baz bar"), vec![pattern]);
+    assert!(tree.find_matches("foo bazbar").is_empty());
+    let stats = tree.stats().expect("hit counting enabled");
+    assert_eq!(stats.fallback_patterns, vec![pattern.to_string()]);
+
+    let mut stream = tree.stream();
+    stream.push(b"foo b");
+    stream.push(b"az bar");
+    assert_eq!(stream.finish(), vec![Arc::from(pattern)]);
+
+    let loaded = RegexTrie::from_bytes(&tree.to_bytes()).expect("can't load");
+    assert_eq!(loaded.engine(id), Some(Engine::Meta));
+    assert_eq!(loaded.find_matches("foo baz bar"), vec![pattern]);
+    tree.self_check().expect("broken trie");
+}
+
+#[test]
+fn test_dfa_config() {
+    let config = DfaConfig {
+        minimize: true,
+        byte_classes: false,
+        determinize_size_limit: None,
+    };
+    let mut tree = RegexTrie::new()
+        .with_match_mode(MatchMode::Prefix)
+        .with_dfa_config(config);
+    assert_eq!(tree.dfa_config(), config);
+    tree.insert("/api/v[0-9]+").expect("can't insert");
+    assert_eq!(tree.find_matches("/api/v2/users"), vec!["/api/v[0-9]+"]);
+    assert!(tree.find_matches("/api/users").is_empty());
+    // Both anchored and unanchored searches are supported
+    let tree = tree.with_match_mode(MatchMode::Full);
+    assert_eq!(tree.find_matches("/api/v2"), vec!["/api/v[0-9]+"]);
+    assert!(tree.find_matches("/api/v2/users").is_empty());
+
+    // Too big to be determinized within the limit
+    let config = DfaConfig {
+        determinize_size_limit: Some(1024),
+        ..DfaConfig::default()
+    };
+    let pattern = r"/[a-z]{32}\w{16}";
+    let mut tree = RegexTrie::new().with_dfa_config(config);
+    assert!(matches!(
+        tree.insert(pattern),
+        Err(RegexTrieError::RegexCompilationFailed(_))
+    ));
+    let mut tree = tree.with_fallback_engine(true);
+    tree.insert(pattern).expect("can't insert");
+    let id = tree.id_of(pattern).expect("missing pattern");
+    assert_eq!(tree.engine(id), Some(Engine::Meta));
+}
+
+#[test]
+fn test_extra_specials() {
+    let patterns = vec![
+        "/api/v1|/api/v2".to_string(),
+        "^/health$".to_string(),
+        r"/a\|b".to_string(),
+        "/api/users".to_string(),
+    ];
+    let tree = RegexTrie::from(&patterns).expect("can't init");
+    assert_eq!(
+        tree.find_matches("/api/v1|/api/v2"),
+        vec!["/api/v1|/api/v2"]
+    );
+    assert!(tree.find_matches("/api/v2").is_empty());
+
+    let tree = tree.with_extra_specials("|^$").expect("can't compile");
+    tree.self_check().expect("inconsistent trie");
+    assert_eq!(tree.find_matches("/api/v2"), vec!["/api/v1|/api/v2"]);
+    assert!(tree.find_matches("/api/v1|/api/v2").is_empty());
+    assert_eq!(tree.find_matches("/health"), vec!["^/health$"]);
+    assert_eq!(tree.find_matches("/a|b"), vec![r"/a\|b"]);
+    assert_eq!(tree.find_matches("/api/users"), vec!["/api/users"]);
+    let id = tree.id_of("^/health$").expect("missing pattern");
+    assert_eq!(
+        tree.entry(id).map(|entry| entry.kind),
+        Some(PatternKind::Regex)
+    );
+
+    // Back to the default meta-characters
+    let mut tree = tree.with_extra_specials("").expect("can't compile");
+    tree.self_check().expect("inconsistent trie");
+    assert!(tree.find_matches("/api/v2").is_empty());
+    assert_eq!(tree.remove_with_prefix("/api/v1|"), 1);
+
+    // Not a valid regex
+    let mut tree = RegexTrie::new();
+    tree.insert(r"/\q|").expect("plain pattern");
+    assert!(matches!(
+        tree.with_extra_specials("|"),
+        Err(RegexTrieError::RegexCompilationFailed(_))
+    ));
+}
+
+#[test]
+fn test_word_boundary() {
+    let mut tree = RegexTrie::new();
+    for pattern in [r"foo\b.*", r"\bbar\b", r"baz\B.+", r"qux(?-u:\b).*"] {
+        tree.insert(pattern).expect("can't insert");
+    }
+    assert_eq!(RegexTrie::literal_prefix(r"foo\b.*"), "foo");
+    let id = tree.id_of(r"foo\b.*").expect("missing pattern");
+    assert_eq!(tree.engine(id), Some(Engine::Meta));
+    // ASCII word boundaries are supported by the dense DFAs
+    let id = tree.id_of(r"qux(?-u:\b).*").expect("missing pattern");
+    assert_eq!(tree.engine(id), Some(Engine::Dfa));
+
+    assert_eq!(tree.find_matches("foo bar"), vec![r"foo\b.*"]);
+    assert!(tree.find_matches("foobar").is_empty());
+    assert_eq!(tree.find_matches("bar"), vec![r"\bbar\b"]);
+    assert_eq!(tree.find_matches("bazaar"), vec![r"baz\B.+"]);
+    assert!(tree.find_matches("baz bar").is_empty());
+    assert_eq!(tree.find_matches("qux-1"), vec![r"qux(?-u:\b).*"]);
+
+    // A literal backslash doesn't start an assertion
+    assert_eq!(
+        RegexTrie::translate_posix_ere(r"a\\b").expect("valid ERE"),
+        r"a[\\]b"
+    );
+    tree.self_check().expect("broken trie");
+}
+
+#[test]
+fn test_utf8_handling() {
+    let patterns = [
+        "/logs/[a-z]+".to_string(),
+        "/logs/.*".to_string(),
+        "/logs/\u{fffd}".to_string(),
+    ];
+    let tree = RegexTrie::from(&patterns).expect("can't init regex trie");
+    let invalid = b"/logs/\xff".as_slice();
+    let to_strings = |matches: Vec<Arc<str>>| -> Vec<String> {
+        matches.iter().map(ToString::to_string).collect()
+    };
+
+    let matches = tree.find_matches_bytes(b"/logs/abc").expect("valid UTF-8");
+    assert_eq!(to_strings(matches), vec!["/logs/[a-z]+", "/logs/.*"]);
+    let err = tree.find_matches_bytes(invalid).expect_err("invalid UTF-8");
+    assert!(matches!(err, RegexTrieError::InvalidUtf8(err) if err.valid_up_to() == 6));
+
+    let tree = tree.with_utf8_handling(Utf8Handling::Lossy);
+    let matches = tree.find_matches_bytes(invalid).expect("lossy");
+    assert_eq!(to_strings(matches), vec!["/logs/\u{fffd}", "/logs/.*"]);
+
+    // Regexes run with byte semantics, and the invalid bytes can be after
+    // the matched prefix.
+    let tree = tree.with_utf8_handling(Utf8Handling::Bytes);
+    let matches = tree.find_matches_bytes(invalid).expect("bytes");
+    assert_eq!(to_strings(matches), vec!["/logs/.*"]);
+    let tree = tree.with_match_mode(MatchMode::Prefix);
+    let matches = tree.find_matches_bytes(b"/logs/abc\xff").expect("bytes");
+    assert_eq!(to_strings(matches), vec!["/logs/[a-z]+", "/logs/.*"]);
+}
+
+#[test]
+fn test_find_matches_path() {
+    let patterns = ["/data/[a-z]+\\.csv".to_string(), "/data/.*".to_string()];
+    let tree = RegexTrie::from(&patterns)
+        .expect("can't init regex trie")
+        .with_match_mode(MatchMode::Prefix);
+    let to_strings = |matches: Vec<Arc<str>>| -> Vec<String> {
+        matches.iter().map(ToString::to_string).collect()
+    };
+
+    let matches = tree.find_matches_path(std::path::Path::new("/data/report.csv"));
+    assert_eq!(to_strings(matches), patterns.to_vec());
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+
+        // Not valid UTF-8, and not mangled into U+FFFD
+        let path = std::path::Path::new(std::ffi::OsStr::from_bytes(b"/data/report.csv\xff"));
+        let matches = tree.find_matches_path(path);
+        assert_eq!(to_strings(matches), patterns.to_vec());
+        let path = std::path::Path::new(std::ffi::OsStr::from_bytes(b"/dat\xff"));
+        assert!(tree.find_matches_path(path).is_empty());
+
+        // The regexes run with byte semantics, so `.` matches invalid bytes.
+        let patterns = [
+            "/tmp/.*".to_string(),
+            "/tmp/[^/]+\\.log".to_string(),
+            "/tmp/\\w+".to_string(),
+        ];
+        let tree = RegexTrie::from(&patterns).expect("can't init regex trie");
+        let path = std::path::Path::new(std::ffi::OsStr::from_bytes(b"/tmp/\xff.log"));
+        assert_eq!(
+            to_strings(tree.find_matches_path(path)),
+            patterns[..2].to_vec()
+        );
+        let path = std::path::Path::new(std::ffi::OsStr::from_bytes(b"/tmp\xff"));
+        assert!(tree.find_matches_path(path).is_empty());
+    }
+}
+
+#[test]
+fn test_path_segment_trie() {
+    let patterns = [
+        "/users/[0-9]+/profile",
+        "/users/[0-9]+/settings",
+        "/users/me/profile",
+        "/users/[a-z]+/profile",
+        "/static/.*",
+        "/static/[a-z]+\\.css",
+        "/(api|v[0-9])/status",
+        "(?:/api/x|/other)",
+        "/files/[^/]+/(?i)raw",
+        "/files/[a-z/]+",
+        "/a\\.b/c",
+    ]
+    .map(ToString::to_string);
+    let tree = RegexTrie::from(&patterns).expect("can't init regex trie");
+    let segments = PathSegmentTrie::from(&patterns).expect("can't init segment trie");
+
+    for input in [
+        "/users/42/profile",
+        "/users/42/settings",
+        "/users/me/profile",
+        "/users/me/settings",
+        "/static/main.css",
+        "/static/js/app.js",
+        "/api/status",
+        "/v2/status",
+        "/api/x",
+        "/other",
+        "/files/a/RAW",
+        "/files/a/b/c",
+        "/a.b/c",
+        "/users",
+        "",
+    ] {
+        assert_eq!(
+            segments.find_matches(input),
+            tree.find_matches(input),
+            "tries disagree on {input}"
+        );
+        assert_eq!(
+            segments.find_best_match(input),
+            tree.find_best_match(input),
+            "tries disagree on {input}"
+        );
+    }
+
+    // root, "", users, [0-9]+, profile, settings, me, profile, [a-z]+,
+    // profile, static, [a-z]+\.css, (api|v[0-9]), status, files, [^/]+, a.b,
+    // c
+    assert_eq!(segments.node_count(), 18);
+    PathSegmentTrie::from(&["/a/[".to_string()]).expect_err("invalid regex");
+}
+
+#[test]
+fn test_token_trie() {
+    let patterns = [
+        "ERROR db timeout after [0-9]+ms",
+        "ERROR db .*",
+        "ERROR dbx [a-z]+",
+        "WARN disk full",
+        "WARN disk [0-9]+% full",
+        "[A-Z]+ cache miss",
+    ]
+    .map(ToString::to_string);
+    let words = || -> crate::TokenizerFuncType {
+        Box::new(|text| text.split(' ').map(ToString::to_string).collect())
+    };
+    let tree = RegexTrie::from(&patterns).expect("can't init regex trie");
+    let tokens = TokenTrie::from(&patterns, words()).expect("can't init token trie");
+
+    for input in [
+        "ERROR db timeout after 30ms",
+        "ERROR db gone",
+        "ERROR dbx down",
+        "WARN disk full",
+        "WARN disk 90% full",
+        "INFO cache miss",
+        "WARN",
+        "",
+    ] {
+        assert_eq!(
+            tokens.find_matches(input),
+            tree.find_matches(input),
+            "tries disagree on {input}"
+        );
+        assert_eq!(
+            tokens.find_best_match(input),
+            tree.find_best_match(input),
+            "tries disagree on {input}"
+        );
+    }
+
+    // root, ERROR, db, timeout, after, dbx, WARN, disk, full
+    assert_eq!(tokens.node_count(), 9);
+    TokenTrie::from(&["a [".to_string()], words()).expect_err("invalid regex");
+}
+
+#[test]
+fn test_word_trie() {
+    let patterns = [
+        "disk usage above [0-9]+%",
+        "disk usage .*",
+        "disk\tfull",
+        "Host [a-z0-9-]+ unreachable",
+        "Host down",
+    ]
+    .map(ToString::to_string);
+    let mut words = TokenTrie::words();
+    words.insert_many(&patterns).expect("can't init word trie");
+
+    assert_eq!(
+        words.find_matches("disk usage above 95%"),
+        vec!["disk usage above [0-9]+%", "disk usage .*"]
+    );
+    assert_eq!(
+        words.find_best_match("disk usage above 95%"),
+        Some("disk usage .*".to_string())
+    );
+    assert_eq!(words.find_matches("disk\tfull"), vec!["disk\tfull"]);
+    // Same words, different whitespace: reaches the pattern, but isn't equal
+    assert!(words.find_matches("disk full").is_empty());
+    assert_eq!(
+        words.find_matches("Host db-01 unreachable"),
+        vec!["Host [a-z0-9-]+ unreachable"]
+    );
+    assert_eq!(words.find_matches("Host down"), vec!["Host down"]);
+    assert!(words.find_matches("Hostess down").is_empty());
+
+    // root, disk, usage, full, Host, down
+    assert_eq!(words.node_count(), 6);
+}
+
+#[test]
+fn test_contains_trie() {
+    let patterns = [
+        ".*ERROR.*timeout.*",
+        "disk (full|quota)",
+        "ERROR",
+        "[0-9]+ms",
+        "(a|b)c",
+    ]
+    .map(ToString::to_string);
+    let tree = ContainsTrie::from(&patterns).expect("can't init contains trie");
+
+    assert_eq!(
+        tree.find_matches("12:00 ERROR db timeout after 30ms"),
+        vec![".*ERROR.*timeout.*", "ERROR", "[0-9]+ms"]
+    );
+    assert_eq!(
+        tree.find_best_match("12:00 ERROR db timeout after 30ms"),
+        Some("ERROR".to_string())
+    );
+    // The required literals occur, but not in the order of the regex
+    assert_eq!(tree.find_matches("timeout then ERROR"), vec!["ERROR"]);
+    assert_eq!(
+        tree.find_matches("WARN disk quota"),
+        vec!["disk (full|quota)"]
+    );
+    assert_eq!(tree.find_matches("abc"), vec!["(a|b)c"]);
+    assert!(tree.find_matches("all good").is_empty());
+
+    // root, then the literals ERROR, timeout, "disk ", ms and c
+    assert_eq!(tree.node_count(), 21);
+    ContainsTrie::from(&["a [".to_string()]).expect_err("invalid regex");
+}
+
+#[test]
+fn test_hostname_trie() {
+    let patterns = [
+        "*.cdn.example.com",
+        "static.cdn.example.com",
+        "example.com",
+        "*.example.com",
+        "api.*.example.com",
+        "*",
+    ]
+    .map(ToString::to_string);
+    let trie = HostnameTrie::from(&patterns).expect("can't init hostname trie");
+
+    assert_eq!(
+        trie.find_matches("img.cdn.example.com"),
+        vec!["*.cdn.example.com", "*.example.com", "*"]
+    );
+    assert_eq!(
+        trie.find_best_match("a.b.cdn.example.com").as_deref(),
+        Some("*.cdn.example.com")
+    );
+    assert_eq!(
+        trie.find_best_match("Static.CDN.example.com.").as_deref(),
+        Some("static.cdn.example.com")
+    );
+    assert_eq!(trie.find_matches("example.com"), vec!["example.com", "*"]);
+    assert_eq!(
+        trie.find_best_match("api.eu.example.com").as_deref(),
+        Some("api.*.example.com")
+    );
+    assert_eq!(trie.find_matches("cdn.example.org"), vec!["*"]);
+    assert!(trie.find_matches("").is_empty());
+
+    // root, com, example, cdn, static, *, api
+    assert_eq!(trie.node_count(), 7);
+    let mut trie = trie;
+    trie.insert("EXAMPLE.com.").expect("can't insert");
+    assert_eq!(trie.find_matches("example.com"), vec!["example.com", "*"]);
+    for pattern in ["a..com", "a*.com", ""] {
+        trie.insert(pattern).expect_err("invalid pattern");
+    }
+}
+
+#[test]
+fn test_cidr() {
+    let translate = |cidr| RegexTrie::translate_cidr(cidr).expect("valid CIDR");
+    assert_eq!(translate("192.168.1.7"), r"192\.168\.1\.7");
+    assert_eq!(
+        translate("192.168.1.0/24"),
+        r"192\.168\.1\.(?:25[0-5]|2[0-4][0-9]|1[0-9][0-9]|[1-9][0-9]|[0-9])"
+    );
+    assert_eq!(
+        translate("172.16.0.0/12").split(r"\.").nth(1),
+        Some("(?:3[0-1]|2[0-9]|1[6-9])")
+    );
+    for cidr in [
+        "10.0.0.1/8",
+        "10.0.0.0/33",
+        "10.0.0/8",
+        "::1/128",
+        "10.0.0.0/x",
+    ] {
+        RegexTrie::translate_cidr(cidr).expect_err("invalid CIDR");
+    }
+
+    let mut tree = RegexTrie::new();
+    tree.insert("http://.*").expect("can't insert");
+    let private = tree.insert_cidr("172.16.0.0/12").expect("can't insert");
+    let host = tree.insert_cidr("10.1.2.3/32").expect("can't insert");
+    let everything = tree.insert_cidr("0.0.0.0/0").expect("can't insert");
+    assert_eq!(
+        tree.find_matches("172.31.255.255"),
+        vec![private.clone(), everything.clone()]
+    );
+    assert_eq!(tree.find_matches("172.32.0.1"), vec![everything.clone()]);
+    assert_eq!(
+        tree.find_matches("10.1.2.3"),
+        vec![host, everything.clone()]
+    );
+    assert!(tree.find_matches("172.016.0.1").is_empty());
+    assert!(tree.find_matches("256.0.0.1").is_empty());
+    assert_eq!(tree.find_matches("http://10.1.2.3"), vec!["http://.*"]);
+
+    // Every octet value, once
+    let octet = format!(
+        "(?:{})",
+        translate("0.0.0.0/0")
+            .split(r"\.")
+            .next()
+            .unwrap_or_default()
+    );
+    let octet = regex_automata::meta::Regex::new(&format!("^{octet}$")).expect("valid regex");
+    for value in 0..1000 {
+        assert_eq!(octet.is_match(&value.to_string()), value < 256, "{value}");
+    }
+}
+
+#[test]
+fn test_numeric_ranges() {
+    let translate =
+        |pattern: &str| RegexTrie::translate_numeric_ranges(pattern).expect("valid ranges");
+    assert_eq!(translate("/status/<200-204>"), "/status/(?:20[0-4])");
+    assert_eq!(translate("port <8-12>"), "port (?:1[0-2]|[8-9])");
+    assert_eq!(translate("<7-7>"), "(?:7)");
+    // Not range tokens
+    for pattern in ["a<b>", r"\<1-2>", "[<1-2>]", "<1->", "<1-2"] {
+        assert_eq!(translate(pattern), pattern);
+    }
+    for pattern in ["<9-1>", "<0-9999999999999999999>"] {
+        RegexTrie::translate_numeric_ranges(pattern).expect_err("invalid range");
+    }
+
+    let mut tree = RegexTrie::new();
+    let ports = tree
+        .insert_with_numeric_ranges("host:<1024-65535>")
+        .expect("can't insert");
+    let ids = tree
+        .insert_with_numeric_ranges("/items/<0-99>/<1-3>")
+        .expect("can't insert");
+    for port in [0, 80, 1023, 1024, 8080, 65535, 65536, 100_000] {
+        let matches = tree.find_matches(&format!("host:{port}"));
+        assert_eq!(
+            matches.len(),
+            usize::from((1024..=65535).contains(&port)),
+            "{port}"
+        );
+    }
+    assert_eq!(tree.find_matches("host:01024"), Vec::<String>::new());
+    assert_eq!(tree.find_matches("/items/42/3"), vec![ids.clone()]);
+    assert!(tree.find_matches("/items/100/3").is_empty());
+    assert!(tree.find_matches("/items/4/0").is_empty());
+    assert!(ports.starts_with("host:(?:"));
+
+    // Every value of arbitrary ranges, once
+    for (min, max) in [(0, 0), (1, 1000), (37, 4321), (99, 101), (5, 5000)] {
+        let regex = regex_automata::meta::Regex::new(&format!(
+            "^{}$",
+            translate(&format!("<{min}-{max}>"))
+        ))
+        .expect("valid regex");
+        for value in 0..6000 {
+            assert_eq!(
+                regex.is_match(&value.to_string()),
+                (min..=max).contains(&value),
+                "{value} in {min}-{max}"
+            );
+        }
+    }
+}
+
+#[test]
+fn test_glob_stars() {
+    let translate = |pattern| RegexTrie::translate_glob_stars(pattern, GlobStar::Segment);
+    assert_eq!(
+        translate("https://google.com/user/.*/photos/*"),
+        "https://google.com/user/.*/photos/[^/]*"
+    );
+    assert_eq!(translate("*/a/*/b"), "[^/]*/a/[^/]*/b");
+    assert_eq!(
+        RegexTrie::translate_glob_stars("/static/*", GlobStar::Any),
+        "/static/.*"
+    );
+    // Not glob stars
+    for pattern in [
+        "/a*",
+        "/*a",
+        r"/\*",
+        "/[/*]",
+        "/[[:alpha:]/]*",
+        "/(*)",
+        "/.*",
+    ] {
+        assert_eq!(translate(pattern), pattern);
+    }
+
+    let mut tree = RegexTrie::new();
+    let photos = tree
+        .insert_with_glob_stars("/user/.*/photos/*", GlobStar::Segment)
+        .expect("can't insert");
+    let files = tree
+        .insert_with_glob_stars("/files/*", GlobStar::Any)
+        .expect("can't insert");
+    assert_eq!(
+        tree.find_matches("/user/42/photos/cat.png"),
+        vec![photos.clone()]
+    );
+    assert_eq!(tree.find_matches("/user/42/photos/"), vec![photos]);
+    assert!(tree.find_matches("/user/42/photos/2024/cat.png").is_empty());
+    assert_eq!(tree.find_matches("/files/a/b"), vec![files]);
+    assert!(tree.find_matches("/user/42/photos//").is_empty());
+}
+
+#[test]
+fn test_insert_literal() {
+    let mut tree = RegexTrie::new();
+    let search = tree
+        .insert_literal("https://a.com/search?q=a+b")
+        .expect("can't insert");
+    assert_eq!(search, r"https://a\.com/search\?q=a\+b");
+    let path = tree.insert_literal(r"C:\*.txt").expect("can't insert");
+    assert_eq!(path, r"C:\\*\.txt");
+    tree.insert("https://a.com/.*").expect("can't insert");
+
+    assert_eq!(
+        tree.find_matches("https://a.com/search?q=a+b"),
+        vec!["https://a.com/search?q=a+b", "https://a.com/.*"]
+    );
+    assert_eq!(
+        tree.find_matches("https://a.com/search?q=aab"),
+        vec!["https://a.com/.*"]
+    );
+    assert_eq!(tree.find_matches(r"C:\*.txt"), vec![r"C:\*.txt"]);
+    assert_eq!(
+        tree.iter()
+            .find(|entry| entry.pattern == path)
+            .map(|entry| entry.kind),
+        Some(PatternKind::Literal)
+    );
+    tree.insert_literal(r"\bword")
+        .expect_err("assertion inserted");
+
+    let mut tree = RegexTrie::new()
+        .with_extra_specials("|")
+        .expect("invalid specials");
+    assert_eq!(tree.insert_literal("a|b").expect("can't insert"), r"a\|b");
+    assert_eq!(tree.find_matches("a|b"), vec!["a|b"]);
+}
+
+#[test]
+fn test_insert_regex() {
+    let mut tree = RegexTrie::new();
+    assert_eq!(tree.force_regex(r"/id/\d"), r"/id/(?:\d)");
+    assert_eq!(tree.force_regex("^/home$"), "(?:^/home$)");
+    assert_eq!(tree.force_regex("/a|/b"), "(?:/a|/b)");
+    for pattern in ["/home", r"/a\.b", "/a.*"] {
+        assert_eq!(tree.force_regex(pattern), pattern);
+    }
+
+    // Plain for the trie, matched verbatim
+    tree.insert(r"/id/\d").expect("can't insert");
+    let id = tree.insert_regex(r"/id/\d").expect("can't insert");
+    let home = tree.insert_regex("^/home$").expect("can't insert");
+    tree.insert_regex("/about").expect("can't insert");
+    assert_eq!(tree.find_matches("/id/7"), vec![id]);
+    assert_eq!(tree.find_matches(r"/id/\d"), vec![r"/id/\d"]);
+    assert_eq!(tree.find_matches("/home"), vec![home]);
+    assert_eq!(
+        tree.iter()
+            .map(|entry| (entry.pattern, entry.kind))
+            .collect::<Vec<_>>(),
+        vec![
+            (r"/id/\d", PatternKind::Literal),
+            (r"/id/(?:\d)", PatternKind::Regex),
+            ("(?:^/home$)", PatternKind::Regex),
+            ("/about", PatternKind::Literal),
+        ]
+    );
+    tree.insert_regex(r"/\q")
+        .expect_err("invalid regex inserted");
+
+    let mut tree = RegexTrie::new().with_literal_detection(LiteralDetection::Strict);
+    assert_eq!(tree.literal_detection(), LiteralDetection::Strict);
+    for pattern in [r"/id/\d", "^/home$", "a|b", r"C:\dir"] {
+        let err = tree
+            .insert(pattern)
+            .expect_err("ambiguous pattern inserted");
+        assert!(matches!(err, RegexTrieError::InvalidPattern(_)), "{err}");
+    }
+    tree.insert_with_options(
+        "^a",
+        InsertOptions {
+            case_insensitive: true,
+        },
+    )
+    .expect_err("ambiguous pattern inserted");
+    for pattern in ["/home", r"/a\.b", r"/id/\d+"] {
+        tree.insert(pattern).expect("can't insert");
+    }
+    let dir = tree.insert_literal(r"C:\dir").expect("can't insert");
+    assert_eq!(tree.find_matches(r"C:\dir"), vec![dir]);
+    assert_eq!(tree.iter().count(), 4);
+}
+
+#[test]
+fn test_classify() {
+    let tree = RegexTrie::new();
+    for (pattern, kind) in [
+        ("/home", PatternKind::Literal),
+        (r"/a\.b\?", PatternKind::Literal),
+        (r"/id/\d", PatternKind::Literal),
+        ("", PatternKind::Literal),
+        ("/a.*", PatternKind::Regex),
+        (r"\bword", PatternKind::Regex),
+        ("a?", PatternKind::Regex),
+    ] {
+        assert_eq!(tree.classify(pattern), kind, "{pattern}");
+    }
+    let tree = tree.with_extra_specials("|").expect("invalid specials");
+    assert_eq!(tree.classify("a|b"), PatternKind::Regex);
+
+    // Same as the kind once inserted
+    let patterns = ["/home", r"/id/\d", "/a.*", "a|b", r"/x/\w+"];
+    let mut tree = RegexTrie::new();
+    tree.insert_many(&patterns.map(String::from))
+        .expect("can't insert");
+    for entry in tree.iter() {
+        assert_eq!(
+            tree.classify(entry.pattern),
+            entry.kind,
+            "{}",
+            entry.pattern
+        );
+    }
+}
+
+#[test]
+fn test_verbatim_literal_matches() {
+    let mut tree = RegexTrie::new();
+    let search = tree.insert_literal("/search?q=a+b").expect("can't insert");
+    tree.insert_literal("/home").expect("can't insert");
+    tree.insert(r"/a\.b").expect("can't insert");
+    tree.insert("/search.*").expect("can't insert");
+
+    let input = "/search?q=a+b";
+    assert_eq!(tree.find_matches(input), vec![input, "/search.*"]);
+    assert_eq!(
+        tree.find_matches_with_scores(input)[0],
+        (input.to_string(), 0)
+    );
+    assert_eq!(tree.find_best_match(input).as_deref(), Some(input));
+    assert_eq!(tree.find_matches("/home"), vec!["/home"]);
+    assert_eq!(tree.find_matches("/a.b"), vec![r"/a\.b"]);
+    let id = tree.id_of(input).expect("unknown literal");
+    assert_eq!(tree.id_of(&search), Some(id));
+    assert_eq!(tree.pattern(id), Some(search.as_str()));
+    let mut cache = QueryCache::default();
+    assert_eq!(
+        tree.find_best_match_id_with_cache(input, &mut cache),
+        Some(id)
+    );
+
+    // Kept by the derived tries, forgotten once removed
+    let copy = RegexTrie::restore(tree.snapshot());
+    assert_eq!(copy.find_best_match(input).as_deref(), Some(input));
+    assert_eq!(tree.remove_with_prefix("/search?"), 1);
+    assert_eq!(tree.id_of(input), None);
+    assert_eq!(tree.find_matches(input), vec!["/search.*"]);
+}
+
+#[test]
+fn test_from_with_deadline() {
+    let patterns = ["/a/[0-9]+", "/plain", "/b/.*", "/other/plain"].map(ToString::to_string);
+    let expected = RegexTrie::from(&patterns).expect("can't init regex trie");
+
+    let later = std::time::Instant::now() + std::time::Duration::from_secs(3600);
+    let (tree, pending) = RegexTrie::from_with_deadline(&patterns, later).expect("can't init");
+    assert!(pending.is_empty());
+    assert!(tree == expected);
+
+    // Past deadline: only the plain patterns are inserted
+    let (mut tree, pending) =
+        RegexTrie::from_with_deadline(&patterns, std::time::Instant::now()).expect("can't init");
+    assert_eq!(pending, vec!["/a/[0-9]+", "/b/.*"]);
+    assert_eq!(tree.find_matches("/plain"), vec!["/plain"]);
+    assert!(tree.find_matches("/a/1").is_empty());
+    tree.insert_many(&pending).expect("can't insert");
+    assert!(tree == expected);
+    assert_eq!(tree.find_matches("/a/1"), vec!["/a/[0-9]+"]);
+
+    RegexTrie::from_with_deadline(&["/a/[".to_string()], later).expect_err("invalid regex");
+}
+
+#[test]
+fn test_background_compilation() {
+    let tree =
+        BackgroundRegexTrie::new(RegexTrie::from(&["/health".to_string()]).expect("can't init"));
+    assert_eq!(tree.find_matches("/health"), vec!["/health"]);
+
+    for index in 0..20 {
+        tree.insert(format!("/api/v{index}/[a-z]+"));
+    }
+    tree.insert("/broken/[");
+    assert!(tree.pending() <= 21);
+    tree.flush();
+    assert_eq!(tree.pending(), 0);
+    assert_eq!(tree.find_matches("/api/v7/users"), vec!["/api/v7/[a-z]+"]);
+    assert_eq!(tree.find_best_match("/health").as_deref(), Some("/health"));
+    assert_eq!(tree.read().iter().count(), 21);
+
+    let errors = tree.take_errors();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].0, "/broken/[");
+    assert!(tree.take_errors().is_empty());
+
+    // Dropping waits for the pending patterns
+    tree.insert("/late/.*");
+    drop(tree);
+
+    // A panicking insert is reported, and doesn't block the flush
+    let tree =
+        BackgroundRegexTrie::new(RegexTrie::new_with_custom_scorer(Box::new(|pattern, _| {
+            assert!(!pattern.starts_with("/panic/"), "scorer panicked");
+            0
+        })));
+    tree.insert("/panic/.*");
+    tree.insert("/after/.*");
+    tree.flush();
+    assert_eq!(tree.pending(), 0);
+    assert_eq!(tree.find_matches("/after/x"), vec!["/after/.*"]);
+    let errors = tree.take_errors();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].0, "/panic/.*");
+}
+
+#[test]
+fn test_sharded_trie() {
+    let tree = ShardedRegexTrie::new(4);
+    assert_eq!(tree.shard_count(), 4);
+    assert!(tree.is_empty());
+    assert_eq!(ShardedRegexTrie::new(0).shard_count(), 1);
+
+    // Patterns sharing a literal prefix share a shard
+    assert_eq!(tree.shard_of("/api/.*"), tree.shard_of("/api/[0-9]+"));
+
+    std::thread::scope(|scope| {
+        for thread in 0..4 {
+            let tree = &tree;
+            scope.spawn(move || {
+                for index in 0..10 {
+                    tree.insert(&format!("/t{thread}/v{index}/.*"))
+                        .expect("can't insert");
+                }
+            });
+        }
+    });
+    assert_eq!(tree.len(), 40);
+
+    tree.insert_many(&["/api/.*".to_string(), "/api/users".to_string()])
+        .expect("can't insert");
+    assert_eq!(tree.len(), 42);
+    assert!(tree.insert_many(&["/bad/[".to_string()]).is_err());
+
+    let mut matches = tree.find_matches("/api/users");
+    matches.sort();
+    assert_eq!(matches, vec!["/api/.*", "/api/users"]);
+    assert_eq!(
+        tree.find_best_match("/api/users").as_deref(),
+        Some("/api/users")
+    );
+    assert_eq!(
+        tree.find_best_match("/t2/v7/x").as_deref(),
+        Some("/t2/v7/.*")
+    );
+    assert_eq!(tree.find_best_match("/nope"), None);
+}
+
+#[test]
+fn test_query_cache() {
+    let pattern = r"foo.*\bbar";
+    let tree = RegexTrie::from(&[pattern.to_string(), "foo[0-9]+".to_string()])
+        .expect("can't init")
+        .with_fallback_engine(true);
+    let other = RegexTrie::from(&[r"\bbaz".to_string()]).expect("can't init");
+
+    let mut cache = QueryCache::new();
+    for _ in 0..3 {
+        assert_eq!(
+            tree.find_matches_with_cache("foo baz bar", &mut cache),
+            vec![Arc::from(pattern)]
+        );
+        assert!(
+            tree.find_matches_with_cache("foo bazbar", &mut cache)
+                .is_empty()
+        );
+        assert_eq!(
+            tree.find_best_match_with_cache("foo42", &mut cache)
+                .as_deref(),
+            Some("foo[0-9]+")
+        );
+    }
+    // Only the fallback patterns have a search cache
+    assert_eq!(cache.cached_regexes(), 1);
+
+    // The same cache serves several tries
+    assert_eq!(
+        other
+            .find_best_match_with_cache("baz", &mut cache)
+            .as_deref(),
+        Some(r"\bbaz")
+    );
+    assert_eq!(cache.cached_regexes(), 2);
+    cache.clear();
+    assert_eq!(cache.cached_regexes(), 0);
+    assert_eq!(tree.find_matches_with_cache("foo bar", &mut cache).len(), 1);
+}
+
+#[test]
+fn test_match_ids_with_cache() {
+    let mut tree = RegexTrie::new().with_candidate_memoization(16);
+    for pattern in TEST_SET.lines() {
+        tree.insert(pattern).expect("can't insert");
+    }
+
+    let mut cache = QueryCache::new();
+    for input in TEST_SET.lines().chain(["/api/users/42", "nowhere"]) {
+        let expected: Vec<PatternId> = tree
+            .find_matches(input)
+            .iter()
+            .filter_map(|pattern| tree.id_of(pattern))
+            .collect();
+        assert_eq!(tree.find_match_ids_with_cache(input, &mut cache), expected);
+        assert_eq!(
+            tree.find_best_match_id_with_cache(input, &mut cache),
+            tree.find_best_match(input)
+                .and_then(|pattern| tree.id_of(&pattern))
+        );
+    }
+
+    // Ids are written in the same buffer from one query to the other
+    let input = TEST_SET.lines().next().expect("empty test set");
+    let first = tree.find_match_ids_with_cache(input, &mut cache).as_ptr();
+    assert_eq!(
+        tree.find_match_ids_with_cache(input, &mut cache).as_ptr(),
+        first
+    );
+}
+
+#[test]
+fn test_best_match_early_exit() {
+    // The fallback regex only gets a search cache once it has run.
+    let patterns = ["/api".to_string(), r"/a[a-z]+\b".to_string()];
+    let tree = RegexTrie::from(&patterns)
+        .expect("can't init")
+        .with_fallback_engine(true);
+    let mut cache = QueryCache::new();
+    assert_eq!(
+        tree.find_best_match_with_cache("/api", &mut cache)
+            .as_deref(),
+        Some("/api")
+    );
+    assert_eq!(cache.cached_regexes(), 0);
+    assert_eq!(
+        tree.find_best_match_with_cache("/apis", &mut cache)
+            .as_deref(),
+        Some(r"/a[a-z]+\b")
+    );
+    assert_eq!(cache.cached_regexes(), 1);
+
+    // Other tie breaks need every match ranked like the best one
+    let mut tree = RegexTrie::new_with_custom_scorer(Box::new(|_, _| 0))
+        .with_fallback_engine(true)
+        .with_tie_break(TieBreak::RoundRobin);
+    for pattern in &patterns {
+        tree.insert(pattern).expect("can't insert");
+    }
+    let mut cache = QueryCache::new();
+    assert_eq!(
+        tree.find_best_match_with_cache("/api", &mut cache)
+            .as_deref(),
+        Some("/api")
+    );
+    assert_eq!(cache.cached_regexes(), 1);
+
+    // So do the hit counters
+    let tree = RegexTrie::from(&patterns)
+        .expect("can't init")
+        .with_fallback_engine(true)
+        .with_hit_counting(true);
+    assert_eq!(tree.find_best_match("/api").as_deref(), Some("/api"));
+    assert_eq!(
+        tree.hit_counts(),
+        vec![(PatternId::new(0), 1), (PatternId::new(1), 1)]
+    );
+}
+
+#[test]
+fn test_best_match_score_pruning() {
+    // The fallback regex only gets a search cache once it has run.
+    let patterns = [
+        "/a.*".to_string(),
+        r"/a[a-z]+\b".to_string(),
+        "/ap.".to_string(),
+    ];
+    let tree = RegexTrie::from(&patterns)
+        .expect("can't init")
+        .with_fallback_engine(true);
+    let mut cache = QueryCache::new();
+    assert_eq!(
+        tree.find_best_match_with_cache("/apis", &mut cache)
+            .as_deref(),
+        Some("/a.*")
+    );
+    assert_eq!(cache.cached_regexes(), 0);
+    // Same best match as when every candidate runs
+    assert_eq!(
+        tree.find_matches_with_scores("/apis").first(),
+        Some(&("/a.*".to_string(), 4))
+    );
+    assert_eq!(tree.find_best_match("/api").as_deref(), Some("/a.*"));
+
+    // Ties still run, when they can win
+    let tree = tree.with_tie_break(TieBreak::RoundRobin);
+    let picked: Vec<_> = (0..2)
+        .map(|_| tree.find_best_match("/api").expect("no match"))
+        .collect();
+    assert_eq!(picked, vec!["/a.*", "/ap."]);
+    assert_eq!(cache.cached_regexes(), 0);
+}
+
+#[test]
+fn test_prefer_exact_literal() {
+    // Regexes beat plain patterns, and the fallback one only gets a search
+    // cache once it has run.
+    let patterns = [
+        "/api".to_string(),
+        r"/a[a-z]+\b".to_string(),
+        "/a.+".to_string(),
+    ];
+    let scorer: crate::ScorerFuncType =
+        Box::new(|pattern, is_regex| if is_regex { 0 } else { pattern.len() });
+    let tree = RegexTrie::from_with_scorer(&patterns, scorer)
+        .expect("can't init")
+        .with_fallback_engine(true);
+    assert!(!tree.prefer_exact_literal());
+    assert_eq!(tree.find_best_match("/api").as_deref(), Some(r"/a[a-z]+\b"));
+
+    let tree = tree
+        .with_prefer_exact_literal(true)
+        .with_tie_break(TieBreak::RoundRobin);
+    let mut cache = QueryCache::new();
+    assert_eq!(
+        tree.find_best_match_with_cache("/api", &mut cache)
+            .as_deref(),
+        Some("/api")
+    );
+    assert_eq!(cache.cached_regexes(), 0);
+    // Every match is still found
+    assert_eq!(tree.find_matches("/api").len(), 3);
+
+    // In prefix mode, only a plain pattern equal to the input is preferred.
+    let tree = tree.with_match_mode(MatchMode::Prefix);
+    assert_eq!(tree.find_best_match("/api").as_deref(), Some("/api"));
+    assert_eq!(
+        tree.find_best_match("/api/v1").as_deref(),
+        Some(r"/a[a-z]+\b")
+    );
+}
+
+#[test]
+fn test_node_alternation() {
+    let mut patterns: Vec<String> = (0..8).map(|n| format!("/api/[a-z]+/v{n}")).collect();
+    patterns.extend(["/api/[a-z]+".to_string(), "/api/users".to_string()]);
+    let inputs = [
+        "/api/users/v3",
+        "/api/users",
+        "/api/x/v9",
+        "/api/users/v3/items",
+        "/api",
+    ];
+    for mode in [MatchMode::Full, MatchMode::Prefix] {
+        let reference = RegexTrie::from(&patterns)
+            .expect("can't init")
+            .with_match_mode(mode);
+        let tree = RegexTrie::from(&patterns)
+            .expect("can't init")
+            .with_node_alternation(8)
+            .with_match_mode(mode);
+        assert_eq!(tree.combined_nodes(), 1);
+        for input in inputs {
+            assert_eq!(
+                tree.find_matches(input),
+                reference.find_matches(input),
+                "{mode:?} {input}"
+            );
+        }
+    }
+
+    // Newly inserted regexes are combined with the others of their node.
+    let mut tree = RegexTrie::from(&patterns)
+        .expect("can't init")
+        .with_node_alternation(9);
+    assert_eq!(tree.combined_nodes(), 1);
+    tree.insert("/api/.+/items").expect("can't insert");
+    tree.insert("/api/[0-9]+").expect("can't insert");
+    assert_eq!(tree.combined_nodes(), 1);
+    assert_eq!(tree.find_matches("/api/users/items"), vec!["/api/.+/items"]);
+    assert_eq!(tree.find_matches("/api/42"), vec!["/api/[0-9]+"]);
+
+    // Compaction moves the compiled patterns, and combines them again.
+    let mut tree = RegexTrie::from(&["/old/.+".to_string()])
+        .expect("can't init")
+        .with_node_alternation(8);
+    tree.insert_many(&patterns).expect("can't insert");
+    assert_eq!(tree.remove_with_prefix("/old/"), 1);
+    tree.compact();
+    assert_eq!(tree.combined_nodes(), 1);
+    assert_eq!(tree.find_matches("/api/users/v3"), vec!["/api/[a-z]+/v3"]);
+
+    // 0 stops combining them.
+    let tree = tree.with_node_alternation(0);
+    assert_eq!(tree.combined_nodes(), 0);
+    assert_eq!(tree.find_matches("/api/users/v3"), vec!["/api/[a-z]+/v3"]);
+}
+
+#[test]
+fn test_trigram_index() {
+    assert_eq!(
+        &*crate::trigram::required_trigrams(".*time(out)+ (a|b)?[0-9]{2}x?"),
+        &[*b"ime", *b"out", *b"tim"]
+    );
+    assert!(crate::trigram::required_trigrams("(timeout|error).*").is_empty());
+
+    let patterns = [
+        ".*timeout.*",
+        ".*disk (full|quota).*",
+        "[a-z]+ error [0-9]+",
+        "(retry)+ later",
+        ".{2}",
+        "/api/.*",
+    ]
+    .map(ToString::to_string);
+    let inputs = [
+        "request timeout after 30s",
+        "disk full on /var",
+        "db error 42",
+        "retryretry later",
+        "ok",
+        "/api/timeout",
+        "nothing",
+    ];
+    for mode in [MatchMode::Full, MatchMode::Prefix] {
+        let reference = RegexTrie::from(&patterns)
+            .expect("can't init")
+            .with_match_mode(mode);
+        let tree = RegexTrie::from(&patterns)
+            .expect("can't init")
+            .with_trigram_index(true)
+            .with_match_mode(mode);
+        assert!(tree.trigram_index());
+        for input in inputs {
+            assert_eq!(
+                tree.find_matches(input),
+                reference.find_matches(input),
+                "{mode:?} {input}"
+            );
+        }
+    }
+
+    // Case-insensitive regexes aren't ruled out by their trigrams.
+    let mut tree = RegexTrie::new().with_trigram_index(true);
+    tree.insert_with_options(
+        ".*TIMEOUT.*",
+        InsertOptions {
+            case_insensitive: true,
+        },
+    )
+    .expect("can't insert");
+    assert_eq!(tree.find_matches("a timeout"), vec![".*TIMEOUT.*"]);
+
+    // Compaction moves the compiled patterns, and indexes them again.
+    let mut tree = RegexTrie::from(&["/old/.+".to_string()])
+        .expect("can't init")
+        .with_trigram_index(true);
+    tree.insert_many(&patterns).expect("can't insert");
+    assert_eq!(tree.remove_with_prefix("/old/"), 1);
+    tree.compact();
+    tree.self_check().expect("inconsistent trie");
+    assert_eq!(
+        tree.find_matches("db error 42"),
+        vec!["[a-z]+ error [0-9]+"]
+    );
+}
+
+#[test]
+fn test_suffix_index() {
+    assert_eq!(
+        crate::suffix::literal_suffix(r".*\.tar\.gz$"),
+        Some(b".tar.gz".to_vec())
+    );
+    assert_eq!(crate::suffix::literal_suffix(r".*\.(gz|bz2)"), None);
+
+    let patterns = [
+        r".*\.tar\.gz$",
+        r".*\.gz",
+        r"[a-z]+\.log",
+        r".*\.(jpg|png)",
+        r"/tmp/.*\.log",
+    ]
+    .map(ToString::to_string);
+    let inputs = [
+        "backup.tar.gz",
+        "notes.gz",
+        "app.log",
+        "/tmp/app.log",
+        "cat.png",
+        "archive.tar.gz.part",
+        "",
+    ];
+    for mode in [MatchMode::Full, MatchMode::Prefix] {
+        let reference = RegexTrie::from(&patterns)
+            .expect("can't init")
+            .with_match_mode(mode);
+        let tree = RegexTrie::from(&patterns)
+            .expect("can't init")
+            .with_suffix_index(true)
+            .with_match_mode(mode);
+        assert!(tree.suffix_index());
+        for input in inputs {
+            assert_eq!(
+                tree.find_matches(input),
+                reference.find_matches(input),
+                "{mode:?} {input}"
+            );
+        }
+    }
+
+    // Compaction moves the compiled patterns, and indexes them again.
+    let mut tree = RegexTrie::from(&["/old/.+".to_string()])
+        .expect("can't init")
+        .with_suffix_index(true);
+    tree.insert_many(&patterns).expect("can't insert");
+    assert_eq!(tree.remove_with_prefix("/old/"), 1);
+    tree.compact();
+    tree.self_check().expect("inconsistent trie");
+    assert_eq!(
+        tree.find_matches("backup.tar.gz"),
+        vec![r".*\.tar\.gz$", r".*\.gz"]
+    );
+}
+
+#[test]
+fn test_literal_suffix_gate() {
+    let mut tree = RegexTrie::from(&[
+        r"https://x\.com/.*\.json".to_string(),
+        r"https://x\.com/[a-z]+".to_string(),
+    ])
+    .expect("can't init");
+    tree.insert_with_options(
+        r"https://x\.com/.*\.XML",
+        InsertOptions {
+            case_insensitive: true,
+        },
+    )
+    .expect("can't insert");
+    assert_eq!(
+        tree.literal_suffixes,
+        vec![Some(b".json".to_vec().into_boxed_slice()), None, None,]
+    );
+
+    assert_eq!(
+        tree.find_matches("https://x.com/data.json"),
+        vec![r"https://x\.com/.*\.json"]
+    );
+    assert!(tree.find_matches("https://x.com/data.json.gz").is_empty());
+    assert_eq!(
+        tree.find_matches("https://x.com/feed.xml"),
+        vec![r"https://x\.com/.*\.XML"]
+    );
+    // The match of a prefix doesn't end with the suffix of the input
+    let tree = tree.with_match_mode(MatchMode::Prefix);
+    assert_eq!(
+        tree.find_matches("https://x.com/data.json.gz"),
+        vec![r"https://x\.com/.*\.json", r"https://x\.com/[a-z]+"]
+    );
+}
+
+#[test]
+fn test_match_length_prefilter() {
+    let mut tree = RegexTrie::from(&[
+        "id-[0-9]{3,5}".to_string(),
+        "id-.+".to_string(),
+        "id-(x|yz)?".to_string(),
+    ])
+    .expect("can't init");
+    tree.insert_with_options(
+        "k{2}",
+        InsertOptions {
+            case_insensitive: true,
+        },
+    )
+    .expect("can't insert");
+    // The Kelvin sign folds to k, and takes 3 bytes
+    assert_eq!(
+        tree.match_lengths,
+        vec![(6, Some(8)), (4, None), (3, Some(5)), (2, Some(6))]
+    );
+
+    assert_eq!(tree.find_matches("id-123"), vec!["id-[0-9]{3,5}", "id-.+"]);
+    assert_eq!(tree.find_matches("id-123456"), vec!["id-.+"]);
+    assert_eq!(tree.find_matches("id-"), vec!["id-(x|yz)?"]);
+    assert_eq!(tree.find_matches("\u{212A}K"), vec!["k{2}"]);
+    let tree = tree.with_match_mode(MatchMode::Prefix);
+    assert_eq!(
+        tree.find_matches("id-123456"),
+        vec!["id-[0-9]{3,5}", "id-.+", "id-(x|yz)?"]
+    );
+}
+
+#[test]
+fn test_best_match_walk_bound() {
+    let patterns = [
+        "/api".to_string(),
+        "/api/v1/[a-z]+".to_string(),
+        "/api/v1/users/.+".to_string(),
+        "/a.*".to_string(),
+    ];
+    let tree = RegexTrie::from(&patterns)
+        .expect("can't init")
+        .with_match_mode(MatchMode::Prefix);
+    tree.self_check().expect("inconsistent trie");
+    assert_eq!(tree.root.min_score, 0);
+    assert_eq!(tree.root.children[&'/'].children[&'a'].min_score, 0);
+
+    // The plain prefix can't be beaten, nothing below it is collected.
+    let mut scratch = Scratch::default();
+    assert!(!tree.collect_best_candidates("/api/v1/users", &mut scratch));
+    assert_eq!(scratch.escaped_pattern, "/api");
+    assert_eq!(scratch.candidate_indices, vec![2]);
+    assert_eq!(
+        tree.find_best_match("/api/v1/users").as_deref(),
+        Some("/api")
+    );
+    assert_eq!(
+        tree.find_matches("/api/v1/users"),
+        vec!["/api", "/api/v1/[a-z]+", "/a.*"]
+    );
+
+    // Deeper patterns can still beat a worse plain prefix.
+    let mut tree = RegexTrie::new_with_custom_scorer(Box::new(|pattern, _| 100 - pattern.len()))
+        .with_match_mode(MatchMode::Prefix);
+    for pattern in &patterns {
+        tree.insert(pattern).expect("can't insert");
+    }
+    assert_eq!(
+        tree.find_best_match("/api/v1/users").as_deref(),
+        Some("/api/v1/[a-z]+")
+    );
+
+    // Removals leave a lower bound, made exact again by compaction.
+    assert_eq!(tree.remove_with_prefix("/api/v1/"), 2);
+    assert_eq!(tree.root.min_score, 100 - "/api/v1/users/.+".len());
+    tree.compact();
+    assert_eq!(tree.root.min_score, 100 - "/api".len());
+    tree.self_check().expect("inconsistent trie");
+}
+
+#[test]
+fn test_candidate_memoization() {
+    let patterns = vec![
+        ".*".to_string(),
+        "https://example/.*".to_string(),
+        "https://example/api/.*".to_string(),
+        "https://example/api/users".to_string(),
+    ];
+    let plain = RegexTrie::from(&patterns).expect("can't init");
+    let mut tree = RegexTrie::from(&patterns)
+        .expect("can't init")
+        .with_candidate_memoization(2);
+    assert_eq!(tree.memoized_nodes(), 0);
+
+    let inputs = [
+        "https://example/api/users",
+        "https://example/api/orders",
+        "https://example/api/users",
+        "https://example/",
+        "https://other/",
+    ];
+    for input in inputs {
+        assert_eq!(
+            tree.find_matches(input),
+            plain.find_matches(input),
+            "{input}"
+        );
+        assert_eq!(tree.find_best_match(input), plain.find_best_match(input));
+    }
+    // Cleared once full
+    assert!(tree.memoized_nodes() <= 2);
+    assert_eq!(RegexTrie::new().memoized_nodes(), 0);
+
+    // Modifying the trie forgets the memoized candidates
+    assert_eq!(tree.find_matches("https://example/api/x").len(), 3);
+    assert!(tree.memoized_nodes() > 0);
+    tree.insert("https://example/api/[a-z]+")
+        .expect("can't insert");
+    assert_eq!(tree.memoized_nodes(), 0);
+    assert_eq!(
+        tree.find_matches("https://example/api/x"),
+        vec![
+            ".*",
+            "https://example/.*",
+            "https://example/api/.*",
+            "https://example/api/[a-z]+"
+        ]
+    );
+    tree.remove_with_prefix("https://example/api/");
+    assert_eq!(tree.memoized_nodes(), 0);
+    assert_eq!(
+        tree.find_matches("https://example/api/x"),
+        vec![".*", "https://example/.*"]
+    );
+    tree.compact();
+    assert_eq!(
+        tree.find_matches("https://example/api/x"),
+        vec![".*", "https://example/.*"]
+    );
+}
+
+#[test]
+fn test_first_byte_filter() {
+    let mut tree = RegexTrie::from(&[
+        "https://[a-z]+/".to_string(),
+        "ftp://host".to_string(),
+        "\u{e9}[0-9]+".to_string(),
+    ])
+    .expect("can't init");
+    assert!(tree.first_bytes.contains(b'h'));
+    assert!(tree.first_bytes.contains(b'f'));
+    assert!(tree.first_bytes.contains("\u{e9}".as_bytes()[0]));
+    assert!(!tree.first_bytes.contains(b'x'));
+
+    // Inputs rejected at the root still match the root patterns
+    assert!(tree.find_matches("xyz").is_empty());
+    tree.insert(".*z").expect("can't insert");
+    assert_eq!(tree.find_matches("xyz"), vec![".*z"]);
+    assert_eq!(tree.find_matches("\u{e9}42"), vec!["\u{e9}[0-9]+"]);
+    assert_eq!(tree.find_matches("ftp://host"), vec!["ftp://host"]);
+
+    assert_eq!(tree.remove_with_prefix("ftp"), 1);
+    assert!(!tree.first_bytes.contains(b'f'));
+    assert!(tree.find_matches("ftp://host").is_empty());
+    tree.remove_with_prefix("");
+    assert!(!tree.first_bytes.contains(b'h'));
+    tree.insert("ftp://host").expect("can't insert");
+    assert_eq!(tree.find_matches("ftp://host"), vec!["ftp://host"]);
+}
+
+#[test]
+fn test_order_candidates_by_hits() {
+    let patterns = vec![
+        "/api/c/.*".to_string(),
+        "/api/[a-z]+".to_string(),
+        "/api/users".to_string(),
+    ];
+    let mut tree = RegexTrie::from(&patterns)
+        .expect("can't init")
+        .with_hit_counting(true);
+    assert!(tree.is_match("/api/users"));
+    assert!(tree.is_match("/api/orders"));
+    assert!(tree.is_match("/api/c/x"));
+    assert!(!tree.is_match("/nope"));
+    assert!(tree.candidate_priority.is_empty());
+
+    for _ in 0..5 {
+        assert_eq!(tree.find_matches("/api/orders"), vec!["/api/[a-z]+"]);
+    }
+    assert!(tree.order_candidates_by_hits());
+    assert_eq!(tree.candidate_priority, vec![0, 5]);
+    assert!(tree.is_match("/api/orders"));
+    assert!(tree.is_match("/api/c/x"));
+    assert!(!tree.is_match("/api/0"));
+    // The reporting order is unchanged
+    assert_eq!(
+        tree.find_matches("/api/users"),
+        vec!["/api/users", "/api/[a-z]+"]
+    );
+
+    assert_eq!(tree.remove_with_prefix("/api/c/"), 1);
+    tree.compact();
+    assert_eq!(tree.candidate_priority, vec![5]);
+    assert!(tree.is_match("/api/orders"));
+
+    let mut prefix = RegexTrie::from(&["/static/".to_string()])
+        .expect("can't init")
+        .with_match_mode(MatchMode::Prefix);
+    assert!(prefix.is_match("/static/app.js"));
+    assert!(!prefix.order_candidates_by_hits());
+}
+
+#[test]
+fn test_optimize() {
+    let patterns = vec![
+        "/api/[0-9]+".to_string(),
+        "/api/[a-z]+".to_string(),
+        "/health".to_string(),
+    ];
+    let replica = RegexTrie::from(&patterns)
+        .expect("can't init")
+        .with_hit_counting(true);
+    for _ in 0..3 {
+        assert_eq!(replica.find_matches("/api/users"), vec!["/api/[a-z]+"]);
+    }
+    let stats = replica.stats().expect("hit counting enabled");
+
+    let mut tree = RegexTrie::from(&patterns).expect("can't init");
+    assert_eq!(
+        tree.optimize(&stats),
+        OptimizeReport {
+            hot_regexes: 1,
+            dead_patterns: vec!["/api/[0-9]+".to_string(), "/health".to_string()],
+        }
+    );
+    assert_eq!(tree.candidate_priority, vec![0, 3]);
+    assert!(tree.candidate_memo.is_some());
+    assert!(tree.is_match("/api/users"));
+    assert!(tree.is_match("/api/42"));
+    assert_eq!(tree.find_matches("/health"), vec!["/health"]);
+    assert_eq!(tree.find_matches("/api/42"), vec!["/api/[0-9]+"]);
+
+    // No traffic, nothing memoized
+    let mut idle = RegexTrie::from(&patterns).expect("can't init");
+    let report = idle.optimize(
+        &RegexTrie::new()
+            .with_hit_counting(true)
+            .stats()
+            .expect("enabled"),
+    );
+    assert_eq!(report.dead_patterns.len(), 3);
+    assert!(idle.candidate_memo.is_none());
+}
+
+#[test]
+fn test_max_patterns() {
+    let mut tree = RegexTrie::new().with_max_patterns(3);
+    tree.insert_many(&["/a".to_string(), "/b/.*".to_string()])
+        .expect("can't insert");
+    // Known patterns and duplicates don't count
+    tree.insert_many(&["/a".to_string(), "/c".to_string(), "/c".to_string()])
+        .expect("can't insert");
+    assert_eq!(tree.iter().count(), 3);
+
+    let err = tree
+        .insert_many(&["/d".to_string(), "/e/.*".to_string()])
+        .expect_err("over capacity");
+    assert!(matches!(
+        err,
+        RegexTrieError::CapacityExceeded {
+            required: 5,
+            capacity: 3
+        }
+    ));
+    assert!(tree.find_matches("/d").is_empty());
+    assert!(matches!(
+        tree.insert_with_options(
+            "/F",
+            InsertOptions {
+                case_insensitive: true
+            }
+        ),
+        Err(RegexTrieError::CapacityExceeded { .. })
+    ));
+
+    // Removed patterns free their slot
+    assert_eq!(tree.remove_with_prefix("/c"), 1);
+    tree.insert("/d").expect("can't insert");
+    assert_eq!(tree.find_matches("/d"), vec!["/d"]);
+    assert!(tree.intersection(&tree).insert("/g").is_err());
+}
+
+#[test]
+fn test_ttl() {
+    let mut tree =
+        RegexTrie::from(&["/health".to_string(), "/api/.*".to_string()]).expect("can't init");
+    tree.insert_with_ttl("/block/[0-9]+", Duration::ZERO)
+        .expect("can't insert");
+    tree.insert_with_ttl("/canary", Duration::ZERO)
+        .expect("can't insert");
+    tree.insert_with_ttl("/later/.*", Duration::from_secs(3600))
+        .expect("can't insert");
+    // Permanent patterns stay permanent
+    tree.insert_with_ttl("/health", Duration::ZERO)
+        .expect("can't insert");
+    let health = tree.id_of("/health").expect("missing pattern");
+    assert_eq!(tree.expires_at(health), None);
+    let later = tree.id_of("/later/.*").expect("missing pattern");
+    assert!(tree.expires_at(later).is_some());
+
+    // Expired patterns match until evicted
+    assert_eq!(tree.find_matches("/block/42"), vec!["/block/[0-9]+"]);
+    assert_eq!(tree.evict_expired(), 2);
+    assert_eq!(tree.evict_expired(), 0);
+    assert!(tree.find_matches("/block/42").is_empty());
+    assert!(tree.find_matches("/canary").is_empty());
+    assert_eq!(tree.find_matches("/later/x"), vec!["/later/.*"]);
+    assert_eq!(tree.find_matches("/health"), vec!["/health"]);
+    assert_eq!(tree.iter().count(), 3);
+    tree.self_check().expect("broken trie");
+    tree.compact();
+    tree.self_check().expect("broken trie");
+
+    // Refreshing the TTL of a temporary pattern
+    tree.insert_with_ttl("/later/.*", Duration::ZERO)
+        .expect("can't insert");
+    assert_eq!(tree.evict_expired(), 1);
+    assert_eq!(tree.expires_at(later), None);
+    tree.self_check().expect("broken trie");
+}
+
+#[test]
+fn test_write_ahead_log() {
+    let path = std::env::temp_dir().join(format!("regextrie-wal-{}.log", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let mut tree = LoggedRegexTrie::open(RegexTrie::new(), &path).expect("can't open");
+    tree.insert("/api/[a-z]+").expect("can't insert");
+    tree.insert("/multi\nline\\").expect("can't insert");
+    tree.insert_with_options(
+        "/CASE",
+        InsertOptions {
+            case_insensitive: true,
+        },
+    )
+    .expect("can't insert");
+    tree.insert("/old/.*").expect("can't insert");
+    assert_eq!(
+        tree.insert_literal("/search?q=a+b").expect("can't insert"),
+        r"/search\?q=a\+b"
+    );
+    // Failed operations aren't logged
+    assert!(tree.insert("/broken/[").is_err());
+    assert_eq!(tree.remove_with_prefix("/old/").expect("can't log"), 1);
+    drop(tree);
+
+    let mut replayed = RegexTrie::new();
+    assert_eq!(replayed.replay(&path).expect("can't replay"), 6);
+    assert_eq!(replayed.find_matches("/api/users"), vec!["/api/[a-z]+"]);
+    assert_eq!(
+        replayed.find_matches("/multi\nline\\"),
+        vec!["/multi\nline\\"]
+    );
+    assert_eq!(replayed.find_matches("/case"), vec!["/CASE"]);
+    assert_eq!(
+        replayed.find_matches("/search?q=a+b"),
+        vec!["/search?q=a+b"]
+    );
+    assert!(replayed.find_matches("/old/x").is_empty());
+    assert_eq!(replayed.iter().count(), 4);
+
+    // A torn last line is ignored, then dropped
+    let mut log = std::fs::read(&path).expect("can't read");
+    log.extend_from_slice(b"+ /torn");
+    std::fs::write(&path, &log).expect("can't write");
+    let mut tree = LoggedRegexTrie::open(RegexTrie::new(), &path).expect("can't open");
+    assert_eq!(tree.trie(), &replayed);
+    tree.insert("/next").expect("can't insert");
+    drop(tree);
+    let mut reopened = RegexTrie::new();
+    assert_eq!(reopened.replay(&path).expect("can't replay"), 7);
+    assert!(reopened.find_matches("/torn").is_empty());
+    assert_eq!(reopened.find_matches("/next"), vec!["/next"]);
+
+    // Operations failing on replay are reported
+    std::fs::write(&path, "+ /broken/[\n").expect("can't write");
+    RegexTrie::new()
+        .replay(&path)
+        .expect_err("operation failing on replay");
+    std::fs::write(&path, "? nope\n").expect("can't write");
+    assert!(matches!(
+        RegexTrie::new().replay(&path),
+        Err(RegexTrieError::LogFailed(_))
+    ));
+    std::fs::remove_file(&path).expect("can't remove");
+    assert_eq!(RegexTrie::new().replay(&path).expect("missing log"), 0);
+}
+
+#[test]
+fn test_snapshot_restore() {
+    let mut tree = RegexTrie::new().with_hit_counting(true);
+    tree.insert("/api/[a-z]+").expect("can't insert");
+    tree.insert("/removed").expect("can't insert");
+    tree.insert("/static/.*").expect("can't insert");
+    assert_eq!(tree.remove_with_prefix("/removed"), 1);
+    let static_id = tree.id_of("/static/.*").expect("missing pattern");
+
+    let snapshot = tree.snapshot();
+    let shared: TrieSnapshot = snapshot.clone();
+    assert_eq!(snapshot.trie(), &tree);
+
+    // Bad rule push
+    assert_eq!(tree.remove_with_prefix("/api/"), 1);
+    tree.insert("/.*").expect("can't insert");
+    assert_eq!(tree.find_best_match("/api/users"), Some("/.*".to_string()));
+    assert_eq!(
+        shared.trie().find_matches("/api/users"),
+        vec!["/api/[a-z]+"]
+    );
+
+    let mut tree = RegexTrie::restore(snapshot);
+    assert_eq!(tree.find_matches("/api/users"), vec!["/api/[a-z]+"]);
+    assert!(tree.find_matches("/other").is_empty());
+    assert_eq!(tree.id_of("/static/.*"), Some(static_id));
+    assert!(tree.find_matches("/removed").is_empty());
+    assert_eq!(
+        tree.hit_counts(),
+        vec![(PatternId::new(0), 1), (static_id, 0)]
+    );
+
+    // The other clone is left untouched
+    tree.insert("/.*").expect("can't insert");
+    assert!(RegexTrie::restore(shared).find_matches("/x").is_empty());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_shared_frozen_trie() {
+    let build = || {
+        let mut tree = RegexTrie::new();
+        for pattern in TEST_SET.lines() {
+            tree.insert(pattern).expect("can't insert");
+        }
+        tree.insert_with_options(
+            "/CASE/[a-z]+",
+            InsertOptions {
+                case_insensitive: true,
+            },
+        )
+        .expect("can't insert");
+        tree
+    };
+    let path = std::env::temp_dir().join(format!("regextrie-shared-{}", std::process::id()));
+    let frozen = build().freeze();
+    frozen.write_shared(&path).expect("can't write");
+
+    // SAFETY: the files are only replaced once no trie maps them anymore.
+    let open = |path: &std::path::Path| unsafe { FrozenRegexTrie::open_shared(path) };
+    let first = open(&path).expect("can't open");
+    let second = open(&path).expect("can't open");
+    for input in TEST_SET
+        .lines()
+        .chain(["/case/abc", "/api/users/42", "nowhere"])
+    {
+        assert_eq!(first.find_matches(input), frozen.find_matches(input));
+        assert_eq!(second.find_best_match(input), frozen.find_best_match(input));
+    }
+    assert_eq!(first.find_matches("/case/abc"), vec!["/CASE/[a-z]+"]);
+    assert_eq!(first.node_count(), frozen.node_count());
+
+    // A shared trie can be written again
+    let copy = path.with_extension("copy");
+    first.write_shared(&copy).expect("can't write");
+    drop((first, second));
+    let reopened = open(&copy).expect("can't open");
+    assert_eq!(reopened.find_matches("/case/abc"), vec!["/CASE/[a-z]+"]);
+    drop(reopened);
+    std::fs::remove_file(&copy).expect("can't remove");
+
+    std::fs::write(&path, build().to_bytes()).expect("can't write");
+    assert!(matches!(
+        open(&path),
+        Err(RegexTrieError::InvalidSerializedData(_))
+    ));
+    std::fs::remove_file(&path).expect("can't remove");
+    assert!(matches!(
+        open(&path),
+        Err(RegexTrieError::SharedMemoryFailed(_))
+    ));
+}
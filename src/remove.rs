@@ -0,0 +1,279 @@
+use crate::{
+    PatternId, RegexTrie,
+    first_byte::FirstBytes,
+    options::stored_prefix,
+    regex_trie::{TrieNode, parse_literal_prefix_with},
+};
+
+/// Collects the escaped plain patterns and the regex indices of `node` and
+/// all the nodes below it, `path` being the escaped path leading to `node`.
+fn collect_subtree(
+    node: &TrieNode,
+    path: &mut String,
+    literals: &mut Vec<String>,
+    indices: &mut Vec<usize>,
+) {
+    if node.contains_non_regex_prefix {
+        literals.push(path.clone());
+    }
+    indices.extend_from_slice(&node.pattern_indices);
+    for (&ch, child) in &node.children {
+        let len = path.len();
+        if child.is_escaped {
+            path.push('\\');
+        }
+        path.push(ch);
+        collect_subtree(child, path, literals, indices);
+        path.truncate(len);
+    }
+}
+
+/// Detaches the subtree at the end of `prefix` (at least one character long)
+/// below `node`, and prunes the nodes left without pattern nor child on the
+/// way back. Returns the detached subtree, if found.
+fn detach(node: &mut TrieNode, prefix: &[char]) -> Option<TrieNode> {
+    let (&ch, rest) = prefix.split_first()?;
+    if rest.is_empty() {
+        return node.children.remove(&ch);
+    }
+
+    let child = node.children.get_mut(&ch)?;
+    let subtree = detach(child, rest)?;
+    if child.children.is_empty()
+        && child.pattern_indices.is_empty()
+        && !child.contains_non_regex_prefix
+    {
+        node.children.remove(&ch);
+    }
+    Some(subtree)
+}
+
+/// Removes a pattern from the node at the end of `prefix` below `node`: its
+/// compiled index if it's a regex, or the plain pattern ending there. Prunes
+/// the nodes left without pattern nor child on the way back, and returns
+/// whether `node` itself is left so.
+fn remove_from_node(
+    node: &mut TrieNode,
+    prefix: &[(char, bool)],
+    compiled_index: Option<usize>,
+) -> bool {
+    match (prefix.split_first(), compiled_index) {
+        (None, Some(compiled_index)) => {
+            node.pattern_indices
+                .retain(|&index| index != compiled_index);
+        }
+        (None, None) => node.contains_non_regex_prefix = false,
+        (Some((&(ch, _), rest)), _) => {
+            let is_empty = node
+                .children
+                .get_mut(&ch)
+                .is_some_and(|child| remove_from_node(child, rest, compiled_index));
+            if is_empty {
+                node.children.remove(&ch);
+            }
+        }
+    }
+    node.children.is_empty() && node.pattern_indices.is_empty() && !node.contains_non_regex_prefix
+}
+
+/// Memory reclaimed by `RegexTrie::compact`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CompactReport {
+    /// Number of freed DFAs, i.e. of removed regex patterns
+    pub freed_dfas: usize,
+    /// Heap memory used by the freed DFAs, in bytes
+    pub freed_bytes: usize,
+}
+
+/// Remaps the compiled pattern indices of `node` and all the nodes below it.
+fn remap_indices(node: &mut TrieNode, new_indices: &[usize]) {
+    for index in &mut node.pattern_indices {
+        *index = new_indices[*index];
+    }
+    for child in node.children.values_mut() {
+        remap_indices(child, new_indices);
+    }
+}
+
+impl RegexTrie {
+    /// Removes all the patterns whose literal prefix (see `literal_prefix`)
+    /// starts with the given string, e.g. every rule under
+    /// `https://old-domain.com/`, in a single walk of the trie. An empty
+    /// string removes everything. Returns the number of removed patterns.
+    ///
+    /// Ids of the removed patterns aren't reused. Their DFAs are kept in
+    /// memory until `compact` is called.
+    pub fn remove_with_prefix(&mut self, literal_prefix: &str) -> usize {
+        self.invalidate_candidate_memo();
+        let prefix: Vec<char> = literal_prefix.chars().collect();
+        let removed = self.remove_subtree(&prefix);
+
+        // Case-insensitive patterns are stored under the start of their
+        // literal prefix only, possibly above the removed subtree, so they're
+        // selected by their own literal prefix.
+        let case_insensitive: Vec<PatternId> = self
+            .case_insensitive
+            .iter()
+            .copied()
+            .filter(|id| {
+                self.patterns[id.index()]
+                    .as_ref()
+                    .is_some_and(|(pattern, _)| {
+                        let (pattern_prefix, _) =
+                            parse_literal_prefix_with(pattern, &self.extra_specials);
+                        pattern_prefix
+                            .iter()
+                            .map(|&(ch, _)| ch)
+                            .take(prefix.len())
+                            .eq(prefix.iter().copied())
+                    })
+            })
+            .collect();
+        for &id in &case_insensitive {
+            self.remove_id(id);
+        }
+        removed + case_insensitive.len()
+    }
+
+    /// Removes the patterns stored in the subtree of the given path, in a
+    /// single walk. Returns the number of removed patterns.
+    fn remove_subtree(&mut self, prefix: &[char]) -> usize {
+        let subtree = if prefix.is_empty() {
+            std::mem::take(&mut self.root)
+        } else {
+            let Some(subtree) = detach(&mut self.root, prefix) else {
+                return 0;
+            };
+            subtree
+        };
+
+        // Special characters of the path are escaped, as they were inserted.
+        let mut path = String::new();
+        for &ch in prefix {
+            if self.is_special(ch) {
+                path.push('\\');
+            }
+            path.push(ch);
+        }
+        self.first_bytes = FirstBytes::of_root(&self.root);
+        let mut literals = Vec::new();
+        let mut indices = Vec::new();
+        collect_subtree(&subtree, &mut path, &mut literals, &mut indices);
+
+        let removed: Vec<PatternId> = literals
+            .iter()
+            .map(String::as_str)
+            .chain(
+                indices
+                    .iter()
+                    .map(|&index| &*self.compiled_patterns[index].0),
+            )
+            .filter_map(|pattern| self.id_of(pattern))
+            .collect();
+        for &id in &removed {
+            self.forget(id);
+        }
+        removed.len()
+    }
+
+    /// Removes the pattern of the given id from the trie. Returns false if
+    /// the id is unknown or already removed. Like `remove_with_prefix`, its
+    /// DFA is kept in memory until `compact` is called.
+    pub(crate) fn remove_id(&mut self, id: PatternId) -> bool {
+        let Some(Some((pattern, compiled_index))) = self.patterns.get(id.index()) else {
+            return false;
+        };
+        let prefix = stored_prefix(pattern, self.is_case_insensitive(id), &self.extra_specials);
+        let compiled_index = *compiled_index;
+        self.invalidate_candidate_memo();
+        remove_from_node(&mut self.root, &prefix, compiled_index);
+        self.first_bytes = FirstBytes::of_root(&self.root);
+        self.forget(id);
+        true
+    }
+
+    /// Removes a pattern from the lookup tables, once removed from the trie
+    /// nodes. Its slot is kept so the other patterns keep their id.
+    pub(crate) fn forget(&mut self, id: PatternId) {
+        if let Some((pattern, compiled_index)) = self.patterns[id.index()].take() {
+            self.ids.remove(&pattern);
+            self.verbatim_literals.remove(&pattern);
+            if compiled_index.is_some() {
+                self.unregister_canonical(id, &pattern);
+            }
+        }
+        self.tags.remove(&id);
+        self.tie_breaker.weights.remove(&id);
+        self.case_insensitive.remove(&id);
+        self.expirations.remove(&id);
+    }
+
+    /// Frees the DFAs of the removed patterns, which are kept until then, and
+    /// reports how much memory was reclaimed. Ids of the patterns don't
+    /// change.
+    pub fn compact(&mut self) -> CompactReport {
+        let mut live = vec![false; self.compiled_patterns.len()];
+        for (_, compiled_index) in self.patterns.iter().flatten() {
+            if let Some(compiled_index) = compiled_index {
+                live[*compiled_index] = true;
+            }
+        }
+        if live.iter().all(|&live| live) {
+            return CompactReport::default();
+        }
+
+        let mut report = CompactReport::default();
+        // New index of every live compiled pattern (dead ones are unused).
+        let mut new_indices = vec![0; live.len()];
+        let compiled_patterns = std::mem::take(&mut self.compiled_patterns);
+        let required_literals = std::mem::take(&mut self.required_literals);
+        let literal_suffixes = std::mem::take(&mut self.literal_suffixes);
+        let match_lengths = std::mem::take(&mut self.match_lengths);
+        for (index, (((compiled, required_literal), literal_suffix), match_length)) in
+            compiled_patterns
+                .into_iter()
+                .zip(required_literals)
+                .zip(literal_suffixes)
+                .zip(match_lengths)
+                .enumerate()
+        {
+            if live[index] {
+                new_indices[index] = self.compiled_patterns.len();
+                self.compiled_patterns.push(compiled);
+                self.required_literals.push(required_literal);
+                self.literal_suffixes.push(literal_suffix);
+                self.match_lengths.push(match_length);
+            } else {
+                let (_, dfa, _) = compiled;
+                report.freed_dfas += 1;
+                report.freed_bytes += dfa.memory_usage();
+            }
+        }
+        self.compiled_patterns.shrink_to_fit();
+        self.required_literals.shrink_to_fit();
+        self.literal_suffixes.shrink_to_fit();
+        self.match_lengths.shrink_to_fit();
+
+        for (_, compiled_index) in self.patterns.iter_mut().flatten() {
+            if let Some(compiled_index) = compiled_index {
+                *compiled_index = new_indices[*compiled_index];
+            }
+        }
+        if !self.candidate_priority.is_empty() {
+            let mut candidate_priority = vec![0; self.compiled_patterns.len()];
+            for (index, &hits) in self.candidate_priority.iter().enumerate() {
+                if live[index] {
+                    candidate_priority[new_indices[index]] = hits;
+                }
+            }
+            self.candidate_priority = candidate_priority;
+        }
+        self.invalidate_candidate_memo();
+        remap_indices(&mut self.root, &new_indices);
+        self.reset_min_scores();
+        self.rebuild_alternations();
+        self.rebuild_trigram_index();
+        self.rebuild_suffix_index();
+        report
+    }
+}
@@ -0,0 +1,39 @@
+use std::{mem, sync::Arc};
+
+use crate::{RegexTrie, regex_trie::Scratch};
+
+/// All the patterns fully matching a single line of a text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineMatches {
+    /// Index of the line in the text, starting from 0
+    pub line: usize,
+    /// Matching patterns, in the `find_matches` order
+    pub matches: Vec<Arc<str>>,
+}
+
+impl RegexTrie {
+    /// Matches every line of a text, as `find_matches` would, in a single
+    /// call. Lines are split on `\n` (a trailing `\r` is dropped), and the
+    /// query buffers are reused from one line to the other, so it's much
+    /// cheaper than calling `find_matches` on millions of lines.
+    ///
+    /// Only the lines with at least one match are returned, in text order.
+    #[must_use]
+    pub fn scan_text(&self, text: &str) -> Vec<LineMatches> {
+        let mut scratch = Scratch::default();
+        let mut matches = Vec::new();
+        let mut lines = Vec::new();
+
+        for (line, content) in text.lines().enumerate() {
+            self.find_matches_into(content, &mut scratch, &mut matches);
+            if !matches.is_empty() {
+                lines.push(LineMatches {
+                    line,
+                    matches: mem::take(&mut matches),
+                });
+            }
+        }
+
+        lines
+    }
+}
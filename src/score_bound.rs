@@ -0,0 +1,133 @@
+use std::cmp::Reverse;
+
+use crate::{
+    MatchMode, RegexTrie, SelectionPolicy,
+    regex_trie::{Scratch, TrieNode},
+};
+
+impl TrieNode {
+    /// Lowers the minimum score of the nodes along the path, the root
+    /// included, for a pattern stored at its end.
+    pub(crate) fn lower_min_score(&mut self, prefix: &[(char, bool)], score: usize) {
+        let mut node = self;
+        node.min_score = node.min_score.min(score);
+        for (ch, _) in prefix {
+            let Some(child) = node.children.get_mut(ch) else {
+                return;
+            };
+            node = child;
+            node.min_score = node.min_score.min(score);
+        }
+    }
+}
+
+/// Sets the exact minimum score of every node of the subtree, from the
+/// scores of the compiled patterns, and the scorer for the plain ones.
+/// Returns the one of the subtree root.
+fn reset_min_scores(
+    node: &mut TrieNode,
+    path: &mut String,
+    compiled_scores: &[usize],
+    literal_score: &dyn Fn(&str) -> usize,
+) -> usize {
+    let mut min_score = node
+        .pattern_indices
+        .iter()
+        .map(|&index| compiled_scores[index])
+        .min()
+        .unwrap_or(usize::MAX);
+    if node.contains_non_regex_prefix {
+        min_score = min_score.min(literal_score(path));
+    }
+    for (&ch, child) in &mut node.children {
+        let len = path.len();
+        if child.is_escaped {
+            path.push('\\');
+        }
+        path.push(ch);
+        min_score = min_score.min(reset_min_scores(
+            child,
+            path,
+            compiled_scores,
+            literal_score,
+        ));
+        path.truncate(len);
+    }
+    node.min_score = min_score;
+    min_score
+}
+
+impl RegexTrie {
+    /// Recomputes the minimum score of every node, which removals leave
+    /// lower than needed.
+    pub(crate) fn reset_min_scores(&mut self) {
+        let compiled_scores: Vec<usize> = self
+            .compiled_patterns
+            .iter()
+            .map(|(_, _, score)| *score)
+            .collect();
+        let scorer = &self.scorer;
+        reset_min_scores(
+            &mut self.root,
+            &mut String::new(),
+            &compiled_scores,
+            &|literal| scorer(literal, false),
+        );
+    }
+
+    /// Same as `collect_candidates`, for a best match query: in prefix mode,
+    /// with the lowest score selection, the walk stops descending once a
+    /// plain prefix has been passed that no pattern of the subtree below
+    /// can beat, according to the minimum score of its nodes. Returns
+    /// whether a plain pattern is equal to the input.
+    pub(crate) fn collect_best_candidates(&self, input: &str, scratch: &mut Scratch) -> bool {
+        if self.match_mode != MatchMode::Prefix
+            || self.selection_policy != SelectionPolicy::LowestScore
+            || self.candidate_memo.is_some()
+            || self.hit_counters.is_some()
+        {
+            return self.collect_candidates(input, scratch);
+        }
+
+        scratch.candidate_indices.clear();
+        scratch.literal_ends.clear();
+        scratch.escaped_pattern.clear();
+        // Highest score a pattern below can have to still be selected
+        let mut max_score = usize::MAX;
+        let visit = |node: &TrieNode, scratch: &mut Scratch, max_score: &mut usize| {
+            scratch
+                .candidate_indices
+                .extend_from_slice(&node.pattern_indices);
+            if node.contains_non_regex_prefix {
+                scratch.literal_ends.push(scratch.escaped_pattern.len());
+                let score = (self.scorer)(&scratch.escaped_pattern, false);
+                *max_score =
+                    (*max_score).min(self.max_useful_score(&(Reverse(0), Reverse(0), score)));
+            }
+        };
+
+        let mut node = &self.root;
+        visit(node, scratch, &mut max_score);
+        let mut input_match_entirely = true;
+        for ch in input.chars() {
+            let Some(child) = node
+                .children
+                .get(&ch)
+                .filter(|child| child.min_score <= max_score)
+            else {
+                input_match_entirely = false;
+                break;
+            };
+            if child.is_escaped {
+                scratch.escaped_pattern.push('\\');
+            }
+            scratch.escaped_pattern.push(ch);
+            node = child;
+            visit(node, scratch, &mut max_score);
+        }
+
+        // Every pattern is stored in a single node, so there's no duplicate.
+        scratch.candidate_indices.sort_unstable();
+        input_match_entirely && node.contains_non_regex_prefix
+    }
+}
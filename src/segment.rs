@@ -0,0 +1,380 @@
+use std::{collections::HashMap, sync::Arc};
+
+use regex_syntax::{
+    Parser,
+    hir::{Class, Hir, HirKind},
+};
+
+use crate::{
+    PatternMatcher, RegexTrieError,
+    engine::{CompiledRegex, DfaConfig, compile},
+    regex_trie::{ScorerFuncType, default_scorer, is_full_match, parse_literal_prefix},
+};
+
+/// How a pattern of a `PathSegmentTrie` (or a `TokenTrie`) is checked
+/// against an input.
+#[derive(Debug)]
+pub(crate) enum Verifier {
+    /// Plain pattern, with its unescaped text
+    Literal(Box<str>),
+    /// Compiled regex
+    Regex(CompiledRegex),
+}
+
+/// Segment of a pattern, as indexed in the trie.
+enum Segment {
+    /// Literal segment, matched by equality
+    Literal(String),
+    /// Regex segment, which can't match a `/`, with its source and its
+    /// compiled form
+    Wildcard(String, CompiledRegex),
+}
+
+/// A node of a `PathSegmentTrie`, reached by a sequence of segments.
+#[derive(Debug, Default)]
+struct SegmentNode {
+    /// Children reached by a literal segment
+    literals: HashMap<Box<str>, SegmentNode>,
+    /// Children reached by a regex segment, with its source
+    wildcards: Vec<(Box<str>, CompiledRegex, SegmentNode)>,
+    /// Patterns whose segments all lead here
+    complete: Vec<usize>,
+    /// Patterns whose next segments aren't indexed (e.g. `.*` could span
+    /// several segments), so they're candidates for any input going through
+    /// this node
+    partial: Vec<usize>,
+}
+
+impl SegmentNode {
+    /// Number of nodes of the subtree.
+    fn count(&self) -> usize {
+        1 + self.literals.values().map(Self::count).sum::<usize>()
+            + self
+                .wildcards
+                .iter()
+                .map(|(_, _, child)| child.count())
+                .sum::<usize>()
+    }
+
+    /// Collects the patterns whose indexed segments match the input ones.
+    fn candidates(&self, segments: &[&str], out: &mut Vec<usize>) {
+        out.extend_from_slice(&self.partial);
+        let Some((segment, rest)) = segments.split_first() else {
+            out.extend_from_slice(&self.complete);
+            return;
+        };
+        if let Some(child) = self.literals.get(*segment) {
+            child.candidates(rest, out);
+        }
+        for (_, regex, child) in &self.wildcards {
+            if is_full_match(regex, segment.as_bytes()) {
+                child.candidates(rest, out);
+            }
+        }
+    }
+}
+
+/// Whether a regex can match a `/`, in which case it could span segments.
+fn can_match_slash(hir: &Hir) -> bool {
+    match hir.kind() {
+        HirKind::Empty | HirKind::Look(_) => false,
+        HirKind::Literal(literal) => literal.0.contains(&b'/'),
+        HirKind::Class(Class::Unicode(class)) => class
+            .ranges()
+            .iter()
+            .any(|range| range.start() <= '/' && '/' <= range.end()),
+        HirKind::Class(Class::Bytes(class)) => class
+            .ranges()
+            .iter()
+            .any(|range| range.start() <= b'/' && b'/' <= range.end()),
+        HirKind::Repetition(repetition) => can_match_slash(&repetition.sub),
+        HirKind::Capture(capture) => can_match_slash(&capture.sub),
+        HirKind::Concat(hirs) | HirKind::Alternation(hirs) => hirs.iter().any(can_match_slash),
+    }
+}
+
+/// Splits a regex on its `/`, outside of character classes (e.g. `[^/]`).
+fn split_parts(pattern: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut in_class = false;
+    let mut chars = pattern.char_indices();
+    while let Some((position, ch)) = chars.next() {
+        match ch {
+            '\\' => {
+                chars.next();
+            }
+            '[' => in_class = true,
+            ']' => in_class = false,
+            '/' if !in_class => {
+                parts.push(&pattern[start..position]);
+                start = position + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&pattern[start..]);
+    parts
+}
+
+/// Splits a regex on its `/`, as long as its parts are regexes of their own
+/// which can't match a `/`, i.e. the regex is their concatenation. The rest
+/// of the regex isn't split, e.g. after a `.*`.
+fn split_regex(pattern: &str) -> Vec<Segment> {
+    // A top-level alternation isn't a concatenation of its parts.
+    let is_alternation = Parser::new()
+        .parse(pattern)
+        .is_ok_and(|hir| matches!(hir.kind(), HirKind::Alternation(_)));
+    if is_alternation {
+        return Vec::new();
+    }
+
+    let mut segments = Vec::new();
+    for part in split_parts(pattern) {
+        // Flags could apply to the next parts as well.
+        if part.replace("(?:", "").contains("(?") {
+            break;
+        }
+        let Some(hir) = Parser::new()
+            .parse(part)
+            .ok()
+            .filter(|hir| !can_match_slash(hir))
+        else {
+            break;
+        };
+        let segment = match hir.kind() {
+            HirKind::Empty => Segment::Literal(String::new()),
+            HirKind::Literal(literal) => match std::str::from_utf8(&literal.0) {
+                Ok(literal) => Segment::Literal(literal.to_string()),
+                Err(_) => break,
+            },
+            _ => match compile(part, false, false, &DfaConfig::default()) {
+                Ok(regex) => Segment::Wildcard(part.to_string(), regex),
+                Err(_) => break,
+            },
+        };
+        segments.push(segment);
+    }
+    segments
+}
+
+/// Alternative to `RegexTrie` for path-like inputs (file paths, URL paths),
+/// keyed on the segments between `/` instead of the characters. Literal
+/// segments are looked up by equality, and regex segments (e.g. `[0-9]+`)
+/// by matching the input segment, so the literal segments after a wildcard
+/// are still indexed: `/users/[0-9]+/profile` and `/users/[0-9]+/settings`
+/// are told apart before running any full regex. A pattern is only indexed
+/// up to the first segment which could match a `/` (e.g. `.*`).
+///
+/// Patterns fully match the input, as with `RegexTrie::find_matches` in
+/// `MatchMode::Full`, and are scored the same way.
+pub struct PathSegmentTrie {
+    /// Root node, reached by no segment
+    root: SegmentNode,
+    /// Every pattern, with its verifier and its score, in insertion order
+    patterns: Vec<(Arc<str>, Verifier, usize)>,
+    /// Reverse lookup of `patterns`
+    ids: HashMap<Arc<str>, usize>,
+    /// Scorer function
+    scorer: ScorerFuncType,
+}
+
+impl Default for PathSegmentTrie {
+    fn default() -> Self {
+        Self::new_with_custom_scorer(Box::new(default_scorer))
+    }
+}
+
+impl std::fmt::Debug for PathSegmentTrie {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PathSegmentTrie")
+            .field("root", &self.root)
+            .field("patterns", &self.patterns)
+            .finish_non_exhaustive()
+    }
+}
+
+impl PathSegmentTrie {
+    /// Creates a new, empty trie with default scorer.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new, empty trie with a custom scorer.
+    #[must_use]
+    pub fn new_with_custom_scorer(scorer: ScorerFuncType) -> Self {
+        Self {
+            root: SegmentNode::default(),
+            patterns: Vec::default(),
+            ids: HashMap::default(),
+            scorer,
+        }
+    }
+
+    /// Creates a new trie from a set of patterns.
+    ///
+    /// ## Errors
+    ///
+    /// If any of the regex pattern can't be compiled
+    pub fn from(patterns: &[String]) -> Result<Self, RegexTrieError> {
+        let mut trie = Self::new();
+        trie.insert_many(patterns)?;
+        Ok(trie)
+    }
+
+    /// Compiles a pattern and inserts it into the trie. Inserting an already
+    /// known pattern does nothing.
+    ///
+    /// ## Errors
+    ///
+    /// If the regex pattern can't be compiled
+    pub fn insert(&mut self, pattern: &str) -> Result<(), RegexTrieError> {
+        self.insert_many(&[pattern.to_string()])
+    }
+
+    /// Insert many patterns at once. Nothing is inserted if any pattern
+    /// can't be compiled.
+    ///
+    /// ## Errors
+    ///
+    /// If any regex pattern can't be compiled
+    pub fn insert_many(&mut self, patterns: &[String]) -> Result<(), RegexTrieError> {
+        let compiled = patterns
+            .iter()
+            .map(|pattern| {
+                let (prefix, is_regex) = parse_literal_prefix(pattern);
+                if !is_regex {
+                    let literal: String = prefix.into_iter().map(|(ch, _)| ch).collect();
+                    let segments = literal
+                        .split('/')
+                        .map(|segment| Segment::Literal(segment.to_string()))
+                        .collect();
+                    return Ok((Verifier::Literal(literal.into_boxed_str()), segments));
+                }
+                Ok((
+                    Verifier::Regex(compile(pattern, false, false, &DfaConfig::default())?),
+                    split_regex(pattern),
+                ))
+            })
+            .collect::<Result<Vec<_>, RegexTrieError>>()?;
+
+        for (pattern, (verifier, segments)) in patterns.iter().zip(compiled) {
+            if self.ids.contains_key(pattern.as_str()) {
+                continue;
+            }
+            let index = self.patterns.len();
+            let is_complete = match &verifier {
+                Verifier::Literal(literal) => literal.split('/').count(),
+                Verifier::Regex(_) => split_parts(pattern).len(),
+            } == segments.len();
+
+            let mut node = &mut self.root;
+            for segment in segments {
+                node = match segment {
+                    Segment::Literal(literal) => {
+                        node.literals.entry(literal.into_boxed_str()).or_default()
+                    }
+                    Segment::Wildcard(source, regex) => {
+                        let position = node
+                            .wildcards
+                            .iter()
+                            .position(|(known, _, _)| **known == source);
+                        let position = position.unwrap_or_else(|| {
+                            node.wildcards.push((
+                                source.into_boxed_str(),
+                                regex,
+                                SegmentNode::default(),
+                            ));
+                            node.wildcards.len() - 1
+                        });
+                        &mut node.wildcards[position].2
+                    }
+                };
+            }
+            if is_complete {
+                node.complete.push(index);
+            } else {
+                node.partial.push(index);
+            }
+
+            let score = (self.scorer)(pattern, matches!(verifier, Verifier::Regex(_)));
+            let pattern: Arc<str> = Arc::from(pattern.as_str());
+            self.ids.insert(Arc::clone(&pattern), index);
+            self.patterns.push((pattern, verifier, score));
+        }
+
+        Ok(())
+    }
+
+    /// Number of nodes of the trie, including the root.
+    #[must_use]
+    pub fn node_count(&self) -> usize {
+        self.root.count()
+    }
+
+    /// Finds all patterns fully matching the input. The order is the one of
+    /// `RegexTrie::find_matches`: the plain pattern equal to the input comes
+    /// first if any, then the matching regexes in insertion order.
+    #[must_use]
+    pub fn find_matches(&self, input: &str) -> Vec<String> {
+        self.matching(input)
+            .map(|(pattern, _)| pattern.to_string())
+            .collect()
+    }
+
+    /// Finds the pattern with the lowest score fully matching the input. On
+    /// equal scores, the first one of `find_matches` wins.
+    #[must_use]
+    pub fn find_best_match(&self, input: &str) -> Option<String> {
+        let mut best_match: Option<(&str, usize)> = None;
+        for (pattern, score) in self.matching(input) {
+            if best_match.is_none_or(|(_, best_score)| score < best_score) {
+                best_match = Some((pattern, score));
+            }
+        }
+
+        best_match.map(|(pattern, _)| pattern.to_string())
+    }
+
+    /// Iterates over all the patterns fully matching the input, with their
+    /// score, in the `find_matches` order.
+    fn matching<'trie>(
+        &'trie self,
+        input: &'trie str,
+    ) -> impl Iterator<Item = (&'trie str, usize)> {
+        let segments: Vec<&str> = input.split('/').collect();
+        let mut candidates = Vec::new();
+        self.root.candidates(&segments, &mut candidates);
+        candidates.sort_unstable_by_key(|&index| {
+            (matches!(self.patterns[index].1, Verifier::Regex(_)), index)
+        });
+
+        candidates.into_iter().filter_map(move |index| {
+            let (pattern, verifier, score) = &self.patterns[index];
+            let is_match = match verifier {
+                Verifier::Literal(literal) => **literal == *input,
+                Verifier::Regex(regex) => is_full_match(regex, input.as_bytes()),
+            };
+            is_match.then_some((&**pattern, *score))
+        })
+    }
+}
+
+impl PatternMatcher for PathSegmentTrie {
+    fn insert(&mut self, pattern: &str) -> Result<(), RegexTrieError> {
+        Self::insert(self, pattern)
+    }
+
+    fn insert_many(&mut self, patterns: &[String]) -> Result<(), RegexTrieError> {
+        Self::insert_many(self, patterns)
+    }
+
+    fn find_matches(&self, input: &str) -> Vec<String> {
+        Self::find_matches(self, input)
+    }
+
+    fn find_best_match(&self, input: &str) -> Option<String> {
+        Self::find_best_match(self, input)
+    }
+}
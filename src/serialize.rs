@@ -0,0 +1,345 @@
+use std::borrow::Cow;
+
+use regex_automata::dfa::{dense::DFA, regex::Regex};
+
+use crate::{
+    Engine, InsertOptions, PatternId, RegexTrie, RegexTrieError,
+    engine::{CompiledRegex, compile_fallback},
+    options::{regex_source, stored_prefix},
+    regex_trie::ScorerFuncType,
+};
+
+/// Header of every serialized trie, with the format version
+const MAGIC: &[u8; 8] = b"RGXTRIE1";
+/// Marker of a plain (non regex) pattern
+const KIND_LITERAL: u8 = 0;
+/// Marker of a compiled regex pattern
+const KIND_REGEX: u8 = 1;
+/// Marker of a removed pattern, whose id is kept reserved
+const KIND_REMOVED: u8 = 2;
+/// Marker of a compiled pattern ignoring case, plain or regex
+const KIND_CASE_INSENSITIVE: u8 = 3;
+/// Marker of a regex pattern run by the fallback engine, compiled again on
+/// load
+const KIND_FALLBACK: u8 = 4;
+/// Marker of a pattern ignoring case, run by the fallback engine
+const KIND_FALLBACK_CASE_INSENSITIVE: u8 = 5;
+/// Alignment of the DFAs serialized with `aligned`, so they can be used in
+/// place
+const DFA_ALIGNMENT: usize = 8;
+
+/// Appends a length prefixed byte string.
+fn write_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    out.extend_from_slice(&(bytes.len() as u64).to_le_bytes());
+    out.extend_from_slice(bytes);
+}
+
+/// Appends a serialized DFA, without the padding regex-automata adds for
+/// alignment. With `aligned`, zeros are added after its length so it starts
+/// at an offset multiple of `DFA_ALIGNMENT` in the output.
+fn write_dfa<T: AsRef<[u32]>>(out: &mut Vec<u8>, dfa: &DFA<T>, aligned: bool) {
+    let (bytes, padding) = dfa.to_bytes_native_endian();
+    let bytes = &bytes[padding..];
+    if !aligned {
+        write_bytes(out, bytes);
+        return;
+    }
+    out.extend_from_slice(&(bytes.len() as u64).to_le_bytes());
+    out.resize(out.len().next_multiple_of(DFA_ALIGNMENT), 0);
+    out.extend_from_slice(bytes);
+}
+
+/// Appends the forward and reverse DFAs of a compiled pattern, if it has
+/// some.
+fn write_dfas(out: &mut Vec<u8>, compiled: &CompiledRegex, aligned: bool) {
+    match compiled {
+        CompiledRegex::Dfa(dfa) => {
+            write_dfa(out, dfa.forward(), aligned);
+            write_dfa(out, dfa.reverse(), aligned);
+        }
+        #[cfg(unix)]
+        CompiledRegex::Shared(shared) => {
+            write_dfa(out, shared.regex().forward(), aligned);
+            write_dfa(out, shared.regex().reverse(), aligned);
+        }
+        CompiledRegex::Meta(_) => {}
+    }
+}
+
+/// Cursor over serialized bytes.
+pub(crate) struct Reader<'data> {
+    /// Remaining bytes to read
+    data: &'data [u8],
+    /// Number of bytes already read
+    offset: usize,
+}
+
+impl<'data> Reader<'data> {
+    /// Reads exactly `len` bytes.
+    ///
+    /// ## Errors
+    ///
+    /// If there isn't enough bytes left
+    fn take(&mut self, len: usize) -> Result<&'data [u8], RegexTrieError> {
+        if self.data.len() < len {
+            return Err(invalid("unexpected end of data"));
+        }
+        let (head, tail) = self.data.split_at(len);
+        self.data = tail;
+        self.offset += len;
+        Ok(head)
+    }
+
+    /// Reads a single byte.
+    ///
+    /// ## Errors
+    ///
+    /// If there isn't enough bytes left
+    fn read_u8(&mut self) -> Result<u8, RegexTrieError> {
+        Ok(self.take(1)?[0])
+    }
+
+    /// Reads a little endian integer.
+    ///
+    /// ## Errors
+    ///
+    /// If there isn't enough bytes left, or the integer doesn't fit a `usize`
+    fn read_usize(&mut self) -> Result<usize, RegexTrieError> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.take(8)?);
+        usize::try_from(u64::from_le_bytes(bytes)).map_err(|_err| invalid("integer overflow"))
+    }
+
+    /// Reads a length prefixed byte string.
+    ///
+    /// ## Errors
+    ///
+    /// If there isn't enough bytes left
+    fn read_bytes(&mut self) -> Result<&'data [u8], RegexTrieError> {
+        let len = self.read_usize()?;
+        self.take(len)
+    }
+
+    /// Reads a length prefixed UTF-8 string.
+    ///
+    /// ## Errors
+    ///
+    /// If there isn't enough bytes left, or the string isn't valid UTF-8
+    fn read_str(&mut self) -> Result<&'data str, RegexTrieError> {
+        std::str::from_utf8(self.read_bytes()?).map_err(|err| invalid(err.to_string()))
+    }
+
+    /// Reads a DFA serialized with `aligned`, returning its bytes, which
+    /// start at an offset multiple of `DFA_ALIGNMENT`.
+    ///
+    /// ## Errors
+    ///
+    /// If there isn't enough bytes left
+    pub(crate) fn read_aligned_dfa_bytes(&mut self) -> Result<&'data [u8], RegexTrieError> {
+        let len = self.read_usize()?;
+        self.take(self.offset.next_multiple_of(DFA_ALIGNMENT) - self.offset)?;
+        self.take(len)
+    }
+
+    /// Reads a serialized DFA.
+    ///
+    /// ## Errors
+    ///
+    /// If there isn't enough bytes left, or the DFA is invalid
+    fn read_dfa(&mut self) -> Result<DFA<Vec<u32>>, RegexTrieError> {
+        // DFAs must be deserialized from u32 aligned memory, which a
+        // sub-slice of the input isn't guaranteed to be.
+        let bytes = self.read_bytes()?;
+        let mut words = vec![0_u32; bytes.len().div_ceil(4)];
+        for (word, chunk) in words.iter_mut().zip(bytes.chunks(4)) {
+            let mut word_bytes = [0; 4];
+            word_bytes[..chunk.len()].copy_from_slice(chunk);
+            *word = u32::from_ne_bytes(word_bytes);
+        }
+        // SAFETY: the pointer comes from a live `Vec<u32>`, whose memory is
+        // initialized and at least `bytes.len()` bytes long, and `u8` has no
+        // alignment requirement nor invalid bit pattern.
+        let aligned =
+            unsafe { std::slice::from_raw_parts(words.as_ptr().cast::<u8>(), bytes.len()) };
+
+        let (dfa, _) = DFA::from_bytes(aligned).map_err(|err| invalid(err.to_string()))?;
+        Ok(dfa.to_owned())
+    }
+}
+
+/// Builds an invalid data error.
+pub(crate) fn invalid(reason: impl Into<String>) -> RegexTrieError {
+    RegexTrieError::InvalidSerializedData(reason.into())
+}
+
+impl RegexTrie {
+    /// Serializes the trie along with its compiled DFAs, so it can be loaded
+    /// back with `from_bytes` without compiling anything. Scores are kept,
+    /// but not the scorer function itself.
+    ///
+    /// The DFAs are stored with the native endianness, so the output can
+    /// only be loaded on a machine with the same endianness.
+    #[must_use]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.serialize(MAGIC, false)
+    }
+
+    /// Serializes the trie after the given header, see `to_bytes`. With
+    /// `aligned`, the DFAs start at offsets multiple of `DFA_ALIGNMENT`.
+    pub(crate) fn serialize(&self, magic: &[u8], aligned: bool) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(magic);
+        out.extend_from_slice(&(self.patterns.len() as u64).to_le_bytes());
+
+        // Patterns are written in insertion order, so they keep their id.
+        for (index, slot) in self.patterns.iter().enumerate() {
+            let Some((pattern, compiled_index)) = slot else {
+                out.push(KIND_REMOVED);
+                continue;
+            };
+            let Some(compiled_index) = compiled_index else {
+                out.push(KIND_LITERAL);
+                write_bytes(&mut out, pattern.as_bytes());
+                continue;
+            };
+
+            let (_, compiled, score) = &self.compiled_patterns[*compiled_index];
+            let case_insensitive = self.is_case_insensitive(PatternId::new(index));
+            out.push(match (compiled.engine(), case_insensitive) {
+                (Engine::Dfa, false) => KIND_REGEX,
+                (Engine::Dfa, true) => KIND_CASE_INSENSITIVE,
+                (Engine::Meta, false) => KIND_FALLBACK,
+                (Engine::Meta, true) => KIND_FALLBACK_CASE_INSENSITIVE,
+            });
+            write_bytes(&mut out, pattern.as_bytes());
+            out.extend_from_slice(&(*score as u64).to_le_bytes());
+            write_dfas(&mut out, compiled, aligned);
+        }
+
+        out
+    }
+
+    /// Loads a trie serialized with `to_bytes`, using the default scorer for
+    /// any further insert.
+    ///
+    /// ## Errors
+    ///
+    /// If the data is truncated, corrupted, or has been serialized on a
+    /// machine with a different endianness
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, RegexTrieError> {
+        let mut trie = Self::new();
+        trie.load_bytes(bytes)?;
+        Ok(trie)
+    }
+
+    /// Loads a trie serialized with `to_bytes`, using the given scorer for
+    /// any further insert. Already serialized patterns keep their score.
+    ///
+    /// ## Errors
+    ///
+    /// If the data is truncated, corrupted, or has been serialized on a
+    /// machine with a different endianness
+    pub fn from_bytes_with_scorer(
+        bytes: &[u8],
+        scorer: ScorerFuncType,
+    ) -> Result<Self, RegexTrieError> {
+        let mut trie = Self::new_with_custom_scorer(scorer);
+        trie.load_bytes(bytes)?;
+        Ok(trie)
+    }
+
+    /// Inserts all the serialized patterns in the trie.
+    ///
+    /// ## Errors
+    ///
+    /// If the data is invalid
+    fn load_bytes(&mut self, bytes: &[u8]) -> Result<(), RegexTrieError> {
+        self.load_with(bytes, MAGIC, |reader| {
+            let forward = reader.read_dfa()?;
+            let reverse = reader.read_dfa()?;
+            let dfa = Regex::builder().build_from_dfas(forward, reverse);
+            Ok(CompiledRegex::Dfa(Box::new(dfa)))
+        })
+    }
+
+    /// Inserts all the patterns serialized after the given header, reading
+    /// the DFAs of the compiled ones with `read_dfas`.
+    ///
+    /// ## Errors
+    ///
+    /// If the data is invalid
+    pub(crate) fn load_with<'data>(
+        &mut self,
+        bytes: &'data [u8],
+        magic: &[u8],
+        mut read_dfas: impl FnMut(&mut Reader<'data>) -> Result<CompiledRegex, RegexTrieError>,
+    ) -> Result<(), RegexTrieError> {
+        let mut reader = Reader {
+            data: bytes,
+            offset: 0,
+        };
+        if reader.take(magic.len())? != magic {
+            return Err(invalid("not a serialized regex trie"));
+        }
+
+        for _ in 0..reader.read_usize()? {
+            let kind = reader.read_u8()?;
+            if kind == KIND_REMOVED {
+                self.add_removed_pattern();
+                continue;
+            }
+            let pattern = reader.read_str()?;
+            let (prefix, is_regex) = self.parse_prefix(pattern);
+            match kind {
+                KIND_LITERAL if !is_regex => {
+                    self.add_pattern(
+                        Cow::Borrowed(pattern),
+                        &prefix,
+                        None,
+                        InsertOptions::default(),
+                    );
+                }
+                KIND_REGEX | KIND_CASE_INSENSITIVE if is_regex || kind == KIND_CASE_INSENSITIVE => {
+                    let options = InsertOptions {
+                        case_insensitive: kind == KIND_CASE_INSENSITIVE,
+                    };
+                    let score = reader.read_usize()?;
+                    let compiled = read_dfas(&mut reader)?;
+                    self.add_pattern(
+                        Cow::Borrowed(pattern),
+                        &stored_prefix(pattern, options.case_insensitive, &self.extra_specials),
+                        Some((compiled, score)),
+                        options,
+                    );
+                }
+                KIND_FALLBACK | KIND_FALLBACK_CASE_INSENSITIVE
+                    if is_regex || kind == KIND_FALLBACK_CASE_INSENSITIVE =>
+                {
+                    let options = InsertOptions {
+                        case_insensitive: kind == KIND_FALLBACK_CASE_INSENSITIVE,
+                    };
+                    let score = reader.read_usize()?;
+                    let compiled = compile_fallback(
+                        &regex_source(pattern, &self.extra_specials),
+                        options.case_insensitive,
+                    )
+                    .map_err(|err| invalid(format!("can't compile {pattern:?}: {err}")))?;
+                    self.add_pattern(
+                        Cow::Borrowed(pattern),
+                        &stored_prefix(pattern, options.case_insensitive, &self.extra_specials),
+                        Some((compiled, score)),
+                        options,
+                    );
+                }
+                _ => return Err(invalid(format!("unexpected pattern kind for {pattern:?}"))),
+            }
+        }
+
+        self.refresh_alternations();
+        if reader.data.is_empty() {
+            Ok(())
+        } else {
+            Err(invalid("trailing data"))
+        }
+    }
+}
@@ -0,0 +1,151 @@
+use std::{
+    borrow::Cow,
+    hash::{DefaultHasher, Hash, Hasher},
+    sync::{PoisonError, RwLock},
+};
+
+use rayon::iter::{IndexedParallelIterator, IntoParallelIterator, ParallelIterator};
+
+use crate::{RegexTrie, RegexTrieError};
+
+/// A set of tries, partitioned by the hash of the literal prefix of the
+/// patterns, for write-heavy workloads: each shard has its own lock, so
+/// inserts into different shards run concurrently, and patterns are compiled
+/// under a read lock, so they don't block the queries of their shard either.
+///
+/// Queries fan out to every shard, one after the other. Matches are ordered
+/// by shard, then by insertion order within a shard.
+#[derive(Debug)]
+pub struct ShardedRegexTrie {
+    /// Tries holding the patterns of every shard
+    shards: Vec<RwLock<RegexTrie>>,
+}
+
+impl ShardedRegexTrie {
+    /// Creates a new, empty trie split in the given number of shards, at least
+    /// one.
+    #[must_use]
+    pub fn new(shard_count: usize) -> Self {
+        Self {
+            shards: (0..shard_count.max(1))
+                .map(|_| RwLock::new(RegexTrie::new()))
+                .collect(),
+        }
+    }
+
+    /// Number of shards.
+    #[must_use]
+    pub const fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// Shard of a pattern, from the hash of its literal prefix. Patterns
+    /// sharing a literal prefix land in the same shard, where they share the
+    /// trie nodes.
+    #[must_use]
+    pub fn shard_of(&self, pattern: &str) -> usize {
+        let mut hasher = DefaultHasher::new();
+        RegexTrie::literal_prefix(pattern).hash(&mut hasher);
+        usize::try_from(hasher.finish() % self.shards.len() as u64).unwrap_or_default()
+    }
+
+    /// Inserts a pattern into its shard, only locking that shard for writing
+    /// once the pattern is compiled.
+    ///
+    /// ## Errors
+    ///
+    /// If the regex pattern can't be compiled
+    pub fn insert(&self, pattern: &str) -> Result<(), RegexTrieError> {
+        self.insert_into(self.shard_of(pattern), vec![Cow::Borrowed(pattern)])
+    }
+
+    /// Insert many patterns at once, each shard being filled on a separate
+    /// worker thread. Nothing is inserted into a shard with an invalid
+    /// pattern, but the other shards may be filled.
+    ///
+    /// ## Errors
+    ///
+    /// If any regex pattern can't be compiled
+    pub fn insert_many(&self, patterns: &[String]) -> Result<(), RegexTrieError> {
+        let mut batches = vec![Vec::new(); self.shards.len()];
+        for pattern in patterns {
+            batches[self.shard_of(pattern)].push(Cow::Borrowed(pattern.as_str()));
+        }
+        batches
+            .into_par_iter()
+            .enumerate()
+            .filter(|(_, batch)| !batch.is_empty())
+            .try_for_each(|(shard, batch)| self.insert_into(shard, batch))
+    }
+
+    /// Compiles the patterns under a read lock of the shard, then adds them
+    /// under its write lock.
+    fn insert_into(&self, shard: usize, patterns: Vec<Cow<'_, str>>) -> Result<(), RegexTrieError> {
+        let lock = &self.shards[shard];
+        let prepared = lock
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .prepare_many(patterns)?;
+        lock.write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .commit_many(prepared);
+        Ok(())
+    }
+
+    /// Number of patterns, over all the shards.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|shard| {
+                shard
+                    .read()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .iter()
+                    .count()
+            })
+            .sum()
+    }
+
+    /// Whether there's no pattern in any shard.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Finds all the patterns matching the input, in every shard.
+    ///
+    /// See `RegexTrie::find_matches`.
+    #[must_use]
+    pub fn find_matches(&self, input: &str) -> Vec<String> {
+        self.shards
+            .iter()
+            .flat_map(|shard| {
+                shard
+                    .read()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .find_matches(input)
+            })
+            .collect()
+    }
+
+    /// Finds the matching pattern with the lowest score, over all the shards.
+    /// On ties between shards, the first shard wins.
+    ///
+    /// See `RegexTrie::find_best_match`.
+    #[must_use]
+    pub fn find_best_match(&self, input: &str) -> Option<String> {
+        self.shards
+            .iter()
+            .filter_map(|shard| {
+                shard
+                    .read()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .find_matches_with_scores(input)
+                    .into_iter()
+                    .next()
+            })
+            .min_by_key(|(_, score)| *score)
+            .map(|(pattern, _)| pattern)
+    }
+}
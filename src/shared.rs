@@ -0,0 +1,171 @@
+use std::{fs::File, os::fd::AsRawFd, path::Path, ptr::NonNull, sync::Arc};
+
+use regex_automata::dfa::{dense::DFA, regex::Regex};
+
+use crate::{
+    FrozenRegexTrie, RegexTrie, RegexTrieError, engine::CompiledRegex, serialize::invalid,
+};
+
+/// Header of a trie serialized for shared memory, with the format version
+const SHARED_MAGIC: &[u8; 8] = b"RGXTRIS1";
+
+/// Read-only memory mapping of a whole file, shared with every process
+/// mapping the same file: the page cache holds a single copy of it.
+#[derive(Debug)]
+pub(crate) struct SharedRegion {
+    /// Start of the mapping
+    address: NonNull<libc::c_void>,
+    /// Length of the mapping, in bytes
+    len: usize,
+}
+
+// SAFETY: the mapping is read-only, and only unmapped on drop.
+unsafe impl Send for SharedRegion {}
+// SAFETY: the mapping is read-only, and only unmapped on drop.
+unsafe impl Sync for SharedRegion {}
+
+impl SharedRegion {
+    /// Maps the whole file, read-only.
+    ///
+    /// ## Errors
+    ///
+    /// If the file is empty, or can't be mapped
+    fn map(file: &File) -> Result<Self, RegexTrieError> {
+        let len = file
+            .metadata()
+            .map_err(|err| RegexTrieError::SharedMemoryFailed(err.to_string()))
+            .and_then(|metadata| {
+                usize::try_from(metadata.len())
+                    .map_err(|err| RegexTrieError::SharedMemoryFailed(err.to_string()))
+            })?;
+        if len == 0 {
+            return Err(invalid("unexpected end of data"));
+        }
+
+        // SAFETY: a new mapping is requested at an address of the system's
+        // choosing, so no existing memory is affected, for a length checked
+        // to be non-zero, and the file descriptor is open for reading.
+        let address = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ,
+                libc::MAP_SHARED,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if address == libc::MAP_FAILED {
+            return Err(RegexTrieError::SharedMemoryFailed(
+                std::io::Error::last_os_error().to_string(),
+            ));
+        }
+        NonNull::new(address)
+            .map(|address| Self { address, len })
+            .ok_or_else(|| RegexTrieError::SharedMemoryFailed("null mapping".to_string()))
+    }
+
+    /// Bytes of the mapping.
+    const fn bytes(&self) -> &[u8] {
+        // SAFETY: the mapping is `len` bytes long, readable, and page
+        // aligned, and stays mapped until the region is dropped, which the
+        // borrow can't outlive.
+        unsafe { std::slice::from_raw_parts(self.address.as_ptr().cast::<u8>(), self.len) }
+    }
+}
+
+impl Drop for SharedRegion {
+    fn drop(&mut self) {
+        // SAFETY: the mapping was created by `map` with this address and
+        // length, and nothing borrows it anymore, every `SharedRegex` using
+        // it holding the region alive.
+        unsafe {
+            libc::munmap(self.address.as_ptr(), self.len);
+        }
+    }
+}
+
+/// Dense DFAs deserialized in place from a shared region, which they keep
+/// mapped.
+#[derive(Debug, Clone)]
+pub(crate) struct SharedRegex {
+    /// DFAs, borrowing the region (dropped first)
+    regex: Regex<DFA<&'static [u32]>>,
+    /// Region holding the DFAs
+    _region: Arc<SharedRegion>,
+}
+
+impl SharedRegex {
+    /// Forward and reverse DFAs.
+    pub(crate) const fn regex(&self) -> &Regex<DFA<&'static [u32]>> {
+        &self.regex
+    }
+}
+
+/// Deserializes a DFA in place.
+///
+/// ## Errors
+///
+/// If the DFA is invalid, or misaligned
+fn dfa_in_place(bytes: &'static [u8]) -> Result<DFA<&'static [u32]>, RegexTrieError> {
+    DFA::from_bytes(bytes)
+        .map(|(dfa, _)| dfa)
+        .map_err(|err| invalid(err.to_string()))
+}
+
+impl FrozenRegexTrie {
+    /// Writes the trie to a file, to be loaded with `open_shared`. Same as
+    /// `RegexTrie::to_bytes`, with the DFAs aligned so they can be used in
+    /// place.
+    ///
+    /// ## Errors
+    ///
+    /// If the file can't be written
+    pub fn write_shared(&self, path: impl AsRef<Path>) -> Result<(), RegexTrieError> {
+        let path = path.as_ref();
+        std::fs::write(path, self.trie.serialize(SHARED_MAGIC, true))
+            .map_err(|err| RegexTrieError::SharedMemoryFailed(format!("{}: {err}", path.display())))
+    }
+
+    /// Loads a trie written with `write_shared` by mapping the file in
+    /// memory, read-only: its DFAs are used in place, without being copied,
+    /// so every process opening the same file (e.g. forked workers) shares
+    /// a single copy of them. Patterns and nodes, far smaller, are loaded in
+    /// each process.
+    ///
+    /// ## Safety
+    ///
+    /// The file must not be modified or truncated, by this process or any
+    /// other, as long as the returned trie (or any trie derived from it)
+    /// lives: the DFAs are only validated when loaded, then read in place
+    /// from the mapping, so a change is undefined behavior (e.g. a `SIGBUS`
+    /// once truncated). Replace it instead, e.g. by renaming a new file over
+    /// it, which leaves the open tries mapping the old one.
+    ///
+    /// ## Errors
+    ///
+    /// If the file can't be mapped, is corrupted, or has been written on a
+    /// machine with a different endianness
+    pub unsafe fn open_shared(path: impl AsRef<Path>) -> Result<Self, RegexTrieError> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(|err| {
+            RegexTrieError::SharedMemoryFailed(format!("{}: {err}", path.display()))
+        })?;
+        let region = Arc::new(SharedRegion::map(&file)?);
+        // SAFETY: every DFA borrowing the bytes is stored along with the
+        // region, in a `SharedRegex`, keeping it mapped as long as they live,
+        // and the caller guarantees the file isn't modified meanwhile.
+        let bytes: &'static [u8] = unsafe { &*std::ptr::from_ref(region.bytes()) };
+
+        let mut trie = RegexTrie::new();
+        trie.load_with(bytes, SHARED_MAGIC, |reader| {
+            let forward = dfa_in_place(reader.read_aligned_dfa_bytes()?)?;
+            let reverse = dfa_in_place(reader.read_aligned_dfa_bytes()?)?;
+            Ok(CompiledRegex::Shared(Box::new(SharedRegex {
+                regex: Regex::builder().build_from_dfas(forward, reverse),
+                _region: Arc::clone(&region),
+            })))
+        })?;
+        Ok(trie.freeze())
+    }
+}
@@ -0,0 +1,43 @@
+use std::sync::Arc;
+
+use crate::RegexTrie;
+
+/// Immutable copy of a trie, taken with `RegexTrie::snapshot` before pushing
+/// new rules, to roll them back with `RegexTrie::restore` if they turn out to
+/// be bad. Clones are cheap, and share the same copy.
+///
+/// Patterns keep their ids. Hit counters start again from zero, and the
+/// candidate order set by `order_candidates_by_hits` or `optimize` isn't
+/// kept.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrieSnapshot(Arc<RegexTrie>);
+
+impl TrieSnapshot {
+    /// Read access to the trie of the snapshot, for the whole query API.
+    #[must_use]
+    pub fn trie(&self) -> &RegexTrie {
+        &self.0
+    }
+}
+
+impl RegexTrie {
+    /// Copies the trie into a snapshot. DFAs are copied instead of being
+    /// compiled again.
+    #[must_use]
+    pub fn snapshot(&self) -> TrieSnapshot {
+        TrieSnapshot(Arc::new(self.copy()))
+    }
+
+    /// Trie of a snapshot, copied again only if the snapshot is still
+    /// shared with some clone.
+    #[must_use]
+    pub fn restore(snapshot: TrieSnapshot) -> Self {
+        Arc::try_unwrap(snapshot.0).unwrap_or_else(|shared| shared.copy())
+    }
+
+    /// Same trie, with the same ids and settings.
+    fn copy(&self) -> Self {
+        self.filtered(|_| true, true)
+            .with_hit_counting(self.hit_counters.is_some())
+    }
+}
@@ -0,0 +1,190 @@
+use std::sync::Arc;
+
+use regex_automata::{
+    Anchored,
+    dfa::Automaton,
+    util::{primitives::StateID, start},
+};
+
+use crate::{
+    RegexTrie,
+    regex_trie::{TrieNode, is_full_match},
+};
+
+/// Matches a stream fed chunk by chunk against the trie, without buffering
+/// it, e.g. for network payloads. Built with `RegexTrie::stream`.
+///
+/// Patterns must fully match the concatenated stream, as with
+/// `find_matches` in the default `MatchMode::Full`, whatever the match mode.
+/// The trie is walked as the stream is decoded, and every candidate met
+/// along the way keeps its DFA state alive across chunk boundaries. Only
+/// the literal prefix walked so far is kept, to bring new candidates up to
+/// date, unless a pattern run by the fallback engine (see
+/// `RegexTrie::with_fallback_engine`) is a candidate: the stream is then
+/// buffered, to match it at the end.
+pub struct StreamMatcher<'trie> {
+    /// Trie being matched
+    trie: &'trie RegexTrie,
+    /// Deepest node reached, `None` once the stream left the trie
+    node: Option<&'trie TrieNode>,
+    /// Path walked in the trie, escaped as it was inserted
+    escaped_pattern: String,
+    /// Bytes of the stream walked in the trie
+    prefix: Vec<u8>,
+    /// Bytes of an incomplete UTF-8 character, split between chunks
+    pending: Vec<u8>,
+    /// Compiled pattern index of the candidates still alive, with their DFA
+    /// state
+    states: Vec<(usize, StateID)>,
+    /// Compiled pattern index of the candidates run by the fallback engine
+    fallbacks: Vec<usize>,
+    /// Whole stream, only kept once there is a fallback candidate
+    buffer: Option<Vec<u8>>,
+}
+
+impl std::fmt::Debug for StreamMatcher<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StreamMatcher")
+            .field("escaped_pattern", &self.escaped_pattern)
+            .field("pending", &self.pending)
+            .field("states", &self.states)
+            .finish_non_exhaustive()
+    }
+}
+
+impl RegexTrie {
+    /// Starts matching a stream, see `StreamMatcher`.
+    #[must_use]
+    pub fn stream(&self) -> StreamMatcher<'_> {
+        let mut stream = StreamMatcher {
+            trie: self,
+            node: Some(&self.root),
+            escaped_pattern: String::new(),
+            prefix: Vec::new(),
+            pending: Vec::new(),
+            states: Vec::new(),
+            fallbacks: Vec::new(),
+            buffer: None,
+        };
+        stream.add_candidates(&self.root);
+        stream
+    }
+}
+
+impl StreamMatcher<'_> {
+    /// Feeds the next chunk of the stream.
+    pub fn push(&mut self, chunk: &[u8]) {
+        let trie = self.trie;
+        for &byte in chunk {
+            self.states.retain_mut(|(index, state)| {
+                let Some(dfa) = trie.compiled_patterns[*index].1.dfa() else {
+                    return false;
+                };
+                let dfa = dfa.forward();
+                *state = dfa.next_state(*state, byte);
+                !dfa.is_dead_state(*state) && !dfa.is_quit_state(*state)
+            });
+            if let Some(buffer) = &mut self.buffer {
+                buffer.push(byte);
+            }
+
+            if self.node.is_some() {
+                self.pending.push(byte);
+                self.walk_pending();
+            }
+        }
+    }
+
+    /// Ends the stream, returning all the patterns fully matching it, in the
+    /// `find_matches` order.
+    #[must_use]
+    pub fn finish(self) -> Vec<Arc<str>> {
+        let mut matching_patterns = Vec::new();
+        let literal = self
+            .node
+            .is_some_and(|node| self.pending.is_empty() && node.contains_non_regex_prefix);
+        if literal {
+            matching_patterns.push(self.trie.shared_literal(&self.escaped_pattern));
+        }
+
+        let compiled_patterns = &self.trie.compiled_patterns;
+        let mut matching_indices: Vec<usize> = self
+            .states
+            .into_iter()
+            .filter(|&(index, state)| {
+                compiled_patterns[index].1.dfa().is_some_and(|dfa| {
+                    let dfa = dfa.forward();
+                    dfa.is_match_state(dfa.next_eoi_state(state))
+                })
+            })
+            .map(|(index, _)| index)
+            .collect();
+        if let Some(buffer) = &self.buffer {
+            matching_indices.extend(
+                self.fallbacks
+                    .into_iter()
+                    .filter(|&index| is_full_match(&compiled_patterns[index].1, buffer)),
+            );
+        }
+        matching_indices.sort_unstable();
+        matching_patterns.extend(
+            matching_indices
+                .into_iter()
+                .map(|index| Arc::clone(&compiled_patterns[index].0)),
+        );
+
+        matching_patterns
+    }
+
+    /// Walks the trie with the pending character, once it's complete.
+    fn walk_pending(&mut self) {
+        let ch = match std::str::from_utf8(&self.pending) {
+            Ok(decoded) => decoded.chars().next(),
+            // Not complete yet
+            Err(err) if err.error_len().is_none() => return,
+            Err(_) => None,
+        };
+
+        let child = ch
+            .zip(self.node)
+            .and_then(|(ch, node)| node.children.get(&ch));
+        let (Some(ch), Some(child)) = (ch, child) else {
+            // Nothing more to find in the trie
+            self.node = None;
+            self.prefix = Vec::new();
+            self.pending = Vec::new();
+            return;
+        };
+
+        if child.is_escaped {
+            self.escaped_pattern.push('\\');
+        }
+        self.escaped_pattern.push(ch);
+        self.prefix.append(&mut self.pending);
+        self.node = Some(child);
+        self.add_candidates(child);
+    }
+
+    /// Starts the DFA of every pattern of the node, brought up to date with
+    /// the prefix walked so far.
+    fn add_candidates(&mut self, node: &TrieNode) {
+        let config = start::Config::new().anchored(Anchored::Yes);
+        for &index in &node.pattern_indices {
+            let Some(dfa) = self.trie.compiled_patterns[index].1.dfa() else {
+                self.fallbacks.push(index);
+                self.buffer.get_or_insert_with(|| self.prefix.clone());
+                continue;
+            };
+            let dfa = dfa.forward();
+            let Ok(mut state) = dfa.start_state(&config) else {
+                continue;
+            };
+            for &byte in &self.prefix {
+                state = dfa.next_state(state, byte);
+            }
+            if !dfa.is_dead_state(state) && !dfa.is_quit_state(state) {
+                self.states.push((index, state));
+            }
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use pretty_assertions::assert_eq;
+
+use crate::RegexTrie;
+
+/// Feed the input to a stream, split in chunks of the given size
+fn stream_matches(tree: &RegexTrie, input: &[u8], chunk_size: usize) -> Vec<String> {
+    let mut stream = tree.stream();
+    for chunk in input.chunks(chunk_size) {
+        stream.push(chunk);
+    }
+    stream.finish().iter().map(ToString::to_string).collect()
+}
+
+/// Test streams match like the whole input, whatever the chunk boundaries
+#[test]
+fn test_stream_chunks() {
+    let patterns = vec![
+        "GET /.*".to_string(),
+        "GET /health".to_string(),
+        "GET /api/[a-z]+/[0-9]+".to_string(),
+        ".*\u{e9}t\u{e9}".to_string(),
+        "caf\u{e9}".to_string(),
+        "[a-z]+".to_string(),
+    ];
+    let tree = RegexTrie::from(&patterns).expect("can't init regex trie");
+
+    for input in [
+        "GET /health",
+        "GET /api/users/42",
+        "GET /api/users/",
+        "GET /\u{e9}t\u{e9}",
+        "caf\u{e9}",
+        "cafe",
+        "",
+        "nothing matches here!",
+    ] {
+        let expected = tree.find_matches(input);
+        for chunk_size in 1..=input.len().max(1) {
+            assert_eq!(
+                expected,
+                stream_matches(&tree, input.as_bytes(), chunk_size),
+                "different matches for {input:?} in chunks of {chunk_size}"
+            );
+        }
+    }
+}
+
+/// Test invalid UTF-8 streams don't match anything
+#[test]
+fn test_stream_invalid_utf8() {
+    let patterns = vec!["ab".to_string(), "a.*".to_string()];
+    let tree = RegexTrie::from(&patterns).expect("can't init regex trie");
+
+    assert!(stream_matches(&tree, b"a\xffb", 1).is_empty());
+    assert_eq!(vec!["ab", "a.*"], stream_matches(&tree, b"ab", 1));
+}
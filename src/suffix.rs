@@ -0,0 +1,176 @@
+use std::collections::HashMap;
+
+use regex_syntax::{
+    Parser,
+    hir::{HirKind, Look},
+};
+
+use crate::{MatchMode, RegexTrie, options::regex_source, regex_trie::Scratch};
+
+/// Literal every match of a regex must end with, e.g. `.tar.gz` for
+/// `.*\.tar\.gz$`, ignoring a trailing `$`. `None` if the regex doesn't end
+/// with a literal, or if it can't be parsed.
+pub(crate) fn literal_suffix(pattern: &str) -> Option<Vec<u8>> {
+    let hir = Parser::new().parse(pattern).ok()?;
+    let pieces = match hir.kind() {
+        HirKind::Concat(pieces) => pieces.as_slice(),
+        _ => std::slice::from_ref(&hir),
+    };
+    let last = pieces
+        .iter()
+        .rev()
+        .find(|piece| !matches!(piece.kind(), HirKind::Look(Look::End)))?;
+    match last.kind() {
+        HirKind::Literal(literal) => Some(literal.0.to_vec()),
+        _ => None,
+    }
+}
+
+/// A node of the trie of the reversed literal suffixes, reached by their
+/// last bytes.
+#[derive(Debug, Default)]
+struct SuffixNode {
+    /// Children, by byte
+    children: HashMap<u8, SuffixNode>,
+    /// Compiled patterns whose reversed literal suffix leads here
+    indices: Vec<usize>,
+}
+
+/// Trie of the reversed literal suffixes of the compiled patterns: walking
+/// the reversed input tells which ones the input ends with.
+#[derive(Debug, Default)]
+pub(crate) struct SuffixIndex {
+    /// Root node, reached by no byte
+    root: SuffixNode,
+    /// Whether every compiled pattern has an indexed literal suffix, indexed
+    /// like `compiled_patterns`
+    indexed: Vec<bool>,
+}
+
+impl SuffixIndex {
+    /// Number of compiled patterns seen by the index.
+    pub(crate) const fn len(&self) -> usize {
+        self.indexed.len()
+    }
+
+    /// Indexes the next compiled pattern, under its literal suffix if any.
+    fn push(&mut self, suffix: Option<Vec<u8>>) {
+        let index = self.indexed.len();
+        self.indexed.push(suffix.is_some());
+        if let Some(suffix) = suffix {
+            let mut node = &mut self.root;
+            for &byte in suffix.iter().rev() {
+                node = node.children.entry(byte).or_default();
+            }
+            node.indices.push(index);
+        }
+    }
+
+    /// Sorted compiled indices of the literal suffixes the input ends with.
+    fn suffixes_of(&self, input: &[u8]) -> Vec<usize> {
+        let mut indices = self.root.indices.clone();
+        let mut node = &self.root;
+        for byte in input.iter().rev() {
+            let Some(child) = node.children.get(byte) else {
+                break;
+            };
+            node = child;
+            indices.extend_from_slice(&node.indices);
+        }
+        indices.sort_unstable();
+        indices
+    }
+}
+
+impl RegexTrie {
+    /// Indexes the literal suffix of the regexes ending with one (e.g.
+    /// `.tar.gz` for `.*\.tar\.gz$`) in a trie of the reversed suffixes, or
+    /// stops with `false`. In `MatchMode::Full`, the reversed input is
+    /// walked in it once per query, and the candidates whose suffix the input
+    /// doesn't end with are ruled out without running their DFA. This is the
+    /// counterpart of the literal prefixes, for the rules like file
+    /// extensions whose prefix is a wildcard. Case-insensitive regexes aren't
+    /// indexed, and the index isn't used in `MatchMode::Prefix`.
+    #[must_use]
+    pub fn with_suffix_index(mut self, enabled: bool) -> Self {
+        self.suffix_index = enabled.then(SuffixIndex::default);
+        self.rebuild_suffix_index();
+        self
+    }
+
+    /// Whether the literal suffixes of the regexes are indexed.
+    #[must_use]
+    pub const fn suffix_index(&self) -> bool {
+        self.suffix_index.is_some()
+    }
+
+    /// Literal suffix of the compiled pattern of the given index, none if
+    /// it's case-insensitive.
+    fn suffix_of(&self, index: usize, case_insensitive: bool) -> Option<Vec<u8>> {
+        if case_insensitive {
+            return None;
+        }
+        let pattern = &self.compiled_patterns[index].0;
+        literal_suffix(&regex_source(pattern, &self.extra_specials))
+    }
+
+    /// Indexes the compiled pattern just added, if enabled.
+    pub(crate) fn index_suffix(&mut self, index: usize, case_insensitive: bool) {
+        if self.suffix_index.is_some() {
+            let suffix = self.suffix_of(index, case_insensitive);
+            if let Some(suffix_index) = &mut self.suffix_index {
+                suffix_index.push(suffix);
+            }
+        }
+    }
+
+    /// Indexes again every compiled pattern, once they have been moved.
+    pub(crate) fn rebuild_suffix_index(&mut self) {
+        if self.suffix_index.is_none() {
+            return;
+        }
+        let mut suffix_index = SuffixIndex::default();
+        for index in 0..self.compiled_patterns.len() {
+            let case_insensitive = self
+                .ids
+                .get(&self.compiled_patterns[index].0)
+                .is_some_and(|&id| self.is_case_insensitive(id));
+            suffix_index.push(self.suffix_of(index, case_insensitive));
+        }
+        self.suffix_index = Some(suffix_index);
+    }
+
+    /// Whether the input ends with the literal suffix of the compiled
+    /// pattern of the given index, which can't match the whole input
+    /// otherwise, checked on the input directly. Always true if it has none,
+    /// or in prefix mode.
+    pub(crate) fn suffix_gate(&self, index: usize, input: &[u8]) -> bool {
+        self.match_mode != MatchMode::Full
+            || self
+                .literal_suffixes
+                .get(index)
+                .and_then(Option::as_ref)
+                .is_none_or(|suffix| input.ends_with(suffix))
+    }
+
+    /// Same as `suffix_gate`, according to the suffix index, walked once per
+    /// query. Always true if it has no suffix, if the index is disabled, or
+    /// in prefix mode.
+    pub(crate) fn suffix_occurs(&self, index: usize, input: &[u8], scratch: &Scratch) -> bool {
+        let Some(suffix_index) = self
+            .suffix_index
+            .as_ref()
+            .filter(|suffix_index| suffix_index.indexed.get(index) == Some(&true))
+        else {
+            return true;
+        };
+        if self.match_mode != MatchMode::Full {
+            return true;
+        }
+        let mut input_suffixes = scratch.input_suffixes.borrow_mut();
+        input_suffixes
+            .get_or_insert_with(|| suffix_index.suffixes_of(input))
+            .binary_search(&index)
+            .is_ok()
+    }
+}
@@ -0,0 +1,63 @@
+use std::{collections::HashMap, sync::Arc};
+
+use crate::{
+    PatternId, RegexTrie, RegexTrieError,
+    regex_trie::{Matched, Scratch},
+};
+
+impl RegexTrie {
+    /// Inserts a pattern, as `insert` does, and tags it (e.g. "blocklist" or
+    /// "analytics"), so matches can be grouped by tag with
+    /// `find_matches_grouped`. A pattern may have several tags. Tags aren't
+    /// serialized, and aren't part of the trie equality.
+    ///
+    /// ## Errors
+    ///
+    /// If the regex pattern can't be compiled
+    pub fn insert_tagged(&mut self, pattern: &str, tag: &str) -> Result<(), RegexTrieError> {
+        self.insert(pattern)?;
+        if let Some(id) = self.id_of(pattern) {
+            self.add_tag(id, tag);
+        }
+        Ok(())
+    }
+
+    /// Tags the pattern of the given id, unless it already has this tag.
+    pub(crate) fn add_tag(&mut self, id: PatternId, tag: &str) {
+        let tags = self.tags.entry(id).or_default();
+        if !tags.iter().any(|known| **known == *tag) {
+            tags.push(Arc::from(tag));
+        }
+    }
+
+    /// Tags of the pattern of the given id, in insertion order.
+    #[must_use]
+    pub fn tags(&self, id: PatternId) -> &[Arc<str>] {
+        self.tags.get(&id).map_or(&[], Vec::as_slice)
+    }
+
+    /// Finds all the patterns matching the input, grouped by tag, with a
+    /// single walk of the trie. Patterns of a group are in the
+    /// `find_matches` order, and untagged patterns are left out.
+    #[must_use]
+    pub fn find_matches_grouped(&self, input: &str) -> HashMap<Arc<str>, Vec<PatternId>> {
+        let mut groups: HashMap<Arc<str>, Vec<PatternId>> = HashMap::new();
+        if self.tags.is_empty() {
+            return groups;
+        }
+
+        self.for_each_match(input, &mut Scratch::default(), |matched| {
+            let pattern = match matched {
+                Matched::Literal(pattern) => pattern,
+                Matched::Regex(pattern, _, _) => pattern,
+            };
+            let Some(id) = self.id_of(pattern) else {
+                return;
+            };
+            for tag in self.tags(id) {
+                groups.entry(Arc::clone(tag)).or_default().push(id);
+            }
+        });
+        groups
+    }
+}
@@ -0,0 +1,130 @@
+use std::{
+    collections::HashMap,
+    hash::{BuildHasher, RandomState},
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use crate::{PatternId, RegexTrie};
+
+/// How `find_best_match` picks a pattern among several ones ranked equally
+/// by the selection policy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TieBreak {
+    /// The first one in the `find_matches` order
+    #[default]
+    First,
+    /// Each of them in turn, proportionally to their weight. The turn is
+    /// shared by all the queries of the trie.
+    RoundRobin,
+    /// A random one, with a probability proportional to its weight
+    WeightedRandom,
+}
+
+/// Tie breaking settings and state.
+#[derive(Debug)]
+pub(crate) struct TieBreaker {
+    /// How ties are broken
+    pub(crate) policy: TieBreak,
+    /// Weight of the patterns, 1 if unset
+    pub(crate) weights: HashMap<PatternId, u32>,
+    /// Turn of the round-robin
+    turn: AtomicU64,
+    /// State of the random generator
+    random: AtomicU64,
+}
+
+impl Default for TieBreaker {
+    fn default() -> Self {
+        Self {
+            policy: TieBreak::default(),
+            weights: HashMap::new(),
+            turn: AtomicU64::new(0),
+            // Every trie gets its own random sequence.
+            random: AtomicU64::new(RandomState::new().hash_one(0_u8)),
+        }
+    }
+}
+
+/// `SplitMix64` increment
+const GAMMA: u64 = 0x9e37_79b9_7f4a_7c15;
+
+/// `SplitMix64` output function, turning a counter into a random number.
+const fn mix(mut value: u64) -> u64 {
+    value = (value ^ (value >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    value = (value ^ (value >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    value ^ (value >> 31)
+}
+
+impl TieBreaker {
+    /// A number deciding which of the tied patterns is picked.
+    fn ticket(&self) -> u64 {
+        match self.policy {
+            TieBreak::First => 0,
+            TieBreak::RoundRobin => self.turn.fetch_add(1, Ordering::Relaxed),
+            TieBreak::WeightedRandom => mix(self.random.fetch_add(GAMMA, Ordering::Relaxed)),
+        }
+    }
+}
+
+impl RegexTrie {
+    /// Sets how `find_best_match` picks a pattern among several ones ranked
+    /// equally by the selection policy, e.g. to spread traffic between
+    /// backend pools. Like the selection policy, it isn't serialized.
+    #[must_use]
+    pub const fn with_tie_break(mut self, tie_break: TieBreak) -> Self {
+        self.tie_breaker.policy = tie_break;
+        self
+    }
+
+    /// How ties between best matches are broken.
+    #[must_use]
+    pub const fn tie_break(&self) -> TieBreak {
+        self.tie_breaker.policy
+    }
+
+    /// Sets the weight of a pattern when breaking ties (1 by default). A
+    /// pattern of weight 0 is only picked if all the tied patterns have a
+    /// weight of 0. Returns false if the id is unknown.
+    pub fn set_weight(&mut self, id: PatternId, weight: u32) -> bool {
+        if self.pattern(id).is_none() {
+            return false;
+        }
+        self.tie_breaker.weights.insert(id, weight);
+        true
+    }
+
+    /// Weight of a pattern when breaking ties.
+    #[must_use]
+    pub fn weight(&self, id: PatternId) -> u32 {
+        self.tie_breaker.weights.get(&id).copied().unwrap_or(1)
+    }
+
+    /// Picks one of the best matches, ranked equally, according to the tie
+    /// break policy.
+    pub(crate) fn break_tie(&self, mut ties: Vec<Arc<str>>) -> Option<Arc<str>> {
+        if ties.len() <= 1 {
+            return ties.pop();
+        }
+
+        let weights: Vec<u64> = ties
+            .iter()
+            .map(|pattern| self.id_of(pattern).map_or(1, |id| self.weight(id).into()))
+            .collect();
+        let total: u64 = weights.iter().sum();
+        if total == 0 {
+            return ties.into_iter().next();
+        }
+
+        let mut ticket = self.tie_breaker.ticket() % total;
+        for (pattern, weight) in ties.into_iter().zip(weights) {
+            if ticket < weight {
+                return Some(pattern);
+            }
+            ticket -= weight;
+        }
+        None
+    }
+}
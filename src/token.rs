@@ -0,0 +1,235 @@
+use std::{collections::HashMap, sync::Arc};
+
+use crate::{
+    PatternMatcher, RegexTrieError,
+    engine::{DfaConfig, compile},
+    regex_trie::{ScorerFuncType, default_scorer, is_full_match, parse_literal_prefix},
+    segment::Verifier,
+};
+
+/// Type for the tokenizer function, splitting a text into the units the
+/// trie is keyed on
+pub type TokenizerFuncType = Box<dyn Fn(&str) -> Vec<String> + Send + Sync>;
+
+/// A node of a `TokenTrie`, reached by a sequence of tokens.
+#[derive(Debug, Default)]
+struct TokenNode {
+    /// Children, by token
+    children: HashMap<Box<str>, TokenNode>,
+    /// Patterns whose literal prefix tokens lead here
+    patterns: Vec<usize>,
+}
+
+impl TokenNode {
+    /// Number of nodes of the subtree.
+    fn count(&self) -> usize {
+        1 + self.children.values().map(Self::count).sum::<usize>()
+    }
+}
+
+/// Alternative to `RegexTrie` keyed on the tokens of a custom tokenizer
+/// (words, log fields, ...) instead of the characters. The literal prefix
+/// of a pattern and the input are split by the same tokenizer: a plain
+/// pattern is indexed by all its tokens, a regex by all the tokens of its
+/// literal prefix but the last one, which the regex could extend.
+///
+/// The tokenizer must keep the tokens of a text, but the last one, when more
+/// text is appended (e.g. splitting on a separator), or some matches are
+/// missed.
+///
+/// Patterns fully match the input, as with `RegexTrie::find_matches` in
+/// `MatchMode::Full`, and are scored the same way.
+pub struct TokenTrie {
+    /// Root node, reached by no token
+    root: TokenNode,
+    /// Every pattern, with its verifier and its score, in insertion order
+    patterns: Vec<(Arc<str>, Verifier, usize)>,
+    /// Reverse lookup of `patterns`
+    ids: HashMap<Arc<str>, usize>,
+    /// Tokenizer function
+    tokenizer: TokenizerFuncType,
+    /// Scorer function
+    scorer: ScorerFuncType,
+}
+
+impl std::fmt::Debug for TokenTrie {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TokenTrie")
+            .field("root", &self.root)
+            .field("patterns", &self.patterns)
+            .finish_non_exhaustive()
+    }
+}
+
+impl TokenTrie {
+    /// Creates a new, empty trie with the given tokenizer and the default
+    /// scorer.
+    #[must_use]
+    pub fn new(tokenizer: TokenizerFuncType) -> Self {
+        Self::new_with_custom_scorer(tokenizer, Box::new(default_scorer))
+    }
+
+    /// Creates a new, empty trie with the given tokenizer and a custom
+    /// scorer.
+    #[must_use]
+    pub fn new_with_custom_scorer(tokenizer: TokenizerFuncType, scorer: ScorerFuncType) -> Self {
+        Self {
+            root: TokenNode::default(),
+            patterns: Vec::default(),
+            ids: HashMap::default(),
+            tokenizer,
+            scorer,
+        }
+    }
+
+    /// Creates a new, empty trie keyed on the whitespace-delimited words, for
+    /// rule patterns matching log messages or alert titles, whose literal
+    /// prefixes are long but whose first characters are shared by many.
+    #[must_use]
+    pub fn words() -> Self {
+        Self::new(Box::new(|text| {
+            text.split_whitespace().map(ToString::to_string).collect()
+        }))
+    }
+
+    /// Creates a new trie from a set of patterns, with the given tokenizer.
+    ///
+    /// ## Errors
+    ///
+    /// If any of the regex pattern can't be compiled
+    pub fn from(patterns: &[String], tokenizer: TokenizerFuncType) -> Result<Self, RegexTrieError> {
+        let mut trie = Self::new(tokenizer);
+        trie.insert_many(patterns)?;
+        Ok(trie)
+    }
+
+    /// Compiles a pattern and inserts it into the trie. Inserting an already
+    /// known pattern does nothing.
+    ///
+    /// ## Errors
+    ///
+    /// If the regex pattern can't be compiled
+    pub fn insert(&mut self, pattern: &str) -> Result<(), RegexTrieError> {
+        self.insert_many(&[pattern.to_string()])
+    }
+
+    /// Insert many patterns at once. Nothing is inserted if any pattern
+    /// can't be compiled.
+    ///
+    /// ## Errors
+    ///
+    /// If any regex pattern can't be compiled
+    pub fn insert_many(&mut self, patterns: &[String]) -> Result<(), RegexTrieError> {
+        let compiled = patterns
+            .iter()
+            .map(|pattern| {
+                let (prefix, is_regex) = parse_literal_prefix(pattern);
+                let literal: String = prefix.into_iter().map(|(ch, _)| ch).collect();
+                let mut tokens = (self.tokenizer)(&literal);
+                if !is_regex {
+                    return Ok((Verifier::Literal(literal.into_boxed_str()), tokens));
+                }
+                tokens.pop();
+                let regex = compile(pattern, false, false, &DfaConfig::default())?;
+                Ok((Verifier::Regex(regex), tokens))
+            })
+            .collect::<Result<Vec<_>, RegexTrieError>>()?;
+
+        for (pattern, (verifier, tokens)) in patterns.iter().zip(compiled) {
+            if self.ids.contains_key(pattern.as_str()) {
+                continue;
+            }
+            let index = self.patterns.len();
+            let mut node = &mut self.root;
+            for token in tokens {
+                node = node.children.entry(token.into_boxed_str()).or_default();
+            }
+            node.patterns.push(index);
+
+            let score = (self.scorer)(pattern, matches!(verifier, Verifier::Regex(_)));
+            let pattern: Arc<str> = Arc::from(pattern.as_str());
+            self.ids.insert(Arc::clone(&pattern), index);
+            self.patterns.push((pattern, verifier, score));
+        }
+
+        Ok(())
+    }
+
+    /// Number of nodes of the trie, including the root.
+    #[must_use]
+    pub fn node_count(&self) -> usize {
+        self.root.count()
+    }
+
+    /// Finds all patterns fully matching the input. The order is the one of
+    /// `RegexTrie::find_matches`: the plain pattern equal to the input comes
+    /// first if any, then the matching regexes in insertion order.
+    #[must_use]
+    pub fn find_matches(&self, input: &str) -> Vec<String> {
+        self.matching(input)
+            .map(|(pattern, _)| pattern.to_string())
+            .collect()
+    }
+
+    /// Finds the pattern with the lowest score fully matching the input. On
+    /// equal scores, the first one of `find_matches` wins.
+    #[must_use]
+    pub fn find_best_match(&self, input: &str) -> Option<String> {
+        let mut best_match: Option<(&str, usize)> = None;
+        for (pattern, score) in self.matching(input) {
+            if best_match.is_none_or(|(_, best_score)| score < best_score) {
+                best_match = Some((pattern, score));
+            }
+        }
+
+        best_match.map(|(pattern, _)| pattern.to_string())
+    }
+
+    /// Iterates over all the patterns fully matching the input, with their
+    /// score, in the `find_matches` order.
+    fn matching<'trie>(
+        &'trie self,
+        input: &'trie str,
+    ) -> impl Iterator<Item = (&'trie str, usize)> {
+        let mut candidates = Vec::new();
+        let mut node = &self.root;
+        candidates.extend_from_slice(&node.patterns);
+        for token in (self.tokenizer)(input) {
+            let Some(child) = node.children.get(token.as_str()) else {
+                break;
+            };
+            node = child;
+            candidates.extend_from_slice(&node.patterns);
+        }
+        candidates.sort_unstable_by_key(|&index| {
+            (matches!(self.patterns[index].1, Verifier::Regex(_)), index)
+        });
+
+        candidates.into_iter().filter_map(move |index| {
+            let (pattern, verifier, score) = &self.patterns[index];
+            let is_match = match verifier {
+                Verifier::Literal(literal) => **literal == *input,
+                Verifier::Regex(regex) => is_full_match(regex, input.as_bytes()),
+            };
+            is_match.then_some((&**pattern, *score))
+        })
+    }
+}
+
+impl PatternMatcher for TokenTrie {
+    fn insert(&mut self, pattern: &str) -> Result<(), RegexTrieError> {
+        Self::insert(self, pattern)
+    }
+
+    fn insert_many(&mut self, patterns: &[String]) -> Result<(), RegexTrieError> {
+        Self::insert_many(self, patterns)
+    }
+
+    fn find_matches(&self, input: &str) -> Vec<String> {
+        Self::find_matches(self, input)
+    }
+
+    fn find_best_match(&self, input: &str) -> Option<String> {
+        Self::find_best_match(self, input)
+    }
+}
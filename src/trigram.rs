@@ -0,0 +1,130 @@
+use std::collections::HashSet;
+
+use regex_syntax::Parser;
+
+use crate::{
+    RegexTrie, options::regex_source, prefilter::collect_required_literals, regex_trie::Scratch,
+};
+
+/// Three consecutive bytes of a literal.
+pub(crate) type Trigram = [u8; 3];
+
+/// Trigrams of the literals every match of a regex must contain, e.g. `err`,
+/// `rro` and `ror` for `.*error.*`, sorted and deduplicated. Empty if there's
+/// no such literal of at least 3 bytes, or if the regex can't be parsed.
+pub(crate) fn required_trigrams(pattern: &str) -> Box<[Trigram]> {
+    let Ok(hir) = Parser::new().parse(pattern) else {
+        return Box::default();
+    };
+    let mut literals = Vec::new();
+    collect_required_literals(&hir, &mut literals);
+    let mut trigrams: Vec<Trigram> = literals
+        .iter()
+        .flat_map(|literal| literal.windows(3))
+        .filter_map(|window| window.try_into().ok())
+        .collect();
+    trigrams.sort_unstable();
+    trigrams.dedup();
+    trigrams.into_boxed_slice()
+}
+
+/// Required trigrams of every compiled pattern, to rule out the candidates
+/// whose required literals don't all occur in the input before running
+/// their DFA.
+#[derive(Debug, Default)]
+pub(crate) struct TrigramIndex {
+    /// Required trigrams of every compiled pattern, indexed like
+    /// `compiled_patterns`
+    required: Vec<Box<[Trigram]>>,
+}
+
+impl TrigramIndex {
+    /// Number of indexed compiled patterns.
+    pub(crate) const fn len(&self) -> usize {
+        self.required.len()
+    }
+}
+
+impl RegexTrie {
+    /// Indexes the trigrams of the literals every match of a regex must
+    /// contain (like code-search engines do), or stops with `false`: the
+    /// candidates whose trigrams don't all occur in the input are ruled out
+    /// without running their DFA. Meant for the patterns without a usable
+    /// literal prefix, like `.*timeout.*`, which the trie can't tell apart,
+    /// and which are otherwise all run against every input. The trigrams of
+    /// the input are only computed by the queries reaching such a regex.
+    /// Case-insensitive regexes aren't indexed.
+    #[must_use]
+    pub fn with_trigram_index(mut self, enabled: bool) -> Self {
+        self.trigram_index = enabled.then(TrigramIndex::default);
+        self.rebuild_trigram_index();
+        self
+    }
+
+    /// Whether the required trigrams of the regexes are indexed.
+    #[must_use]
+    pub const fn trigram_index(&self) -> bool {
+        self.trigram_index.is_some()
+    }
+
+    /// Required trigrams of the compiled pattern of the given index, none if
+    /// it's case-insensitive.
+    fn trigrams_of(&self, index: usize, case_insensitive: bool) -> Box<[Trigram]> {
+        if case_insensitive {
+            return Box::default();
+        }
+        let pattern = &self.compiled_patterns[index].0;
+        required_trigrams(&regex_source(pattern, &self.extra_specials))
+    }
+
+    /// Indexes the compiled pattern just added, if enabled.
+    pub(crate) fn index_trigrams(&mut self, index: usize, case_insensitive: bool) {
+        if self.trigram_index.is_some() {
+            let trigrams = self.trigrams_of(index, case_insensitive);
+            if let Some(trigram_index) = &mut self.trigram_index {
+                trigram_index.required.push(trigrams);
+            }
+        }
+    }
+
+    /// Indexes again every compiled pattern, once they have been moved.
+    pub(crate) fn rebuild_trigram_index(&mut self) {
+        if self.trigram_index.is_none() {
+            return;
+        }
+        let required = (0..self.compiled_patterns.len())
+            .map(|index| {
+                let case_insensitive = self
+                    .ids
+                    .get(&self.compiled_patterns[index].0)
+                    .is_some_and(|&id| self.is_case_insensitive(id));
+                self.trigrams_of(index, case_insensitive)
+            })
+            .collect();
+        self.trigram_index = Some(TrigramIndex { required });
+    }
+
+    /// Whether every required trigram of the compiled pattern of the given
+    /// index occurs in the input, which it can't match otherwise. Always true
+    /// if the index is disabled.
+    pub(crate) fn trigrams_occur(&self, index: usize, input: &[u8], scratch: &Scratch) -> bool {
+        let Some(required) = self
+            .trigram_index
+            .as_ref()
+            .and_then(|trigram_index| trigram_index.required.get(index))
+            .filter(|required| !required.is_empty())
+        else {
+            return true;
+        };
+        let mut input_trigrams = scratch.input_trigrams.borrow_mut();
+        let input_trigrams = input_trigrams.get_or_insert_with(|| {
+            input
+                .windows(3)
+                .filter_map(|window| window.try_into().ok())
+                .collect::<HashSet<Trigram>>()
+        });
+        required
+            .iter()
+            .all(|trigram| input_trigrams.contains(trigram))
+    }
+}
@@ -0,0 +1,55 @@
+use std::time::{Duration, Instant};
+
+use crate::{PatternId, RegexTrie, RegexTrieError};
+
+impl RegexTrie {
+    /// Inserts a temporary pattern (e.g. an incident block, or a canary
+    /// route), as `insert` does, which `evict_expired` removes once its TTL
+    /// has elapsed. It keeps matching until then.
+    ///
+    /// Inserting a known temporary pattern again refreshes its expiration,
+    /// while a known permanent pattern stays permanent. Expirations aren't
+    /// serialized.
+    ///
+    /// ## Errors
+    ///
+    /// If the regex pattern can't be compiled
+    pub fn insert_with_ttl(&mut self, pattern: &str, ttl: Duration) -> Result<(), RegexTrieError> {
+        let is_new = !self.ids.contains_key(pattern);
+        self.insert(pattern)?;
+        let Some(id) = self.id_of(pattern) else {
+            // Dropped as equivalent to a known regex, see `with_regex_dedup`.
+            return Ok(());
+        };
+        if !is_new && !self.expirations.contains_key(&id) {
+            return Ok(());
+        }
+        // A TTL too long to be represented never expires.
+        if let Some(expiration) = Instant::now().checked_add(ttl) {
+            self.expirations.insert(id, expiration);
+        }
+        Ok(())
+    }
+
+    /// Expiration time of the pattern of the given id, `None` if it's
+    /// permanent, or if the id is unknown.
+    #[must_use]
+    pub fn expires_at(&self, id: PatternId) -> Option<Instant> {
+        self.expirations.get(&id).copied()
+    }
+
+    /// Removes the patterns whose TTL has elapsed (see `insert_with_ttl`),
+    /// and returns how many. Like `remove_with_prefix`, their DFAs are kept
+    /// in memory until `compact` is called.
+    pub fn evict_expired(&mut self) -> usize {
+        let now = Instant::now();
+        let mut expired: Vec<PatternId> = self
+            .expirations
+            .iter()
+            .filter(|&(_, &expiration)| expiration <= now)
+            .map(|(&id, _)| id)
+            .collect();
+        expired.sort_unstable();
+        expired.into_iter().filter(|&id| self.remove_id(id)).count()
+    }
+}
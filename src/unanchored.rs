@@ -0,0 +1,147 @@
+use std::{
+    ops::{ControlFlow, Range},
+    sync::Arc,
+};
+
+use regex_automata::{Anchored, Input};
+
+use crate::{PatternId, RegexTrie, prefilter::LiteralPositions};
+
+/// A pattern matching somewhere in a text, with the byte offsets of its
+/// leftmost match.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnanchoredMatch {
+    /// Id of the matching pattern
+    pub id: PatternId,
+    /// Matching pattern, shared with the trie
+    pub pattern: Arc<str>,
+    /// Byte offset where the match starts
+    pub start: usize,
+    /// Byte offset where the match ends (exclusive)
+    pub end: usize,
+}
+
+impl RegexTrie {
+    /// Substring mode: finds all the patterns matching somewhere in the text,
+    /// instead of the whole of it, e.g. to highlight where a pattern hit in a
+    /// log line.
+    ///
+    /// Each pattern is reported once, at its leftmost match, with the end of
+    /// the match the DFA reports from that start. Results are sorted by start
+    /// offset, then by id. See `scan_with` to get every hit instead.
+    #[must_use]
+    pub fn find_matches_unanchored(&self, text: &str) -> Vec<UnanchoredMatch> {
+        let mut seen = vec![false; self.patterns.len()];
+        let mut matches = Vec::new();
+        self.scan_with(text, |id, range| {
+            if !seen[id.index()] {
+                seen[id.index()] = true;
+                if let Some((pattern, _)) = &self.patterns[id.index()] {
+                    matches.push(UnanchoredMatch {
+                        id,
+                        pattern: Arc::clone(pattern),
+                        start: range.start,
+                        end: range.end,
+                    });
+                }
+            }
+            ControlFlow::Continue(())
+        });
+
+        matches
+    }
+
+    /// Substring mode scanner: calls `on_match` for every hit of a pattern in
+    /// the haystack, with the byte range of the match, until it breaks.
+    ///
+    /// The trie is walked from every character of the haystack, so only the
+    /// patterns whose literal prefix appears at a given offset are evaluated
+    /// there. A pattern hits at most once per start offset, ending where the
+    /// DFA reports it. Hits are reported by start offset, then by id.
+    ///
+    /// Regexes are only run where the longest literal they require (e.g.
+    /// `.log` for `/var/.*\.log`) still occurs in the rest of the haystack,
+    /// which is checked with a SIMD substring search, once per occurrence.
+    pub fn scan_with(
+        &self,
+        haystack: &str,
+        mut on_match: impl FnMut(PatternId, Range<usize>) -> ControlFlow<()>,
+    ) {
+        let mut escaped_pattern = String::new();
+        let mut hits = Vec::new();
+        let mut positions = LiteralPositions::default();
+
+        // The end of the haystack is a valid start too, for empty matches.
+        let starts = haystack
+            .char_indices()
+            .map(|(start, _)| start)
+            .chain([haystack.len()]);
+        for start in starts {
+            self.hits_at(
+                haystack,
+                start,
+                &mut escaped_pattern,
+                &mut hits,
+                &mut positions,
+            );
+            for (id, end) in hits.drain(..) {
+                if on_match(id, start..end).is_break() {
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Collects all the patterns matching the haystack from the `start`
+    /// offset, with the end of their match, sorted by id.
+    fn hits_at(
+        &self,
+        haystack: &str,
+        start: usize,
+        escaped_pattern: &mut String,
+        hits: &mut Vec<(PatternId, usize)>,
+        positions: &mut LiteralPositions,
+    ) {
+        let mut node = &self.root;
+        let mut end = start;
+        escaped_pattern.clear();
+        loop {
+            let literal = node
+                .contains_non_regex_prefix
+                .then(|| self.ids.get(escaped_pattern.as_str()))
+                .flatten();
+            if let Some(&id) = literal {
+                hits.push((id, end));
+            }
+
+            for &index in &node.pattern_indices {
+                // Cheap substring search first, a regex can't match without
+                // its required literal.
+                let finder = self.required_literals[index].as_ref();
+                if !positions.may_match(index, finder, haystack.as_bytes(), start) {
+                    continue;
+                }
+                let (pattern, dfa, _) = &self.compiled_patterns[index];
+                let input = Input::new(haystack).range(start..).anchored(Anchored::Yes);
+                if let Some(found) = dfa.find(input) {
+                    hits.push((self.ids[pattern], found.end()));
+                }
+            }
+
+            let Some(ch) = haystack[end..].chars().next() else {
+                break;
+            };
+            let Some(child) = node.children.get(&ch) else {
+                break;
+            };
+            if child.is_escaped {
+                escaped_pattern.push('\\');
+            }
+            escaped_pattern.push(ch);
+            end += ch.len_utf8();
+            node = child;
+        }
+
+        hits.sort_unstable_by_key(|(id, _)| *id);
+    }
+}
@@ -0,0 +1,156 @@
+use std::{ops::ControlFlow, sync::Arc};
+
+use pretty_assertions::assert_eq;
+
+use crate::{InsertOptions, RegexTrie, UnanchoredMatch, prefilter::required_literal};
+
+/// Shorthand to build the expected matches
+fn found(tree: &RegexTrie, pattern: &str, start: usize, end: usize) -> UnanchoredMatch {
+    UnanchoredMatch {
+        id: tree.id_of(pattern).expect("pattern should exist"),
+        pattern: Arc::from(pattern),
+        start,
+        end,
+    }
+}
+
+/// Test patterns are found anywhere in the text, with their offsets
+#[test]
+fn test_unanchored_offsets() {
+    let patterns = vec![
+        "error [0-9]+".to_string(),
+        "timeout".to_string(),
+        "user=[a-z]+".to_string(),
+        "nowhere.*".to_string(),
+    ];
+    let tree = RegexTrie::from(&patterns).expect("can't init regex trie");
+
+    let text = "GET /login user=bob timeout, error 504 then error 500";
+    assert_eq!(
+        vec![
+            found(&tree, "user=[a-z]+", 11, 19),
+            found(&tree, "timeout", 20, 27),
+            found(&tree, "error [0-9]+", 29, 38),
+        ],
+        tree.find_matches_unanchored(text)
+    );
+    assert!(tree.find_matches_unanchored("nothing here").is_empty());
+}
+
+/// Test patterns without literal prefix, and multi bytes characters
+#[test]
+fn test_unanchored_edge_cases() {
+    let patterns = vec![
+        "[0-9]+".to_string(),
+        "\u{e9}.".to_string(),
+        "[xy]*".to_string(),
+    ];
+    let tree = RegexTrie::from(&patterns).expect("can't init regex trie");
+
+    // Empty matches are reported too, at the very first offset.
+    assert_eq!(
+        vec![
+            found(&tree, "[xy]*", 0, 0),
+            found(&tree, "\u{e9}.", 1, 4),
+            found(&tree, "[0-9]+", 5, 7),
+        ],
+        tree.find_matches_unanchored("a\u{e9}b-42")
+    );
+    assert_eq!(
+        vec![found(&tree, "[xy]*", 0, 0)],
+        tree.find_matches_unanchored("")
+    );
+}
+
+/// Test every hit is reported to the callback, until it breaks
+#[test]
+fn test_scan_with() {
+    let patterns = vec![
+        "error [0-9]+".to_string(),
+        "timeout".to_string(),
+        "e".to_string(),
+    ];
+    let tree = RegexTrie::from(&patterns).expect("can't init regex trie");
+    let error = tree.id_of("error [0-9]+").expect("pattern should exist");
+    let timeout = tree.id_of("timeout").expect("pattern should exist");
+    let letter = tree.id_of("e").expect("pattern should exist");
+
+    let text = "error 1, timeout, error 2";
+    let mut hits = Vec::new();
+    tree.scan_with(text, |id, range| {
+        hits.push((id, range));
+        ControlFlow::Continue(())
+    });
+    assert_eq!(
+        vec![
+            (error, 0..7),
+            (letter, 0..1),
+            (timeout, 9..16),
+            (letter, 12..13),
+            (error, 18..25),
+            (letter, 18..19),
+        ],
+        hits
+    );
+
+    // Early termination
+    let mut hits = Vec::new();
+    tree.scan_with(text, |id, range| {
+        hits.push((id, range));
+        if id == timeout {
+            ControlFlow::Break(())
+        } else {
+            ControlFlow::Continue(())
+        }
+    });
+    assert_eq!(3, hits.len());
+}
+
+/// Test the required literals only skip the regexes which can't match
+#[test]
+fn test_unanchored_required_literal() {
+    let required = |pattern: &str| {
+        required_literal(pattern).map(|finder| String::from_utf8_lossy(finder.needle()).to_string())
+    };
+    assert_eq!(required(r"/var/.*\.log").as_deref(), Some(".log"));
+    assert_eq!(required("a[0-9]+bcd[a-z]e").as_deref(), Some("bcd"));
+    assert_eq!(required("ab|cd"), None);
+    assert_eq!(required("[a-z]+"), None);
+
+    let mut tree = RegexTrie::from(&[r"/var/.*\.log".to_string(), "id=[0-9]+;".to_string()])
+        .expect("can't init");
+    tree.insert_with_options(
+        "/TMP/[a-z]+END",
+        InsertOptions {
+            case_insensitive: true,
+        },
+    )
+    .expect("can't insert");
+
+    let text = format!("{} /var/a.log /var/b /tmp/xend id=12;", "x".repeat(10_000));
+    let starts: Vec<_> = tree
+        .find_matches_unanchored(&text)
+        .into_iter()
+        .map(|found| (found.pattern.to_string(), found.start - 10_000))
+        .collect();
+    assert_eq!(
+        starts,
+        vec![
+            (r"/var/.*\.log".to_string(), 1),
+            ("/TMP/[a-z]+END".to_string(), 19),
+            ("id=[0-9]+;".to_string(), 29),
+        ]
+    );
+    // The last occurrence of the literal is before the second start
+    let mut hits = 0;
+    tree.scan_with("/var/a.log /var/b", |_, _| {
+        hits += 1;
+        ControlFlow::Continue(())
+    });
+    assert_eq!(hits, 1);
+
+    tree.remove_with_prefix("/var/");
+    tree.compact();
+    tree.self_check().expect("broken trie");
+    assert_eq!(tree.find_matches_unanchored("/var/a.log id=1;").len(), 1);
+}
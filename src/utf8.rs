@@ -0,0 +1,119 @@
+use std::{path::Path, sync::Arc};
+
+use regex_automata::{Anchored, Input, meta, util::syntax};
+
+use crate::{
+    MatchMode, RegexTrie, RegexTrieError,
+    options::regex_source,
+    regex_trie::{Scratch, is_full_match, is_prefix_match},
+};
+
+/// How `RegexTrie::find_matches_bytes` handles inputs which aren't valid
+/// UTF-8, e.g. log lines with broken sequences.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Utf8Handling {
+    /// Rejects them with `RegexTrieError::InvalidUtf8`
+    #[default]
+    Strict,
+    /// Replaces the invalid sequences with U+FFFD, then matches the text
+    Lossy,
+    /// Matches the raw bytes, like `RegexTrie::find_matches_path`: the
+    /// regexes run with byte semantics, as with `(?-u)`, so `.` matches any
+    /// byte but `\w` or a case-insensitive letter only ASCII ones. They're
+    /// compiled again for each such input, which is much slower, and the
+    /// ones which can't be without Unicode (e.g. with `\p{L}`) only match
+    /// valid UTF-8.
+    Bytes,
+}
+
+impl RegexTrie {
+    /// Sets how `find_matches_bytes` handles invalid UTF-8, see
+    /// `Utf8Handling`.
+    #[must_use]
+    pub const fn with_utf8_handling(mut self, utf8_handling: Utf8Handling) -> Self {
+        self.utf8_handling = utf8_handling;
+        self
+    }
+
+    /// Same as `find_matches_shared`, for an input which may not be valid
+    /// UTF-8. Valid inputs are matched as text, invalid ones as configured
+    /// with `with_utf8_handling`.
+    ///
+    /// ## Errors
+    ///
+    /// If the input isn't valid UTF-8, with `Utf8Handling::Strict`
+    pub fn find_matches_bytes(&self, input: &[u8]) -> Result<Vec<Arc<str>>, RegexTrieError> {
+        let err = match std::str::from_utf8(input) {
+            Ok(input) => return Ok(self.find_matches_shared(input)),
+            Err(err) => err,
+        };
+        match self.utf8_handling {
+            Utf8Handling::Strict => Err(RegexTrieError::InvalidUtf8(err)),
+            Utf8Handling::Lossy => Ok(self.find_matches_shared(&String::from_utf8_lossy(input))),
+            Utf8Handling::Bytes => Ok(self.find_matches_raw(input)),
+        }
+    }
+
+    /// Same as `find_matches_shared`, for a file path in the platform
+    /// encoding (bytes on Unix, WTF-8 on Windows), without a lossy conversion
+    /// which could make distinct names match the same patterns. Paths which
+    /// aren't valid UTF-8 are matched as raw bytes, like with
+    /// `Utf8Handling::Bytes` (see its caveats), whatever the UTF-8 handling.
+    #[must_use]
+    pub fn find_matches_path(&self, path: &Path) -> Vec<Arc<str>> {
+        self.find_matches_raw(path.as_os_str().as_encoded_bytes())
+    }
+
+    /// Matches an input as text if it's valid UTF-8, or as raw bytes: the
+    /// trie is walked up to the first invalid sequence, and the candidate
+    /// regexes run on the whole input with byte semantics.
+    fn find_matches_raw(&self, input: &[u8]) -> Vec<Arc<str>> {
+        let valid_up_to = match std::str::from_utf8(input) {
+            Ok(input) => return self.find_matches_shared(input),
+            Err(err) => err.valid_up_to(),
+        };
+        let valid = std::str::from_utf8(&input[..valid_up_to]).unwrap_or_default();
+        let mut scratch = Scratch::default();
+        self.candidates_into(valid, &mut scratch);
+        // No plain pattern is the whole input, but some can be a prefix of it.
+        let mut matches = Vec::new();
+        if self.match_mode == MatchMode::Prefix {
+            for &end in &scratch.literal_ends {
+                matches.push(self.shared_literal(&scratch.escaped_pattern[..end]));
+            }
+        }
+        for &index in &scratch.candidate_indices {
+            if self.is_bytes_match(index, input) {
+                matches.push(Arc::clone(&self.compiled_patterns[index].0));
+            }
+        }
+        matches
+    }
+
+    /// Whether the compiled pattern at the given index matches an input with
+    /// byte semantics, compiled again without Unicode, or with its own DFA if
+    /// it can't be.
+    fn is_bytes_match(&self, index: usize, input: &[u8]) -> bool {
+        let (pattern, dfa, _) = &self.compiled_patterns[index];
+        let case_insensitive = self
+            .ids
+            .get(pattern)
+            .is_some_and(|&id| self.is_case_insensitive(id));
+        let source = regex_source(pattern, &self.extra_specials);
+        let source = match self.match_mode {
+            MatchMode::Full => format!(r"(?:{source})\z"),
+            MatchMode::Prefix => source.into_owned(),
+        };
+        let syntax = syntax::Config::new()
+            .case_insensitive(case_insensitive)
+            .unicode(false)
+            .utf8(false);
+        let Ok(regex) = meta::Regex::builder().syntax(syntax).build(&source) else {
+            return match self.match_mode {
+                MatchMode::Full => is_full_match(dfa, input),
+                MatchMode::Prefix => is_prefix_match(dfa, input),
+            };
+        };
+        regex.is_match(Input::new(input).anchored(Anchored::Yes))
+    }
+}
@@ -0,0 +1,49 @@
+use crate::{PatternId, RegexTrie, regex_trie::TrieNode};
+
+impl RegexTrie {
+    /// Traverses the whole trie depth first, calling `visitor` on every node
+    /// with its escaped prefix, the ids of the patterns stored there and its
+    /// depth (in characters). The root comes first, with the patterns having
+    /// no literal prefix. Children are visited in character order, and the
+    /// plain pattern of a node comes before its regexes, so the traversal is
+    /// deterministic. Meant for exports, analysis or custom visualizations.
+    pub fn walk(&self, mut visitor: impl FnMut(&str, &[PatternId], usize)) {
+        let mut prefix = String::new();
+        let mut ids = Vec::new();
+        self.walk_node(&self.root, &mut prefix, 0, &mut ids, &mut visitor);
+    }
+
+    /// Recursive part of `walk`, `prefix` being the escaped path leading to
+    /// `node`, and `ids` a buffer reused for every node.
+    fn walk_node(
+        &self,
+        node: &TrieNode,
+        prefix: &mut String,
+        depth: usize,
+        ids: &mut Vec<PatternId>,
+        visitor: &mut impl FnMut(&str, &[PatternId], usize),
+    ) {
+        ids.clear();
+        if node.contains_non_regex_prefix {
+            ids.extend(self.id_of(prefix));
+        }
+        ids.extend(
+            node.pattern_indices
+                .iter()
+                .filter_map(|&index| self.id_of(&self.compiled_patterns[index].0)),
+        );
+        visitor(prefix, ids, depth);
+
+        let mut children: Vec<_> = node.children.iter().collect();
+        children.sort_unstable_by_key(|&(&ch, _)| ch);
+        for (&ch, child) in children {
+            let len = prefix.len();
+            if child.is_escaped {
+                prefix.push('\\');
+            }
+            prefix.push(ch);
+            self.walk_node(child, prefix, depth + 1, ids, visitor);
+            prefix.truncate(len);
+        }
+    }
+}
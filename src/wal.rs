@@ -0,0 +1,276 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, ErrorKind, Write},
+    path::Path,
+};
+
+use crate::{InsertOptions, RegexTrie, RegexTrieError};
+
+/// An operation of the log, as a single line: its kind, a space, and its
+/// argument, escaped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Operation {
+    /// `insert_with_options` of a pattern
+    Insert(String, InsertOptions),
+    /// `insert_literal` of a string
+    InsertLiteral(String),
+    /// `remove_with_prefix` of a literal prefix
+    RemoveWithPrefix(String),
+}
+
+impl Operation {
+    /// Line of the operation, newline included.
+    fn to_line(&self) -> String {
+        let (kind, argument) = match self {
+            Self::Insert(pattern, options) if options.case_insensitive => ("+i", pattern),
+            Self::Insert(pattern, _) => ("+", pattern),
+            Self::InsertLiteral(literal) => ("=", literal),
+            Self::RemoveWithPrefix(prefix) => ("-", prefix),
+        };
+        format!("{kind} {}\n", escape(argument))
+    }
+
+    /// Parses a line of the log, without its newline.
+    fn from_line(line: &str) -> Option<Self> {
+        let (kind, argument) = line.split_once(' ')?;
+        let argument = unescape(argument)?;
+        match kind {
+            "+" => Some(Self::Insert(argument, InsertOptions::default())),
+            "+i" => Some(Self::Insert(
+                argument,
+                InsertOptions {
+                    case_insensitive: true,
+                },
+            )),
+            "=" => Some(Self::InsertLiteral(argument)),
+            "-" => Some(Self::RemoveWithPrefix(argument)),
+            _ => None,
+        }
+    }
+
+    /// Applies the operation to the trie.
+    ///
+    /// ## Errors
+    ///
+    /// If the pattern can't be inserted
+    fn apply(&self, trie: &mut RegexTrie) -> Result<(), RegexTrieError> {
+        match self {
+            Self::Insert(pattern, options) => trie.insert_with_options(pattern, *options),
+            Self::InsertLiteral(literal) => trie.insert_literal(literal).map(drop),
+            Self::RemoveWithPrefix(prefix) => {
+                trie.remove_with_prefix(prefix);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Escapes the backslashes and line breaks of a log argument.
+fn escape(argument: &str) -> String {
+    let mut escaped = String::with_capacity(argument.len());
+    for ch in argument.chars() {
+        match ch {
+            '\\' => escaped.push_str(r"\\"),
+            '\n' => escaped.push_str(r"\n"),
+            '\r' => escaped.push_str(r"\r"),
+            _ => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+/// Reverse of `escape`, `None` on an invalid escape.
+fn unescape(escaped: &str) -> Option<String> {
+    let mut argument = String::with_capacity(escaped.len());
+    let mut chars = escaped.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            argument.push(ch);
+            continue;
+        }
+        argument.push(match chars.next()? {
+            '\\' => '\\',
+            'n' => '\n',
+            'r' => '\r',
+            _ => return None,
+        });
+    }
+    Some(argument)
+}
+
+/// Error of an I/O operation on the log.
+fn log_error(path: &Path, err: &std::io::Error) -> RegexTrieError {
+    RegexTrieError::LogFailed(format!("{}: {err}", path.display()))
+}
+
+/// A trie whose inserts and removals are first appended to a log file, so it
+/// can be rebuilt exactly after a crash with `RegexTrie::replay`, without
+/// fetching its rules again. Every operation is checked, then synced to disk
+/// before being applied, so only the ones which succeed are logged.
+///
+/// Settings (match mode, scorer, ...) aren't logged: the trie is rebuilt
+/// with the ones of the trie given to `open`. Neither are expirations, which
+/// are relative to the running process: `RegexTrie::insert_with_ttl` and
+/// `RegexTrie::evict_expired` aren't available here, as they wouldn't be
+/// durable.
+#[derive(Debug)]
+pub struct LoggedRegexTrie {
+    /// Trie the operations are applied to
+    trie: RegexTrie,
+    /// Log, opened for appending
+    log: File,
+    /// Path of the log, for the errors
+    path: Box<Path>,
+}
+
+impl LoggedRegexTrie {
+    /// Replays the log at the given path into the trie, if it exists, and
+    /// opens it to append the next operations, creating it if needed. A torn
+    /// last line is dropped.
+    ///
+    /// ## Errors
+    ///
+    /// If the log can't be read, created, or is corrupted
+    pub fn open(mut trie: RegexTrie, path: impl AsRef<Path>) -> Result<Self, RegexTrieError> {
+        let path = path.as_ref();
+        let (_, complete_len) = trie.replay_log(path)?;
+        let log = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|err| log_error(path, &err))?;
+        // Drops a torn last line, so the next operation starts a new one.
+        log.set_len(complete_len)
+            .map_err(|err| log_error(path, &err))?;
+        Ok(Self {
+            trie,
+            log,
+            path: Box::from(path),
+        })
+    }
+
+    /// Read access to the trie, for the whole query API.
+    #[must_use]
+    pub const fn trie(&self) -> &RegexTrie {
+        &self.trie
+    }
+
+    /// Checks and logs, then inserts a pattern.
+    ///
+    /// ## Errors
+    ///
+    /// If the operation can't be logged, or the pattern inserted
+    pub fn insert(&mut self, pattern: &str) -> Result<(), RegexTrieError> {
+        self.insert_with_options(pattern, InsertOptions::default())
+    }
+
+    /// Checks and logs, then inserts a pattern with the given options.
+    ///
+    /// ## Errors
+    ///
+    /// If the pattern can't be inserted, or the operation logged
+    pub fn insert_with_options(
+        &mut self,
+        pattern: &str,
+        options: InsertOptions,
+    ) -> Result<(), RegexTrieError> {
+        let prepared = self.trie.prepare_with_options(pattern, options)?;
+        self.append(&Operation::Insert(pattern.to_string(), options))?;
+        self.trie.commit_with_options(prepared);
+        Ok(())
+    }
+
+    /// Checks and logs, then inserts a string as a plain pattern matching it
+    /// verbatim, see `RegexTrie::insert_literal`. Returns the escaped
+    /// pattern.
+    ///
+    /// ## Errors
+    ///
+    /// If the string can't be inserted, or the operation logged
+    pub fn insert_literal(&mut self, literal: &str) -> Result<String, RegexTrieError> {
+        self.trie.prepare_literal(literal)?;
+        self.append(&Operation::InsertLiteral(literal.to_string()))?;
+        self.trie.insert_literal(literal)
+    }
+
+    /// Logs, then removes all the patterns under a literal prefix. Returns
+    /// the number of removed patterns.
+    ///
+    /// ## Errors
+    ///
+    /// If the operation can't be logged
+    pub fn remove_with_prefix(&mut self, literal_prefix: &str) -> Result<usize, RegexTrieError> {
+        self.append(&Operation::RemoveWithPrefix(literal_prefix.to_string()))?;
+        Ok(self.trie.remove_with_prefix(literal_prefix))
+    }
+
+    /// Appends an operation to the log, and syncs it to disk.
+    ///
+    /// ## Errors
+    ///
+    /// If the log can't be written
+    fn append(&mut self, operation: &Operation) -> Result<(), RegexTrieError> {
+        self.log
+            .write_all(operation.to_line().as_bytes())
+            .and_then(|()| self.log.sync_data())
+            .map_err(|err| log_error(&self.path, &err))
+    }
+}
+
+impl RegexTrie {
+    /// Applies the operations of a log written by `LoggedRegexTrie`, in
+    /// order, and returns how many. A missing log has none, and the last
+    /// line is ignored if it's incomplete, e.g. if the process crashed while
+    /// writing it. Only operations which succeeded are logged, so they're
+    /// expected to succeed again, with the same settings.
+    ///
+    /// ## Errors
+    ///
+    /// If the log can't be read, is corrupted, or an operation fails
+    pub fn replay(&mut self, path: impl AsRef<Path>) -> Result<usize, RegexTrieError> {
+        self.replay_log(path.as_ref()).map(|(replayed, _)| replayed)
+    }
+
+    /// Same as `replay`, also returning the length of the complete lines of
+    /// the log, in bytes.
+    ///
+    /// ## Errors
+    ///
+    /// If the log can't be read, is corrupted, or an operation fails
+    fn replay_log(&mut self, path: &Path) -> Result<(usize, u64), RegexTrieError> {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok((0, 0)),
+            Err(err) => return Err(log_error(path, &err)),
+        };
+
+        let mut reader = BufReader::new(file);
+        let mut line = Vec::new();
+        let mut replayed = 0;
+        let mut complete_len = 0;
+        loop {
+            line.clear();
+            reader
+                .read_until(b'\n', &mut line)
+                .map_err(|err| log_error(path, &err))?;
+            let Some(complete) = line.strip_suffix(b"\n") else {
+                // End of the log, or a torn last line
+                break;
+            };
+            let operation = std::str::from_utf8(complete)
+                .ok()
+                .and_then(Operation::from_line)
+                .ok_or_else(|| {
+                    RegexTrieError::LogFailed(format!(
+                        "{}: invalid operation {:?}",
+                        path.display(),
+                        String::from_utf8_lossy(complete)
+                    ))
+                })?;
+            operation.apply(self)?;
+            replayed += 1;
+            complete_len += line.len() as u64;
+        }
+        Ok((replayed, complete_len))
+    }
+}
@@ -0,0 +1,44 @@
+use wasm_bindgen::prelude::*;
+
+use crate::RegexTrie;
+
+/// Javascript facing wrapper around a `RegexTrie`.
+#[wasm_bindgen(js_name = RegexTrie)]
+#[derive(Debug, Default)]
+pub struct WasmRegexTrie {
+    /// Wrapped trie
+    inner: RegexTrie,
+}
+
+#[wasm_bindgen(js_class = RegexTrie)]
+impl WasmRegexTrie {
+    /// Creates a new, empty trie with the default scorer.
+    #[wasm_bindgen(constructor)]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Compiles a regex pattern and inserts it into the trie.
+    ///
+    /// ## Errors
+    ///
+    /// Throws if the regex pattern can't be compiled
+    pub fn insert(&mut self, pattern: &str) -> Result<(), JsError> {
+        self.inner.insert(pattern).map_err(JsError::from)
+    }
+
+    /// Finds all patterns fully matching the given input.
+    #[wasm_bindgen(js_name = findMatches)]
+    #[must_use]
+    pub fn find_matches(&self, input: &str) -> Vec<String> {
+        self.inner.find_matches(input)
+    }
+
+    /// Finds the best pattern matching the given input, or `undefined`.
+    #[wasm_bindgen(js_name = findBestMatch)]
+    #[must_use]
+    pub fn find_best_match(&self, input: &str) -> Option<String> {
+        self.inner.find_best_match(input)
+    }
+}
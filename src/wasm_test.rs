@@ -0,0 +1,21 @@
+use pretty_assertions::assert_eq;
+
+use crate::WasmRegexTrie;
+
+/// Test the javascript facing wrapper forwards to the trie
+#[test]
+fn test_wasm_wrapper() {
+    let mut trie = WasmRegexTrie::new();
+    for pattern in ["a.*", "a[0-9]+b.*", "abc"] {
+        assert!(trie.insert(pattern).is_ok(), "should insert {pattern}");
+    }
+
+    let mut matches = trie.find_matches("a123bbb");
+    matches.sort();
+    assert_eq!(vec!["a.*", "a[0-9]+b.*"], matches, "wrong matches");
+    assert_eq!(
+        Some("abc".to_string()),
+        trie.find_best_match("abc"),
+        "plain match should win"
+    );
+}
@@ -0,0 +1,339 @@
+//! Tests of the `regextrie` command line, running the built binary.
+#![cfg(test)]
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use pretty_assertions::assert_eq;
+
+/// Patterns shared by the tests
+const PATTERNS: &str = "/api/[a-z]+\n/api/users\n/static/.*\n";
+
+/// Output of a run of the binary
+#[derive(Debug)]
+struct Output {
+    /// Exit status
+    code: i32,
+    /// Standard output
+    stdout: String,
+    /// Standard error
+    stderr: String,
+}
+
+/// Runs the binary with the given arguments, and the given standard input.
+fn run_with_stdin(args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_regextrie"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("can't run the binary");
+    child
+        .stdin
+        .take()
+        .expect("missing stdin")
+        .write_all(stdin.as_bytes())
+        .expect("can't write stdin");
+    let output = child.wait_with_output().expect("can't wait for the binary");
+    Output {
+        code: output.status.code().expect("killed by a signal"),
+        stdout: String::from_utf8(output.stdout).expect("invalid stdout"),
+        stderr: String::from_utf8(output.stderr).expect("invalid stderr"),
+    }
+}
+
+/// Runs the binary with the given arguments, and an empty standard input.
+fn run(args: &[&str]) -> Output {
+    run_with_stdin(args, "")
+}
+
+/// Temporary directory of a test, created empty.
+fn temp_dir(test: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("regextrie-cli-{test}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).expect("can't create the temporary directory");
+    dir
+}
+
+/// Writes a file in the given directory, returning its path as a string.
+fn write_file(dir: &Path, name: &str, content: impl AsRef<[u8]>) -> String {
+    let path = dir.join(name);
+    fs::write(&path, content).expect("can't write the file");
+    path.to_str().expect("non UTF-8 path").to_string()
+}
+
+/// Test the best match is printed, and the exit status tells whether any
+/// input matched
+#[test]
+fn test_match() {
+    let dir = temp_dir("match");
+    let patterns = write_file(&dir, "patterns.txt", PATTERNS);
+
+    let output = run(&["-p", &patterns, "/api/users"]);
+    assert_eq!((output.code, output.stdout.as_str()), (0, "/api/users\n"));
+
+    let output = run(&["-p", &patterns, "/nope"]);
+    assert_eq!((output.code, output.stdout.as_str()), (1, ""));
+
+    // Inputs are prefixed when there are many
+    let output = run(&["-p", &patterns, "--all", "/api/users", "/nope"]);
+    assert_eq!(
+        (output.code, output.stdout.as_str()),
+        (0, "/api/users\t/api/users\n/api/users\t/api/[a-z]+\n")
+    );
+
+    // Or read from stdin
+    let output = run_with_stdin(&["-p", &patterns], "/static/a\n/x\n");
+    assert_eq!(
+        (output.code, output.stdout.as_str()),
+        (0, "/static/a\t/static/.*\n")
+    );
+
+    fs::remove_dir_all(&dir).expect("can't remove");
+}
+
+/// Test the structured output formats
+#[test]
+fn test_output_formats() {
+    let dir = temp_dir("formats");
+    let patterns = write_file(&dir, "patterns.txt", PATTERNS);
+
+    let output = run(&["-p", &patterns, "-o", "json", "/api/users", "/nope"]);
+    assert_eq!(output.code, 0);
+    assert_eq!(
+        output.stdout,
+        concat!(
+            r#"[{"input":"/api/users","matches":["/api/users","/api/[a-z]+"],"best":"/api/users"},"#,
+            r#"{"input":"/nope","matches":[],"best":null}]"#,
+            "\n"
+        )
+    );
+
+    let output = run(&["-p", &patterns, "-o", "jsonl", "/static/a", "/nope"]);
+    assert_eq!(output.code, 0);
+    assert_eq!(
+        output.stdout,
+        concat!(
+            r#"{"input":"/static/a","matches":["/static/.*"],"best":"/static/.*"}"#,
+            "\n",
+            r#"{"input":"/nope","matches":[],"best":null}"#,
+            "\n"
+        )
+    );
+
+    let output = run(&["-p", &patterns, "-o", "jsonl", "/nope"]);
+    assert_eq!(output.code, 1);
+
+    fs::remove_dir_all(&dir).expect("can't remove");
+}
+
+/// Test the count and quiet modes
+#[test]
+fn test_count_and_quiet() {
+    let dir = temp_dir("count");
+    let patterns = write_file(&dir, "patterns.txt", PATTERNS);
+
+    let output = run(&["-p", &patterns, "-c", "/api/users", "/static/x", "/nope"]);
+    assert_eq!(
+        (output.code, output.stdout.as_str()),
+        (0, "/api/users\t2\n/static/x\t1\n/nope\t0\n")
+    );
+
+    let output = run(&["-p", &patterns, "-q", "/nope", "/api/users"]);
+    assert_eq!((output.code, output.stdout.as_str()), (0, ""));
+    let output = run(&["-p", &patterns, "-q", "/nope"]);
+    assert_eq!((output.code, output.stdout.as_str()), (1, ""));
+
+    fs::remove_dir_all(&dir).expect("can't remove");
+}
+
+/// Test files are scanned line by line, past the unreadable ones
+#[test]
+fn test_grep() {
+    let dir = temp_dir("grep");
+    let patterns = write_file(&dir, "patterns.txt", ".*/api/[a-z]+\n");
+    let log = write_file(&dir, "access.log", b"GET /api/users\nGET /\n\xff/api/x\r\n");
+    let missing = dir.join("missing.log");
+    let missing = missing.to_str().expect("non UTF-8 path");
+
+    let expected = format!("{log}:1: .*/api/[a-z]+\n{log}:3: .*/api/[a-z]+\n");
+    for parallel in [false, true] {
+        let mut args = vec!["grep", "-p", &patterns, &log];
+        if parallel {
+            args.push("-j");
+        }
+        let output = run(&args);
+        assert_eq!(
+            (output.code, output.stdout.as_str()),
+            (0, expected.as_str())
+        );
+
+        args.splice(3..3, [missing]);
+        let output = run(&args);
+        assert_eq!(
+            (output.code, output.stdout.as_str()),
+            (2, expected.as_str())
+        );
+        assert!(
+            output.stderr.contains(&format!("can't read {missing}")),
+            "{}",
+            output.stderr
+        );
+    }
+
+    let empty = write_file(&dir, "empty.log", "");
+    let output = run(&["grep", "-p", &patterns, &empty]);
+    assert_eq!((output.code, output.stdout.as_str()), (1, ""));
+
+    fs::remove_dir_all(&dir).expect("can't remove");
+}
+
+/// Test a compiled snapshot is loaded instead of the patterns
+#[test]
+fn test_compile() {
+    let dir = temp_dir("compile");
+    let patterns = write_file(&dir, "patterns.txt", PATTERNS);
+    let snapshot = dir.join("patterns.bin");
+    let snapshot = snapshot.to_str().expect("non UTF-8 path");
+
+    let output = run(&["compile", "-p", &patterns, "-o", snapshot]);
+    assert_eq!((output.code, output.stdout.as_str()), (0, ""));
+
+    let output = run(&["--from-snapshot", snapshot, "--all", "/api/users"]);
+    assert_eq!(
+        (output.code, output.stdout.as_str()),
+        (0, "/api/users\n/api/[a-z]+\n")
+    );
+    let output = run(&["grep", "--from-snapshot", snapshot, &patterns]);
+    assert_eq!(
+        (output.code, output.stdout),
+        (
+            0,
+            format!("{patterns}:2: /api/users\n{patterns}:3: /static/.*\n")
+        )
+    );
+
+    // Not a snapshot
+    let output = run(&["--from-snapshot", &patterns, "/api/users"]);
+    assert_eq!((output.code, output.stdout.as_str()), (2, ""));
+    assert!(
+        output.stderr.starts_with("regextrie: "),
+        "{}",
+        output.stderr
+    );
+
+    fs::remove_dir_all(&dir).expect("can't remove");
+}
+
+/// Test pattern files are checked, with their errors and warnings reported
+#[test]
+fn test_validate() {
+    let dir = temp_dir("validate");
+    let valid = write_file(&dir, "valid.txt", PATTERNS);
+    let warned = write_file(&dir, "warned.txt", "/api/.*\n.*\\.png\n");
+    let invalid = write_file(&dir, "invalid.txt", "/ok\n\n/broken/[\n");
+
+    let output = run(&["validate", &valid]);
+    assert_eq!((output.code, output.stderr.as_str()), (0, ""));
+
+    let output = run(&["validate", &warned]);
+    assert_eq!(output.code, 0);
+    assert!(
+        output.stderr.starts_with(&format!(
+            "{warned}:2: warning: \".*\\\\.png\" has no literal prefix"
+        )),
+        "{}",
+        output.stderr
+    );
+    assert!(output.stderr.ends_with("0 error(s), 1 warning(s)\n"));
+    let output = run(&["validate", "--strict", &warned]);
+    assert_eq!(output.code, 1);
+
+    let output = run(&["validate", &valid, &invalid]);
+    assert_eq!(output.code, 1);
+    assert!(
+        output
+            .stderr
+            .starts_with(&format!("{invalid}:3: error: \"/broken/[\": ")),
+        "{}",
+        output.stderr
+    );
+    assert!(output.stderr.ends_with("1 error(s), 0 warning(s)\n"));
+    assert_eq!(output.stdout, "");
+
+    fs::remove_dir_all(&dir).expect("can't remove");
+}
+
+/// Test the interactive prompt prints the matches of every input
+#[test]
+fn test_repl() {
+    let dir = temp_dir("repl");
+    let patterns = write_file(&dir, "patterns.txt", PATTERNS);
+
+    let output = run_with_stdin(&["repl", "-p", &patterns], "/api/users\n/nope\n:quit\n");
+    assert_eq!(output.code, 0);
+    assert_eq!(
+        output.stdout,
+        ">   - /api/users\n  - /api/[a-z]+\nbest: /api/users\n> no match\n> "
+    );
+
+    fs::remove_dir_all(&dir).expect("can't remove");
+}
+
+/// Test errors are reported on stderr, with the exit status 2
+#[test]
+fn test_errors() {
+    let dir = temp_dir("errors");
+    let missing = dir.join("missing.txt");
+    let missing = missing.to_str().expect("non UTF-8 path");
+    let invalid = write_file(&dir, "invalid.txt", "/broken/[\n");
+    let patterns = write_file(&dir, "patterns.txt", PATTERNS);
+
+    let output = run(&["-p", missing, "/api/users"]);
+    assert_eq!((output.code, output.stdout.as_str()), (2, ""));
+    assert!(
+        output
+            .stderr
+            .starts_with(&format!("regextrie: can't read {missing}")),
+        "{}",
+        output.stderr
+    );
+
+    let output = run(&["-p", &invalid, "/broken/"]);
+    assert_eq!((output.code, output.stdout.as_str()), (2, ""));
+    assert!(
+        output.stderr.starts_with("regextrie: "),
+        "{}",
+        output.stderr
+    );
+
+    let output = run(&["validate", missing]);
+    assert_eq!(output.code, 2);
+
+    let output = run(&[
+        "compile",
+        "-p",
+        &patterns,
+        "-o",
+        missing.replace("missing.txt", "no/such/dir").as_str(),
+    ]);
+    assert_eq!(output.code, 2);
+    assert!(output.stderr.contains("can't write"), "{}", output.stderr);
+
+    // Invalid arguments
+    for args in [
+        &["/api/users"][..],
+        &["-p", &patterns, "-c", "-o", "json", "/api/users"],
+        &["-p", &patterns, "--from-snapshot", &patterns, "/api/users"],
+        &["grep", "-p", &patterns],
+    ] {
+        let output = run(args);
+        assert_eq!((output.code, output.stdout.as_str()), (2, ""), "{args:?}");
+    }
+
+    fs::remove_dir_all(&dir).expect("can't remove");
+}